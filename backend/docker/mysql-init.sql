@@ -0,0 +1,2 @@
+CREATE DATABASE admin_test CHARACTER SET utf8mb4;
+GRANT ALL PRIVILEGES ON admin_test.* TO 'admin'@'%';
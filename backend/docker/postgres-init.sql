@@ -0,0 +1 @@
+CREATE DATABASE admin_test OWNER admin;
@@ -0,0 +1,275 @@
+-- MySQL 8.0 的初始表结构, 与 SQLite 的 0001_initial_schema 对应.
+-- 已发布的迁移不能修改, 结构变化需要在各方言目录下新增编号相同的迁移文件.
+-- 需要建唯一索引或普通索引的字符串字段使用 VARCHAR; before、key 是保留字, 需要加反引号
+
+CREATE TABLE menus (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    parent_id BIGINT,
+    name VARCHAR(255) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    component VARCHAR(255) NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    icon VARCHAR(255),
+    sort INT NOT NULL DEFAULT 0,
+    is_hidden BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- storage_quota 为存储配额(字节), 为空表示未设置
+CREATE TABLE users (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    username VARCHAR(64) NOT NULL UNIQUE,
+    password VARCHAR(255) NOT NULL,
+    nickname VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    avatar VARCHAR(1024),
+    status INT NOT NULL DEFAULT 1,
+    storage_quota BIGINT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE roles (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    code VARCHAR(255) NOT NULL UNIQUE,
+    description TEXT,
+    status INT NOT NULL DEFAULT 1,
+    color_start VARCHAR(32),
+    color_end VARCHAR(32),
+    storage_quota BIGINT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 权限的编号在模型中是 32 位整数
+CREATE TABLE permissions (
+    id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    code VARCHAR(255) NOT NULL UNIQUE,
+    type_name VARCHAR(32) NOT NULL DEFAULT 'PAGE',
+    resource VARCHAR(255),
+    action VARCHAR(32),
+    parent_id INT,
+    sort INT DEFAULT 0,
+    description TEXT,
+    color_start VARCHAR(32),
+    color_end VARCHAR(32),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (parent_id) REFERENCES permissions (id)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE role_permissions (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    role_id BIGINT NOT NULL,
+    permission_id INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (role_id, permission_id),
+    FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
+    FOREIGN KEY (permission_id) REFERENCES permissions (id) ON DELETE CASCADE
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE user_roles (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, role_id),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 参数和错误信息的全文索引使用 ngram 分词, 对应 SQLite 中使用 trigram 分词的 operation_logs_fts
+CREATE TABLE operation_logs (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id BIGINT,
+    username VARCHAR(64),
+    module VARCHAR(255),
+    operation VARCHAR(255),
+    method VARCHAR(16),
+    path VARCHAR(1024),
+    params MEDIUMTEXT,
+    ip VARCHAR(64),
+    user_agent VARCHAR(1024),
+    request_id VARCHAR(64),
+    status INT,
+    error TEXT,
+    response MEDIUMTEXT,
+    duration_ms BIGINT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    prev_hash CHAR(64),
+    hash CHAR(64),
+    ip_num BIGINT,
+    INDEX idx_operation_logs_request_id (request_id),
+    INDEX idx_operation_logs_created_at (created_at),
+    INDEX idx_operation_logs_user_id (user_id),
+    INDEX idx_operation_logs_ip_num (ip_num),
+    FULLTEXT INDEX idx_operation_logs_fulltext (params, error) WITH PARSER ngram
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE operation_log_changes (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    log_id BIGINT NOT NULL,
+    entity VARCHAR(64) NOT NULL,
+    entity_id BIGINT NOT NULL,
+    action VARCHAR(16) NOT NULL,
+    `before` MEDIUMTEXT,
+    `after` MEDIUMTEXT,
+    diff MEDIUMTEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_operation_log_changes_log_id (log_id),
+    FOREIGN KEY (log_id) REFERENCES operation_logs (id) ON DELETE CASCADE
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE operation_log_checkpoints (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    last_log_id BIGINT NOT NULL,
+    last_hash CHAR(64) NOT NULL,
+    purged_count BIGINT NOT NULL,
+    cutoff VARCHAR(64) NOT NULL,
+    reason VARCHAR(255) NOT NULL,
+    created_by VARCHAR(64),
+    archive VARCHAR(255),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 归档日志导入表, 用于排查已归档的日志
+CREATE TABLE archived_operation_logs (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    archive VARCHAR(255) NOT NULL,
+    original_id BIGINT NOT NULL,
+    user_id BIGINT,
+    username VARCHAR(64),
+    module VARCHAR(255),
+    operation VARCHAR(255),
+    method VARCHAR(16),
+    path VARCHAR(1024),
+    params MEDIUMTEXT,
+    ip VARCHAR(64),
+    user_agent VARCHAR(1024),
+    request_id VARCHAR(64),
+    status INT,
+    error TEXT,
+    response MEDIUMTEXT,
+    duration_ms BIGINT,
+    created_at DATETIME,
+    prev_hash CHAR(64),
+    hash CHAR(64),
+    changes MEDIUMTEXT,
+    INDEX idx_archived_operation_logs_archive (archive)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE operation_log_rules (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    method VARCHAR(16) NOT NULL DEFAULT '*',
+    path VARCHAR(1024) NOT NULL,
+    action VARCHAR(16) NOT NULL CHECK (action IN ('include', 'exclude')),
+    priority INT NOT NULL DEFAULT 0,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    description VARCHAR(255),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+-- 默认规则: 记录导出, 不记录查看操作日志本身和分片上传的单个分片
+INSERT INTO operation_log_rules (method, path, action, priority, description) VALUES
+    ('GET', '/users/export', 'include', 100, '记录用户导出'),
+    ('GET', '/operation-logs/export', 'include', 100, '记录操作日志导出'),
+    ('*', '/operation-logs/**', 'exclude', 10, '不记录操作日志管理接口'),
+    ('PUT', '/upload/sessions/*/chunks/*', 'exclude', 10, '不记录分片上传的单个分片');
+
+-- 登录日志, 登录失败时 user_id 为空
+CREATE TABLE login_logs (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user_id BIGINT,
+    username VARCHAR(64) NOT NULL,
+    success BOOLEAN NOT NULL,
+    failure_reason VARCHAR(64),
+    ip VARCHAR(64),
+    user_agent VARCHAR(1024),
+    request_id VARCHAR(64),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_login_logs_created_at (created_at),
+    INDEX idx_login_logs_user_id (user_id),
+    INDEX idx_login_logs_username (username)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 上传文件登记表, blob_key 为去重保存的文件内容位置
+CREATE TABLE files (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    storage_name VARCHAR(255) NOT NULL UNIQUE,
+    original_name VARCHAR(255) NOT NULL,
+    size BIGINT NOT NULL,
+    mime VARCHAR(255) NOT NULL,
+    sha256 CHAR(64) NOT NULL,
+    purpose VARCHAR(32) NOT NULL,
+    visibility VARCHAR(16) NOT NULL DEFAULT 'private',
+    blob_key VARCHAR(255),
+    uploader_id BIGINT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_files_uploader_id (uploader_id),
+    INDEX idx_files_sha256 (sha256),
+    INDEX idx_files_created_at (created_at),
+    INDEX idx_files_blob_key (blob_key),
+    FOREIGN KEY (uploader_id) REFERENCES users (id) ON DELETE SET NULL
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 文件的派生版本, 例如头像的各尺寸缩略图, 随原文件一起删除
+CREATE TABLE file_variants (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    file_id BIGINT NOT NULL,
+    variant VARCHAR(32) NOT NULL,
+    storage_name VARCHAR(255) NOT NULL UNIQUE,
+    blob_key VARCHAR(255),
+    size BIGINT NOT NULL,
+    width INT NOT NULL,
+    height INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (file_id, variant),
+    INDEX idx_file_variants_blob_key (blob_key),
+    FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 按内容去重保存的文件, 没有引用且超过宽限期后由清理任务删除
+CREATE TABLE file_blobs (
+    `key` VARCHAR(255) NOT NULL PRIMARY KEY,
+    sha256 CHAR(64) NOT NULL,
+    size BIGINT NOT NULL,
+    mime VARCHAR(255) NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_file_blobs_sha256 (sha256)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 分片上传会话, 分片内容暂存在本地目录, 合并后写入存储
+CREATE TABLE upload_sessions (
+    id VARCHAR(64) NOT NULL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    purpose VARCHAR(32) NOT NULL,
+    visibility VARCHAR(16) NOT NULL DEFAULT 'private',
+    file_name VARCHAR(255) NOT NULL,
+    total_size BIGINT NOT NULL,
+    chunk_size BIGINT NOT NULL,
+    total_chunks BIGINT NOT NULL,
+    sha256 CHAR(64),
+    status VARCHAR(16) NOT NULL DEFAULT 'uploading',
+    file_id BIGINT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    INDEX idx_upload_sessions_user_id (user_id),
+    INDEX idx_upload_sessions_expires_at (expires_at),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE SET NULL
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+CREATE TABLE upload_session_chunks (
+    session_id VARCHAR(64) NOT NULL,
+    chunk_index BIGINT NOT NULL,
+    size BIGINT NOT NULL,
+    sha256 CHAR(64) NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (session_id, chunk_index),
+    FOREIGN KEY (session_id) REFERENCES upload_sessions (id) ON DELETE CASCADE
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
@@ -0,0 +1,6 @@
+-- 初始数据的写入记录, 初始数据文件的版本号大于已写入的版本时才会在启动时写入
+CREATE TABLE seed_history (
+    version BIGINT NOT NULL PRIMARY KEY,
+    checksum CHAR(64) NOT NULL,
+    applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
@@ -0,0 +1,20 @@
+-- 清洗历史日志参数的记录, 每次清洗为一条按顺序串联哈希的事件, 校验时据此接受内容被脱敏的日志
+CREATE TABLE operation_log_redactions (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    reason VARCHAR(255) NOT NULL,
+    redacted_count BIGINT NOT NULL,
+    created_at DATETIME NOT NULL,
+    prev_hash CHAR(64) NOT NULL,
+    hash CHAR(64) NOT NULL
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+-- 被清洗的日志, original_hash 为日志原有的哈希, redacted_hash 为按清洗后内容计算的哈希
+CREATE TABLE operation_log_redacted_rows (
+    redaction_id BIGINT NOT NULL,
+    log_id BIGINT NOT NULL,
+    original_hash CHAR(64) NOT NULL,
+    redacted_hash CHAR(64) NOT NULL,
+    PRIMARY KEY (redaction_id, log_id),
+    KEY idx_operation_log_redacted_rows_log_id (log_id),
+    CONSTRAINT fk_operation_log_redacted_rows_redaction FOREIGN KEY (redaction_id) REFERENCES operation_log_redactions(id)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
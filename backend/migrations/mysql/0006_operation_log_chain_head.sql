@@ -0,0 +1,6 @@
+-- 哈希链的链头, 只有一行. 写入日志、清洗和清理的事务先锁定这一行, 多个实例依次读取和延长哈希链
+CREATE TABLE operation_log_chain_head (
+    id INT NOT NULL PRIMARY KEY CHECK (id = 1)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4;
+
+INSERT INTO operation_log_chain_head (id) VALUES (1);
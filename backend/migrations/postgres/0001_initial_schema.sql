@@ -0,0 +1,266 @@
+-- PostgreSQL 的初始表结构, 与 SQLite 的 0001_initial_schema 对应.
+-- 已发布的迁移不能修改, 结构变化需要在各方言目录下新增编号相同的迁移文件
+
+CREATE TABLE menus (
+    id BIGSERIAL PRIMARY KEY,
+    parent_id BIGINT,
+    name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    component TEXT NOT NULL,
+    title TEXT NOT NULL,
+    icon TEXT,
+    sort INTEGER NOT NULL DEFAULT 0,
+    is_hidden BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- storage_quota 为存储配额(字节), 为空表示未设置
+CREATE TABLE users (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password TEXT NOT NULL,
+    nickname TEXT NOT NULL,
+    email TEXT,
+    avatar TEXT,
+    status INTEGER NOT NULL DEFAULT 1,
+    storage_quota BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE roles (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    code TEXT NOT NULL UNIQUE,
+    description TEXT,
+    status INTEGER NOT NULL DEFAULT 1,
+    color_start TEXT,
+    color_end TEXT,
+    storage_quota BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- 权限的编号在模型中是 32 位整数
+CREATE TABLE permissions (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    code TEXT NOT NULL UNIQUE,
+    type_name TEXT NOT NULL DEFAULT 'PAGE',
+    resource TEXT,
+    action TEXT,
+    parent_id INTEGER REFERENCES permissions (id),
+    sort INTEGER DEFAULT 0,
+    description TEXT,
+    color_start TEXT,
+    color_end TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE role_permissions (
+    id BIGSERIAL PRIMARY KEY,
+    role_id BIGINT NOT NULL REFERENCES roles (id) ON DELETE CASCADE,
+    permission_id INTEGER NOT NULL REFERENCES permissions (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (role_id, permission_id)
+);
+
+CREATE TABLE user_roles (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role_id BIGINT NOT NULL REFERENCES roles (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, role_id)
+);
+
+CREATE TABLE operation_logs (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT,
+    username TEXT,
+    module TEXT,
+    operation TEXT,
+    method TEXT,
+    path TEXT,
+    params TEXT,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    status INTEGER,
+    error TEXT,
+    response TEXT,
+    duration_ms BIGINT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    prev_hash TEXT,
+    hash TEXT,
+    ip_num BIGINT
+);
+CREATE INDEX idx_operation_logs_request_id ON operation_logs (request_id);
+CREATE INDEX idx_operation_logs_created_at ON operation_logs (created_at);
+CREATE INDEX idx_operation_logs_user_id ON operation_logs (user_id);
+CREATE INDEX idx_operation_logs_ip_num ON operation_logs (ip_num);
+
+-- 参数和错误信息的子串搜索, 对应 SQLite 中使用 trigram 分词的 operation_logs_fts
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX idx_operation_logs_params_trgm ON operation_logs USING gin (params gin_trgm_ops);
+CREATE INDEX idx_operation_logs_error_trgm ON operation_logs USING gin (error gin_trgm_ops);
+
+CREATE TABLE operation_log_changes (
+    id BIGSERIAL PRIMARY KEY,
+    log_id BIGINT NOT NULL REFERENCES operation_logs (id) ON DELETE CASCADE,
+    entity TEXT NOT NULL,
+    entity_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    before TEXT,
+    after TEXT,
+    diff TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_operation_log_changes_log_id ON operation_log_changes (log_id);
+
+CREATE TABLE operation_log_checkpoints (
+    id BIGSERIAL PRIMARY KEY,
+    last_log_id BIGINT NOT NULL,
+    last_hash TEXT NOT NULL,
+    purged_count BIGINT NOT NULL,
+    cutoff TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_by TEXT,
+    archive TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- 归档日志导入表, 用于排查已归档的日志
+CREATE TABLE archived_operation_logs (
+    id BIGSERIAL PRIMARY KEY,
+    archive TEXT NOT NULL,
+    original_id BIGINT NOT NULL,
+    user_id BIGINT,
+    username TEXT,
+    module TEXT,
+    operation TEXT,
+    method TEXT,
+    path TEXT,
+    params TEXT,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    status INTEGER,
+    error TEXT,
+    response TEXT,
+    duration_ms BIGINT,
+    created_at TIMESTAMP,
+    prev_hash TEXT,
+    hash TEXT,
+    changes TEXT
+);
+CREATE INDEX idx_archived_operation_logs_archive ON archived_operation_logs (archive);
+
+CREATE TABLE operation_log_rules (
+    id BIGSERIAL PRIMARY KEY,
+    method TEXT NOT NULL DEFAULT '*',
+    path TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('include', 'exclude')),
+    priority INTEGER NOT NULL DEFAULT 0,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+-- 默认规则: 记录导出, 不记录查看操作日志本身和分片上传的单个分片
+INSERT INTO operation_log_rules (method, path, action, priority, description) VALUES
+    ('GET', '/users/export', 'include', 100, '记录用户导出'),
+    ('GET', '/operation-logs/export', 'include', 100, '记录操作日志导出'),
+    ('*', '/operation-logs/**', 'exclude', 10, '不记录操作日志管理接口'),
+    ('PUT', '/upload/sessions/*/chunks/*', 'exclude', 10, '不记录分片上传的单个分片');
+
+-- 登录日志, 登录失败时 user_id 为空
+CREATE TABLE login_logs (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT,
+    username TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    failure_reason TEXT,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_login_logs_created_at ON login_logs (created_at);
+CREATE INDEX idx_login_logs_user_id ON login_logs (user_id);
+CREATE INDEX idx_login_logs_username ON login_logs (username);
+
+-- 上传文件登记表, blob_key 为去重保存的文件内容位置
+CREATE TABLE files (
+    id BIGSERIAL PRIMARY KEY,
+    storage_name TEXT NOT NULL UNIQUE,
+    original_name TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    mime TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    purpose TEXT NOT NULL,
+    visibility TEXT NOT NULL DEFAULT 'private',
+    blob_key TEXT,
+    uploader_id BIGINT REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_files_uploader_id ON files (uploader_id);
+CREATE INDEX idx_files_sha256 ON files (sha256);
+CREATE INDEX idx_files_created_at ON files (created_at);
+CREATE INDEX idx_files_blob_key ON files (blob_key);
+
+-- 文件的派生版本, 例如头像的各尺寸缩略图, 随原文件一起删除
+CREATE TABLE file_variants (
+    id BIGSERIAL PRIMARY KEY,
+    file_id BIGINT NOT NULL REFERENCES files (id) ON DELETE CASCADE,
+    variant TEXT NOT NULL,
+    storage_name TEXT NOT NULL UNIQUE,
+    blob_key TEXT,
+    size BIGINT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (file_id, variant)
+);
+CREATE INDEX idx_file_variants_blob_key ON file_variants (blob_key);
+
+-- 按内容去重保存的文件, 没有引用且超过宽限期后由清理任务删除
+CREATE TABLE file_blobs (
+    key TEXT PRIMARY KEY,
+    sha256 TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    mime TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_file_blobs_sha256 ON file_blobs (sha256);
+
+-- 分片上传会话, 分片内容暂存在本地目录, 合并后写入存储
+CREATE TABLE upload_sessions (
+    id TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    purpose TEXT NOT NULL,
+    visibility TEXT NOT NULL DEFAULT 'private',
+    file_name TEXT NOT NULL,
+    total_size BIGINT NOT NULL,
+    chunk_size BIGINT NOT NULL,
+    total_chunks BIGINT NOT NULL,
+    sha256 TEXT,
+    status TEXT NOT NULL DEFAULT 'uploading',
+    file_id BIGINT REFERENCES files (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL
+);
+CREATE INDEX idx_upload_sessions_user_id ON upload_sessions (user_id);
+CREATE INDEX idx_upload_sessions_expires_at ON upload_sessions (expires_at);
+
+CREATE TABLE upload_session_chunks (
+    session_id TEXT NOT NULL REFERENCES upload_sessions (id) ON DELETE CASCADE,
+    chunk_index BIGINT NOT NULL,
+    size BIGINT NOT NULL,
+    sha256 TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (session_id, chunk_index)
+);
@@ -0,0 +1,6 @@
+-- 初始数据的写入记录, 初始数据文件的版本号大于已写入的版本时才会在启动时写入
+CREATE TABLE seed_history (
+    version BIGINT PRIMARY KEY,
+    checksum TEXT NOT NULL,
+    applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
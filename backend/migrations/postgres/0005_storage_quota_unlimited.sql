@@ -0,0 +1,6 @@
+-- 用户和角色的 storage_quota: 为空表示未设置(用户使用角色的配额, 角色不参与计算, 都没有设置时使用默认配额),
+-- -1 表示不限制, 其他值为配额字节数
+UPDATE users SET storage_quota = NULL WHERE storage_quota < -1;
+UPDATE roles SET storage_quota = NULL WHERE storage_quota < -1;
+ALTER TABLE users ADD CONSTRAINT chk_users_storage_quota CHECK (storage_quota >= -1);
+ALTER TABLE roles ADD CONSTRAINT chk_roles_storage_quota CHECK (storage_quota >= -1);
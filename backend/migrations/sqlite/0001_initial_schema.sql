@@ -0,0 +1,302 @@
+-- 初始表结构. 已发布的迁移不能修改, 结构变化需要新增迁移文件
+
+CREATE TABLE IF NOT EXISTS menus (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    parent_id INTEGER,
+    name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    component TEXT NOT NULL,
+    title TEXT NOT NULL,
+    icon TEXT,
+    sort INTEGER NOT NULL DEFAULT 0,
+    is_hidden BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- storage_quota 为存储配额(字节), 为空表示未设置
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    password TEXT NOT NULL,
+    nickname TEXT NOT NULL,
+    email TEXT,
+    avatar TEXT,
+    status INTEGER NOT NULL DEFAULT 1,
+    storage_quota INTEGER,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS roles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    code TEXT NOT NULL UNIQUE,
+    description TEXT,
+    status INTEGER NOT NULL DEFAULT 1,
+    color_start TEXT,
+    color_end TEXT,
+    storage_quota INTEGER,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS permissions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    code TEXT NOT NULL UNIQUE,
+    type_name TEXT NOT NULL DEFAULT 'PAGE',
+    resource TEXT,
+    action TEXT,
+    parent_id INTEGER,
+    sort INTEGER DEFAULT 0,
+    description TEXT,
+    color_start TEXT,
+    color_end TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (parent_id) REFERENCES permissions(id)
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    role_id INTEGER NOT NULL,
+    permission_id INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
+    FOREIGN KEY (permission_id) REFERENCES permissions (id) ON DELETE CASCADE,
+    UNIQUE (role_id, permission_id)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
+    UNIQUE (user_id, role_id)
+);
+
+CREATE TABLE IF NOT EXISTS operation_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,
+    username TEXT,
+    module TEXT,
+    operation TEXT,
+    method TEXT,
+    path TEXT,
+    params TEXT,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    status INTEGER,
+    error TEXT,
+    response TEXT,
+    duration_ms INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    prev_hash TEXT,
+    hash TEXT,
+    ip_num INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_operation_logs_request_id ON operation_logs (request_id);
+CREATE INDEX IF NOT EXISTS idx_operation_logs_created_at ON operation_logs (created_at);
+CREATE INDEX IF NOT EXISTS idx_operation_logs_user_id ON operation_logs (user_id);
+CREATE INDEX IF NOT EXISTS idx_operation_logs_ip_num ON operation_logs (ip_num);
+
+-- 参数和错误信息的全文索引, 使用 trigram 分词以支持中文和任意子串搜索
+CREATE VIRTUAL TABLE IF NOT EXISTS operation_logs_fts USING fts5(
+    params, error,
+    content = 'operation_logs', content_rowid = 'id',
+    tokenize = 'trigram'
+);
+CREATE TRIGGER IF NOT EXISTS operation_logs_fts_insert AFTER INSERT ON operation_logs BEGIN
+    INSERT INTO operation_logs_fts (rowid, params, error) VALUES (new.id, new.params, new.error);
+END;
+CREATE TRIGGER IF NOT EXISTS operation_logs_fts_delete AFTER DELETE ON operation_logs BEGIN
+    INSERT INTO operation_logs_fts (operation_logs_fts, rowid, params, error)
+    VALUES ('delete', old.id, old.params, old.error);
+END;
+CREATE TRIGGER IF NOT EXISTS operation_logs_fts_update AFTER UPDATE OF params, error ON operation_logs BEGIN
+    INSERT INTO operation_logs_fts (operation_logs_fts, rowid, params, error)
+    VALUES ('delete', old.id, old.params, old.error);
+    INSERT INTO operation_logs_fts (rowid, params, error) VALUES (new.id, new.params, new.error);
+END;
+-- 为已有日志建立索引
+INSERT INTO operation_logs_fts (operation_logs_fts) VALUES ('rebuild');
+
+CREATE TABLE IF NOT EXISTS operation_log_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    log_id INTEGER NOT NULL,
+    entity TEXT NOT NULL,
+    entity_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    before TEXT,
+    after TEXT,
+    diff TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (log_id) REFERENCES operation_logs (id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_operation_log_changes_log_id ON operation_log_changes (log_id);
+
+CREATE TABLE IF NOT EXISTS operation_log_checkpoints (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    last_log_id INTEGER NOT NULL,
+    last_hash TEXT NOT NULL,
+    purged_count INTEGER NOT NULL,
+    cutoff TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_by TEXT,
+    archive TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- 归档日志导入表, 用于排查已归档的日志
+CREATE TABLE IF NOT EXISTS archived_operation_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    archive TEXT NOT NULL,
+    original_id INTEGER NOT NULL,
+    user_id INTEGER,
+    username TEXT,
+    module TEXT,
+    operation TEXT,
+    method TEXT,
+    path TEXT,
+    params TEXT,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    status INTEGER,
+    error TEXT,
+    response TEXT,
+    duration_ms INTEGER,
+    created_at DATETIME,
+    prev_hash TEXT,
+    hash TEXT,
+    changes TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_archived_operation_logs_archive ON archived_operation_logs (archive);
+
+CREATE TABLE IF NOT EXISTS operation_log_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    method TEXT NOT NULL DEFAULT '*',
+    path TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('include', 'exclude')),
+    priority INTEGER NOT NULL DEFAULT 0,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    description TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+-- 默认规则与原有行为一致: 记录导出, 不记录查看操作日志本身; 已有规则时不再添加
+INSERT INTO operation_log_rules (method, path, action, priority, description)
+SELECT 'GET', '/users/export', 'include', 100, '记录用户导出'
+WHERE NOT EXISTS (SELECT 1 FROM operation_log_rules)
+UNION ALL
+SELECT 'GET', '/operation-logs/export', 'include', 100, '记录操作日志导出'
+WHERE NOT EXISTS (SELECT 1 FROM operation_log_rules)
+UNION ALL
+SELECT '*', '/operation-logs/**', 'exclude', 10, '不记录操作日志管理接口'
+WHERE NOT EXISTS (SELECT 1 FROM operation_log_rules);
+-- 分片上传的每个分片都是一次请求, 默认只记录创建和完成会话
+INSERT INTO operation_log_rules (method, path, action, priority, description)
+SELECT 'PUT', '/upload/sessions/*/chunks/*', 'exclude', 10, '不记录分片上传的单个分片'
+WHERE NOT EXISTS (
+    SELECT 1 FROM operation_log_rules WHERE path = '/upload/sessions/*/chunks/*'
+);
+
+-- 登录日志, 登录失败时 user_id 为空
+CREATE TABLE IF NOT EXISTS login_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,
+    username TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    failure_reason TEXT,
+    ip TEXT,
+    user_agent TEXT,
+    request_id TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_login_logs_created_at ON login_logs (created_at);
+CREATE INDEX IF NOT EXISTS idx_login_logs_user_id ON login_logs (user_id);
+CREATE INDEX IF NOT EXISTS idx_login_logs_username ON login_logs (username);
+
+-- 上传文件登记表, blob_key 为空的是去重之前上传的文件, 直接按 storage_name 保存
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    storage_name TEXT NOT NULL UNIQUE,
+    original_name TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    mime TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    purpose TEXT NOT NULL,
+    visibility TEXT NOT NULL DEFAULT 'private',
+    blob_key TEXT,
+    uploader_id INTEGER,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (uploader_id) REFERENCES users (id) ON DELETE SET NULL
+);
+CREATE INDEX IF NOT EXISTS idx_files_uploader_id ON files (uploader_id);
+CREATE INDEX IF NOT EXISTS idx_files_sha256 ON files (sha256);
+CREATE INDEX IF NOT EXISTS idx_files_created_at ON files (created_at);
+CREATE INDEX IF NOT EXISTS idx_files_blob_key ON files (blob_key);
+
+-- 文件的派生版本, 例如头像的各尺寸缩略图, 随原文件一起删除
+CREATE TABLE IF NOT EXISTS file_variants (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_id INTEGER NOT NULL,
+    variant TEXT NOT NULL,
+    storage_name TEXT NOT NULL UNIQUE,
+    blob_key TEXT,
+    size INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (file_id, variant),
+    FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_file_variants_blob_key ON file_variants (blob_key);
+
+-- 按内容去重保存的文件, 多次上传相同内容时共用同一份存储;
+-- last_used_at 在每次被引用时更新, 没有引用且超过宽限期后由清理任务删除
+CREATE TABLE IF NOT EXISTS file_blobs (
+    key TEXT PRIMARY KEY,
+    sha256 TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    mime TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_file_blobs_sha256 ON file_blobs (sha256);
+
+-- 分片上传会话, 分片内容暂存在本地目录, 合并后写入存储
+CREATE TABLE IF NOT EXISTS upload_sessions (
+    id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    purpose TEXT NOT NULL,
+    visibility TEXT NOT NULL DEFAULT 'private',
+    file_name TEXT NOT NULL,
+    total_size INTEGER NOT NULL,
+    chunk_size INTEGER NOT NULL,
+    total_chunks INTEGER NOT NULL,
+    sha256 TEXT,
+    status TEXT NOT NULL DEFAULT 'uploading',
+    file_id INTEGER,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE SET NULL
+);
+CREATE INDEX IF NOT EXISTS idx_upload_sessions_user_id ON upload_sessions (user_id);
+CREATE INDEX IF NOT EXISTS idx_upload_sessions_expires_at ON upload_sessions (expires_at);
+
+CREATE TABLE IF NOT EXISTS upload_session_chunks (
+    session_id TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (session_id, chunk_index),
+    FOREIGN KEY (session_id) REFERENCES upload_sessions (id) ON DELETE CASCADE
+);
@@ -0,0 +1,20 @@
+-- 清洗历史日志参数的记录, 每次清洗为一条按顺序串联哈希的事件, 校验时据此接受内容被脱敏的日志
+CREATE TABLE IF NOT EXISTS operation_log_redactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    reason TEXT NOT NULL,
+    redacted_count INTEGER NOT NULL,
+    created_at DATETIME NOT NULL,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+
+-- 被清洗的日志, original_hash 为日志原有的哈希, redacted_hash 为按清洗后内容计算的哈希
+CREATE TABLE IF NOT EXISTS operation_log_redacted_rows (
+    redaction_id INTEGER NOT NULL REFERENCES operation_log_redactions(id),
+    log_id INTEGER NOT NULL,
+    original_hash TEXT NOT NULL,
+    redacted_hash TEXT NOT NULL,
+    PRIMARY KEY (redaction_id, log_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_operation_log_redacted_rows_log_id ON operation_log_redacted_rows(log_id);
@@ -0,0 +1,5 @@
+-- 按地址范围查询改用同时支持 IPv4 和 IPv6 的排序键(32位十六进制), 启动时为已有日志补充
+DROP INDEX IF EXISTS idx_operation_logs_ip_num;
+ALTER TABLE operation_logs DROP COLUMN ip_num;
+ALTER TABLE operation_logs ADD COLUMN ip_key TEXT;
+CREATE INDEX IF NOT EXISTS idx_operation_logs_ip_key ON operation_logs (ip_key);
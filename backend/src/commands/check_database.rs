@@ -0,0 +1,72 @@
+use crate::repositories::Repositories;
+
+// 每组孤立数据最多列出的行号
+const MAX_LISTED_ROWS: usize = 20;
+
+// 删除孤立数据可能级联产生新的孤立数据, 最多重复清理的次数
+const MAX_FIX_ROUNDS: usize = 5;
+
+// 检查数据库文件是否损坏以及是否有违反外键约束的数据, 发现问题时返回错误
+pub async fn run(repos: &Repositories) -> Result<(), String> {
+    // 只有 SQLite 需要检查数据库文件, PostgreSQL 和 MySQL 由数据库服务保证
+    let integrity = repos
+        .schema
+        .integrity_check()
+        .await
+        .map_err(|e| e.to_string())?;
+    let corrupted = match &integrity {
+        Some(integrity) if integrity.iter().any(|line| line != "ok") => {
+            for line in integrity {
+                println!("[Database] {}", line);
+            }
+            true
+        }
+        Some(_) => {
+            println!("[Database] Integrity check passed");
+            false
+        }
+        None => false,
+    };
+
+    let orphans = repos
+        .schema
+        .orphaned_rows()
+        .await
+        .map_err(|e| e.to_string())?;
+    for orphan in &orphans {
+        let rowids = repos
+            .schema
+            .orphaned_rowids(orphan, MAX_LISTED_ROWS as i64)
+            .await
+            .map_err(|e| e.to_string())?;
+        let more = if orphan.count as usize > rowids.len() {
+            ", ..."
+        } else {
+            ""
+        };
+        println!(
+            "[Database] {} rows in {} reference missing rows in {} (rowid {:?}{})",
+            orphan.count, orphan.table_name, orphan.parent, rowids, more
+        );
+    }
+    if orphans.is_empty() {
+        println!("[Database] No orphaned rows found");
+    }
+
+    let orphaned: i64 = orphans.iter().map(|o| o.count).sum();
+    match (corrupted, orphaned) {
+        (false, 0) => Ok(()),
+        (true, _) => Err("数据库完整性检查未通过".to_string()),
+        (false, count) => Err(format!("发现 {} 行违反外键约束的数据", count)),
+    }
+}
+
+// 清理违反外键约束的数据: 外键为 ON DELETE SET NULL 或可为空且没有级联删除时置空,
+// 例如上级权限已删除的权限改为顶级权限; 其余(级联删除的关联数据)直接删除. 在一个事务中执行
+pub async fn fix(repos: &Repositories) -> Result<(), String> {
+    repos
+        .schema
+        .fix_orphaned_rows(MAX_FIX_ROUNDS)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,42 @@
+use crate::config::migrations;
+use crate::repositories::Repositories;
+
+pub async fn run(repos: &Repositories) -> Result<(), String> {
+    let applied = migrations::run_pending(repos)
+        .await
+        .map_err(|e| e.to_string())?;
+    if applied.is_empty() {
+        println!("[Migration] Database is up to date");
+    } else {
+        println!("[Migration] Applied {} migrations", applied.len());
+    }
+    Ok(())
+}
+
+// 列出所有迁移及执行状态, 同时校验已执行迁移的内容
+pub async fn status(repos: &Repositories) -> Result<(), String> {
+    let pending = migrations::pending(repos)
+        .await
+        .map_err(|e| e.to_string())?;
+    let applied = repos
+        .schema
+        .applied_migrations()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for migration in migrations::migrations(repos.schema.kind()) {
+        match applied.iter().find(|a| a.version == migration.version) {
+            Some(record) => println!(
+                "{:04}_{}  applied at {} ({}ms)",
+                migration.version, migration.name, record.applied_at, record.execution_ms
+            ),
+            None => println!("{:04}_{}  pending", migration.version, migration.name),
+        }
+    }
+    println!(
+        "[Migration] {} applied, {} pending",
+        applied.len(),
+        pending.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,71 @@
+pub mod check_database;
+pub mod migrate;
+pub mod scrub_operation_logs;
+
+use crate::config::seed;
+use crate::repositories::Repositories;
+use crate::services::files::sweep_orphan_files;
+use crate::services::retention::run_operation_log_retention;
+use crate::services::upload_session::purge_expired_sessions;
+
+// 迁移命令在初始化数据库之前执行, 其他命令需要完成初始化
+pub fn is_migration_command(command: &str) -> bool {
+    matches!(command, "migrate" | "migrate-status")
+}
+
+// 命令行子命令, 例如: cargo run -- scrub-operation-logs
+pub async fn run(command: &str, repos: &Repositories) -> Result<(), String> {
+    match command {
+        "migrate" => migrate::run(repos).await,
+        "migrate-status" => migrate::status(repos).await,
+        // 忽略版本号重新写入初始数据, 用于恢复被误删或修改的内置菜单和权限
+        "seed" => {
+            let (seed, checksum) = seed::load().map_err(|e| e.to_string())?;
+            seed::apply(repos, &seed, &checksum)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        // --fix 先清理违反外键约束的数据再检查
+        "check-database" => {
+            if std::env::args().nth(2).as_deref() == Some("--fix") {
+                check_database::fix(repos).await?;
+            }
+            check_database::run(repos).await
+        }
+        // 第二个参数为清洗原因, 记录在清洗事件中
+        "scrub-operation-logs" => {
+            let reason = std::env::args()
+                .nth(2)
+                .unwrap_or_else(|| "按当前脱敏规则清洗历史日志".to_string());
+            scrub_operation_logs::run(repos, &reason)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        "archive-operation-logs" => {
+            let result = run_operation_log_retention(repos)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "[Retention] Archived and purged {} operation logs into {:?}",
+                result.purged, result.archives
+            );
+            Ok(())
+        }
+        "sweep-files" => {
+            let sessions = purge_expired_sessions(repos)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("[File Sweep] Removed {} expired upload sessions", sessions);
+            let result = sweep_orphan_files(repos).await.map_err(|e| e.to_string())?;
+            println!(
+                "[File Sweep] Removed {} orphan files ({} bytes) and {} unused blobs ({} bytes)",
+                result.removed, result.bytes, result.blobs, result.blob_bytes
+            );
+            Ok(())
+        }
+        _ => Err(format!(
+            "未知命令: {}\n可用命令: migrate, migrate-status, seed, check-database, scrub-operation-logs, archive-operation-logs, sweep-files",
+            command
+        )),
+    }
+}
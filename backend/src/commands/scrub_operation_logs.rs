@@ -0,0 +1,94 @@
+use serde_json::Value;
+
+use crate::config::operation_log::{operation_log_config, RedactRule};
+use crate::middleware::operation_log::get_module_name;
+use crate::models::OperationLog;
+use crate::repositories::Repositories;
+use crate::services::operation_log::record_redaction;
+use crate::utils::redact::{redact_json, truncate_params, REDACTED};
+
+const BATCH_SIZE: i64 = 500;
+
+// 对历史操作日志中的参数重新执行脱敏.
+// 不重新计算哈希链, 被修改的日志记录为一次清洗事件, 校验时按清洗记录确认
+pub async fn run(repos: &Repositories, reason: &str) -> Result<(), sqlx::Error> {
+    let mut last_id = 0;
+    let mut scanned = 0;
+    let mut scrubbed_logs = Vec::new();
+
+    loop {
+        let rows = repos
+            .operation_logs
+            .with_params_after(last_id, BATCH_SIZE)
+            .await?;
+
+        if rows.is_empty() {
+            break;
+        }
+
+        for mut row in rows {
+            last_id = row.id;
+            scanned += 1;
+
+            let original = row.params.clone().unwrap_or_default();
+            let scrubbed = scrub(&row, &original);
+            if scrubbed != original {
+                row.params = Some(scrubbed);
+                scrubbed_logs.push(row);
+            }
+        }
+    }
+
+    let redaction_id = record_redaction(repos, reason, &scrubbed_logs).await?;
+    println!(
+        "[Scrub] Scanned {} operation log rows, redacted {}",
+        scanned,
+        scrubbed_logs.len()
+    );
+    if let Some(redaction_id) = redaction_id {
+        println!(
+            "[Scrub] Recorded redaction {} (reason: {}), the hash chain is unchanged",
+            redaction_id, reason
+        );
+    }
+    Ok(())
+}
+
+fn scrub(row: &OperationLog, original: &str) -> String {
+    let config = operation_log_config();
+    let method = row.method.clone();
+
+    let mut params = match serde_json::from_str::<Value>(original) {
+        Ok(params) => params,
+        // 无法解析的参数无法判断内容, 整体替换
+        Err(_) => return serde_json::to_string(&Value::String(REDACTED.to_string())).unwrap(),
+    };
+
+    // 较早的记录没有保存请求路径, 按模块名匹配路由规则
+    let module = row.module.clone();
+    let rules: Vec<&RedactRule> = config
+        .redact_rules
+        .iter()
+        .filter(|rule| match row.path {
+            Some(ref path) => rule.matches(&method, path),
+            None => {
+                rule.method.eq_ignore_ascii_case(&method) && get_module_name(&rule.path) == module
+            }
+        })
+        .collect();
+    for rule in rules {
+        if rule.redacts_whole_body() {
+            if !params["body"].is_null() {
+                params["body"] = Value::String(REDACTED.to_string());
+            }
+        } else {
+            redact_json(&mut params["body"], &rule.fields);
+        }
+    }
+
+    // 字段名规则与新日志一致
+    redact_json(&mut params, &config.redact_keys);
+
+    let scrubbed = serde_json::to_string(&params).unwrap_or_default();
+    truncate_params(scrubbed, config.max_params_bytes)
+}
@@ -1,531 +1,232 @@
-use crate::utils::password::hash_password;
-use sqlx::sqlite::SqlitePool;
-use sqlx::Row;
+use crate::config::operation_log::env_usize;
+use crate::config::{migrations, seed};
+use crate::repositories::Repositories;
+use crate::services::operation_log::{backfill_ip_keys, seal_legacy_logs};
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool};
+use sqlx::pool::PoolOptions;
+use sqlx::postgres::{PgConnectOptions, PgPool};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqliteSynchronous};
 use std::env;
 use std::fs;
-use std::path::Path;
-use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+const DEFAULT_DATABASE_URL: &str = "sqlite:data/data.db";
+
+// 默认的 SQLite 连接设置: WAL 模式下读写互不阻塞, 并且 NORMAL 同步模式不会在断电时损坏数据库;
+// 写入冲突时最多等待 5 秒
+const DEFAULT_JOURNAL_MODE: &str = "wal";
+const DEFAULT_SYNCHRONOUS: &str = "normal";
+const DEFAULT_BUSY_TIMEOUT_MS: usize = 5000;
+const DEFAULT_MAX_CONNECTIONS: usize = 10;
+const DEFAULT_MIN_CONNECTIONS: usize = 0;
+const DEFAULT_ACQUIRE_TIMEOUT_SECS: usize = 30;
+const DEFAULT_IDLE_TIMEOUT_SECS: usize = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DatabaseKind {
+    Sqlite,
+    Postgres,
+    MySql,
+}
 
-pub async fn create_pool() -> Result<SqlitePool, sqlx::Error> {
-    // 创建 data 目录
-    let data_dir = Path::new("data");
-    if !data_dir.exists() {
-        fs::create_dir_all(data_dir).expect("Failed to create data directory");
+impl DatabaseKind {
+    pub fn from_url(url: &str) -> Option<Self> {
+        match url.split(':').next()? {
+            "sqlite" => Some(DatabaseKind::Sqlite),
+            "postgres" | "postgresql" => Some(DatabaseKind::Postgres),
+            "mysql" | "mariadb" => Some(DatabaseKind::MySql),
+            _ => None,
+        }
     }
 
-    // 创建数据库文件
-    let db_path = data_dir.join("data.db");
-    if !db_path.exists() {
-        fs::File::create(&db_path).expect("Failed to create database file");
+    // 同时也是 migrations 下对应方言的目录名
+    pub fn name(&self) -> &'static str {
+        match self {
+            DatabaseKind::Sqlite => "sqlite",
+            DatabaseKind::Postgres => "postgres",
+            DatabaseKind::MySql => "mysql",
+        }
     }
-
-    let database_url = format!("sqlite:{}", db_path.display());
-    println!("Connected to database at: {}", database_url);
-
-    let pool = SqlitePool::connect(&database_url).await?;
-    init_database(&pool).await?;
-    Ok(pool)
 }
 
-pub async fn init_database(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    // 创建菜单表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS menus (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            parent_id INTEGER,
-            name TEXT NOT NULL,
-            path TEXT NOT NULL,
-            component TEXT NOT NULL,
-            title TEXT NOT NULL,
-            icon TEXT,
-            sort INTEGER NOT NULL DEFAULT 0,
-            is_hidden BOOLEAN NOT NULL DEFAULT FALSE,
-            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-    println!("Created menus table");
-
-    // 创建用户表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            username TEXT NOT NULL UNIQUE,
-            password TEXT NOT NULL,
-            nickname TEXT NOT NULL,
-            email TEXT,
-            avatar TEXT,
-            status INTEGER NOT NULL DEFAULT 1,
-            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-    println!("Created users table");
-
-    // 创建角色表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS roles (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            code TEXT NOT NULL UNIQUE,
-            description TEXT,
-            status INTEGER NOT NULL DEFAULT 1,
-            color_start TEXT,
-            color_end TEXT,
-            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-    println!("Created roles table");
-
-    // 创建权限表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS permissions (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            code TEXT NOT NULL UNIQUE,
-            type_name TEXT NOT NULL DEFAULT 'PAGE',
-            resource TEXT,
-            action TEXT,
-            parent_id INTEGER,
-            sort INTEGER DEFAULT 0,
-            description TEXT,
-            color_start TEXT,
-            color_end TEXT,
-            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (parent_id) REFERENCES permissions(id)
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-    println!("Created permissions table");
-
-    // 创建角色-权限关联表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS role_permissions (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            role_id INTEGER NOT NULL,
-            permission_id INTEGER NOT NULL,
-            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
-            FOREIGN KEY (permission_id) REFERENCES permissions (id) ON DELETE CASCADE,
-            UNIQUE (role_id, permission_id)
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-    println!("Created role_permissions table");
-
-    // 创建用户-角色关联表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS user_roles (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            user_id INTEGER NOT NULL,
-            role_id INTEGER NOT NULL,
-            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
-            FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
-            UNIQUE (user_id, role_id)
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-    println!("Created user_roles table");
-
-    // 创建操作日志表
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS operation_logs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            user_id INTEGER,
-            username TEXT,
-            module TEXT,
-            operation TEXT,
-            method TEXT,
-            params TEXT,
-            ip TEXT,
-            status INTEGER,
-            error TEXT,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-    println!("Created operation_logs table");
-
-    // 检查是否已有菜单数据
-    let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM menus")
-        .fetch_one(pool)
-        .await?;
-
-    // 如果没有数据，添加初始菜单
-    if count == 0 {
-        println!("Adding initial menu data...");
-        // 添加仪表盘菜单
-        sqlx::query(
-            r#"
-            INSERT INTO menus (name, path, component, title, icon, sort, is_hidden)
-            VALUES ('Dashboard', '/', '../views/Dashboard.vue', '仪表盘', 'Histogram', 0, false)
-            "#,
-        )
-        .execute(pool)
-        .await?;
-
-        // 添加系统管理菜单
-        let system_id = sqlx::query(
-            r#"
-            INSERT INTO menus (name, path, component, title, icon, sort, is_hidden)
-            VALUES ('System', '/system', 'LAYOUT', '系统管理', 'Setting', 1, false)
-            RETURNING id
-            "#,
-        )
-        .fetch_one(pool)
-        .await?
-        .get::<i64, _>("id");
-
-        // 添加菜单管理子菜单
-        sqlx::query(
-            r#"
-            INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
-            VALUES (?, 'MenuList', '/menus', '../views/system/MenuList.vue', '菜单管理', 'Menu', 0, false)
-            "#,
-        )
-        .bind(system_id)
-        .execute(pool)
-        .await?;
-
-        // 添加用户管理子菜单
-        sqlx::query(
-            r#"
-            INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
-            VALUES (?, 'UserList', '/users', '../views/system/UserList.vue', '用户管理', 'User', 1, false)
-            "#,
-        )
-        .bind(system_id)
-        .execute(pool)
-        .await?;
-
-        // 添加角色管理子菜单
-        sqlx::query(
-            r#"
-            INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
-            VALUES (?, 'RoleList', '/roles', '../views/system/RoleList.vue', '角色管理', 'UserFilled', 2, false)
-            "#,
-        )
-        .bind(system_id)
-        .execute(pool)
-        .await?;
-
-        // 添加权限管理子菜单
-        sqlx::query(
-            r#"
-            INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
-            VALUES (?, 'PermissionList', '/permissions', '../views/system/PermissionList.vue', '权限管理', 'Lock', 3, false)
-            "#,
-        )
-        .bind(system_id)
-        .execute(pool)
-        .await?;
-
-        // 添加操作日志子菜单
-        sqlx::query(
-            r#"
-            INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
-            VALUES (?, 'OperationLogList', '/operation-logs', '../views/system/OperationLogList.vue', '操作日志', 'Document', 4, false)
-            "#,
-        )
-        .bind(system_id)
-        .execute(pool)
-        .await?;
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub journal_mode: SqliteJournalMode,
+    pub synchronous: SqliteSynchronous,
+    pub foreign_keys: bool,
+    pub busy_timeout: Duration,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    // 为空表示空闲连接不会被关闭
+    pub idle_timeout: Option<Duration>,
+}
 
-        // 添加个人信息设置菜单
-        sqlx::query(
-            r#"
-            INSERT INTO menus (name, path, component, title, icon, sort, is_hidden)
-            VALUES ('Profile', '/profile', '../views/user/Profile.vue', '个人信息', 'User', 99, true)
-            "#,
-        )
-        .execute(pool)
-        .await?;
-        println!("Added initial menu data");
+impl DatabaseConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let url = var("DATABASE_URL")
+            .map(|v| v.trim().to_string())
+            .unwrap_or_else(|| DEFAULT_DATABASE_URL.to_string());
+
+        let journal_mode = var("DB_JOURNAL_MODE").unwrap_or_else(|| DEFAULT_JOURNAL_MODE.into());
+        let journal_mode = SqliteJournalMode::from_str(journal_mode.trim())
+            .map_err(|_| format!("不支持的 DB_JOURNAL_MODE: {}", journal_mode))?;
+        let synchronous = var("DB_SYNCHRONOUS").unwrap_or_else(|| DEFAULT_SYNCHRONOUS.into());
+        let synchronous = SqliteSynchronous::from_str(synchronous.trim())
+            .map_err(|_| format!("不支持的 DB_SYNCHRONOUS: {}", synchronous))?;
+        let foreign_keys = match var("DB_FOREIGN_KEYS").as_deref().map(str::trim) {
+            None | Some("1") | Some("true") => true,
+            Some("0") | Some("false") => false,
+            Some(other) => return Err(format!("不支持的 DB_FOREIGN_KEYS: {}", other)),
+        };
+
+        let max_connections = env_usize("DB_MAX_CONNECTIONS", DEFAULT_MAX_CONNECTIONS).max(1);
+        let min_connections =
+            env_usize("DB_MIN_CONNECTIONS", DEFAULT_MIN_CONNECTIONS).min(max_connections);
+        let busy_timeout_ms = env_usize("DB_BUSY_TIMEOUT_MS", DEFAULT_BUSY_TIMEOUT_MS);
+        let acquire_timeout_secs =
+            env_usize("DB_ACQUIRE_TIMEOUT_SECS", DEFAULT_ACQUIRE_TIMEOUT_SECS).max(1);
+        let idle_timeout_secs = env_usize("DB_IDLE_TIMEOUT_SECS", DEFAULT_IDLE_TIMEOUT_SECS);
+
+        Ok(Self {
+            url,
+            journal_mode,
+            synchronous,
+            foreign_keys,
+            busy_timeout: Duration::from_millis(busy_timeout_ms as u64),
+            max_connections: max_connections as u32,
+            min_connections: min_connections as u32,
+            acquire_timeout: Duration::from_secs(acquire_timeout_secs as u64),
+            idle_timeout: (idle_timeout_secs > 0)
+                .then(|| Duration::from_secs(idle_timeout_secs as u64)),
+        })
     }
+}
 
-    // 检查是否已有用户数据
-    let user_count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users")
-        .fetch_one(pool)
-        .await?;
-
-    // 如果没有数据，添加默认管理员账号
-    if user_count == 0 {
-        println!("Adding default admin user...");
-        let password_hash = hash_password("admin123");
-        sqlx::query(
-            r#"
-            INSERT INTO users (username, password, nickname, status)
-            VALUES ('admin', ?, '管理员', 1)
-            "#,
-        )
-        .bind(&password_hash)
-        .execute(pool)
-        .await?;
-        println!("Created admin user with password: admin123");
+// 按 DATABASE_URL 连接数据库并创建对应方言的仓储
+pub async fn connect() -> Result<Repositories, sqlx::Error> {
+    let config = DatabaseConfig::from_env().map_err(|e| sqlx::Error::Configuration(e.into()))?;
+    let kind = DatabaseKind::from_url(&config.url).ok_or_else(|| {
+        sqlx::Error::Configuration(
+            "无法识别的 DATABASE_URL, 支持 sqlite:、postgres: 和 mysql:".into(),
+        )
+    })?;
+    match kind {
+        DatabaseKind::Sqlite => connect_sqlite(&config).await.map(Repositories::sqlite),
+        DatabaseKind::Postgres => connect_postgres(&config).await.map(Repositories::postgres),
+        DatabaseKind::MySql => connect_mysql(&config).await.map(Repositories::mysql),
     }
+}
 
-    // 检查是否已有权限数据
-    let permission_count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM permissions")
-        .fetch_one(pool)
-        .await?;
-
-    // 如果没有数据，添加初始权限
-    if permission_count == 0 {
-        println!("Adding initial permissions...");
-        // 系统管理权限
-        let system_id = sqlx::query_scalar::<_, i64>(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, sort, description, color_start, color_end)
-            VALUES ('系统管理', 'system', 'MENU', '/system', 'VIEW', 1, '系统管理相关权限', '#9C27B0', '#BA68C8')
-            RETURNING id
-            "#,
-        )
-        .fetch_one(pool)
-        .await?;
-
-        // 菜单管理
-        let menu_id = sqlx::query_scalar::<_, i64>(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
-            VALUES ('菜单管理', 'system:menu', 'PAGE', '/system/menus', 'VIEW', ?, 1, '菜单的增删改查权限', '#FF5722', '#FF7043')
-            RETURNING id
-            "#,
-        )
-        .bind(system_id)
-        .fetch_one(pool)
-        .await?;
-
-        // 菜单管理API权限
-        sqlx::query(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description)
-            VALUES 
-                ('查看菜单', 'system:menu:view', 'API', '/api/menus', 'GET', ?, 1, '查看菜单列表和详情'),
-                ('创建菜单', 'system:menu:create', 'API', '/api/menus', 'POST', ?, 2, '创建新菜单'),
-                ('编辑菜单', 'system:menu:edit', 'API', '/api/menus/*', 'PUT', ?, 3, '修改菜单信息'),
-                ('删除菜单', 'system:menu:delete', 'API', '/api/menus/*', 'DELETE', ?, 4, '删除菜单')
-            "#,
-        )
-        .bind(menu_id)
-        .bind(menu_id)
-        .bind(menu_id)
-        .bind(menu_id)
-        .execute(pool)
-        .await?;
-
-        // 用户管理
-        let user_id = sqlx::query_scalar::<_, i64>(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
-            VALUES ('用户管理', 'system:user', 'PAGE', '/system/users', 'VIEW', ?, 2, '用户的增删改查权限', '#1976D2', '#42A5F5')
-            RETURNING id
-            "#,
-        )
-        .bind(system_id)
-        .fetch_one(pool)
-        .await?;
-
-        // 用户管理API权限
-        sqlx::query(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description)
-            VALUES 
-                ('查看用户', 'system:user:view', 'API', '/api/users', 'GET', ?, 1, '查看用户列表和详情'),
-                ('创建用户', 'system:user:create', 'API', '/api/users', 'POST', ?, 2, '创建新用户'),
-                ('编辑用户', 'system:user:edit', 'API', '/api/users/*', 'PUT', ?, 3, '修改用户信息'),
-                ('删除用户', 'system:user:delete', 'API', '/api/users/*', 'DELETE', ?, 4, '删除用户')
-            "#,
-        )
-        .bind(user_id)
-        .bind(user_id)
-        .bind(user_id)
-        .bind(user_id)
-        .execute(pool)
-        .await?;
-
-        // 角色管理
-        let role_id = sqlx::query_scalar::<_, i64>(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
-            VALUES ('角色管理', 'system:role', 'PAGE', '/system/roles', 'VIEW', ?, 3, '角色的增删改查权限', '#388E3C', '#66BB6A')
-            RETURNING id
-            "#,
-        )
-        .bind(system_id)
-        .fetch_one(pool)
-        .await?;
-
-        // 角色管理API权限
-        sqlx::query(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description)
-            VALUES 
-                ('查看角色', 'system:role:view', 'API', '/api/roles', 'GET', ?, 1, '查看角色列表和详情'),
-                ('创建角色', 'system:role:create', 'API', '/api/roles', 'POST', ?, 2, '创建新角色'),
-                ('编辑角色', 'system:role:edit', 'API', '/api/roles/*', 'PUT', ?, 3, '修改角色信息'),
-                ('删除角色', 'system:role:delete', 'API', '/api/roles/*', 'DELETE', ?, 4, '删除角色')
-            "#,
-        )
-        .bind(role_id)
-        .bind(role_id)
-        .bind(role_id)
-        .bind(role_id)
-        .execute(pool)
-        .await?;
-
-        // 权限管理
-        let perm_id = sqlx::query_scalar::<_, i64>(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
-            VALUES ('权限管理', 'system:permission', 'PAGE', '/system/permissions', 'VIEW', ?, 4, '权限的增删改查权限', '#0097A7', '#26C6DA')
-            RETURNING id
-            "#,
-        )
-        .bind(system_id)
-        .fetch_one(pool)
-        .await?;
-
-        // 权限管理API权限
-        sqlx::query(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description)
-            VALUES 
-                ('查看权限', 'system:permission:view', 'API', '/api/permissions', 'GET', ?, 1, '查看权限列表和详情'),
-                ('创建权限', 'system:permission:create', 'API', '/api/permissions', 'POST', ?, 2, '创建新权限'),
-                ('编辑权限', 'system:permission:edit', 'API', '/api/permissions/*', 'PUT', ?, 3, '修改权限信息'),
-                ('删除权限', 'system:permission:delete', 'API', '/api/permissions/*', 'DELETE', ?, 4, '删除权限')
-            "#,
-        )
-        .bind(perm_id)
-        .bind(perm_id)
-        .bind(perm_id)
-        .bind(perm_id)
-        .execute(pool)
-        .await?;
-
-        // 操作日志
-        let log_id = sqlx::query_scalar::<_, i64>(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
-            VALUES ('操作日志', 'system:log', 'PAGE', '/system/operation-logs', 'VIEW', ?, 5, '操作日志的查看和清空权限', '#5D4037', '#8D6E63')
-            RETURNING id
-            "#,
-        )
-        .bind(system_id)
-        .fetch_one(pool)
-        .await?;
-
-        // 操作日志API权限
-        sqlx::query(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description)
-            VALUES 
-                ('查看日志', 'system:log:view', 'API', '/api/operation-logs', 'GET', ?, 1, '查看操作日志列表'),
-                ('清空日志', 'system:log:clear', 'API', '/api/operation-logs', 'DELETE', ?, 2, '清空操作日志')
-            "#,
-        )
-        .bind(log_id)
-        .bind(log_id)
-        .execute(pool)
-        .await?;
+async fn connect_sqlite(config: &DatabaseConfig) -> Result<SqlitePool, sqlx::Error> {
+    // 这些设置对每个连接生效, 由连接池在建立连接时设置
+    let options = SqliteConnectOptions::from_str(&config.url)?
+        .create_if_missing(true)
+        .journal_mode(config.journal_mode)
+        .synchronous(config.synchronous)
+        .foreign_keys(config.foreign_keys)
+        .busy_timeout(config.busy_timeout);
+
+    // 创建数据库文件所在的目录, 数据库文件由连接时创建
+    if let Some(dir) = options.get_filename().parent() {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            fs::create_dir_all(dir)?;
+        }
+    }
 
-        // 个人信息
-        sqlx::query(
-            r#"
-            INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
-            VALUES ('个人信息', 'system:profile', 'PAGE', '/profile', 'VIEW', ?, 6, '个人信息的查看和修改权限', '#795548', '#A1887F')
-            "#,
-        )
-        .bind(system_id)
-        .execute(pool)
-        .await?;
+    println!(
+        "Connected to database at: {} (journal_mode={:?}, synchronous={:?}, foreign_keys={}, max_connections={})",
+        config.url,
+        config.journal_mode,
+        config.synchronous,
+        config.foreign_keys,
+        config.max_connections
+    );
 
-        println!("Added initial permissions");
-    }
+    pool_options(config).connect_with(options).await
+}
 
-    // 检查是否已有角色数据
-    let role_count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM roles")
-        .fetch_one(pool)
-        .await?;
+// 时间以 UTC 写入和比较, 与 SQLite 的 CURRENT_TIMESTAMP 一致
+async fn connect_postgres(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
+    let options = PgConnectOptions::from_str(&config.url)?.options([("timezone", "UTC")]);
+    // 地址中可能包含密码, 不输出完整地址
+    println!(
+        "Connected to {} database {} at {}:{} (max_connections={})",
+        DatabaseKind::Postgres.name(),
+        options.get_database().unwrap_or("-"),
+        options.get_host(),
+        options.get_port(),
+        config.max_connections
+    );
+    pool_options(config).connect_with(options).await
+}
 
-    // 如果没有数据，添加级管理员角色
-    if role_count == 0 {
-        println!("Adding super admin role...");
-        // 添加超级管理员角色
-        let role_id = sqlx::query_scalar::<_, i64>(
-            r#"
-            INSERT INTO roles (name, code, description, status)
-            VALUES ('超级管理员', 'super_admin', '系统超级管理员，拥有所有权限', 1)
-            RETURNING id
-            "#,
-        )
-        .fetch_one(pool)
-        .await?;
+async fn connect_mysql(config: &DatabaseConfig) -> Result<MySqlPool, sqlx::Error> {
+    let options = MySqlConnectOptions::from_str(&config.url)?
+        .timezone(Some("+00:00".to_string()))
+        .charset("utf8mb4");
+    println!(
+        "Connected to {} database {} at {}:{} (max_connections={})",
+        DatabaseKind::MySql.name(),
+        options.get_database().unwrap_or("-"),
+        options.get_host(),
+        options.get_port(),
+        config.max_connections
+    );
+    pool_options(config).connect_with(options).await
+}
 
-        // 获取所有权ID
-        let permission_ids = sqlx::query_scalar::<_, i64>("SELECT id FROM permissions")
-            .fetch_all(pool)
-            .await?;
+fn pool_options<DB: sqlx::Database>(config: &DatabaseConfig) -> PoolOptions<DB> {
+    PoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout)
+        .idle_timeout(config.idle_timeout)
+}
 
-        // 为超级管理员分配所有权限
-        for permission_id in permission_ids {
-            sqlx::query(
-                r#"
-                INSERT INTO role_permissions (role_id, permission_id)
-                VALUES (?, ?)
-                "#,
-            )
-            .bind(role_id)
-            .bind(permission_id)
-            .execute(pool)
-            .await?;
+// 启动时执行迁移(或在关闭自动迁移时检查是否有未执行的迁移), 然后写入新版本的初始数据
+pub async fn init_database(repos: &Repositories) -> Result<(), String> {
+    if migrations::auto_migrate() {
+        migrations::run_pending(repos)
+            .await
+            .map_err(|e| e.to_string())?;
+    } else {
+        let pending = migrations::pending(repos)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !pending.is_empty() {
+            return Err(format!(
+                "数据库有 {} 个未执行的迁移, 请先执行: backend migrate",
+                pending.len()
+            ));
         }
+    }
 
-        // 为admin用户分配超级管理员角色
-        let admin_id =
-            sqlx::query_scalar::<_, i64>("SELECT id FROM users WHERE username = 'admin'")
-                .fetch_one(pool)
-                .await?;
+    report_orphaned_rows(repos)
+        .await
+        .map_err(|e| e.to_string())?;
+    seed::run_if_newer(repos).await.map_err(|e| e.to_string())?;
+    seal_legacy_logs(repos).await.map_err(|e| e.to_string())?;
+    backfill_ip_keys(repos).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-        sqlx::query(
-            r#"
-            INSERT INTO user_roles (user_id, role_id)
-            VALUES (?, ?)
-            "#,
-        )
-        .bind(admin_id)
-        .bind(role_id)
-        .execute(pool)
-        .await?;
-        println!("Added super admin role and assigned to admin user");
+// 启动时只报告, 不自动删除, 由管理员确认后处理
+async fn report_orphaned_rows(repos: &Repositories) -> Result<(), sqlx::Error> {
+    let orphans = repos.schema.orphaned_rows().await?;
+    for orphan in &orphans {
+        eprintln!(
+            "[Database] {} rows in {} reference missing rows in {}",
+            orphan.count, orphan.table_name, orphan.parent
+        );
+    }
+    if !orphans.is_empty() {
+        eprintln!(
+            "[Database] Run `backend check-database` for details, or `backend check-database --fix` to clean them up"
+        );
     }
-
     Ok(())
 }
@@ -0,0 +1,235 @@
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+
+use crate::config::database::DatabaseKind;
+use crate::repositories::{DbTime, Repositories};
+
+// 数据库迁移按版本号顺序执行, 每个迁移在单独的事务中执行并记录到 schema_migrations.
+// 已发布的迁移不能修改(启动时会校验内容), 结构变化需要新增迁移文件并添加到这里
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: include_str!("../../migrations/sqlite/0001_initial_schema.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "seed_history",
+        sql: include_str!("../../migrations/sqlite/0002_seed_history.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "operation_log_redactions",
+        sql: include_str!("../../migrations/sqlite/0003_operation_log_redactions.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "operation_log_ip_key",
+        sql: include_str!("../../migrations/sqlite/0004_operation_log_ip_key.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "storage_quota_unlimited",
+        sql: include_str!("../../migrations/sqlite/0005_storage_quota_unlimited.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "operation_log_chain_head",
+        sql: include_str!("../../migrations/sqlite/0006_operation_log_chain_head.sql"),
+    },
+];
+
+const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: include_str!("../../migrations/postgres/0001_initial_schema.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "seed_history",
+        sql: include_str!("../../migrations/postgres/0002_seed_history.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "operation_log_redactions",
+        sql: include_str!("../../migrations/postgres/0003_operation_log_redactions.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "operation_log_ip_key",
+        sql: include_str!("../../migrations/postgres/0004_operation_log_ip_key.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "storage_quota_unlimited",
+        sql: include_str!("../../migrations/postgres/0005_storage_quota_unlimited.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "operation_log_chain_head",
+        sql: include_str!("../../migrations/postgres/0006_operation_log_chain_head.sql"),
+    },
+];
+
+const MYSQL_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: include_str!("../../migrations/mysql/0001_initial_schema.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "seed_history",
+        sql: include_str!("../../migrations/mysql/0002_seed_history.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "operation_log_redactions",
+        sql: include_str!("../../migrations/mysql/0003_operation_log_redactions.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "operation_log_ip_key",
+        sql: include_str!("../../migrations/mysql/0004_operation_log_ip_key.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "storage_quota_unlimited",
+        sql: include_str!("../../migrations/mysql/0005_storage_quota_unlimited.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "operation_log_chain_head",
+        sql: include_str!("../../migrations/mysql/0006_operation_log_chain_head.sql"),
+    },
+];
+
+// 每种数据库有各自的迁移文件, 版本号和名称保持一致
+pub fn migrations(kind: DatabaseKind) -> &'static [Migration] {
+    match kind {
+        DatabaseKind::Sqlite => SQLITE_MIGRATIONS,
+        DatabaseKind::Postgres => POSTGRES_MIGRATIONS,
+        DatabaseKind::MySql => MYSQL_MIGRATIONS,
+    }
+}
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.sql.as_bytes()))
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    #[sqlx(try_from = "DbTime")]
+    pub applied_at: String,
+    pub execution_ms: i64,
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    // 数据库中记录的迁移内容与程序中的不一致, 说明已发布的迁移被修改过
+    ChecksumMismatch { version: i64, name: String },
+    // 数据库由更新版本的程序迁移过
+    UnknownVersion { version: i64, name: String },
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::ChecksumMismatch { version, name } => write!(
+                f,
+                "迁移 {:04}_{} 的内容与已执行的版本不一致, 已发布的迁移不能修改",
+                version, name
+            ),
+            MigrationError::UnknownVersion { version, name } => write!(
+                f,
+                "数据库已执行迁移 {:04}_{}, 当前程序中没有该迁移, 请使用更新版本的程序",
+                version, name
+            ),
+            MigrationError::Database(e) => write!(f, "执行迁移失败: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(e: sqlx::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+// DB_AUTO_MIGRATE=false 时启动不执行迁移, 需要先执行 migrate 命令
+pub fn auto_migrate() -> bool {
+    !matches!(
+        env::var("DB_AUTO_MIGRATE").ok().as_deref().map(str::trim),
+        Some("0") | Some("false")
+    )
+}
+
+fn verify(migrations: &[Migration], applied: &[AppliedMigration]) -> Result<(), MigrationError> {
+    for record in applied {
+        match migrations.iter().find(|m| m.version == record.version) {
+            Some(migration) if migration.checksum() == record.checksum => {}
+            Some(_) => {
+                return Err(MigrationError::ChecksumMismatch {
+                    version: record.version,
+                    name: record.name.clone(),
+                })
+            }
+            None => {
+                return Err(MigrationError::UnknownVersion {
+                    version: record.version,
+                    name: record.name.clone(),
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+// 校验已执行的迁移并返回尚未执行的迁移
+pub async fn pending(repos: &Repositories) -> Result<Vec<&'static Migration>, MigrationError> {
+    let migrations = migrations(repos.schema.kind());
+    let applied = repos.schema.applied_migrations().await?;
+    verify(migrations, &applied)?;
+    Ok(migrations
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+        .collect())
+}
+
+pub async fn run_pending(repos: &Repositories) -> Result<Vec<i64>, MigrationError> {
+    let pending = pending(repos).await?;
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // 引入迁移之前创建的数据库已经有表但没有迁移记录, 执行第一个迁移前先补齐旧版本缺少的字段
+    let legacy = repos.schema.is_legacy().await?;
+
+    let mut versions = Vec::new();
+    for migration in pending {
+        let execution_ms = repos
+            .schema
+            .apply_migration(migration, legacy && versions.is_empty())
+            .await?;
+        println!(
+            "[Migration] Applied {:04}_{} ({}ms)",
+            migration.version, migration.name, execution_ms
+        );
+        versions.push(migration.version);
+    }
+    Ok(versions)
+}
@@ -1 +1,6 @@
 pub mod database;
+pub mod migrations;
+pub mod operation_log;
+pub mod seed;
+pub mod storage;
+pub mod upload;
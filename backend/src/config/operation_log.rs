@@ -0,0 +1,197 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+// 默认需要脱敏的字段名模式(不区分大小写, 支持 * 通配符)
+const DEFAULT_REDACT_KEYS: &[&str] = &[
+    "*password*",
+    "*secret*",
+    "*token*",
+    "authorization",
+    "*api_key*",
+];
+
+// 默认的路由脱敏规则, "*" 表示整个请求体都不记录
+const DEFAULT_REDACT_ROUTES: &str = "PATCH /profile/password=*;POST /users=password";
+
+// 默认的参数最大记录长度(字节)
+const DEFAULT_MAX_PARAMS_BYTES: usize = 4096;
+
+// 默认的响应内容最大记录长度(字节)
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 2048;
+
+// 默认的最短保留天数, 清理接口不能删除更新的日志
+const DEFAULT_MIN_RETENTION_DAYS: usize = 90;
+
+// 默认的自动归档策略: 保留 365 天, 不限制条数, 每小时检查一次
+const DEFAULT_RETENTION_DAYS: usize = 365;
+const DEFAULT_RETENTION_MAX_ROWS: usize = 0;
+const DEFAULT_RETENTION_INTERVAL_SECS: usize = 3600;
+const DEFAULT_ARCHIVE_DIR: &str = "data/archives";
+
+// 默认的登录日志保留天数, 登录日志不归档, 过期直接删除
+const DEFAULT_LOGIN_LOG_RETENTION_DAYS: usize = 180;
+
+// 默认的日志写入队列: 队列满时最多等待 100 毫秒, 每 100 条或每秒写入一次
+const DEFAULT_QUEUE_SIZE: usize = 1024;
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_FLUSH_INTERVAL_MS: usize = 1000;
+const DEFAULT_ENQUEUE_TIMEOUT_MS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct RedactRule {
+    pub method: String,
+    pub path: String,
+    pub fields: Vec<String>,
+}
+
+impl RedactRule {
+    pub fn matches(&self, method: &str, path: &str) -> bool {
+        self.method.eq_ignore_ascii_case(method) && path_matches(&self.path, path)
+    }
+
+    pub fn redacts_whole_body(&self) -> bool {
+        self.fields.iter().any(|f| f == "*")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationLogConfig {
+    pub redact_keys: Vec<String>,
+    pub redact_rules: Vec<RedactRule>,
+    pub max_params_bytes: usize,
+    pub max_response_bytes: usize,
+    pub min_retention_days: usize,
+    pub retention_days: usize,
+    pub retention_max_rows: usize,
+    pub retention_interval_secs: u64,
+    pub archive_dir: PathBuf,
+    pub login_log_retention_days: usize,
+    pub queue_size: usize,
+    pub batch_size: usize,
+    pub flush_interval_ms: u64,
+    pub enqueue_timeout_ms: u64,
+}
+
+impl OperationLogConfig {
+    pub fn from_env() -> Self {
+        let redact_keys = match env::var("OPERATION_LOG_REDACT_KEYS") {
+            Ok(keys) => split_list(&keys, ','),
+            Err(_) => DEFAULT_REDACT_KEYS.iter().map(|k| k.to_string()).collect(),
+        };
+
+        let routes = env::var("OPERATION_LOG_REDACT_ROUTES")
+            .unwrap_or_else(|_| DEFAULT_REDACT_ROUTES.to_string());
+        let redact_rules = split_list(&routes, ';')
+            .iter()
+            .filter_map(|rule| parse_redact_rule(rule))
+            .collect();
+
+        let max_params_bytes =
+            env_usize("OPERATION_LOG_MAX_PARAMS_BYTES", DEFAULT_MAX_PARAMS_BYTES);
+        let max_response_bytes = env_usize(
+            "OPERATION_LOG_MAX_RESPONSE_BYTES",
+            DEFAULT_MAX_RESPONSE_BYTES,
+        );
+        let min_retention_days = env_usize(
+            "OPERATION_LOG_MIN_RETENTION_DAYS",
+            DEFAULT_MIN_RETENTION_DAYS,
+        );
+        let retention_days = env_usize("OPERATION_LOG_RETENTION_DAYS", DEFAULT_RETENTION_DAYS);
+        let retention_max_rows = env_usize(
+            "OPERATION_LOG_RETENTION_MAX_ROWS",
+            DEFAULT_RETENTION_MAX_ROWS,
+        );
+        let retention_interval_secs = env_usize(
+            "OPERATION_LOG_RETENTION_INTERVAL_SECS",
+            DEFAULT_RETENTION_INTERVAL_SECS,
+        ) as u64;
+        let archive_dir = PathBuf::from(
+            env::var("OPERATION_LOG_ARCHIVE_DIR").unwrap_or_else(|_| DEFAULT_ARCHIVE_DIR.into()),
+        );
+        let login_log_retention_days =
+            env_usize("LOGIN_LOG_RETENTION_DAYS", DEFAULT_LOGIN_LOG_RETENTION_DAYS);
+        let queue_size = env_usize("OPERATION_LOG_QUEUE_SIZE", DEFAULT_QUEUE_SIZE).max(1);
+        let batch_size = env_usize("OPERATION_LOG_BATCH_SIZE", DEFAULT_BATCH_SIZE).max(1);
+        let flush_interval_ms =
+            env_usize("OPERATION_LOG_FLUSH_INTERVAL_MS", DEFAULT_FLUSH_INTERVAL_MS).max(1) as u64;
+        let enqueue_timeout_ms = env_usize(
+            "OPERATION_LOG_ENQUEUE_TIMEOUT_MS",
+            DEFAULT_ENQUEUE_TIMEOUT_MS,
+        ) as u64;
+
+        Self {
+            redact_keys,
+            redact_rules,
+            max_params_bytes,
+            max_response_bytes,
+            min_retention_days,
+            retention_days,
+            retention_max_rows,
+            retention_interval_secs,
+            archive_dir,
+            login_log_retention_days,
+            queue_size,
+            batch_size,
+            flush_interval_ms,
+            enqueue_timeout_ms,
+        }
+    }
+}
+
+pub fn operation_log_config() -> &'static OperationLogConfig {
+    static CONFIG: OnceLock<OperationLogConfig> = OnceLock::new();
+    CONFIG.get_or_init(OperationLogConfig::from_env)
+}
+
+// 路由规则格式: "METHOD /path/*=field1,field2"
+fn parse_redact_rule(rule: &str) -> Option<RedactRule> {
+    let (route, fields) = rule.split_once('=')?;
+    let (method, path) = route.trim().split_once(' ')?;
+    let fields = split_list(fields, ',');
+    if fields.is_empty() {
+        eprintln!("[Config] Ignoring redact rule without fields: {}", rule);
+        return None;
+    }
+    Some(RedactRule {
+        method: method.trim().to_uppercase(),
+        path: path.trim().to_string(),
+        fields,
+    })
+}
+
+pub fn env_usize(key: &str, default: usize) -> usize {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+pub fn split_list(value: &str, separator: char) -> Vec<String> {
+    value
+        .split(separator)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+// 路径模式中的 * 匹配任意单个路径段, 例如 /users/*; 末尾的 ** 匹配任意多个路径段, 例如 /operation-logs/**
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern_parts: Vec<&str> = pattern.trim_end_matches('/').split('/').collect();
+    let path_parts: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    let any_suffix = pattern_parts.last() == Some(&"**");
+    if any_suffix {
+        pattern_parts.pop();
+    }
+    let length_matches = if any_suffix {
+        path_parts.len() >= pattern_parts.len()
+    } else {
+        path_parts.len() == pattern_parts.len()
+    };
+    length_matches
+        && pattern_parts
+            .iter()
+            .zip(path_parts.iter())
+            .all(|(p, s)| *p == "*" || p == s)
+}
@@ -0,0 +1,167 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+use std::fs;
+
+use crate::repositories::Repositories;
+
+// 内置的初始数据, 可以通过 DB_SEED_FILE 指定其他文件
+const DEFAULT_SEED: &str = include_str!("../../seeds/seed.json");
+
+// 初始数据文件描述菜单、权限、角色及其授权和默认用户, 按编码(菜单按名称)写入:
+// 不存在时创建, 已存在时更新为文件中的内容, 不会删除文件中没有的数据.
+// 新版本增加权限时修改文件并增加 version, 已有数据库在启动时写入新增的内容
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedFile {
+    pub version: i64,
+    #[serde(default)]
+    pub menus: Vec<SeedMenu>,
+    #[serde(default)]
+    pub permissions: Vec<SeedPermission>,
+    #[serde(default)]
+    pub roles: Vec<SeedRole>,
+    #[serde(default)]
+    pub users: Vec<SeedUser>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedMenu {
+    pub name: String,
+    pub parent: Option<String>,
+    pub path: String,
+    pub component: String,
+    pub title: String,
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub sort: i64,
+    #[serde(default)]
+    pub is_hidden: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedPermission {
+    pub code: String,
+    pub name: String,
+    pub parent: Option<String>,
+    #[serde(default = "default_type_name")]
+    pub type_name: String,
+    pub resource: Option<String>,
+    pub action: Option<String>,
+    #[serde(default)]
+    pub sort: i64,
+    pub description: Option<String>,
+    pub color_start: Option<String>,
+    pub color_end: Option<String>,
+}
+
+// permissions 为授予角色的权限编码, "*" 表示所有权限; 只增加授权, 不撤销已有的授权
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedRole {
+    pub code: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default = "default_status")]
+    pub status: i32,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+// 用户只在不存在时创建, 不会覆盖已修改的密码和资料
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedUser {
+    pub username: String,
+    pub password: String,
+    pub nickname: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+fn default_type_name() -> String {
+    "PAGE".to_string()
+}
+
+fn default_status() -> i32 {
+    1
+}
+
+#[derive(Debug)]
+pub enum SeedError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    // 引用了不存在的上级菜单、上级权限或角色等
+    Invalid(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedError::Io(e) => write!(f, "读取初始数据文件失败: {}", e),
+            SeedError::Parse(e) => write!(f, "初始数据文件格式错误: {}", e),
+            SeedError::Invalid(message) => write!(f, "初始数据错误: {}", message),
+            SeedError::Database(e) => write!(f, "写入初始数据失败: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for SeedError {
+    fn from(e: std::io::Error) -> Self {
+        SeedError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SeedError {
+    fn from(e: serde_json::Error) -> Self {
+        SeedError::Parse(e)
+    }
+}
+
+impl From<sqlx::Error> for SeedError {
+    fn from(e: sqlx::Error) -> Self {
+        SeedError::Database(e)
+    }
+}
+
+// 读取并解析初始数据文件, 返回内容和校验和
+pub fn load() -> Result<(SeedFile, String), SeedError> {
+    let content = match env::var("DB_SEED_FILE")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+    {
+        Some(path) => fs::read_to_string(path.trim())?,
+        None => DEFAULT_SEED.to_string(),
+    };
+    let seed = serde_json::from_str::<SeedFile>(&content)?;
+    Ok((seed, hex::encode(Sha256::digest(content.as_bytes()))))
+}
+
+// 启动时调用: 初始数据的版本号大于已写入的版本时才写入, 避免每次启动都覆盖管理员的修改
+pub async fn run_if_newer(repos: &Repositories) -> Result<Option<i64>, SeedError> {
+    let (seed, checksum) = load()?;
+    let applied = repos.schema.seed_version().await?;
+    if seed.version <= applied {
+        return Ok(None);
+    }
+    apply(repos, &seed, &checksum).await?;
+    Ok(Some(seed.version))
+}
+
+// 在一个事务中写入全部初始数据, 任何一项出错都不会写入
+pub async fn apply(repos: &Repositories, seed: &SeedFile, checksum: &str) -> Result<(), SeedError> {
+    repos.schema.apply_seed(seed, checksum).await?;
+    println!(
+        "[Seed] Applied seed version {} ({} menus, {} permissions, {} roles, {} users)",
+        seed.version,
+        seed.menus.len(),
+        seed.permissions.len(),
+        seed.roles.len(),
+        seed.users.len()
+    );
+    Ok(())
+}
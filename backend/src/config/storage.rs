@@ -0,0 +1,91 @@
+use std::env;
+use std::sync::OnceLock;
+
+use crate::config::operation_log::env_usize;
+
+const DEFAULT_LOCAL_DIR: &str = "uploads";
+const DEFAULT_S3_REGION: &str = "us-east-1";
+
+// 预签名下载地址的默认有效期(秒)
+const DEFAULT_PRESIGN_EXPIRES_SECS: usize = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageBackend {
+    Local,
+    S3,
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    // MinIO 等兼容服务的地址, 为空时使用 AWS 默认地址
+    pub endpoint: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub force_path_style: bool,
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    pub local_dir: String,
+    pub s3: S3Config,
+    pub presign_expires_secs: u64,
+    // 为 true 时下载请求重定向到预签名地址, 否则由服务端转发文件内容
+    pub redirect: bool,
+}
+
+impl StorageConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let flag = |key: &str, default: bool| match var(key).as_deref() {
+            Some("1") | Some("true") => true,
+            Some("0") | Some("false") => false,
+            _ => default,
+        };
+
+        let backend = match var("STORAGE_BACKEND").as_deref() {
+            None | Some("local") => StorageBackend::Local,
+            Some("s3") => StorageBackend::S3,
+            Some(other) => return Err(format!("不支持的存储类型: {}", other)),
+        };
+
+        let s3 = S3Config {
+            bucket: var("S3_BUCKET").unwrap_or_default(),
+            region: var("S3_REGION").unwrap_or_else(|| DEFAULT_S3_REGION.into()),
+            endpoint: var("S3_ENDPOINT"),
+            access_key: var("S3_ACCESS_KEY_ID"),
+            secret_key: var("S3_SECRET_ACCESS_KEY"),
+            // 自定义地址一般是 MinIO, 默认使用路径风格访问
+            force_path_style: flag("S3_FORCE_PATH_STYLE", var("S3_ENDPOINT").is_some()),
+            prefix: var("S3_PREFIX")
+                .map(|p| format!("{}/", p.trim_matches('/')))
+                .unwrap_or_default(),
+        };
+        if backend == StorageBackend::S3 && s3.bucket.is_empty() {
+            return Err("使用 S3 存储时必须设置 S3_BUCKET".into());
+        }
+
+        Ok(Self {
+            backend,
+            local_dir: var("UPLOAD_DIR").unwrap_or_else(|| DEFAULT_LOCAL_DIR.into()),
+            s3,
+            presign_expires_secs: env_usize(
+                "STORAGE_PRESIGN_EXPIRES_SECS",
+                DEFAULT_PRESIGN_EXPIRES_SECS,
+            )
+            .max(1) as u64,
+            redirect: flag("STORAGE_REDIRECT", true),
+        })
+    }
+}
+
+pub fn storage_config() -> Result<&'static StorageConfig, String> {
+    static CONFIG: OnceLock<Result<StorageConfig, String>> = OnceLock::new();
+    CONFIG
+        .get_or_init(StorageConfig::from_env)
+        .as_ref()
+        .map_err(|e| e.clone())
+}
@@ -0,0 +1,190 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::config::operation_log::{env_usize, split_list};
+
+// 默认的上传大小限制(字节): 附件 10MB, 头像 2MB
+const DEFAULT_MAX_SIZE: usize = 10 * 1024 * 1024;
+const DEFAULT_AVATAR_MAX_SIZE: usize = 2 * 1024 * 1024;
+
+// 默认允许的文件类型(扩展名), 不包含 html、svg 等可执行脚本的类型
+const DEFAULT_AVATAR_TYPES: &str = "jpg,png,gif,webp";
+const DEFAULT_ATTACHMENT_TYPES: &str = "jpg,png,gif,webp,pdf,txt,csv,zip,docx,xlsx,pptx";
+
+// 头像处理: 原图最长边不超过 1024 像素, 并生成 64、128、256 像素的方形缩略图
+const DEFAULT_AVATAR_MAX_DIMENSION: usize = 1024;
+const DEFAULT_AVATAR_THUMBNAIL_SIZES: &str = "64,128,256";
+
+// 分片上传的默认设置: 单个文件最大 2GB, 每片 5MB, 单片最大 32MB, 会话 24 小时后过期
+const DEFAULT_CHUNKED_MAX_SIZE: usize = 2 * 1024 * 1024 * 1024;
+const DEFAULT_CHUNK_SIZE: usize = 5 * 1024 * 1024;
+const DEFAULT_MAX_CHUNK_SIZE: usize = 32 * 1024 * 1024;
+const DEFAULT_SESSION_TTL_HOURS: usize = 24;
+const DEFAULT_SESSION_DIR: &str = "data/upload-sessions";
+
+// 默认的孤立文件清理策略: 上传 24 小时后仍未被引用则删除, 每小时检查一次
+const DEFAULT_ORPHAN_GRACE_HOURS: usize = 24;
+const DEFAULT_SWEEP_INTERVAL_SECS: usize = 3600;
+
+// 私有文件签名地址的默认有效期 10 分钟, 最长 7 天
+const DEFAULT_SIGNED_URL_EXPIRES_SECS: usize = 600;
+const DEFAULT_SIGNED_URL_MAX_EXPIRES_SECS: usize = 7 * 24 * 3600;
+const DEFAULT_SIGNING_SECRET_FILE: &str = "data/upload-signing-secret";
+
+// 没有为用户或角色设置配额时的默认存储配额(字节), 0 表示不限制
+const DEFAULT_QUOTA: usize = 0;
+
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    pub max_size: u64,
+    pub avatar_max_size: u64,
+    pub avatar_types: Vec<String>,
+    pub attachment_types: Vec<String>,
+    pub avatar_max_dimension: u32,
+    pub avatar_thumbnail_sizes: Vec<u32>,
+    pub chunked_max_size: u64,
+    pub chunk_size: u64,
+    pub max_chunk_size: u64,
+    pub session_ttl_hours: usize,
+    pub session_dir: String,
+    pub orphan_grace_hours: usize,
+    pub sweep_interval_secs: u64,
+    pub default_quota: u64,
+    pub signing_secret: Vec<u8>,
+    pub signed_url_expires_secs: u64,
+    pub signed_url_max_expires_secs: u64,
+}
+
+impl UploadConfig {
+    pub fn from_env() -> Self {
+        let types = |key: &str, default: &str| {
+            split_list(&env::var(key).unwrap_or_else(|_| default.into()), ',')
+                .into_iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect()
+        };
+
+        let signed_url_max_expires_secs = env_usize(
+            "UPLOAD_SIGNED_URL_MAX_EXPIRES_SECS",
+            DEFAULT_SIGNED_URL_MAX_EXPIRES_SECS,
+        )
+        .max(1) as u64;
+
+        Self {
+            max_size: env_usize("UPLOAD_MAX_SIZE", DEFAULT_MAX_SIZE) as u64,
+            avatar_max_size: env_usize("UPLOAD_AVATAR_MAX_SIZE", DEFAULT_AVATAR_MAX_SIZE) as u64,
+            avatar_types: types("UPLOAD_AVATAR_TYPES", DEFAULT_AVATAR_TYPES),
+            attachment_types: types("UPLOAD_ATTACHMENT_TYPES", DEFAULT_ATTACHMENT_TYPES),
+            avatar_max_dimension: env_usize(
+                "UPLOAD_AVATAR_MAX_DIMENSION",
+                DEFAULT_AVATAR_MAX_DIMENSION,
+            )
+            .clamp(16, 8192) as u32,
+            avatar_thumbnail_sizes: thumbnail_sizes(),
+            chunked_max_size: env_usize("UPLOAD_CHUNKED_MAX_SIZE", DEFAULT_CHUNKED_MAX_SIZE) as u64,
+            chunk_size: env_usize("UPLOAD_CHUNK_SIZE", DEFAULT_CHUNK_SIZE) as u64,
+            max_chunk_size: env_usize("UPLOAD_MAX_CHUNK_SIZE", DEFAULT_MAX_CHUNK_SIZE) as u64,
+            session_ttl_hours: env_usize("UPLOAD_SESSION_TTL_HOURS", DEFAULT_SESSION_TTL_HOURS)
+                .max(1),
+            session_dir: env::var("UPLOAD_SESSION_DIR")
+                .unwrap_or_else(|_| DEFAULT_SESSION_DIR.into()),
+            orphan_grace_hours: env_usize("UPLOAD_ORPHAN_GRACE_HOURS", DEFAULT_ORPHAN_GRACE_HOURS),
+            sweep_interval_secs: env_usize(
+                "UPLOAD_SWEEP_INTERVAL_SECS",
+                DEFAULT_SWEEP_INTERVAL_SECS,
+            ) as u64,
+            default_quota: env_usize("UPLOAD_DEFAULT_QUOTA", DEFAULT_QUOTA) as u64,
+            signing_secret: signing_secret(),
+            signed_url_max_expires_secs,
+            signed_url_expires_secs: (env_usize(
+                "UPLOAD_SIGNED_URL_EXPIRES_SECS",
+                DEFAULT_SIGNED_URL_EXPIRES_SECS,
+            ) as u64)
+                .clamp(1, signed_url_max_expires_secs),
+        }
+    }
+}
+
+// 未配置签名密钥时使用保存在 UPLOAD_SIGNING_SECRET_FILE 中的密钥, 文件不存在则生成一次并保存,
+// 保证重启后之前签发的地址仍然有效; 多实例部署时必须配置相同的密钥或共享该文件
+fn signing_secret() -> Vec<u8> {
+    if let Ok(secret) = env::var("UPLOAD_SIGNING_SECRET") {
+        if !secret.trim().is_empty() {
+            return secret.into_bytes();
+        }
+    }
+
+    let path = env::var("UPLOAD_SIGNING_SECRET_FILE")
+        .unwrap_or_else(|_| DEFAULT_SIGNING_SECRET_FILE.into());
+    match load_or_create_secret(Path::new(&path)) {
+        Ok(secret) => secret,
+        Err(e) => {
+            // 无法得到稳定的密钥时拒绝启动, 避免每次重启都使已签发的地址失效
+            eprintln!(
+                "[Upload] UPLOAD_SIGNING_SECRET is not set and the secret file {} is unusable: {}",
+                path, e
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn load_or_create_secret(path: &Path) -> io::Result<Vec<u8>> {
+    match fs::read_to_string(path) {
+        Ok(secret) if !secret.trim().is_empty() => return Ok(secret.trim().as_bytes().to_vec()),
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "secret file is empty",
+            ))
+        }
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        Err(_) => {}
+    }
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    match options.open(path) {
+        Ok(mut file) => {
+            file.write_all(secret.as_bytes())?;
+            file.sync_all()?;
+            println!("[Upload] Generated a signing secret in {}", path.display());
+            Ok(secret.into_bytes())
+        }
+        // 其他实例同时生成了密钥, 使用已经保存的密钥
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            Ok(fs::read_to_string(path)?.trim().as_bytes().to_vec())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// 无效的尺寸忽略, 结果按从小到大排序
+fn thumbnail_sizes() -> Vec<u32> {
+    let value = env::var("UPLOAD_AVATAR_THUMBNAIL_SIZES")
+        .unwrap_or_else(|_| DEFAULT_AVATAR_THUMBNAIL_SIZES.into());
+    let mut sizes: Vec<u32> = split_list(&value, ',')
+        .iter()
+        .filter_map(|size| size.parse().ok())
+        .filter(|size| (1..=2048).contains(size))
+        .collect();
+    sizes.sort_unstable();
+    sizes.dedup();
+    sizes
+}
+
+pub fn upload_config() -> &'static UploadConfig {
+    static CONFIG: OnceLock<UploadConfig> = OnceLock::new();
+    CONFIG.get_or_init(UploadConfig::from_env)
+}
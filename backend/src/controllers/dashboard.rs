@@ -0,0 +1,28 @@
+use salvo::prelude::*;
+use serde_json::json;
+
+use crate::repositories::Repositories;
+use crate::services::dashboard::{dashboard_stats, StatsWindow};
+
+#[handler]
+pub async fn get_dashboard_stats(req: &mut Request, res: &mut Response) {
+    let window = req.query::<String>("window").unwrap_or_default();
+    let window = match StatsWindow::parse(window.trim()) {
+        Ok(window) => window,
+        Err(message) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": message })));
+            return;
+        }
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match dashboard_stats(repos, window).await {
+        Ok(stats) => res.render(Json(&*stats)),
+        Err(e) => {
+            eprintln!("Failed to compute dashboard stats: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取统计数据失败" })));
+        }
+    }
+}
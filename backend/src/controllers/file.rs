@@ -0,0 +1,402 @@
+use salvo::prelude::*;
+use serde_json::json;
+
+use crate::config::upload::upload_config;
+use crate::controllers::upload::send_stored_file;
+use crate::controllers::user::PageResponse;
+use crate::models::{FileRecord, UpdateFileVisibility, UpdateStorageQuota};
+use crate::repositories::file::FileQuery;
+use crate::repositories::Repositories;
+use crate::services::file_access::{can_modify_file, can_read_file, sign_file_urls, Visibility};
+use crate::services::files::delete_file;
+use crate::services::quota::UNLIMITED_QUOTA;
+use crate::services::upload::UploadPurpose;
+use crate::utils::audit::record_change;
+
+#[handler]
+pub async fn get_files(req: &mut Request, res: &mut Response) {
+    let page = req.query::<i64>("page").unwrap_or(1).max(1);
+    let page_size = req.query::<i64>("page_size").unwrap_or(10).clamp(1, 100);
+    let query = FileQuery {
+        name: req.query::<String>("name").unwrap_or_default(),
+        purpose: req.query::<String>("purpose").unwrap_or_default(),
+        uploader_id: req.query::<i64>("uploader_id"),
+        sha256: req.query::<String>("sha256").unwrap_or_default(),
+        visibility: req.query::<String>("visibility").unwrap_or_default(),
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let total = match repos.files.count(&query).await {
+        Ok(total) => total,
+        Err(e) => {
+            eprintln!("Failed to count files: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取文件列表失败" })));
+            return;
+        }
+    };
+
+    match repos
+        .files
+        .list(&query, page_size, (page - 1) * page_size)
+        .await
+    {
+        Ok(items) => res.render(Json(PageResponse {
+            items,
+            total,
+            page,
+            page_size,
+        })),
+        Err(e) => {
+            eprintln!("Failed to fetch files: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取文件列表失败" })));
+        }
+    }
+}
+
+#[handler]
+pub async fn get_file(req: &mut Request, res: &mut Response) {
+    let id = req.param::<i64>("id").unwrap_or_default();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos.files.find(id).await {
+        Ok(Some(file)) => res.render(Json(file)),
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "文件不存在" })));
+        }
+        Err(e) => {
+            eprintln!("Failed to fetch file: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取文件失败" })));
+        }
+    }
+}
+
+// 仍被引用的文件(例如正在使用的头像)不能删除
+#[handler]
+pub async fn delete_file_by_id(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let id = req.param::<i64>("id").unwrap_or_default();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    let file = match repos.files.find(id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "文件不存在" })));
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to fetch file: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "删除文件失败" })));
+            return;
+        }
+    };
+
+    if file.reference_count > 0 {
+        res.status_code(StatusCode::CONFLICT);
+        res.render(Json(json!({
+            "message": format!("文件仍被引用 {} 次, 不能删除", file.reference_count)
+        })));
+        return;
+    }
+
+    match delete_file(repos, &file).await {
+        Ok(()) => {
+            record_change::<FileRecord>(depot, "file", file.id, Some(&file), None);
+            res.status_code(StatusCode::NO_CONTENT);
+        }
+        Err(e) => {
+            eprintln!("Failed to delete file {}: {}", file.storage_name, e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "删除文件失败" })));
+        }
+    }
+}
+
+enum FileAccess {
+    Read,
+    Modify,
+}
+
+// 查找文件并检查当前用户的权限, 失败时直接写入响应; 私有文件对无权访问的用户按不存在处理
+async fn accessible_file(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    access: FileAccess,
+) -> Option<FileRecord> {
+    let id = req.param::<i64>("id").unwrap_or_default();
+    let user_id = *depot.get::<i64>("user_id").unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let file = match repos.files.find(id).await {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "文件不存在" })));
+            return None;
+        }
+        Err(e) => {
+            eprintln!("Failed to fetch file: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取文件失败" })));
+            return None;
+        }
+    };
+
+    let (readable, modifiable) = match access {
+        FileAccess::Read => (can_read_file(repos, user_id, &file).await, Ok(true)),
+        FileAccess::Modify => (
+            can_read_file(repos, user_id, &file).await,
+            can_modify_file(repos, user_id, &file).await,
+        ),
+    };
+    match (readable, modifiable) {
+        (Ok(true), Ok(true)) => Some(file),
+        (Ok(false), _) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "文件不存在" })));
+            None
+        }
+        (Ok(true), Ok(false)) => {
+            res.status_code(StatusCode::FORBIDDEN);
+            res.render(Json(json!({ "message": "只能修改自己上传的文件" })));
+            None
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Failed to check file permission: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "服务器内部错误" })));
+            None
+        }
+    }
+}
+
+// 上传者和文件管理员可以下载私有文件, 公开文件所有登录用户都可以下载
+#[handler]
+pub async fn download_file(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let Some(file) = accessible_file(req, depot, res, FileAccess::Read).await else {
+        return;
+    };
+    if file.visibility != Visibility::Public.name() {
+        let _ = res.add_header("Cache-Control", "private, no-store", true);
+    }
+    send_stored_file(req, res, file.storage_key(), &file.storage_name).await;
+}
+
+// 生成有时效的访问地址, 用于在页面中嵌入私有图片; 公开文件返回原地址
+#[handler]
+pub async fn get_file_signed_url(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let expires_in = req
+        .query::<u64>("expires_in")
+        .unwrap_or(upload_config().signed_url_expires_secs);
+    let Some(mut file) = accessible_file(req, depot, res, FileAccess::Read).await else {
+        return;
+    };
+
+    let expires_at = sign_file_urls(&mut file, expires_in);
+    let thumbnails = file
+        .variants
+        .iter()
+        .map(|variant| (variant.variant.clone(), json!(variant.url)))
+        .collect::<serde_json::Map<_, _>>();
+    res.render(Json(json!({
+        "url": file.url,
+        "expires_at": expires_at,
+        "thumbnails": thumbnails
+    })));
+}
+
+// 上传者和文件管理员可以修改附件的可见性, 头像始终公开
+#[handler]
+pub async fn update_file_visibility(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let visibility = match req.parse_json::<UpdateFileVisibility>().await {
+        Ok(request) => match Visibility::parse(request.visibility.trim()) {
+            Some(visibility) => visibility,
+            None => {
+                res.status_code(StatusCode::BAD_REQUEST);
+                res.render(Json(json!({
+                    "message": format!("无效的可见性: {}", request.visibility)
+                })));
+                return;
+            }
+        },
+        Err(e) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": format!("请求参数错误: {}", e) })));
+            return;
+        }
+    };
+    let Some(file) = accessible_file(req, depot, res, FileAccess::Modify).await else {
+        return;
+    };
+    if let Err(e) = UploadPurpose::parse(&file.purpose)
+        .and_then(|purpose| purpose.visibility(Some(visibility.name())))
+    {
+        res.status_code(e.status_code());
+        res.render(Json(json!({ "message": e.to_string() })));
+        return;
+    }
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    if let Err(e) = repos.files.set_visibility(file.id, visibility.name()).await {
+        eprintln!("Failed to update file visibility: {}", e);
+        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+        res.render(Json(json!({ "message": "修改文件可见性失败" })));
+        return;
+    }
+
+    match repos.files.find(file.id).await {
+        Ok(Some(updated)) => {
+            record_change(depot, "file", file.id, Some(&file), Some(&updated));
+            res.render(Json(updated));
+        }
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "文件不存在" })));
+        }
+        Err(e) => {
+            eprintln!("Failed to fetch file: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取文件失败" })));
+        }
+    }
+}
+
+// 按用户统计的存储用量, 默认按用量从大到小排列
+#[handler]
+pub async fn get_storage_usage(req: &mut Request, res: &mut Response) {
+    let page = req.query::<i64>("page").unwrap_or(1).max(1);
+    let page_size = req.query::<i64>("page_size").unwrap_or(10).clamp(1, 100);
+    let name = req.query::<String>("name").unwrap_or_default();
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let total = match repos.files.count_usage(&name).await {
+        Ok(total) => total,
+        Err(e) => {
+            eprintln!("Failed to count storage usage: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取存储用量失败" })));
+            return;
+        }
+    };
+
+    match repos
+        .files
+        .list_usage(&name, page_size, (page - 1) * page_size)
+        .await
+    {
+        Ok(items) => res.render(Json(PageResponse {
+            items,
+            total,
+            page,
+            page_size,
+        })),
+        Err(e) => {
+            eprintln!("Failed to fetch storage usage: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取存储用量失败" })));
+        }
+    }
+}
+
+#[handler]
+pub async fn get_my_storage_usage(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let user_id = *depot.get::<i64>("user_id").unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos.files.usage(user_id).await {
+        Ok(Some(usage)) => res.render(Json(usage)),
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "用户不存在" })));
+        }
+        Err(e) => {
+            eprintln!("Failed to fetch storage usage: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取存储用量失败" })));
+        }
+    }
+}
+
+async fn parse_quota(req: &mut Request, res: &mut Response) -> Option<Option<i64>> {
+    match req.parse_json::<UpdateStorageQuota>().await {
+        Ok(UpdateStorageQuota { quota: Some(quota) }) if quota < UNLIMITED_QUOTA => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(
+                json!({ "message": "存储配额只能是 -1(不限制)或不小于 0 的字节数" }),
+            ));
+            None
+        }
+        Ok(request) => Some(request.quota),
+        Err(e) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": format!("请求参数错误: {}", e) })));
+            None
+        }
+    }
+}
+
+// 设置用户单独的存储配额(字节), quota 为 -1 表示不限制, 为 null 时改为使用角色的配额
+#[handler]
+pub async fn update_user_storage_quota(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let id = req.param::<i64>("id").unwrap_or_default();
+    let Some(quota) = parse_quota(req, res).await else {
+        return;
+    };
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let before = repos.users.find(id).await.ok().flatten();
+    match repos.users.set_storage_quota(id, quota).await {
+        Ok(Some(user)) => {
+            record_change(depot, "user", id, before.as_ref(), Some(&user));
+            match repos.files.usage(id).await {
+                Ok(usage) => res.render(Json(usage)),
+                Err(e) => {
+                    eprintln!("Failed to fetch storage usage: {}", e);
+                    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                    res.render(Json(json!({ "message": "获取存储用量失败" })));
+                }
+            }
+        }
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "用户不存在" })));
+        }
+        Err(e) => {
+            eprintln!("Failed to update storage quota: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "设置存储配额失败" })));
+        }
+    }
+}
+
+// 设置角色的存储配额(字节), quota 为 -1 表示该角色不限制配额,
+// 为 null 表示未设置, 按用户的其他角色或默认配额计算
+#[handler]
+pub async fn update_role_storage_quota(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let id = req.param::<i64>("id").unwrap_or_default();
+    let Some(quota) = parse_quota(req, res).await else {
+        return;
+    };
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let before = repos.roles.find(id).await.ok().flatten();
+    match repos.roles.set_storage_quota(id, quota).await {
+        Ok(Some(role)) => {
+            record_change(depot, "role", id, before.as_ref(), Some(&role));
+            res.render(Json(role));
+        }
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "message": "角色不存在" })));
+        }
+        Err(e) => {
+            eprintln!("Failed to update storage quota: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "设置存储配额失败" })));
+        }
+    }
+}
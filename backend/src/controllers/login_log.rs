@@ -0,0 +1,115 @@
+use salvo::prelude::*;
+use serde_json::json;
+
+use crate::controllers::user::PageResponse;
+use crate::models::LoginLog;
+use crate::repositories::login_log::LoginLogQuery;
+use crate::repositories::Repositories;
+use crate::services::operation_log_filter::parse_time;
+use crate::utils::ip::parse_ip;
+
+// "我的登录记录"默认返回的条数
+const MY_LOGINS_PAGE_SIZE: i64 = 10;
+
+fn login_log_filter(req: &mut Request) -> Result<LoginLogQuery, String> {
+    let text = |req: &mut Request, key: &str| {
+        req.query::<String>(key)
+            .map(|v| v.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut query = LoginLogQuery {
+        username: text(req, "username"),
+        user_id: req.query::<i64>("user_id"),
+        ..Default::default()
+    };
+    query.success = match text(req, "success").to_lowercase().as_str() {
+        "" => None,
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        other => return Err(format!("无效的登录结果: {}", other)),
+    };
+    let ip = text(req, "ip");
+    if !ip.is_empty() {
+        let addr = parse_ip(&ip).ok_or_else(|| format!("无效的IP地址: {}", ip))?;
+        query.ip = Some(addr.to_string());
+    }
+    let start_time = text(req, "start_time");
+    if !start_time.is_empty() {
+        query.start_time = Some(parse_time(&start_time, false)?);
+    }
+    let end_time = text(req, "end_time");
+    if !end_time.is_empty() {
+        query.end_time = Some(parse_time(&end_time, true)?);
+    }
+    Ok(query)
+}
+
+async fn fetch_page(
+    repos: &Repositories,
+    query: &LoginLogQuery,
+    page: i64,
+    page_size: i64,
+) -> Result<PageResponse<LoginLog>, sqlx::Error> {
+    let total = repos.login_logs.count(query).await?;
+    let items = repos
+        .login_logs
+        .list(query, page_size, (page - 1) * page_size)
+        .await?;
+
+    Ok(PageResponse {
+        items,
+        total,
+        page,
+        page_size,
+    })
+}
+
+#[handler]
+pub async fn get_login_logs(req: &mut Request, res: &mut Response) {
+    let page = req.query::<i64>("page").unwrap_or(1).max(1);
+    let page_size = req.query::<i64>("page_size").unwrap_or(10).clamp(1, 100);
+    let query = match login_log_filter(req) {
+        Ok(query) => query,
+        Err(message) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": message })));
+            return;
+        }
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match fetch_page(repos, &query, page, page_size).await {
+        Ok(response) => res.render(Json(response)),
+        Err(e) => {
+            eprintln!("Failed to fetch login logs: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取登录日志失败" })));
+        }
+    }
+}
+
+// 当前用户最近的登录记录, 包含使用其用户名但密码错误的失败登录
+#[handler]
+pub async fn get_my_login_logs(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let user_id = *depot.get::<i64>("user_id").unwrap();
+    let page = req.query::<i64>("page").unwrap_or(1).max(1);
+    let page_size = req
+        .query::<i64>("page_size")
+        .unwrap_or(MY_LOGINS_PAGE_SIZE)
+        .clamp(1, 100);
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    let query = LoginLogQuery {
+        user_id: Some(user_id),
+        ..Default::default()
+    };
+    match fetch_page(repos, &query, page, page_size).await {
+        Ok(response) => res.render(Json(response)),
+        Err(e) => {
+            eprintln!("Failed to fetch login logs: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({ "message": "获取登录记录失败" })));
+        }
+    }
+}
@@ -1,16 +1,14 @@
 use crate::models::Menu;
+use crate::repositories::Repositories;
+use crate::utils::audit::record_change;
 use salvo::prelude::*;
 use serde_json::json;
-use sqlx::SqlitePool;
 
 #[handler]
 pub async fn get_menus(req: &mut Request, res: &mut Response) {
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    match sqlx::query_as::<_, Menu>("SELECT * FROM menus WHERE is_hidden = 0 ORDER BY sort")
-        .fetch_all(pool)
-        .await
-    {
+    match repos.menus.list_visible().await {
         Ok(menus) => {
             res.render(Json(menus));
         }
@@ -36,26 +34,9 @@ pub async fn create_menu(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    match sqlx::query_as::<_, Menu>(
-        r#"
-        INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-        RETURNING *
-        "#,
-    )
-    .bind(menu.parent_id)
-    .bind(&menu.name)
-    .bind(&menu.path)
-    .bind(&menu.component)
-    .bind(&menu.title)
-    .bind(&menu.icon)
-    .bind(menu.sort)
-    .bind(menu.is_hidden)
-    .fetch_one(pool)
-    .await
-    {
+    match repos.menus.create(&menu).await {
         Ok(menu) => {
             res.status_code(StatusCode::CREATED);
             res.render(Json(menu));
@@ -70,7 +51,7 @@ pub async fn create_menu(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn update_menu(req: &mut Request, res: &mut Response) {
+pub async fn update_menu(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id = req.param::<i64>("id").unwrap();
     let menu = match req.parse_json::<Menu>().await {
         Ok(menu) => menu,
@@ -84,44 +65,19 @@ pub async fn update_menu(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    // 更新菜单
-    let result = sqlx::query(
-        "UPDATE menus SET title = ?, path = ?, icon = ?, parent_id = ?, sort = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
-    )
-    .bind(&menu.title)
-    .bind(&menu.path)
-    .bind(&menu.icon)
-    .bind(&menu.parent_id)
-    .bind(&menu.sort)
-    .bind(id)
-    .execute(pool)
-    .await;
+    // 记录修改前的数据, 用于操作日志的变更对比
+    let before = repos.menus.find(id).await.ok().flatten();
 
-    match result {
-        Ok(_) => {
-            // 查询更新后的菜单
-            let updated_menu = sqlx::query_as::<_, Menu>("SELECT * FROM menus WHERE id = ?")
-                .bind(id)
-                .fetch_one(pool)
-                .await;
-
-            match updated_menu {
-                Ok(menu) => {
-                    res.render(Json(json!({
-                        "message": "更新成功",
-                        "menu": menu
-                    })));
-                }
-                Err(e) => {
-                    eprintln!("获取更新后的菜单失败: {:?}", e);
-                    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-                    res.render(Json(json!({
-                        "message": format!("获取更新后的菜单失败: {}", e)
-                    })));
-                }
-            }
+    // 更新菜单并查询更新后的菜单
+    match repos.menus.update(id, &menu).await {
+        Ok(menu) => {
+            record_change(depot, "menu", id, before.as_ref(), Some(&menu));
+            res.render(Json(json!({
+                "message": "更新成功",
+                "menu": menu
+            })));
         }
         Err(e) => {
             eprintln!("更新菜单失败: {:?}", e);
@@ -134,16 +90,16 @@ pub async fn update_menu(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn delete_menu(req: &mut Request, res: &mut Response) {
+pub async fn delete_menu(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 记录删除前的数据, 用于操作日志的变更对比
+    let before = repos.menus.find(id).await.ok().flatten();
 
-    match sqlx::query("DELETE FROM menus WHERE id = ?")
-        .bind(id)
-        .execute(pool)
-        .await
-    {
+    match repos.menus.delete(id).await {
         Ok(_) => {
+            record_change::<Menu>(depot, "menu", id, before.as_ref(), None);
             res.status_code(StatusCode::NO_CONTENT);
         }
         Err(e) => {
@@ -1,6 +1,11 @@
+pub mod dashboard;
+pub mod file;
+pub mod login_log;
 pub mod menu;
 pub mod operation_log;
+pub mod operation_log_rule;
 pub mod permission;
 pub mod role;
 pub mod upload;
+pub mod upload_session;
 pub mod user;
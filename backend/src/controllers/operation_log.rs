@@ -1,9 +1,26 @@
+use futures_util::TryStreamExt;
+use salvo::fs::NamedFile;
+use salvo::http::mime;
 use salvo::prelude::*;
 use serde_json::json;
-use sqlx::SqlitePool;
+use std::time::Instant;
+use tokio::sync::mpsc;
 
+use crate::config::operation_log::operation_log_config;
 use crate::controllers::user::PageResponse;
-use crate::models::OperationLog;
+use crate::models::CreateOperationLog;
+use crate::repositories::operation_log::ArchivedLogQuery;
+use crate::repositories::Repositories;
+use crate::services::audit_stream::{stream_audit_events, AuditEventFilter};
+use crate::services::export::{
+    record_export, stream_export, Cell, ExportColumn, ExportFormat, Lang, EXPORT_QUEUE_SIZE,
+};
+use crate::services::log_writer::LogWriter;
+use crate::services::operation_log::{expired_log_id, purge_operation_logs, verify_chain};
+use crate::services::operation_log_archive::{archive_path, import_archive, list_archives};
+use crate::services::operation_log_filter::OperationLogFilter;
+use crate::services::retention::days_ago;
+use crate::utils::audit::FieldChange;
 
 #[handler]
 pub async fn get_operation_logs(req: &mut Request, res: &mut Response) {
@@ -11,110 +28,489 @@ pub async fn get_operation_logs(req: &mut Request, res: &mut Response) {
     let page_size = req.query::<i64>("page_size").unwrap_or(10);
     let offset = (page - 1) * page_size;
 
-    // 获取查询参数
-    let username = req.query::<String>("username").unwrap_or_default();
-    let module = req.query::<String>("module").unwrap_or_default();
-    let operation = req.query::<String>("operation").unwrap_or_default();
-    let status = req.query::<i32>("status");
+    let filter = match OperationLogFilter::from_request(req) {
+        Ok(filter) => filter,
+        Err(message) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": message })));
+            return;
+        }
+    };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    // 构建查询条件
-    let mut conditions = Vec::new();
-    let mut params = Vec::new();
+    let total = match repos.operation_logs.count(&filter).await {
+        Ok(total) => total,
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取日志总数失败: {}", e)
+            })));
+            return;
+        }
+    };
 
-    if !username.is_empty() {
-        conditions.push("username LIKE ?");
-        params.push(format!("%{}%", username));
-    }
-    if !module.is_empty() {
-        conditions.push("module LIKE ?");
-        params.push(format!("%{}%", module));
+    match repos.operation_logs.list(&filter, page_size, offset).await {
+        Ok(logs) => {
+            let response = PageResponse {
+                items: logs,
+                total,
+                page,
+                page_size,
+            };
+            res.render(Json(response));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取日志列表失败: {}", e)
+            })));
+        }
     }
-    if !operation.is_empty() {
-        conditions.push("operation LIKE ?");
-        params.push(format!("%{}%", operation));
+}
+
+const OPERATION_LOG_EXPORT_COLUMNS: &[ExportColumn] = &[
+    ExportColumn::new("ID", "ID", 8.0),
+    ExportColumn::new("操作时间", "Time", 20.0),
+    ExportColumn::new("用户ID", "User ID", 8.0),
+    ExportColumn::new("用户名", "Username", 14.0),
+    ExportColumn::new("模块", "Module", 12.0),
+    ExportColumn::new("操作", "Operation", 10.0),
+    ExportColumn::new("请求方法", "Method", 10.0),
+    ExportColumn::new("请求路径", "Path", 24.0),
+    ExportColumn::new("状态码", "Status", 8.0),
+    ExportColumn::new("耗时(毫秒)", "Duration (ms)", 12.0),
+    ExportColumn::new("IP地址", "IP", 16.0),
+    ExportColumn::new("User-Agent", "User-Agent", 30.0),
+    ExportColumn::new("请求ID", "Request ID", 38.0),
+    ExportColumn::new("请求参数", "Parameters", 40.0),
+    ExportColumn::new("错误信息", "Error", 30.0),
+];
+
+#[handler]
+pub async fn export_operation_logs(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let format = ExportFormat::parse(&req.query::<String>("format").unwrap_or_default());
+    let filter = OperationLogFilter::from_request(req);
+    let (format, filter) = match (format, filter) {
+        (Ok(format), Ok(filter)) => (format, filter),
+        (Err(message), _) | (_, Err(message)) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": message })));
+            return;
+        }
+    };
+    let lang = Lang::from_request(req);
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 边查询边写出, 不在内存中缓存整张表
+    let mut rows = repos.operation_logs.export(&filter);
+    let (tx, rx) = mpsc::channel(EXPORT_QUEUE_SIZE);
+    let producer = tokio::spawn(async move {
+        while let Some(log) = rows.try_next().await? {
+            let row = vec![
+                Cell::from(log.id),
+                Cell::from(log.created_at),
+                Cell::from(log.user_id),
+                Cell::from(log.username),
+                Cell::from(log.module),
+                Cell::from(log.operation),
+                Cell::from(log.method),
+                Cell::from(log.path),
+                Cell::from(log.status),
+                Cell::from(log.duration_ms),
+                Cell::from(log.ip),
+                Cell::from(log.user_agent),
+                Cell::from(log.request_id),
+                Cell::from(log.params),
+                Cell::from(log.error),
+            ];
+            if tx.send(row).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+
+    let writer = req.extensions().get::<LogWriter>().unwrap().clone();
+    let log = depot.remove::<CreateOperationLog>("operation_log").ok();
+    let started_at = depot
+        .get::<Instant>("operation_log_started_at")
+        .ok()
+        .copied();
+    let file_name = format!(
+        "operation_logs_{}",
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    );
+    let export = stream_export(
+        res,
+        format,
+        &file_name,
+        OPERATION_LOG_EXPORT_COLUMNS,
+        lang,
+        rx,
+    );
+    tokio::spawn(record_export(
+        writer, log, started_at, format, producer, export,
+    ));
+}
+
+#[handler]
+pub async fn get_operation_log(req: &mut Request, res: &mut Response) {
+    let id = req.param::<i64>("id").unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let log = match repos.operation_logs.find(id).await {
+        Ok(Some(log)) => log,
+        Ok(None) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({
+                "message": "操作日志不存在"
+            })));
+            return;
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取操作日志失败: {}", e)
+            })));
+            return;
+        }
+    };
+
+    let changes = match repos.operation_logs.changes(id).await {
+        Ok(changes) => changes,
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取数据变更失败: {}", e)
+            })));
+            return;
+        }
+    };
+
+    // 展开字段级变更, 便于前端直接展示
+    let changes: Vec<_> = changes
+        .into_iter()
+        .map(|change| {
+            let fields = serde_json::from_str::<Vec<FieldChange>>(&change.diff).unwrap_or_default();
+            let parse = |s: &Option<String>| {
+                s.as_deref()
+                    .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+            };
+            json!({
+                "id": change.id,
+                "entity": change.entity,
+                "entity_id": change.entity_id,
+                "action": change.action,
+                "before": parse(&change.before),
+                "after": parse(&change.after),
+                "fields": fields,
+                "created_at": change.created_at
+            })
+        })
+        .collect();
+
+    res.render(Json(json!({
+        "log": log,
+        "changes": changes
+    })));
+}
+
+#[handler]
+pub async fn get_operation_log_writer_stats(req: &mut Request, res: &mut Response) {
+    let writer = req.extensions().get::<LogWriter>().unwrap();
+    res.render(Json(writer.stats()));
+}
+
+// 实时推送新写入的操作日志和登录事件, 需要 system:log:stream 权限
+#[handler]
+pub async fn stream_operation_log_events(req: &mut Request, res: &mut Response) {
+    let filter = match AuditEventFilter::from_request(req) {
+        Ok(filter) => filter,
+        Err(message) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": message })));
+            return;
+        }
+    };
+
+    let writer = req.extensions().get::<LogWriter>().unwrap();
+    stream_audit_events(res, writer.events(), filter);
+}
+
+#[handler]
+pub async fn verify_operation_logs(req: &mut Request, res: &mut Response) {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    match verify_chain(repos).await {
+        Ok(report) => {
+            res.render(Json(report));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("校验操作日志失败: {}", e)
+            })));
+        }
     }
-    if let Some(status) = status {
-        conditions.push("status = ?");
-        params.push(status.to_string());
+}
+
+#[handler]
+pub async fn get_operation_log_checkpoints(req: &mut Request, res: &mut Response) {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    match repos.operation_logs.checkpoints().await {
+        Ok(checkpoints) => {
+            res.render(Json(checkpoints));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取清理记录失败: {}", e)
+            })));
+        }
     }
+}
 
-    // 构建查询语句
-    let mut query = String::from("SELECT * FROM operation_logs");
-    if !conditions.is_empty() {
-        query.push_str(" WHERE ");
-        query.push_str(&conditions.join(" AND "));
+// 历史日志参数的清洗记录
+#[handler]
+pub async fn get_operation_log_redactions(req: &mut Request, res: &mut Response) {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    match repos.operation_logs.redactions().await {
+        Ok(redactions) => {
+            res.render(Json(redactions));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取清洗记录失败: {}", e)
+            })));
+        }
     }
-    query.push_str(" ORDER BY created_at DESC LIMIT ? OFFSET ?");
+}
 
-    // 构建计数查询语句
-    let mut count_query = String::from("SELECT COUNT(*) FROM operation_logs");
-    if !conditions.is_empty() {
-        count_query.push_str(" WHERE ");
-        count_query.push_str(&conditions.join(" AND "));
+// 只允许清理超过保留期限的日志, 并记录检查点
+#[handler]
+pub async fn delete_operation_logs(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let min_days = operation_log_config().min_retention_days as i64;
+    let days = req.query::<i64>("days").unwrap_or(min_days);
+    if days < min_days {
+        res.status_code(StatusCode::BAD_REQUEST);
+        res.render(Json(json!({
+            "message": format!("操作日志至少保留 {} 天", min_days)
+        })));
+        return;
     }
 
-    // 获取总数
-    let mut count_query_builder = sqlx::query_scalar::<_, i64>(&count_query);
-    for param in &params {
-        count_query_builder = count_query_builder.bind(param);
+    let cutoff = days_ago(days as usize);
+    let floor = days_ago(min_days as usize);
+    let username = depot.get::<String>("username").ok().cloned();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let up_to_id = match expired_log_id(repos, Some(&cutoff), None, &floor).await {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            res.render(Json(json!({
+                "message": format!("没有 {} 天前的操作日志需要清理", days),
+                "purged": 0
+            })));
+            return;
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("清理操作日志失败: {}", e)
+            })));
+            return;
+        }
+    };
+
+    match purge_operation_logs(repos, up_to_id, &cutoff, "manual", username.as_deref()).await {
+        Ok(result) => {
+            res.render(Json(json!({
+                "message": format!("已归档并清理 {} 天前的操作日志 {} 条", days, result.purged),
+                "purged": result.purged,
+                "checkpoint_id": result.checkpoint_id,
+                "archives": result.archives
+            })));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("清理操作日志失败: {}", e)
+            })));
+        }
     }
+}
 
-    let total = match count_query_builder.fetch_one(pool).await {
-        Ok(total) => total,
+#[handler]
+pub async fn get_operation_log_archives(req: &mut Request, res: &mut Response) {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let archives = match list_archives(&operation_log_config().archive_dir).await {
+        Ok(archives) => archives,
         Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
             res.render(Json(json!({
-                "message": format!("获取日志总数失败: {}", e)
+                "message": format!("获取归档列表失败: {}", e)
             })));
             return;
         }
     };
 
-    // 获取分页数据
-    let mut query_builder = sqlx::query_as::<_, OperationLog>(&query);
-    for param in &params {
-        query_builder = query_builder.bind(param);
+    let checkpoints = match repos.operation_logs.checkpoints().await {
+        Ok(checkpoints) => checkpoints,
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取清理记录失败: {}", e)
+            })));
+            return;
+        }
+    };
+
+    let imported = repos
+        .operation_logs
+        .archived_counts()
+        .await
+        .unwrap_or_default();
+
+    let items: Vec<_> = archives
+        .into_iter()
+        .map(|archive| {
+            let checkpoint = checkpoints
+                .iter()
+                .find(|c| c.archive.as_deref() == Some(archive.name.as_str()));
+            let imported_count = imported
+                .iter()
+                .find(|(name, _)| *name == archive.name)
+                .map(|(_, count)| *count)
+                .unwrap_or(0);
+            json!({
+                "name": archive.name,
+                "size": archive.size,
+                "modified": archive.modified,
+                "checkpoint": checkpoint,
+                "imported": imported_count
+            })
+        })
+        .collect();
+
+    res.render(Json(items));
+}
+
+#[handler]
+pub async fn download_operation_log_archive(req: &mut Request, res: &mut Response) {
+    let name = req.param::<String>("name").unwrap_or_default();
+    let path = match archive_path(&operation_log_config().archive_dir, &name) {
+        Some(path) if path.exists() => path,
+        _ => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({
+                "message": "归档文件不存在"
+            })));
+            return;
+        }
+    };
+
+    NamedFile::builder(path)
+        .attached_name(&name)
+        .content_type(mime::APPLICATION_OCTET_STREAM)
+        .send(req.headers(), res)
+        .await;
+}
+
+#[handler]
+pub async fn import_operation_log_archive(req: &mut Request, res: &mut Response) {
+    let name = req.param::<String>("name").unwrap_or_default();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    let dir = &operation_log_config().archive_dir;
+
+    if !archive_path(dir, &name).is_some_and(|path| path.exists()) {
+        res.status_code(StatusCode::NOT_FOUND);
+        res.render(Json(json!({
+            "message": "归档文件不存在"
+        })));
+        return;
     }
-    query_builder = query_builder.bind(page_size).bind(offset);
 
-    match query_builder.fetch_all(pool).await {
+    match import_archive(repos, dir, &name).await {
+        Ok(imported) => {
+            res.render(Json(json!({
+                "message": format!("已导入 {} 条归档日志", imported),
+                "imported": imported
+            })));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("导入归档失败: {}", e)
+            })));
+        }
+    }
+}
+
+#[handler]
+pub async fn get_archived_operation_logs(req: &mut Request, res: &mut Response) {
+    let page = req.query::<i64>("page").unwrap_or(1);
+    let page_size = req.query::<i64>("page_size").unwrap_or(10);
+    let offset = (page - 1) * page_size;
+    let query = ArchivedLogQuery {
+        archive: req.query::<String>("archive").unwrap_or_default(),
+        username: req.query::<String>("username").unwrap_or_default(),
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let total = match repos.operation_logs.count_archived(&query).await {
+        Ok(total) => total,
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("获取归档日志总数失败: {}", e)
+            })));
+            return;
+        }
+    };
+
+    match repos
+        .operation_logs
+        .list_archived(&query, page_size, offset)
+        .await
+    {
         Ok(logs) => {
-            let response = PageResponse {
+            res.render(Json(PageResponse {
                 items: logs,
                 total,
                 page,
                 page_size,
-            };
-            res.render(Json(response));
+            }));
         }
         Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
             res.render(Json(json!({
-                "message": format!("获取日志列表失败: {}", e)
+                "message": format!("获取归档日志失败: {}", e)
             })));
         }
     }
 }
 
 #[handler]
-pub async fn delete_operation_logs(req: &mut Request, res: &mut Response) {
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+pub async fn delete_archived_operation_logs(req: &mut Request, res: &mut Response) {
+    let archive = req.query::<String>("archive").unwrap_or_default();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    match sqlx::query("DELETE FROM operation_logs")
-        .execute(pool)
-        .await
-    {
-        Ok(_) => {
+    match repos.operation_logs.delete_archived(&archive).await {
+        Ok(removed) => {
             res.render(Json(json!({
-                "message": "清空操作日志成功"
+                "message": format!("已移除 {} 条导入的归档日志", removed)
             })));
         }
         Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
             res.render(Json(json!({
-                "message": format!("清空操作日志失败: {}", e)
+                "message": format!("移除导入的归档日志失败: {}", e)
             })));
         }
     }
@@ -0,0 +1,209 @@
+use salvo::prelude::*;
+
+use crate::models::{OperationLogRule, SaveOperationLogRule};
+use crate::repositories::Repositories;
+use crate::services::capture_rules::invalidate_capture_rules;
+use crate::utils::audit::record_change;
+
+const METHODS: &[&str] = &["*", "GET", "POST", "PUT", "PATCH", "DELETE"];
+
+// 不允许排除的接口: 登录、记录规则本身以及用户、角色、权限和菜单的管理接口.
+// 排除这些接口的修改请求会让越权操作不留痕迹
+const PROTECTED_PATHS: &[&str] = &[
+    "/auth/**",
+    "/operation-log-rules/**",
+    "/users/**",
+    "/roles/**",
+    "/permissions/**",
+    "/menus/**",
+];
+
+fn split_pattern(pattern: &str) -> (Vec<&str>, bool) {
+    let mut parts: Vec<&str> = pattern.trim_end_matches('/').split('/').collect();
+    let any_suffix = parts.last() == Some(&"**");
+    if any_suffix {
+        parts.pop();
+    }
+    (parts, any_suffix)
+}
+
+// 两个路径规则是否可能匹配同一个路径
+fn patterns_overlap(a: &str, b: &str) -> bool {
+    let (a_parts, a_any) = split_pattern(a);
+    let (b_parts, b_any) = split_pattern(b);
+    let segments_match = a_parts
+        .iter()
+        .zip(b_parts.iter())
+        .all(|(x, y)| *x == "*" || *y == "*" || x == y);
+    segments_match
+        && match a_parts.len().cmp(&b_parts.len()) {
+            std::cmp::Ordering::Equal => true,
+            std::cmp::Ordering::Less => a_any,
+            std::cmp::Ordering::Greater => b_any,
+        }
+}
+
+fn validate_rule(rule: &mut SaveOperationLogRule) -> Result<(), String> {
+    rule.method = rule.method.trim().to_uppercase();
+    rule.path = rule.path.trim().to_string();
+    if !METHODS.contains(&rule.method.as_str()) {
+        return Err(format!("无效的请求方法: {}", rule.method));
+    }
+    if !rule.path.starts_with('/') {
+        return Err("路径必须以 / 开头".to_string());
+    }
+    // ** 只能作为最后一个路径段
+    if rule
+        .path
+        .trim_end_matches('/')
+        .split('/')
+        .rev()
+        .skip(1)
+        .any(|part| part == "**")
+    {
+        return Err("** 只能出现在路径末尾".to_string());
+    }
+    if rule.action != "include" && rule.action != "exclude" {
+        return Err(format!("无效的规则动作: {}", rule.action));
+    }
+    // 默认不记录 GET 请求, 只检查会排除修改请求的规则
+    if rule.action == "exclude" && rule.method != "GET" {
+        if let Some(protected) = PROTECTED_PATHS
+            .iter()
+            .find(|protected| patterns_overlap(&rule.path, protected))
+        {
+            return Err(format!(
+                "不能排除 {} 下的接口, 这些操作必须记录操作日志",
+                protected
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn parse_rule(req: &mut Request, res: &mut Response) -> Option<SaveOperationLogRule> {
+    let mut rule: SaveOperationLogRule = match req.parse_json().await {
+        Ok(rule) => rule,
+        Err(e) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(serde_json::json!({
+                "error": format!("Invalid rule data: {}", e)
+            })));
+            return None;
+        }
+    };
+    if let Err(message) = validate_rule(&mut rule) {
+        res.status_code(StatusCode::BAD_REQUEST);
+        res.render(Json(serde_json::json!({ "error": message })));
+        return None;
+    }
+    Some(rule)
+}
+
+#[handler]
+pub async fn get_operation_log_rules(req: &mut Request, res: &mut Response) {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos.operation_log_rules.list().await {
+        Ok(rules) => res.render(Json(rules)),
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(serde_json::json!({
+                "error": format!("Failed to fetch rules: {}", e)
+            })));
+        }
+    }
+}
+
+#[handler]
+pub async fn create_operation_log_rule(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let Some(rule) = parse_rule(req, res).await else {
+        return;
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos.operation_log_rules.create(&rule).await {
+        Ok(rule) => {
+            invalidate_capture_rules();
+            record_change(depot, "operation_log_rule", rule.id, None, Some(&rule));
+            res.status_code(StatusCode::CREATED);
+            res.render(Json(rule));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(serde_json::json!({
+                "error": format!("Failed to create rule: {}", e)
+            })));
+        }
+    }
+}
+
+#[handler]
+pub async fn update_operation_log_rule(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let id = req.param::<i64>("id").unwrap_or_default();
+    let Some(rule) = parse_rule(req, res).await else {
+        return;
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 记录修改前的数据, 用于操作日志的变更对比
+    let before = repos.operation_log_rules.find(id).await.ok().flatten();
+    if before.is_none() {
+        res.status_code(StatusCode::NOT_FOUND);
+        res.render(Json(serde_json::json!({ "error": "规则不存在" })));
+        return;
+    }
+
+    match repos.operation_log_rules.update(id, &rule).await {
+        Ok(rule) => {
+            invalidate_capture_rules();
+            record_change(
+                depot,
+                "operation_log_rule",
+                id,
+                before.as_ref(),
+                Some(&rule),
+            );
+            res.render(Json(rule));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(serde_json::json!({
+                "error": format!("Failed to update rule: {}", e)
+            })));
+        }
+    }
+}
+
+#[handler]
+pub async fn delete_operation_log_rule(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let id = req.param::<i64>("id").unwrap_or_default();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    let before = repos.operation_log_rules.find(id).await.ok().flatten();
+    if before.is_none() {
+        res.status_code(StatusCode::NOT_FOUND);
+        res.render(Json(serde_json::json!({ "error": "规则不存在" })));
+        return;
+    }
+
+    match repos.operation_log_rules.delete(id).await {
+        Ok(_) => {
+            invalidate_capture_rules();
+            record_change::<OperationLogRule>(
+                depot,
+                "operation_log_rule",
+                id,
+                before.as_ref(),
+                None,
+            );
+            res.status_code(StatusCode::NO_CONTENT);
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(serde_json::json!({
+                "error": format!("Failed to delete rule: {}", e)
+            })));
+        }
+    }
+}
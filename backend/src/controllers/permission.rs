@@ -1,9 +1,10 @@
 use salvo::prelude::*;
-use serde_json::json;
-use sqlx::SqlitePool;
 
 use crate::controllers::user::PageResponse;
 use crate::models::{CreatePermission, Permission, UpdatePermission};
+use crate::repositories::{PermissionQuery, Repositories};
+use crate::services::capture_rules::invalidate_capture_rules;
+use crate::utils::audit::record_change;
 
 #[handler]
 pub async fn get_permissions(req: &mut Request, res: &mut Response) {
@@ -12,46 +13,15 @@ pub async fn get_permissions(req: &mut Request, res: &mut Response) {
     let offset = (page - 1) * page_size;
 
     // 获取查询参数
-    let name = req.query::<String>("name").unwrap_or_default();
-    let code = req.query::<String>("code").unwrap_or_default();
-
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-
-    // 构建查询条件
-    let mut conditions = Vec::new();
-    let mut params: Vec<String> = Vec::new();
-
-    if !name.is_empty() {
-        conditions.push("name LIKE ?");
-        params.push(format!("%{}%", name));
-    }
-    if !code.is_empty() {
-        conditions.push("code LIKE ?");
-        params.push(format!("%{}%", code));
-    }
+    let query = PermissionQuery {
+        name: req.query::<String>("name").unwrap_or_default(),
+        code: req.query::<String>("code").unwrap_or_default(),
+    };
 
-    // 构建查询语句
-    let mut query = String::from("SELECT * FROM permissions");
-    if !conditions.is_empty() {
-        query.push_str(" WHERE ");
-        query.push_str(&conditions.join(" AND "));
-    }
-    query.push_str(" LIMIT ? OFFSET ?");
-
-    // 构建计数查询语句
-    let mut count_query = String::from("SELECT COUNT(*) FROM permissions");
-    if !conditions.is_empty() {
-        count_query.push_str(" WHERE ");
-        count_query.push_str(&conditions.join(" AND "));
-    }
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
     // 获取总数
-    let mut count_query_builder = sqlx::query_scalar::<_, i64>(&count_query);
-    for param in &params {
-        count_query_builder = count_query_builder.bind(param);
-    }
-
-    let total = match count_query_builder.fetch_one(pool).await {
+    let total = match repos.permissions.count(&query).await {
         Ok(total) => total,
         Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
@@ -63,13 +33,7 @@ pub async fn get_permissions(req: &mut Request, res: &mut Response) {
     };
 
     // 获取分页数据
-    let mut query_builder = sqlx::query_as::<_, Permission>(&query);
-    for param in &params {
-        query_builder = query_builder.bind(param);
-    }
-    query_builder = query_builder.bind(page_size).bind(offset);
-
-    match query_builder.fetch_all(pool).await {
+    match repos.permissions.list(&query, page_size, offset).await {
         Ok(permissions) => {
             let response = PageResponse {
                 items: permissions,
@@ -101,28 +65,10 @@ pub async fn create_permission(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-    match sqlx::query_as::<_, Permission>(
-        r#"
-        INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-        RETURNING *
-        "#,
-    )
-    .bind(&permission.name)
-    .bind(&permission.code)
-    .bind(&permission.type_name)
-    .bind(&permission.resource)
-    .bind(&permission.action)
-    .bind(permission.parent_id)
-    .bind(permission.sort)
-    .bind(&permission.description)
-    .bind(&permission.color_start)
-    .bind(&permission.color_end)
-    .fetch_one(pool)
-    .await
-    {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos.permissions.create(&permission).await {
         Ok(permission) => {
+            invalidate_capture_rules();
             res.status_code(StatusCode::CREATED);
             res.render(Json(permission));
         }
@@ -136,7 +82,7 @@ pub async fn create_permission(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn update_permission(req: &mut Request, res: &mut Response) {
+pub async fn update_permission(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
     let permission: UpdatePermission = match req.parse_json().await {
         Ok(permission) => permission,
@@ -149,32 +95,15 @@ pub async fn update_permission(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-    match sqlx::query_as::<_, Permission>(
-        r#"
-        UPDATE permissions 
-        SET name = ?, code = ?, type_name = ?, resource = ?, action = ?, 
-            parent_id = ?, sort = ?, description = ?, color_start = ?, color_end = ?,
-            updated_at = CURRENT_TIMESTAMP
-        WHERE id = ?
-        RETURNING *
-        "#,
-    )
-    .bind(&permission.name)
-    .bind(&permission.code)
-    .bind(&permission.type_name)
-    .bind(&permission.resource)
-    .bind(&permission.action)
-    .bind(permission.parent_id)
-    .bind(permission.sort)
-    .bind(&permission.description)
-    .bind(&permission.color_start)
-    .bind(&permission.color_end)
-    .bind(id)
-    .fetch_one(pool)
-    .await
-    {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 记录修改前的数据, 用于操作日志的变更对比
+    let before = repos.permissions.find(id).await.ok().flatten();
+
+    match repos.permissions.update(id, &permission).await {
         Ok(permission) => {
+            invalidate_capture_rules();
+            record_change(depot, "permission", id, before.as_ref(), Some(&permission));
             res.render(Json(permission));
         }
         Err(e) => {
@@ -187,16 +116,44 @@ pub async fn update_permission(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn delete_permission(req: &mut Request, res: &mut Response) {
+pub async fn delete_permission(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 记录删除前的数据, 用于操作日志的变更对比
+    let before = repos.permissions.find(id).await.ok().flatten();
+
+    // 上级外键不会级联删除, 有下级权限时要求先删除或移动下级权限
+    match repos.permissions.children(id).await {
+        Ok(children) if !children.is_empty() => {
+            res.status_code(StatusCode::CONFLICT);
+            res.render(Json(serde_json::json!({
+                "error": "Permission has child permissions, delete or move them first",
+                "children": children
+                    .iter()
+                    .map(|child| serde_json::json!({
+                        "id": child.id,
+                        "code": child.code,
+                        "name": child.name,
+                    }))
+                    .collect::<Vec<_>>()
+            })));
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(serde_json::json!({
+                "error": format!("Failed to delete permission: {}", e)
+            })));
+            return;
+        }
+    }
 
-    match sqlx::query("DELETE FROM permissions WHERE id = ?")
-        .bind(id)
-        .execute(pool)
-        .await
-    {
+    match repos.permissions.delete(id).await {
         Ok(_) => {
+            invalidate_capture_rules();
+            record_change::<Permission>(depot, "permission", id, before.as_ref(), None);
             res.status_code(StatusCode::NO_CONTENT);
         }
         Err(e) => {
@@ -1,21 +1,17 @@
 use crate::controllers::user::PageResponse;
-use crate::models::{CreateRole, Permission, PermissionTree, Role};
+use crate::models::{CreateRole, PermissionTree, Role};
+use crate::repositories::role::RoleQuery;
+use crate::repositories::Repositories;
+use crate::services::assignment::{AssignmentError, AssignmentMode, ROLE_PERMISSIONS};
+use crate::utils::audit::record_change;
 use salvo::prelude::*;
-use serde::{Deserialize, Serialize};
 use serde_json::json;
-use sqlx::SqlitePool;
 
 #[derive(Debug, serde::Deserialize)]
 pub struct RolePermissions {
     pub permission_ids: Vec<i64>,
 }
 
-#[derive(Debug, serde::Deserialize)]
-pub struct RoleQuery {
-    pub name: Option<String>,
-    pub code: Option<String>,
-}
-
 #[handler]
 pub async fn get_roles(req: &mut Request, res: &mut Response) {
     let page = req.query::<i64>("page").unwrap_or(1);
@@ -23,46 +19,15 @@ pub async fn get_roles(req: &mut Request, res: &mut Response) {
     let offset = (page - 1) * page_size;
 
     // 获取查询参数
-    let name = req.query::<String>("name").unwrap_or_default();
-    let code = req.query::<String>("code").unwrap_or_default();
-
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-
-    // 构建查询条件
-    let mut conditions = Vec::new();
-    let mut params: Vec<String> = Vec::new();
-
-    if !name.is_empty() {
-        conditions.push("name LIKE ?");
-        params.push(format!("%{}%", name));
-    }
-    if !code.is_empty() {
-        conditions.push("code LIKE ?");
-        params.push(format!("%{}%", code));
-    }
-
-    // 构建查询语句
-    let mut query = String::from("SELECT * FROM roles");
-    if !conditions.is_empty() {
-        query.push_str(" WHERE ");
-        query.push_str(&conditions.join(" AND "));
-    }
-    query.push_str(" LIMIT ? OFFSET ?");
+    let query = RoleQuery {
+        name: req.query::<String>("name").unwrap_or_default(),
+        code: req.query::<String>("code").unwrap_or_default(),
+    };
 
-    // 构建计数查询语句
-    let mut count_query = String::from("SELECT COUNT(*) FROM roles");
-    if !conditions.is_empty() {
-        count_query.push_str(" WHERE ");
-        count_query.push_str(&conditions.join(" AND "));
-    }
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
     // 获取总数
-    let mut count_query_builder = sqlx::query_scalar::<_, i64>(&count_query);
-    for param in &params {
-        count_query_builder = count_query_builder.bind(param);
-    }
-
-    let total = match count_query_builder.fetch_one(pool).await {
+    let total = match repos.roles.count(&query).await {
         Ok(total) => total,
         Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
@@ -74,13 +39,7 @@ pub async fn get_roles(req: &mut Request, res: &mut Response) {
     };
 
     // 获取分页数据
-    let mut query_builder = sqlx::query_as::<_, Role>(&query);
-    for param in &params {
-        query_builder = query_builder.bind(param);
-    }
-    query_builder = query_builder.bind(page_size).bind(offset);
-
-    match query_builder.fetch_all(pool).await {
+    match repos.roles.list(&query, page_size, offset).await {
         Ok(roles) => {
             let response = PageResponse {
                 items: roles,
@@ -112,20 +71,8 @@ pub async fn create_role(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-    match sqlx::query_as::<_, Role>(
-        r#"
-        INSERT INTO roles (name, code, description)
-        VALUES (?, ?, ?)
-        RETURNING *
-        "#,
-    )
-    .bind(&role.name)
-    .bind(&role.code)
-    .bind(&role.description)
-    .fetch_one(pool)
-    .await
-    {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos.roles.create(&role).await {
         Ok(role) => {
             res.status_code(StatusCode::CREATED);
             res.render(Json(role));
@@ -140,7 +87,7 @@ pub async fn create_role(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn update_role(req: &mut Request, res: &mut Response) {
+pub async fn update_role(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
     let role: CreateRole = match req.parse_json().await {
         Ok(role) => role,
@@ -153,24 +100,14 @@ pub async fn update_role(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-    match sqlx::query_as::<_, Role>(
-        r#"
-        UPDATE roles 
-        SET name = ?, code = ?, description = ?,
-            updated_at = CURRENT_TIMESTAMP
-        WHERE id = ?
-        RETURNING *
-        "#,
-    )
-    .bind(&role.name)
-    .bind(&role.code)
-    .bind(&role.description)
-    .bind(id)
-    .fetch_one(pool)
-    .await
-    {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 记录修改前的数据, 用于操作日志的变更对比
+    let before = repos.roles.find(id).await.ok().flatten();
+
+    match repos.roles.update(id, &role).await {
         Ok(role) => {
+            record_change(depot, "role", id, before.as_ref(), Some(&role));
             res.render(Json(role));
         }
         Err(e) => {
@@ -183,16 +120,16 @@ pub async fn update_role(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn delete_role(req: &mut Request, res: &mut Response) {
+pub async fn delete_role(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    match sqlx::query("DELETE FROM roles WHERE id = ?")
-        .bind(id)
-        .execute(pool)
-        .await
-    {
+    // 记录删除前的数据, 用于操作日志的变更对比
+    let before = repos.roles.find(id).await.ok().flatten();
+
+    match repos.roles.delete(id).await {
         Ok(_) => {
+            record_change::<Role>(depot, "role", id, before.as_ref(), None);
             res.status_code(StatusCode::NO_CONTENT);
         }
         Err(e) => {
@@ -207,18 +144,10 @@ pub async fn delete_role(req: &mut Request, res: &mut Response) {
 #[handler]
 pub async fn get_role_permissions(req: &mut Request, res: &mut Response) {
     let role_id = req.param::<i64>("id").unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
     // 查询所有权限
-    let permissions = match sqlx::query_as::<_, Permission>(
-        r#"
-        SELECT * FROM permissions 
-        ORDER BY sort, id
-        "#,
-    )
-    .fetch_all(pool)
-    .await
-    {
+    let permissions = match repos.permissions.list_all().await {
         Ok(permissions) => permissions,
         Err(e) => {
             eprintln!("获取权限列表失败: {:?}", e);
@@ -232,16 +161,7 @@ pub async fn get_role_permissions(req: &mut Request, res: &mut Response) {
     };
 
     // 查询角色的权限ID列表
-    let role_permission_ids = match sqlx::query_scalar::<_, i64>(
-        r#"
-        SELECT permission_id FROM role_permissions 
-        WHERE role_id = ?
-        "#,
-    )
-    .bind(role_id)
-    .fetch_all(pool)
-    .await
-    {
+    let role_permission_ids = match repos.roles.permission_ids(role_id).await {
         Ok(ids) => ids,
         Err(e) => {
             eprintln!("获取角色权限失败: {:?}", e);
@@ -280,7 +200,7 @@ pub async fn get_role_permissions(req: &mut Request, res: &mut Response) {
     for permission in &permissions {
         if let Some(parent_id) = permission.parent_id {
             if let Some(node) = permission_map.remove(&permission.id) {
-                nodes_to_move.push((parent_id as i32, node));
+                nodes_to_move.push((parent_id, node));
             }
         }
     }
@@ -319,7 +239,26 @@ pub async fn get_role_permissions(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn update_role_permissions(req: &mut Request, res: &mut Response) {
+pub async fn update_role_permissions(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    assign_permissions(req, depot, res, AssignmentMode::Replace).await;
+}
+
+#[handler]
+pub async fn add_role_permissions(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    assign_permissions(req, depot, res, AssignmentMode::Add).await;
+}
+
+#[handler]
+pub async fn remove_role_permissions(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    assign_permissions(req, depot, res, AssignmentMode::Remove).await;
+}
+
+async fn assign_permissions(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    mode: AssignmentMode,
+) {
     let role_id = req.param::<i64>("id").unwrap();
     let role_permissions: RolePermissions = match req.parse_json().await {
         Ok(data) => data,
@@ -332,55 +271,45 @@ pub async fn update_role_permissions(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-    let mut tx = match pool.begin().await {
-        Ok(tx) => tx,
-        Err(e) => {
-            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-            res.render(Json(serde_json::json!({
-                "error": format!("Failed to start transaction: {}", e)
-            })));
-            return;
-        }
-    };
-
-    // 删除现有权限
-    if let Err(e) = sqlx::query("DELETE FROM role_permissions WHERE role_id = ?")
-        .bind(role_id)
-        .execute(&mut *tx)
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos
+        .assignments
+        .apply(
+            &ROLE_PERMISSIONS,
+            role_id,
+            &role_permissions.permission_ids,
+            mode,
+        )
         .await
     {
-        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-        res.render(Json(serde_json::json!({
-            "error": format!("Failed to delete existing permissions: {}", e)
-        })));
-        return;
-    }
-
-    // 添加新权限
-    for permission_id in role_permissions.permission_ids {
-        if let Err(e) =
-            sqlx::query("INSERT INTO role_permissions (role_id, permission_id) VALUES (?, ?)")
-                .bind(role_id)
-                .bind(permission_id)
-                .execute(&mut *tx)
-                .await
-        {
+        Ok(diff) => {
+            if !diff.is_empty() {
+                record_change(
+                    depot,
+                    "role_permissions",
+                    role_id,
+                    Some(&json!({ "permission_ids": diff.before })),
+                    Some(&json!({ "permission_ids": diff.after })),
+                );
+            }
+            res.render(Json(diff));
+        }
+        Err(AssignmentError::OwnerNotFound) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "error": "Role not found" })));
+        }
+        Err(AssignmentError::MissingTargets(ids)) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({
+                "error": "Unknown permission ids",
+                "missing_ids": ids
+            })));
+        }
+        Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-            res.render(Json(serde_json::json!({
-                "error": format!("Failed to add new permissions: {}", e)
+            res.render(Json(json!({
+                "error": format!("Failed to update role permissions: {}", e)
             })));
-            return;
         }
     }
-
-    if let Err(e) = tx.commit().await {
-        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-        res.render(Json(serde_json::json!({
-            "error": format!("Failed to commit transaction: {}", e)
-        })));
-        return;
-    }
-
-    res.status_code(StatusCode::NO_CONTENT);
 }
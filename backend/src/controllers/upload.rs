@@ -1,32 +1,302 @@
+use salvo::fs::NamedFile;
 use salvo::prelude::*;
 use serde_json::json;
-use std::path::Path;
 use uuid::Uuid;
 
+use crate::config::upload::upload_config;
+use crate::models::{CheckUpload, CreateFile, FileRecord};
+use crate::repositories::Repositories;
+use crate::services::avatar::{store_avatar, CropRect};
+use crate::services::file_access::{check_key_access, sign_file_urls, KeyAccess, Visibility};
+use crate::services::files::{blob_key, sha256_file, store_upload, FileError};
+use crate::services::storage::{storage, Download, DownloadOptions, StorageError};
+use crate::services::upload::{
+    claimed_extension, download_content_type, validate_upload, UploadError, UploadPurpose,
+};
+
+// 请求体除文件内容外的 multipart 边界和字段所占的余量
+const MULTIPART_OVERHEAD: u64 = 64 * 1024;
+
+pub fn render_upload_error(res: &mut Response, error: UploadError) {
+    res.status_code(error.status_code());
+    res.render(Json(json!({
+        "message": error.to_string()
+    })));
+}
+
+// 头像等带缩略图的文件同时返回各尺寸的地址, 私有文件返回有时效的签名地址
+fn uploaded_file_json(mut file: FileRecord) -> serde_json::Value {
+    let expires_at = sign_file_urls(&mut file, upload_config().signed_url_expires_secs);
+    let mut body = json!({
+        "id": file.id,
+        "url": file.url,
+        "content_type": file.mime,
+        "size": file.size,
+        "sha256": file.sha256,
+        "visibility": file.visibility
+    });
+    if let Some(expires_at) = expires_at {
+        body["expires_at"] = json!(expires_at);
+    }
+    if !file.variants.is_empty() {
+        body["thumbnails"] = file
+            .variants
+            .iter()
+            .map(|variant| (variant.variant.clone(), json!(variant.url)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+    body
+}
+
+pub fn render_uploaded_file(res: &mut Response, file: FileRecord) {
+    res.render(Json(uploaded_file_json(file)));
+}
+
+// 上传前按 sha256 检查服务端是否已有相同内容, 有则直接登记为新的上传, 客户端不需要再发送文件;
+// 头像需要重新处理, 总是返回不存在
 #[handler]
-pub async fn upload_file(req: &mut Request, res: &mut Response) {
+pub async fn check_upload(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let user_id = *depot.get::<i64>("user_id").unwrap();
+    let request = match req.parse_json::<CheckUpload>().await {
+        Ok(request) => request,
+        Err(e) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({
+                "message": format!("请求参数错误: {}", e)
+            })));
+            return;
+        }
+    };
+    let sha256 = request.sha256.trim().to_lowercase();
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        res.status_code(StatusCode::BAD_REQUEST);
+        res.render(Json(json!({
+            "message": "sha256 必须是 64 位十六进制字符串"
+        })));
+        return;
+    }
+    if request.size <= 0 {
+        res.status_code(StatusCode::BAD_REQUEST);
+        res.render(Json(json!({
+            "message": "文件大小必须大于 0"
+        })));
+        return;
+    }
+
+    let file_name = request.file_name.trim();
+    let checked =
+        UploadPurpose::parse(request.purpose.as_deref().unwrap_or("").trim()).and_then(|purpose| {
+            purpose.check_extension(file_name)?;
+            if request.size as u64 > purpose.max_chunked_size() {
+                return Err(UploadError::TooLarge(purpose.max_chunked_size()));
+            }
+            Ok((purpose, purpose.visibility(request.visibility.as_deref())?))
+        });
+    let (purpose, visibility) = match checked {
+        Ok((UploadPurpose::Avatar, _)) => {
+            res.render(Json(json!({ "exists": false })));
+            return;
+        }
+        Ok(checked) => checked,
+        Err(e) => {
+            render_upload_error(res, e);
+            return;
+        }
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    if let Err(e) = repos.files.check_quota(user_id, request.size as u64).await {
+        render_upload_error(res, e);
+        return;
+    }
+    let extension = claimed_extension(file_name);
+    let key = blob_key(&sha256, &extension);
+    let original_name = file_name.to_string();
+    let blob_key = key.clone();
+    let record = move |mime: String| CreateFile {
+        storage_name: format!("{}.{}", Uuid::new_v4(), extension),
+        original_name,
+        size: request.size,
+        mime,
+        sha256,
+        purpose: purpose.name().to_string(),
+        visibility: visibility.name().to_string(),
+        blob_key,
+        uploader_id: user_id,
+    };
+    let registered = repos
+        .files
+        .reuse_blob(&key, request.size, user_id, Box::new(record))
+        .await;
+
+    match registered {
+        Ok(Some(file)) => {
+            println!("[Upload] Reused stored content for {}", file_name);
+            res.render(Json(json!({
+                "exists": true,
+                "file": uploaded_file_json(file)
+            })));
+        }
+        Ok(None) => res.render(Json(json!({ "exists": false }))),
+        Err(FileError::Upload(e)) => render_upload_error(res, e),
+        Err(e) => {
+            eprintln!("[Upload] Failed to check {}: {}", file_name, e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": "检查文件失败"
+            })));
+        }
+    }
+}
+
+// 附件默认私有, 可以通过 visibility=public 查询参数上传为公开文件
+#[handler]
+pub async fn upload_file(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let purpose = req.query::<String>("purpose").unwrap_or_default();
+    let visibility = req.query::<String>("visibility");
+    let resolved = UploadPurpose::parse(purpose.trim())
+        .and_then(|purpose| Ok((purpose, purpose.visibility(visibility.as_deref())?)));
+    match resolved {
+        Ok((purpose, visibility)) => {
+            receive_upload(req, depot, res, purpose, visibility, None).await
+        }
+        Err(e) => render_upload_error(res, e),
+    }
+}
+
+// 上传头像, 可以通过 x、y、width、height 查询参数指定裁剪区域
+#[handler]
+pub async fn upload_avatar(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let fields = [
+        req.query::<u32>("x"),
+        req.query::<u32>("y"),
+        req.query::<u32>("width"),
+        req.query::<u32>("height"),
+    ];
+    let crop = match fields {
+        [Some(x), Some(y), Some(width), Some(height)] => Some(CropRect {
+            x,
+            y,
+            width,
+            height,
+        }),
+        [None, None, None, None] => None,
+        _ => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({
+                "message": "裁剪区域需要同时提供 x、y、width、height"
+            })));
+            return;
+        }
+    };
+    receive_upload(
+        req,
+        depot,
+        res,
+        UploadPurpose::Avatar,
+        Visibility::Public,
+        crop,
+    )
+    .await;
+}
+
+async fn receive_upload(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    purpose: UploadPurpose,
+    visibility: Visibility,
+    crop: Option<CropRect>,
+) {
+    let user_id = *depot.get::<i64>("user_id").unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap().clone();
+
+    // 在读取请求体之前按 Content-Length 拒绝过大的上传
+    match req.header::<u64>("Content-Length") {
+        Some(length) if length > purpose.max_size() + MULTIPART_OVERHEAD => {
+            render_upload_error(res, UploadError::TooLarge(purpose.max_size()));
+            return;
+        }
+        Some(_) => {}
+        None => {
+            res.status_code(StatusCode::LENGTH_REQUIRED);
+            res.render(Json(json!({
+                "message": "缺少 Content-Length 请求头"
+            })));
+            return;
+        }
+    }
+
     if let Some(files) = req.files("file").await {
         if let Some(file) = files.first() {
             let name = file.name().unwrap_or("unknown").to_string();
-            let ext = Path::new(&name)
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or("bin");
-
-            let filename = format!("{}.{}", Uuid::new_v4(), ext);
-            let filepath = Path::new("uploads").join(&filename);
-
-            if let Err(e) = std::fs::copy(file.path(), &filepath) {
-                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-                res.render(Json(json!({
-                    "message": format!("Failed to save file: {}", e)
-                })));
+            if let Err(e) = repos.files.check_quota(user_id, file.size()).await {
+                println!("[Upload] Rejected {}: {}", name, e);
+                render_upload_error(res, e);
                 return;
             }
 
-            res.render(Json(json!({
-                "url": format!("/uploads/{}", filename)
-            })));
+            // 头像需要解码处理并生成缩略图
+            if purpose == UploadPurpose::Avatar {
+                match store_avatar(&repos, file.path(), &name, file.size(), crop, user_id).await {
+                    Ok(file) => render_uploaded_file(res, file),
+                    Err(e) => {
+                        println!("[Upload] Rejected avatar {}: {}", name, e);
+                        res.status_code(e.status_code());
+                        res.render(Json(json!({
+                            "message": e.to_string()
+                        })));
+                    }
+                }
+                return;
+            }
+
+            let detected =
+                match validate_upload(purpose, &name, file.path(), file.size(), purpose.max_size())
+                {
+                    Ok(detected) => detected,
+                    Err(e) => {
+                        println!("[Upload] Rejected {}: {}", name, e);
+                        render_upload_error(res, e);
+                        return;
+                    }
+                };
+
+            let sha256 = match sha256_file(file.path()) {
+                Ok(sha256) => sha256,
+                Err(e) => {
+                    render_upload_error(res, UploadError::Io(e));
+                    return;
+                }
+            };
+
+            match store_upload(
+                &repos,
+                file.path(),
+                &name,
+                file.size(),
+                sha256,
+                detected,
+                purpose,
+                visibility,
+                user_id,
+            )
+            .await
+            {
+                Ok(file) => render_uploaded_file(res, file),
+                Err(FileError::Upload(e)) => {
+                    println!("[Upload] Rejected {}: {}", name, e);
+                    render_upload_error(res, e);
+                }
+                Err(e) => {
+                    eprintln!("[Upload] Failed to store {}: {}", name, e);
+                    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                    res.render(Json(json!({
+                        "message": format!("Failed to save file: {}", e)
+                    })));
+                }
+            }
         } else {
             res.status_code(StatusCode::BAD_REQUEST);
             res.render(Json(json!({
@@ -40,3 +310,127 @@ pub async fn upload_file(req: &mut Request, res: &mut Response) {
         })));
     }
 }
+
+fn render_not_found(res: &mut Response) {
+    res.status_code(StatusCode::NOT_FOUND);
+    res.render(Json(json!({
+        "message": "文件不存在"
+    })));
+}
+
+// 公开文件直接访问, 私有文件需要带上有效的 expires 和 signature 参数
+#[handler]
+pub async fn download_upload(req: &mut Request, res: &mut Response) {
+    let key = req.params().tail().unwrap_or_default().to_string();
+    let valid_key = !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && !segment.starts_with('.')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+    if !valid_key {
+        render_not_found(res);
+        return;
+    }
+
+    let repos = req.extensions().get::<Repositories>().unwrap().clone();
+    let expires = req.query::<i64>("expires");
+    let signature = req.query::<String>("signature");
+    let storage_key = match check_key_access(&repos, &key, expires, signature.as_deref()).await {
+        Ok(KeyAccess::Allowed(storage_key)) => storage_key,
+        Ok(KeyAccess::Hidden) => {
+            render_not_found(res);
+            return;
+        }
+        Ok(KeyAccess::Denied) => {
+            res.status_code(StatusCode::FORBIDDEN);
+            res.render(Json(json!({
+                "message": "访问地址已过期或签名无效"
+            })));
+            return;
+        }
+        Err(e) => {
+            eprintln!("[Upload] Failed to check access to {}: {}", key, e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": "服务器内部错误"
+            })));
+            return;
+        }
+    };
+    // 私有文件的响应不允许共享缓存保存
+    if expires.is_some() {
+        let _ = res.add_header("Cache-Control", "private, no-store", true);
+    }
+    let name = key.rsplit('/').next().unwrap_or(&key);
+    send_stored_file(req, res, &storage_key, name).await;
+}
+
+// 按扩展名强制使用安全的 Content-Type, 图片以外的文件一律作为附件下载;
+// 对象存储开启重定向时返回预签名地址, 否则由服务端转发文件内容
+// name 为下载时使用的文件名, 同时决定响应的类型
+pub async fn send_stored_file(req: &mut Request, res: &mut Response, key: &str, name: &str) {
+    let (content_type, inline) = download_content_type(name);
+    let disposition = if inline { "inline" } else { "attachment" };
+    let options = DownloadOptions {
+        content_type,
+        disposition: format!("{}; filename=\"{}\"", disposition, name),
+    };
+
+    let download = match storage().download(key, &options).await {
+        Ok(download) => download,
+        Err(StorageError::NotFound) => {
+            render_not_found(res);
+            return;
+        }
+        Err(e) => {
+            eprintln!("[Upload] Failed to read {}: {}", key, e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(json!({
+                "message": format!("Failed to read file: {}", e)
+            })));
+            return;
+        }
+    };
+
+    let _ = res.add_header("X-Content-Type-Options", "nosniff", true);
+    let _ = res.add_header(
+        "Content-Security-Policy",
+        "default-src 'none'; sandbox",
+        true,
+    );
+    match download {
+        Download::Redirect(url) => {
+            // 预签名地址有时效, 不允许缓存重定向
+            let _ = res.add_header("Cache-Control", "no-store", true);
+            res.render(Redirect::found(url));
+        }
+        Download::File(path) => {
+            let builder = NamedFile::builder(&path)
+                .content_type(content_type.parse().unwrap())
+                .attached_name(name)
+                .disposition_type(disposition);
+            match builder.build().await {
+                Ok(file) => file.send(req.headers(), res).await,
+                Err(e) => {
+                    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                    res.render(Json(json!({
+                        "message": format!("Failed to read file: {}", e)
+                    })));
+                }
+            }
+        }
+        Download::Stream { body, length } => {
+            let _ = res.add_header("Content-Type", content_type, true);
+            let _ = res.add_header("Content-Disposition", &options.disposition, true);
+            if let Some(length) = length {
+                let _ = res.add_header("Content-Length", length, true);
+            }
+            res.stream(futures_util::stream::unfold(body, |mut body| async move {
+                body.next().await.map(|chunk| (chunk, body))
+            }));
+        }
+    }
+}
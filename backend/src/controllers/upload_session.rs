@@ -0,0 +1,141 @@
+use salvo::prelude::*;
+use serde_json::json;
+
+use crate::controllers::upload::render_uploaded_file;
+use crate::models::{CompleteUploadSession, CreateUploadSession, UploadSession};
+use crate::repositories::Repositories;
+use crate::services::upload_session::{
+    abort_session, complete_session, create_session, find_session, save_chunk, session_status,
+    SessionError,
+};
+
+// 客户端可以在请求头中提供分片的 sha256, 服务端校验后再保存
+const CHUNK_CHECKSUM_HEADER: &str = "X-Chunk-Sha256";
+
+fn render_session_error(res: &mut Response, error: SessionError) {
+    if error.status_code().is_server_error() {
+        eprintln!("[Upload Session] {}", error);
+    }
+    res.status_code(error.status_code());
+    res.render(Json(json!({
+        "message": error.to_string()
+    })));
+}
+
+async fn current_session(
+    req: &mut Request,
+    depot: &mut Depot,
+) -> Result<(Repositories, UploadSession), SessionError> {
+    let repos = req.extensions().get::<Repositories>().unwrap().clone();
+    let user_id = *depot.get::<i64>("user_id").unwrap();
+    let id = req.param::<String>("id").unwrap_or_default();
+    let session = find_session(&repos, &id, user_id).await?;
+    Ok((repos, session))
+}
+
+#[handler]
+pub async fn create_upload_session(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let user_id = *depot.get::<i64>("user_id").unwrap();
+    let request = match req.parse_json::<CreateUploadSession>().await {
+        Ok(request) => request,
+        Err(e) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({
+                "message": format!("Invalid request: {}", e)
+            })));
+            return;
+        }
+    };
+
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match create_session(repos, user_id, request).await {
+        Ok(status) => {
+            res.status_code(StatusCode::CREATED);
+            res.render(Json(status));
+        }
+        Err(e) => render_session_error(res, e),
+    }
+}
+
+#[handler]
+pub async fn get_upload_session(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let result = match current_session(req, depot).await {
+        Ok((repos, session)) => session_status(&repos, session).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(status) => res.render(Json(status)),
+        Err(e) => render_session_error(res, e),
+    }
+}
+
+#[handler]
+pub async fn upload_session_chunk(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let (repos, session) = match current_session(req, depot).await {
+        Ok(current) => current,
+        Err(e) => {
+            render_session_error(res, e);
+            return;
+        }
+    };
+    let index = req.param::<i64>("index").unwrap_or(-1);
+    let checksum = req.header::<String>(CHUNK_CHECKSUM_HEADER);
+
+    // 请求体最多读取一个分片的大小, 超出时拒绝
+    let data = match req.payload_with_max_size(session.chunk_size as usize).await {
+        Ok(data) => data,
+        Err(e) => {
+            res.status_code(StatusCode::PAYLOAD_TOO_LARGE);
+            res.render(Json(json!({
+                "message": format!("分片大小不能超过 {} 字节: {}", session.chunk_size, e)
+            })));
+            return;
+        }
+    };
+
+    match save_chunk(&repos, &session, index, data, checksum.as_deref()).await {
+        Ok(receipt) => res.render(Json(receipt)),
+        Err(e) => render_session_error(res, e),
+    }
+}
+
+#[handler]
+pub async fn complete_upload_session(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let (repos, session) = match current_session(req, depot).await {
+        Ok(current) => current,
+        Err(e) => {
+            render_session_error(res, e);
+            return;
+        }
+    };
+    // 请求体可以为空, 此时使用创建会话时提供的 sha256
+    let request = req
+        .parse_json::<CompleteUploadSession>()
+        .await
+        .unwrap_or_default();
+
+    match complete_session(&repos, &session, request.sha256.as_deref()).await {
+        Ok(file) => {
+            println!(
+                "[Upload Session] Completed {} as file {}",
+                session.id, file.id
+            );
+            render_uploaded_file(res, file);
+        }
+        Err(e) => render_session_error(res, e),
+    }
+}
+
+#[handler]
+pub async fn abort_upload_session(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let result = match current_session(req, depot).await {
+        Ok((repos, session)) => abort_session(&repos, &session).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {
+            res.status_code(StatusCode::NO_CONTENT);
+        }
+        Err(e) => render_session_error(res, e),
+    }
+}
@@ -1,10 +1,24 @@
-use crate::models::{CreateUser, Permission, Role, UpdateUser, User};
+use crate::models::{CreateLoginLog, CreateOperationLog, CreateUser, LoginLog, UpdateUser, User};
+use crate::repositories::user::{UpdateProfile, UserQuery};
+use crate::repositories::Repositories;
+use crate::services::assignment::{AssignmentError, AssignmentMode, USER_ROLES};
+use crate::services::audit_stream::AuditEvent;
+use crate::services::avatar::check_avatar_url;
+use crate::services::export::{
+    record_export, stream_export, Cell, ExportColumn, ExportFormat, Lang, EXPORT_QUEUE_SIZE,
+};
+use crate::services::log_writer::LogWriter;
+use crate::services::login_log::{record_login, REASON_USER_NOT_FOUND, REASON_WRONG_PASSWORD};
+use crate::utils::audit::record_change;
+use crate::utils::ip::parse_ip;
 use crate::utils::jwt::generate_token;
 use crate::utils::password::{hash_password, verify_password};
+use futures_util::TryStreamExt;
 use salvo::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use sqlx::SqlitePool;
+use std::time::Instant;
+use tokio::sync::mpsc;
 
 #[derive(Debug, serde::Deserialize)]
 pub struct UserRoles {
@@ -19,14 +33,6 @@ pub struct PageResponse<T> {
     pub page_size: i64,
 }
 
-#[derive(Debug, serde::Deserialize)]
-pub struct UserQuery {
-    pub username: Option<String>,
-    pub nickname: Option<String>,
-    pub email: Option<String>,
-    pub status: Option<i32>,
-}
-
 #[derive(Deserialize, Serialize)]
 pub struct UpdateProfileRequest {
     pub nickname: Option<String>,
@@ -46,14 +52,15 @@ pub struct LoginRequest {
     password: String,
 }
 
-#[derive(Serialize)]
-pub struct LoginResponse {
-    token: String,
-    user: User,
+// 登录日志写入后推送到实时审计事件流
+fn publish_login(log_writer: Option<&LogWriter>, login_log: Option<LoginLog>) {
+    if let (Some(writer), Some(login_log)) = (log_writer, login_log) {
+        writer.events().publish(AuditEvent::Login(login_log));
+    }
 }
 
 #[handler]
-pub async fn login(req: &mut Request, res: &mut Response) {
+pub async fn login(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     // 解析登录请求
     let login_req = match req.parse_json::<LoginRequest>().await {
         Ok(req) => req,
@@ -66,14 +73,20 @@ pub async fn login(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap().clone();
+    let log_writer = req.extensions().get::<LogWriter>().cloned();
+    let mut login_log = CreateLoginLog {
+        user_id: None,
+        username: login_req.username.clone(),
+        success: false,
+        failure_reason: None,
+        ip: parse_ip(&req.remote_addr().to_string()).map(|ip| ip.to_string()),
+        user_agent: req.header::<String>("User-Agent"),
+        request_id: depot.get::<String>("request_id").ok().cloned(),
+    };
 
     // 查询用户
-    let user = match sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
-        .bind(&login_req.username)
-        .fetch_optional(pool)
-        .await
-    {
+    let user = match repos.users.find_by_username(&login_req.username).await {
         Ok(user) => user,
         Err(e) => {
             eprintln!("Database error: {}", e);
@@ -86,34 +99,42 @@ pub async fn login(req: &mut Request, res: &mut Response) {
     };
 
     match user {
-        Some(user) => {
-            // 验证密码
-            if verify_password(&login_req.password, &user.password) {
-                // 生成 token
-                let token = generate_token(user.id);
+        // 验证密码
+        Some(user) if verify_password(&login_req.password, &user.password) => {
+            login_log.user_id = Some(user.id);
+            login_log.success = true;
+            publish_login(log_writer.as_ref(), record_login(&repos, login_log).await);
 
-                // 返回登录成功响应
-                res.render(Json(json!({
-                    "token": token,
-                    "user": json!({
-                        "id": user.id,
-                        "username": user.username,
-                        "nickname": user.nickname,
-                        "email": user.email,
-                        "avatar": user.avatar,
-                        "status": user.status,
-                        "created_at": user.created_at,
-                        "updated_at": user.updated_at
-                    })
-                })));
-            } else {
-                res.status_code(StatusCode::UNAUTHORIZED);
-                res.render(Json(json!({
-                    "message": "用户名或密码错误"
-                })));
-            }
+            // 生成 token
+            let token = generate_token(user.id);
+
+            // 返回登录成功响应
+            res.render(Json(json!({
+                "token": token,
+                "user": json!({
+                    "id": user.id,
+                    "username": user.username,
+                    "nickname": user.nickname,
+                    "email": user.email,
+                    "avatar": user.avatar,
+                    "status": user.status,
+                    "created_at": user.created_at,
+                    "updated_at": user.updated_at
+                })
+            })));
         }
-        None => {
+        user => {
+            // 用户名存在但密码错误时记录用户ID, 便于用户查看自己账号的失败登录
+            login_log.failure_reason = Some(
+                match &user {
+                    Some(_) => REASON_WRONG_PASSWORD,
+                    None => REASON_USER_NOT_FOUND,
+                }
+                .to_string(),
+            );
+            login_log.user_id = user.map(|user| user.id);
+            publish_login(log_writer.as_ref(), record_login(&repos, login_log).await);
+
             res.status_code(StatusCode::UNAUTHORIZED);
             res.render(Json(json!({
                 "message": "用户名或密码错误"
@@ -125,15 +146,16 @@ pub async fn login(req: &mut Request, res: &mut Response) {
 #[handler]
 pub async fn update_password(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let user_id = depot.get::<i64>("user_id").unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap().clone();
+    let repos = req.extensions().get::<Repositories>().unwrap().clone();
 
     match req.parse_json::<UpdatePasswordRequest>().await {
         Ok(pwd_req) => {
             // 先验证旧密码
-            let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-                .bind(user_id)
-                .fetch_one(&pool)
-                .await;
+            let user = repos
+                .users
+                .find(*user_id)
+                .await
+                .and_then(|user| user.ok_or(sqlx::Error::RowNotFound));
 
             match user {
                 Ok(user) => {
@@ -149,11 +171,7 @@ pub async fn update_password(req: &mut Request, depot: &mut Depot, res: &mut Res
                     let hashed = hash_password(&pwd_req.new_password);
 
                     // 更新密码
-                    let result = sqlx::query("UPDATE users SET password = ? WHERE id = ?")
-                        .bind(&hashed)
-                        .bind(user_id)
-                        .execute(&pool)
-                        .await;
+                    let result = repos.users.update_password(*user_id, &hashed).await;
 
                     match result {
                         Ok(_) => {
@@ -186,63 +204,28 @@ pub async fn update_password(req: &mut Request, depot: &mut Depot, res: &mut Res
     }
 }
 
+// 用户列表和导出共用的查询条件
+pub fn user_filter(req: &mut Request) -> UserQuery {
+    UserQuery {
+        username: req.query::<String>("username").unwrap_or_default(),
+        nickname: req.query::<String>("nickname").unwrap_or_default(),
+        email: req.query::<String>("email").unwrap_or_default(),
+        status: req.query::<i32>("status"),
+    }
+}
+
 #[handler]
 pub async fn get_users(req: &mut Request, res: &mut Response) {
     let page = req.query::<i64>("page").unwrap_or(1);
     let page_size = req.query::<i64>("page_size").unwrap_or(10);
     let offset = (page - 1) * page_size;
 
-    // 获取查询参数
-    let username = req.query::<String>("username").unwrap_or_default();
-    let nickname = req.query::<String>("nickname").unwrap_or_default();
-    let email = req.query::<String>("email").unwrap_or_default();
-    let status = req.query::<i32>("status");
-
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-
-    // 构建查询条件
-    let mut conditions = Vec::new();
-    let mut params: Vec<String> = Vec::new();
-
-    if !username.is_empty() {
-        conditions.push("username LIKE ?");
-        params.push(format!("%{}%", username));
-    }
-    if !nickname.is_empty() {
-        conditions.push("nickname LIKE ?");
-        params.push(format!("%{}%", nickname));
-    }
-    if !email.is_empty() {
-        conditions.push("email LIKE ?");
-        params.push(format!("%{}%", email));
-    }
-    if let Some(status) = status {
-        conditions.push("status = ?");
-        params.push(status.to_string());
-    }
-
     // 构建查询语句
-    let mut query = String::from("SELECT * FROM users");
-    if !conditions.is_empty() {
-        query.push_str(" WHERE ");
-        query.push_str(&conditions.join(" AND "));
-    }
-    query.push_str(" LIMIT ? OFFSET ?");
-
-    // 构建计数查询语句
-    let mut count_query = String::from("SELECT COUNT(*) FROM users");
-    if !conditions.is_empty() {
-        count_query.push_str(" WHERE ");
-        count_query.push_str(&conditions.join(" AND "));
-    }
+    let query = user_filter(req);
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
     // 获取总数
-    let mut count_query_builder = sqlx::query_scalar::<_, i64>(&count_query);
-    for param in &params {
-        count_query_builder = count_query_builder.bind(param);
-    }
-
-    let total = match count_query_builder.fetch_one(pool).await {
+    let total = match repos.users.count(&query).await {
         Ok(total) => total,
         Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
@@ -254,13 +237,7 @@ pub async fn get_users(req: &mut Request, res: &mut Response) {
     };
 
     // 获取分页数据
-    let mut query_builder = sqlx::query_as::<_, User>(&query);
-    for param in &params {
-        query_builder = query_builder.bind(param);
-    }
-    query_builder = query_builder.bind(page_size).bind(offset);
-
-    match query_builder.fetch_all(pool).await {
+    match repos.users.list(&query, page_size, offset).await {
         Ok(users) => {
             let response = PageResponse {
                 items: users,
@@ -279,6 +256,71 @@ pub async fn get_users(req: &mut Request, res: &mut Response) {
     }
 }
 
+const USER_EXPORT_COLUMNS: &[ExportColumn] = &[
+    ExportColumn::new("ID", "ID", 8.0),
+    ExportColumn::new("用户名", "Username", 16.0),
+    ExportColumn::new("昵称", "Nickname", 16.0),
+    ExportColumn::new("邮箱", "Email", 28.0),
+    ExportColumn::new("角色", "Roles", 24.0),
+    ExportColumn::new("状态", "Status", 8.0),
+    ExportColumn::new("创建时间", "Created At", 20.0),
+    ExportColumn::new("更新时间", "Updated At", 20.0),
+];
+
+#[handler]
+pub async fn export_users(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let format = match ExportFormat::parse(&req.query::<String>("format").unwrap_or_default()) {
+        Ok(format) => format,
+        Err(message) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({ "message": message })));
+            return;
+        }
+    };
+    let lang = Lang::from_request(req);
+    let query = user_filter(req);
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 边查询边写出, 不在内存中缓存整张表
+    let (tx, rx) = mpsc::channel(EXPORT_QUEUE_SIZE);
+    let mut rows = repos.users.export(&query);
+    let producer = tokio::spawn(async move {
+        while let Some(user) = rows.try_next().await? {
+            let status = if user.status == 1 { "启用" } else { "禁用" };
+            let row = vec![
+                Cell::from(user.id),
+                Cell::from(user.username),
+                Cell::from(user.nickname),
+                Cell::from(user.email),
+                Cell::from(user.roles),
+                Cell::from(match lang {
+                    Lang::Zh => status,
+                    Lang::En if user.status == 1 => "Enabled",
+                    Lang::En => "Disabled",
+                }),
+                Cell::from(user.created_at),
+                Cell::from(user.updated_at),
+            ];
+            if tx.send(row).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+
+    let writer = req.extensions().get::<LogWriter>().unwrap().clone();
+    let log = depot.remove::<CreateOperationLog>("operation_log").ok();
+    let started_at = depot
+        .get::<Instant>("operation_log_started_at")
+        .ok()
+        .copied();
+    let file_name = format!("users_{}", chrono::Utc::now().format("%Y%m%d%H%M%S"));
+    let export = stream_export(res, format, &file_name, USER_EXPORT_COLUMNS, lang, rx);
+    tokio::spawn(record_export(
+        writer, log, started_at, format, producer, export,
+    ));
+}
+
 #[handler]
 pub async fn create_user(req: &mut Request, res: &mut Response) {
     let user: CreateUser = match req.parse_json().await {
@@ -292,24 +334,16 @@ pub async fn create_user(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    if let Some(avatar) = &user.avatar {
+        if let Err(message) = check_avatar_url(repos, avatar, None).await {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(serde_json::json!({ "error": message })));
+            return;
+        }
+    }
     let hashed_password = hash_password(&user.password);
-    match sqlx::query_as::<_, User>(
-        r#"
-        INSERT INTO users (username, password, nickname, email, avatar, status)
-        VALUES (?, ?, ?, ?, ?, ?)
-        RETURNING *
-        "#,
-    )
-    .bind(&user.username)
-    .bind(&hashed_password)
-    .bind(&user.nickname)
-    .bind(&user.email)
-    .bind(&user.avatar)
-    .bind(user.status)
-    .fetch_one(pool)
-    .await
-    {
+    match repos.users.create(&user, &hashed_password).await {
         Ok(user) => {
             res.status_code(StatusCode::CREATED);
             res.render(Json(user));
@@ -324,7 +358,7 @@ pub async fn create_user(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn update_user(req: &mut Request, res: &mut Response) {
+pub async fn update_user(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
     let user: UpdateUser = match req.parse_json().await {
         Ok(user) => user,
@@ -337,25 +371,28 @@ pub async fn update_user(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-    match sqlx::query_as::<_, User>(
-        r#"
-        UPDATE users 
-        SET nickname = ?, email = ?, avatar = ?, status = ?,
-            updated_at = CURRENT_TIMESTAMP
-        WHERE id = ?
-        RETURNING *
-        "#,
-    )
-    .bind(&user.nickname)
-    .bind(&user.email)
-    .bind(&user.avatar)
-    .bind(user.status)
-    .bind(id)
-    .fetch_one(pool)
-    .await
-    {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    // 记录修改前的数据, 用于操作日志的变更对比
+    let before = repos.users.find(id).await.ok().flatten();
+
+    // 未修改的头像不再校验, 兼容登记文件之前上传的头像
+    if let Some(avatar) = &user.avatar {
+        let unchanged = before
+            .as_ref()
+            .is_some_and(|b| b.avatar.as_ref() == Some(avatar));
+        if !unchanged {
+            if let Err(message) = check_avatar_url(repos, avatar, None).await {
+                res.status_code(StatusCode::BAD_REQUEST);
+                res.render(Json(serde_json::json!({ "error": message })));
+                return;
+            }
+        }
+    }
+
+    match repos.users.update(id, &user).await {
         Ok(user) => {
+            record_change(depot, "user", id, before.as_ref(), Some(&user));
             res.render(Json(user));
         }
         Err(e) => {
@@ -368,16 +405,16 @@ pub async fn update_user(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn delete_user(req: &mut Request, res: &mut Response) {
+pub async fn delete_user(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    match sqlx::query("DELETE FROM users WHERE id = ?")
-        .bind(id)
-        .execute(pool)
-        .await
-    {
+    // 记录删除前的数据, 用于操作日志的变更对比
+    let before = repos.users.find(id).await.ok().flatten();
+
+    match repos.users.delete(id).await {
         Ok(_) => {
+            record_change::<User>(depot, "user", id, before.as_ref(), None);
             res.status_code(StatusCode::NO_CONTENT);
         }
         Err(e) => {
@@ -392,13 +429,9 @@ pub async fn delete_user(req: &mut Request, res: &mut Response) {
 #[handler]
 pub async fn get_user(req: &mut Request, res: &mut Response) {
     let id: i64 = req.param::<String>("id").unwrap().parse().unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
-    match sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-        .bind(id)
-        .fetch_optional(pool)
-        .await
-    {
+    match repos.users.find(id).await {
         Ok(Some(user)) => {
             res.render(Json(user));
         }
@@ -420,17 +453,14 @@ pub async fn get_user(req: &mut Request, res: &mut Response) {
 #[handler]
 pub async fn get_current_user(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let user_id = depot.get::<i64>("user_id").unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
     // 查询用户基本信息
-    let user = match sqlx::query_as::<_, User>(
-        r#"
-        SELECT * FROM users WHERE id = ?
-        "#,
-    )
-    .bind(user_id)
-    .fetch_one(pool)
-    .await
+    let user = match repos
+        .users
+        .find(*user_id)
+        .await
+        .and_then(|user| user.ok_or(sqlx::Error::RowNotFound))
     {
         Ok(user) => user,
         Err(e) => {
@@ -445,17 +475,7 @@ pub async fn get_current_user(req: &mut Request, depot: &mut Depot, res: &mut Re
     };
 
     // 查询用户的角色
-    let roles = match sqlx::query_as::<_, Role>(
-        r#"
-        SELECT r.* FROM roles r
-        INNER JOIN user_roles ur ON ur.role_id = r.id
-        WHERE ur.user_id = ?
-        "#,
-    )
-    .bind(user_id)
-    .fetch_all(pool)
-    .await
-    {
+    let roles = match repos.roles.for_user(*user_id).await {
         Ok(roles) => roles,
         Err(e) => {
             eprintln!("获取用户角色失败: {:?}", e);
@@ -489,19 +509,9 @@ pub async fn get_current_user(req: &mut Request, depot: &mut Depot, res: &mut Re
 #[handler]
 pub async fn get_user_roles(req: &mut Request, res: &mut Response) {
     let user_id = req.param::<i64>("id").unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-
-    match sqlx::query_as::<_, Role>(
-        r#"
-        SELECT r.* FROM roles r
-        INNER JOIN user_roles ur ON ur.role_id = r.id
-        WHERE ur.user_id = ?
-        "#,
-    )
-    .bind(user_id)
-    .fetch_all(pool)
-    .await
-    {
+    let repos = req.extensions().get::<Repositories>().unwrap();
+
+    match repos.roles.for_user(user_id).await {
         Ok(roles) => {
             res.render(Json(json!({
                 "roles": roles
@@ -517,7 +527,26 @@ pub async fn get_user_roles(req: &mut Request, res: &mut Response) {
 }
 
 #[handler]
-pub async fn update_user_roles(req: &mut Request, res: &mut Response) {
+pub async fn update_user_roles(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    assign_roles(req, depot, res, AssignmentMode::Replace).await;
+}
+
+#[handler]
+pub async fn add_user_roles(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    assign_roles(req, depot, res, AssignmentMode::Add).await;
+}
+
+#[handler]
+pub async fn remove_user_roles(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    assign_roles(req, depot, res, AssignmentMode::Remove).await;
+}
+
+async fn assign_roles(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    mode: AssignmentMode,
+) {
     let user_id = req.param::<i64>("id").unwrap();
     let user_roles: UserRoles = match req.parse_json().await {
         Ok(data) => data,
@@ -530,56 +559,42 @@ pub async fn update_user_roles(req: &mut Request, res: &mut Response) {
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
-    let mut tx = match pool.begin().await {
-        Ok(tx) => tx,
-        Err(e) => {
-            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-            res.render(Json(serde_json::json!({
-                "error": format!("Failed to start transaction: {}", e)
-            })));
-            return;
-        }
-    };
-
-    // 删除现有角色
-    if let Err(e) = sqlx::query("DELETE FROM user_roles WHERE user_id = ?")
-        .bind(user_id)
-        .execute(&mut *tx)
+    let repos = req.extensions().get::<Repositories>().unwrap();
+    match repos
+        .assignments
+        .apply(&USER_ROLES, user_id, &user_roles.role_ids, mode)
         .await
     {
-        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-        res.render(Json(serde_json::json!({
-            "error": format!("Failed to delete existing roles: {}", e)
-        })));
-        return;
-    }
-
-    // 添加新角色
-    for role_id in user_roles.role_ids {
-        if let Err(e) = sqlx::query("INSERT INTO user_roles (user_id, role_id) VALUES (?, ?)")
-            .bind(user_id)
-            .bind(role_id)
-            .execute(&mut *tx)
-            .await
-        {
+        Ok(diff) => {
+            if !diff.is_empty() {
+                record_change(
+                    depot,
+                    "user_roles",
+                    user_id,
+                    Some(&json!({ "role_ids": diff.before })),
+                    Some(&json!({ "role_ids": diff.after })),
+                );
+            }
+            res.render(Json(diff));
+        }
+        Err(AssignmentError::OwnerNotFound) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(json!({ "error": "User not found" })));
+        }
+        Err(AssignmentError::MissingTargets(ids)) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(json!({
+                "error": "Unknown role ids",
+                "missing_ids": ids
+            })));
+        }
+        Err(e) => {
             res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-            res.render(Json(serde_json::json!({
-                "error": format!("Failed to add new roles: {}", e)
+            res.render(Json(json!({
+                "error": format!("Failed to update user roles: {}", e)
             })));
-            return;
         }
     }
-
-    if let Err(e) = tx.commit().await {
-        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-        res.render(Json(serde_json::json!({
-            "error": format!("Failed to commit transaction: {}", e)
-        })));
-        return;
-    }
-
-    res.status_code(StatusCode::NO_CONTENT);
 }
 
 #[handler]
@@ -599,13 +614,14 @@ pub async fn update_profile(req: &mut Request, depot: &mut Depot, res: &mut Resp
         }
     };
 
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
     // 先获取当前用户信息
-    let current_user = match sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-        .bind(user_id)
-        .fetch_one(pool)
+    let current_user = match repos
+        .users
+        .find(*user_id)
         .await
+        .and_then(|user| user.ok_or(sqlx::Error::RowNotFound))
     {
         Ok(user) => user,
         Err(e) => {
@@ -645,51 +661,38 @@ pub async fn update_profile(req: &mut Request, depot: &mut Depot, res: &mut Resp
         current_user.email
     };
 
+    if let Some(avatar) = &profile_req.avatar {
+        if current_user.avatar.as_ref() != Some(avatar) {
+            if let Err(message) = check_avatar_url(repos, avatar, Some(*user_id)).await {
+                res.status_code(StatusCode::BAD_REQUEST);
+                res.render(Json(json!({ "message": message })));
+                return;
+            }
+        }
+    }
     let avatar = profile_req.avatar.or(current_user.avatar);
 
     // 执行更新
-    let result = sqlx::query(
-        "UPDATE users SET nickname = ?, email = ?, avatar = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
-    )
-    .bind(&nickname)
-    .bind(&email)
-    .bind(&avatar)
-    .bind(user_id)
-    .execute(pool)
-    .await;
-
-    match result {
-        Ok(_) => {
-            // 查询更新后的用户信息
-            let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-                .bind(user_id)
-                .fetch_one(pool)
-                .await;
-
-            match user {
-                Ok(user) => {
-                    res.render(Json(json!({
-                        "message": "更新成功",
-                        "user": json!({
-                            "id": user.id,
-                            "username": user.username,
-                            "nickname": user.nickname,
-                            "email": user.email,
-                            "avatar": user.avatar,
-                            "status": user.status,
-                            "created_at": user.created_at,
-                            "updated_at": user.updated_at
-                        })
-                    })));
-                }
-                Err(e) => {
-                    eprintln!("获取更新后的用户信息失败: {:?}", e);
-                    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
-                    res.render(Json(json!({
-                        "message": format!("获取更新后的用户信息失败: {}", e)
-                    })));
-                }
-            }
+    let profile = UpdateProfile {
+        nickname,
+        email,
+        avatar,
+    };
+    match repos.users.update_profile(*user_id, &profile).await {
+        Ok(user) => {
+            res.render(Json(json!({
+                "message": "更新成功",
+                "user": json!({
+                    "id": user.id,
+                    "username": user.username,
+                    "nickname": user.nickname,
+                    "email": user.email,
+                    "avatar": user.avatar,
+                    "status": user.status,
+                    "created_at": user.created_at,
+                    "updated_at": user.updated_at
+                })
+            })));
         }
         Err(e) => {
             eprintln!("更新用户信息失败: {:?}", e);
@@ -704,20 +707,10 @@ pub async fn update_profile(req: &mut Request, depot: &mut Depot, res: &mut Resp
 #[handler]
 pub async fn get_user_permissions(req: &mut Request, depot: &mut Depot, res: &mut Response) {
     let user_id = depot.get::<i64>("user_id").unwrap();
-    let pool = req.extensions().get::<SqlitePool>().unwrap();
+    let repos = req.extensions().get::<Repositories>().unwrap();
 
     // 查询用户的角色
-    let roles = match sqlx::query_as::<_, Role>(
-        r#"
-        SELECT r.* FROM roles r
-        INNER JOIN user_roles ur ON ur.role_id = r.id
-        WHERE ur.user_id = ?
-        "#,
-    )
-    .bind(user_id)
-    .fetch_all(pool)
-    .await
-    {
+    let roles = match repos.roles.for_user(*user_id).await {
         Ok(roles) => roles,
         Err(e) => {
             eprintln!("获取用户角色失败: {:?}", e);
@@ -733,17 +726,7 @@ pub async fn get_user_permissions(req: &mut Request, depot: &mut Depot, res: &mu
     // 获取角色的所有权限
     let mut permissions = Vec::new();
     for role in roles {
-        match sqlx::query_as::<_, Permission>(
-            r#"
-            SELECT p.* FROM permissions p
-            INNER JOIN role_permissions rp ON rp.permission_id = p.id
-            WHERE rp.role_id = ?
-            "#,
-        )
-        .bind(role.id)
-        .fetch_all(pool)
-        .await
-        {
+        match repos.permissions.for_role(role.id).await {
             Ok(role_permissions) => {
                 permissions.extend(role_permissions);
             }
@@ -760,7 +743,7 @@ pub async fn get_user_permissions(req: &mut Request, depot: &mut Depot, res: &mu
     }
 
     // 去重
-    permissions.sort_by(|a, b| a.id.cmp(&b.id));
+    permissions.sort_by_key(|p| p.id);
     permissions.dedup_by(|a, b| a.id == b.id);
 
     // 获取菜单权限
@@ -1,46 +1,111 @@
+mod commands;
 mod config;
 mod controllers;
 mod middleware;
 mod models;
+mod repositories;
+mod services;
 mod utils;
 
 use crate::config::database;
+use crate::controllers::dashboard::get_dashboard_stats;
+use crate::controllers::file::{
+    delete_file_by_id, download_file, get_file, get_file_signed_url, get_files,
+    get_my_storage_usage, get_storage_usage, update_file_visibility, update_role_storage_quota,
+    update_user_storage_quota,
+};
+use crate::controllers::login_log::{get_login_logs, get_my_login_logs};
 use crate::controllers::menu::{create_menu, delete_menu, get_menus, update_menu};
-use crate::controllers::operation_log::{delete_operation_logs, get_operation_logs};
+use crate::controllers::operation_log::{
+    delete_archived_operation_logs, delete_operation_logs, download_operation_log_archive,
+    export_operation_logs, get_archived_operation_logs, get_operation_log,
+    get_operation_log_archives, get_operation_log_checkpoints, get_operation_log_redactions,
+    get_operation_log_writer_stats, get_operation_logs, import_operation_log_archive,
+    stream_operation_log_events, verify_operation_logs,
+};
+use crate::controllers::operation_log_rule::{
+    create_operation_log_rule, delete_operation_log_rule, get_operation_log_rules,
+    update_operation_log_rule,
+};
 use crate::controllers::permission::{
     create_permission, delete_permission, get_permissions, update_permission,
 };
 use crate::controllers::role::{
-    create_role, delete_role, get_role_permissions, get_roles, update_role, update_role_permissions,
+    add_role_permissions, create_role, delete_role, get_role_permissions, get_roles,
+    remove_role_permissions, update_role, update_role_permissions,
+};
+use crate::controllers::upload_session::{
+    abort_upload_session, complete_upload_session, create_upload_session, get_upload_session,
+    upload_session_chunk,
 };
 use crate::controllers::user::{
-    create_user, delete_user, get_current_user, get_user_permissions, get_user_roles, get_users,
-    login, update_password, update_profile, update_user, update_user_roles,
+    add_user_roles, create_user, delete_user, export_users, get_current_user, get_user_permissions,
+    get_user_roles, get_users, login, remove_user_roles, update_password, update_profile,
+    update_user, update_user_roles,
 };
 use crate::middleware::auth::auth_middleware;
 use crate::middleware::operation_log::{operation_log_after_middleware, operation_log_middleware};
+use crate::middleware::permission::require_permission;
+use crate::middleware::request_id::{request_id_middleware, REQUEST_ID_HEADER};
+use crate::repositories::Repositories;
+use crate::services::log_writer::LogWriter;
 
 use salvo::cors::Cors;
 use salvo::http::Method;
 use salvo::prelude::*;
-use salvo::serve_static::StaticDir;
-use std::path::Path;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
-    let pool = match database::create_pool().await {
-        Ok(pool) => pool,
+    let repositories = match database::connect().await {
+        Ok(repositories) => repositories,
         Err(e) => {
-            eprintln!("Failed to create database pool: {}", e);
-            return;
+            eprintln!("Failed to connect to database: {}", e);
+            std::process::exit(1);
         }
     };
 
-    let upload_dir = Path::new("uploads");
-    if !upload_dir.exists() {
-        std::fs::create_dir_all(upload_dir).unwrap();
+    let command = std::env::args().nth(1);
+
+    // 迁移命令不受 DB_AUTO_MIGRATE 影响
+    if let Some(command) = command
+        .as_deref()
+        .filter(|c| commands::is_migration_command(c))
+    {
+        if let Err(e) = commands::run(command, &repositories).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Err(e) = database::init_database(&repositories).await {
+        eprintln!("Failed to initialize database: {}", e);
+        std::process::exit(1);
     }
 
+    if let Err(e) = services::storage::init_storage().await {
+        eprintln!("Failed to initialize storage: {}", e);
+        std::process::exit(1);
+    }
+
+    // 启动时加载上传配置, 签名密钥不可用时直接退出而不是在第一次签名时才失败
+    config::upload::upload_config();
+
+    // 执行命令行子命令后退出
+    if let Some(command) = command {
+        if let Err(e) = commands::run(&command, &repositories).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // 启动操作日志归档清理任务、孤立文件清理任务和日志写入服务
+    services::retention::spawn_retention_task(repositories.clone());
+    services::files::spawn_file_sweep_task(repositories.clone());
+    let (log_writer, log_writer_handle) = LogWriter::spawn(repositories.clone());
+
     println!("Server is running at http://localhost:3000");
 
     let router = Router::new()
@@ -66,6 +131,11 @@ async fn main() {
                     Router::with_path("users")
                         .get(get_users)
                         .post(create_user)
+                        .push(
+                            Router::with_path("export")
+                                .hoop(require_permission("system:user:view"))
+                                .get(export_users),
+                        )
                         .push(
                             Router::with_path("<id>")
                                 .put(update_user)
@@ -73,7 +143,14 @@ async fn main() {
                                 .push(
                                     Router::with_path("roles")
                                         .get(get_user_roles)
-                                        .put(update_user_roles),
+                                        .put(update_user_roles)
+                                        .post(add_user_roles)
+                                        .delete(remove_user_roles),
+                                )
+                                .push(
+                                    Router::with_path("storage-quota")
+                                        .hoop(require_permission("system:file:manage"))
+                                        .put(update_user_storage_quota),
                                 ),
                         ),
                 )
@@ -88,7 +165,14 @@ async fn main() {
                                 .push(
                                     Router::with_path("permissions")
                                         .get(get_role_permissions)
-                                        .put(update_role_permissions),
+                                        .put(update_role_permissions)
+                                        .post(add_role_permissions)
+                                        .delete(remove_role_permissions),
+                                )
+                                .push(
+                                    Router::with_path("storage-quota")
+                                        .hoop(require_permission("system:file:manage"))
+                                        .put(update_role_storage_quota),
                                 ),
                         ),
                 )
@@ -104,14 +188,137 @@ async fn main() {
                 )
                 .push(Router::with_path("profile").patch(update_profile))
                 .push(Router::with_path("profile/password").patch(update_password))
-                .push(Router::with_path("upload").post(controllers::upload::upload_file))
+                .push(Router::with_path("profile/logins").get(get_my_login_logs))
+                .push(Router::with_path("profile/storage").get(get_my_storage_usage))
+                .push(
+                    Router::with_path("login-logs")
+                        .hoop(require_permission("system:log:view"))
+                        .get(get_login_logs),
+                )
+                .push(
+                    Router::with_path("dashboard/stats")
+                        .hoop(require_permission("system:log:view"))
+                        .get(get_dashboard_stats),
+                )
+                .push(
+                    Router::with_path("upload")
+                        .post(controllers::upload::upload_file)
+                        .push(Router::with_path("avatar").post(controllers::upload::upload_avatar))
+                        .push(Router::with_path("check").post(controllers::upload::check_upload))
+                        .push(
+                            Router::with_path("sessions")
+                                .post(create_upload_session)
+                                .push(
+                                    Router::with_path("<id>")
+                                        .get(get_upload_session)
+                                        .delete(abort_upload_session)
+                                        .push(
+                                            Router::with_path("chunks/<index:num>")
+                                                .put(upload_session_chunk),
+                                        )
+                                        .push(
+                                            Router::with_path("complete")
+                                                .post(complete_upload_session),
+                                        ),
+                                ),
+                        ),
+                )
+                .push(
+                    Router::with_path("files")
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:file:manage"))
+                                .get(get_files)
+                                .push(Router::with_path("usage").get(get_storage_usage))
+                                .push(
+                                    Router::with_path("<id:num>")
+                                        .get(get_file)
+                                        .delete(delete_file_by_id),
+                                ),
+                        )
+                        .push(
+                            Router::with_path("<id:num>")
+                                .push(Router::with_path("download").get(download_file))
+                                .push(Router::with_path("signed-url").get(get_file_signed_url))
+                                .push(
+                                    Router::with_path("visibility").patch(update_file_visibility),
+                                ),
+                        ),
+                )
                 .push(
                     Router::with_path("operation-logs")
-                        .get(get_operation_logs)
-                        .delete(delete_operation_logs),
+                        .push(
+                            Router::with_path("stream")
+                                .hoop(require_permission("system:log:stream"))
+                                .get(stream_operation_log_events),
+                        )
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:log:view"))
+                                .get(get_operation_logs)
+                                .push(
+                                    Router::with_path("writer-stats")
+                                        .get(get_operation_log_writer_stats),
+                                )
+                                .push(
+                                    Router::new()
+                                        .hoop(require_permission("system:log:clear"))
+                                        .delete(delete_operation_logs),
+                                )
+                                .push(Router::with_path("export").get(export_operation_logs))
+                                .push(Router::with_path("verify").get(verify_operation_logs))
+                                .push(
+                                    Router::with_path("checkpoints")
+                                        .get(get_operation_log_checkpoints),
+                                )
+                                .push(
+                                    Router::with_path("redactions")
+                                        .get(get_operation_log_redactions),
+                                )
+                                .push(
+                                    Router::with_path("archives")
+                                        .get(get_operation_log_archives)
+                                        .push(
+                                            Router::with_path("<name>")
+                                                .get(download_operation_log_archive)
+                                                .push(
+                                                    Router::with_path("import")
+                                                        .hoop(require_permission(
+                                                            "system:log:archive",
+                                                        ))
+                                                        .post(import_operation_log_archive),
+                                                ),
+                                        ),
+                                )
+                                .push(
+                                    Router::with_path("archived")
+                                        .get(get_archived_operation_logs)
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission("system:log:archive"))
+                                                .delete(delete_archived_operation_logs),
+                                        ),
+                                )
+                                .push(Router::with_path("<id:num>").get(get_operation_log)),
+                        ),
+                )
+                .push(
+                    Router::with_path("operation-log-rules")
+                        .hoop(require_permission("system:log:view"))
+                        .get(get_operation_log_rules)
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:log:rule"))
+                                .post(create_operation_log_rule)
+                                .push(
+                                    Router::with_path("<id>")
+                                        .put(update_operation_log_rule)
+                                        .delete(delete_operation_log_rule),
+                                ),
+                        ),
                 ),
         )
-        .push(Router::with_path("uploads/<**path>").get(StaticDir::new(["uploads"])));
+        .push(Router::with_path("uploads/<**path>").get(controllers::upload::download_upload));
 
     let cors_handler = Cors::new()
         .allow_origin("http://localhost:5173")
@@ -124,20 +331,67 @@ async fn main() {
             Method::OPTIONS,
         ])
         .allow_headers(vec!["content-type", "authorization"])
+        .expose_headers(vec![REQUEST_ID_HEADER])
         .allow_credentials(true)
         .max_age(3600)
         .into_handler();
 
-    let db_middleware = DbMiddleware { pool };
+    let audit_events = log_writer.events().clone();
+    let db_middleware = DbMiddleware {
+        repositories,
+        log_writer,
+    };
 
-    let service = Service::new(router).hoop(cors_handler).hoop(db_middleware);
+    let service = Service::new(router)
+        .hoop(cors_handler)
+        .hoop(db_middleware)
+        .hoop(request_id_middleware);
 
     let acceptor = TcpListener::new("0.0.0.0:3000").bind().await;
-    Server::new(acceptor).serve(service).await;
+    let server = Server::new(acceptor);
+
+    // 收到退出信号后停止接收新请求, 等待处理中的请求完成
+    let server_handle = server.handle();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        println!("Shutting down server");
+        audit_events.close();
+        server_handle.stop_graceful(Duration::from_secs(30));
+    });
+
+    server.serve(service).await;
+
+    // 写完队列中剩余的操作日志再退出
+    log_writer_handle.shutdown().await;
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
 struct DbMiddleware {
-    pool: sqlx::SqlitePool,
+    repositories: Repositories,
+    log_writer: LogWriter,
 }
 
 #[async_trait]
@@ -149,7 +403,8 @@ impl Handler for DbMiddleware {
         _res: &mut Response,
         ctrl: &mut FlowCtrl,
     ) {
-        req.extensions_mut().insert(self.pool.clone());
+        req.extensions_mut().insert(self.repositories.clone());
+        req.extensions_mut().insert(self.log_writer.clone());
         ctrl.call_next(req, _depot, _res).await;
     }
 }
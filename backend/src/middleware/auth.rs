@@ -1,7 +1,7 @@
+use crate::repositories::Repositories;
 use crate::utils::jwt::verify_token;
 use salvo::prelude::*;
 use serde_json::json;
-use sqlx::SqlitePool;
 
 #[handler]
 pub async fn auth_middleware(
@@ -29,34 +29,37 @@ pub async fn auth_middleware(
                 return;
             }
         }
-        None => {
-            res.status_code(StatusCode::UNAUTHORIZED);
-            res.render(Json(json!({
-                "message": "未提供认证信息"
-            })));
-            ctrl.skip_rest();
-            return;
-        }
+        // EventSource 无法设置请求头, 实时事件流允许通过 access_token 参数传递 token
+        None => match req
+            .query::<String>("access_token")
+            .filter(|_| req.uri().path().ends_with("/stream"))
+        {
+            Some(token) => token,
+            None => {
+                res.status_code(StatusCode::UNAUTHORIZED);
+                res.render(Json(json!({
+                    "message": "未提供认证信息"
+                })));
+                ctrl.skip_rest();
+                return;
+            }
+        },
     };
 
     // 验证 token
     match verify_token(&token) {
         Some(user_id) => {
             // 获取数据库连接
-            let pool = req.extensions().get::<SqlitePool>().unwrap();
+            let repos = req.extensions().get::<Repositories>().unwrap();
 
             // 查询用户信息
-            match sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = ?")
-                .bind(user_id)
-                .fetch_one(pool)
-                .await
-            {
-                Ok(username) => {
+            match repos.users.find(user_id).await {
+                Ok(Some(user)) => {
                     // 存储用户ID和用户名到depot
                     depot.insert("user_id", user_id);
-                    depot.insert("username", username);
+                    depot.insert("username", user.username);
                 }
-                Err(_) => {
+                _ => {
                     res.status_code(StatusCode::UNAUTHORIZED);
                     res.render(Json(json!({
                         "message": "用户不存在"
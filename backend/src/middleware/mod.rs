@@ -1,2 +1,4 @@
 pub mod auth;
 pub mod operation_log;
+pub mod permission;
+pub mod request_id;
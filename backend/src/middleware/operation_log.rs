@@ -1,8 +1,15 @@
+use salvo::http::ResBody;
 use salvo::prelude::*;
 use serde_json::json;
-use sqlx::SqlitePool;
+use std::time::Instant;
 
+use crate::config::operation_log::operation_log_config;
 use crate::models::CreateOperationLog;
+use crate::repositories::Repositories;
+use crate::services::capture_rules::capture_rules;
+use crate::services::log_writer::LogWriter;
+use crate::utils::ip::parse_ip;
+use crate::utils::redact::{redact_json, redact_params, truncate_params};
 
 #[handler]
 pub async fn operation_log_middleware(
@@ -16,8 +23,16 @@ pub async fn operation_log_middleware(
     let method = req.method().to_string();
     println!("[Operation Log] Processing request: {} {}", method, path);
 
-    // 跳过不需要记录日志的接口
-    if path.starts_with("/auth/login") || path.starts_with("/operation-logs") || method == "GET" {
+    // 按记录规则跳过不需要记录日志的接口
+    let repos = match req.extensions().get::<Repositories>() {
+        Some(repos) => repos.clone(),
+        None => {
+            println!("[Operation Log] Failed to get repositories");
+            return;
+        }
+    };
+    let rules = capture_rules(&repos).await;
+    if path.starts_with("/auth/login") || !rules.should_capture(&method, &path) {
         println!("[Operation Log] Skipping request: {} {}", method, path);
         return;
     }
@@ -52,44 +67,52 @@ pub async fn operation_log_middleware(
         });
 
         // 获取查询参数
-        let query_params: Vec<(String, String)> = req
+        let query_params: serde_json::Map<String, serde_json::Value> = req
             .queries()
             .iter()
-            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .map(|(k, v)| (k.to_string(), json!(v)))
             .collect();
         if !query_params.is_empty() {
-            println!("[Operation Log] Query params: {:?}", query_params);
-            params_data["query"] = json!(query_params);
+            params_data["query"] = serde_json::Value::Object(query_params);
         }
 
         // 获取请求体
         if let Ok(body) = req.parse_json::<serde_json::Value>().await {
-            println!("[Operation Log] Request body: {}", body);
             params_data["body"] = body;
         }
 
         // 如果没有任何参数，返回 None
         if params_data["query"]
             .as_object()
-            .map_or(true, |obj| obj.is_empty())
+            .is_none_or(|obj| obj.is_empty())
             && params_data["body"].is_null()
         {
             println!("[Operation Log] No parameters found");
             None
         } else {
+            // 记录前脱敏敏感字段并限制长度
+            let config = operation_log_config();
+            redact_params(&method, &path, &mut params_data, config);
             let params_str = serde_json::to_string(&params_data).unwrap_or_default();
+            let params_str = truncate_params(params_str, config.max_params_bytes);
             println!("[Operation Log] Parameters: {}", params_str);
             Some(params_str)
         }
     };
 
-    // 获取客户端IP
-    let ip = Some(req.remote_addr().to_string());
+    // 获取客户端IP和User-Agent
+    let ip = parse_ip(&req.remote_addr().to_string()).map(|ip| ip.to_string());
     println!("[Operation Log] Client IP: {:?}", ip);
+    let user_agent = req.header::<String>("User-Agent");
+
+    // 请求ID由 request_id_middleware 生成
+    let request_id = depot.get::<String>("request_id").ok().cloned();
 
     // 构建日志记录
-    let module = get_module_name(&path);
-    let operation = get_operation_name(&method, &path);
+    // 优先使用权限配置中的名称, 没有对应的权限时按路径推断
+    let (module, operation) = rules
+        .labels(&method, &path)
+        .unwrap_or_else(|| (get_module_name(&path), get_operation_name(&method, &path)));
     println!(
         "[Operation Log] Module: {}, Operation: {}",
         module, operation
@@ -101,14 +124,21 @@ pub async fn operation_log_middleware(
         module,
         operation,
         method,
+        path,
         params,
         ip,
+        user_agent,
+        request_id,
         status: 0, // 初始状态
         error: None,
+        response: None,
+        duration_ms: 0,
+        changes: Vec::new(),
     };
 
     // 保存到depot中,供后续使用
     depot.insert("operation_log", log);
+    depot.insert("operation_log_started_at", Instant::now());
     println!("[Operation Log] Log record created and stored in depot");
 }
 
@@ -117,8 +147,10 @@ pub async fn operation_log_after_middleware(
     req: &mut Request,
     depot: &mut Depot,
     res: &mut Response,
-    _ctrl: &mut FlowCtrl,
+    ctrl: &mut FlowCtrl,
 ) {
+    // 先执行后续处理器, 再根据响应结果补全日志
+    ctrl.call_next(req, depot, res).await;
     println!("[Operation Log] Processing after middleware");
 
     // 获取日志记录
@@ -130,56 +162,76 @@ pub async fn operation_log_after_middleware(
         }
     };
 
+    // 记录耗时
+    if let Ok(started_at) = depot.get::<Instant>("operation_log_started_at") {
+        log.duration_ms = started_at.elapsed().as_millis() as i64;
+    }
+
     // 获取响应状态
     let status = res.status_code.unwrap_or(StatusCode::OK).as_u16() as i32;
     log.status = status;
-    println!("[Operation Log] Response status: {}", status);
+    println!(
+        "[Operation Log] Response status: {}, duration: {}ms",
+        status, log.duration_ms
+    );
 
-    // 如果状态码不是2xx，则记录错误信息
-    if status < 200 || status >= 300 {
+    // 记录脱敏并截断后的响应内容
+    let config = operation_log_config();
+    let body = response_body_text(res);
+    if let Some(ref body) = body {
+        let summary = match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(mut value) => {
+                redact_json(&mut value, &config.redact_keys);
+                value.to_string()
+            }
+            Err(_) => body.clone(),
+        };
+        log.response = Some(truncate_params(summary, config.max_response_bytes));
+    }
+
+    // 如果状态码不是2xx，则记录实际的错误信息
+    if !(200..300).contains(&status) {
+        let message = body
+            .as_deref()
+            .and_then(error_message_from_body)
+            .unwrap_or_else(|| "请求失败".to_string());
         let error_msg = json!({
             "status": status,
-            "message": "请求失败"
+            "message": message
         });
         log.error = Some(error_msg.to_string());
         println!("[Operation Log] Error recorded: {}", error_msg);
     }
 
-    // 获取数据库连接并克隆
-    let pool = req.extensions().get::<SqlitePool>().unwrap().clone();
-
-    // 异步记录日志
-    tokio::spawn(async move {
-        println!("[Operation Log] Inserting log record into database");
-        let result = sqlx::query(
-            r#"
-            INSERT INTO operation_logs (
-                user_id, username, module, operation, method,
-                params, ip, status, error, created_at
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
-            "#,
-        )
-        .bind(&log.user_id)
-        .bind(&log.username)
-        .bind(&log.module)
-        .bind(&log.operation)
-        .bind(&log.method)
-        .bind(&log.params)
-        .bind(&log.ip)
-        .bind(&log.status)
-        .bind(&log.error)
-        .execute(&pool)
-        .await;
-
-        match result {
-            Ok(_) => println!("[Operation Log] Successfully inserted log record"),
-            Err(e) => eprintln!("[Operation Log] Failed to insert log record: {}", e),
-        }
-    });
+    // 交给日志写入服务批量写入
+    let writer = req.extensions().get::<LogWriter>().unwrap();
+    writer.submit(log).await;
+    println!("[Operation Log] Log record queued");
+}
+
+// 读取已渲染的响应体, 流式响应不读取
+fn response_body_text(res: &Response) -> Option<String> {
+    let bytes = match &res.body {
+        ResBody::Once(bytes) => bytes.to_vec(),
+        ResBody::Chunks(chunks) => chunks.iter().flat_map(|c| c.iter().copied()).collect(),
+        _ => return None,
+    };
+    if bytes.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
 }
 
-fn get_module_name(path: &str) -> String {
+// 控制器的错误响应使用 message 或 error 字段
+fn error_message_from_body(body: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(body).ok()?;
+    ["message", "error"]
+        .iter()
+        .find_map(|key| value.get(*key).and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+}
+
+pub fn get_module_name(path: &str) -> String {
     let parts: Vec<&str> = path.split('/').collect();
     if parts.len() > 1 {
         match parts[1] {
@@ -188,6 +240,7 @@ fn get_module_name(path: &str) -> String {
             "permissions" => "权限管理",
             "menus" => "菜单管理",
             "operation-logs" => "操作日志",
+            "operation-log-rules" => "操作日志",
             "profile" => "个人信息",
             _ => "其他",
         }
@@ -197,7 +250,10 @@ fn get_module_name(path: &str) -> String {
     }
 }
 
-fn get_operation_name(method: &str, _path: &str) -> String {
+fn get_operation_name(method: &str, path: &str) -> String {
+    if path.ends_with("/export") {
+        return "导出".to_string();
+    }
     match method {
         "GET" => "查询",
         "POST" => "新增",
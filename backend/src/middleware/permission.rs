@@ -0,0 +1,53 @@
+use salvo::prelude::*;
+use serde_json::json;
+
+use crate::repositories::Repositories;
+
+// 要求当前用户通过已启用的角色拥有指定权限编码, 需放在 auth_middleware 之后
+pub struct RequirePermission {
+    code: &'static str,
+}
+
+pub fn require_permission(code: &'static str) -> RequirePermission {
+    RequirePermission { code }
+}
+
+#[async_trait]
+impl Handler for RequirePermission {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let Ok(user_id) = depot.get::<i64>("user_id").copied() else {
+            res.status_code(StatusCode::UNAUTHORIZED);
+            res.render(Json(json!({
+                "message": "未提供认证信息"
+            })));
+            ctrl.skip_rest();
+            return;
+        };
+
+        let repos = req.extensions().get::<Repositories>().unwrap();
+        match repos.permissions.has_permission(user_id, self.code).await {
+            Ok(true) => {}
+            Ok(false) => {
+                res.status_code(StatusCode::FORBIDDEN);
+                res.render(Json(json!({
+                    "message": format!("缺少权限: {}", self.code)
+                })));
+                ctrl.skip_rest();
+            }
+            Err(e) => {
+                eprintln!("Failed to check permission {}: {}", self.code, e);
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                res.render(Json(json!({
+                    "message": "服务器内部错误"
+                })));
+                ctrl.skip_rest();
+            }
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use salvo::prelude::*;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+#[handler]
+pub async fn request_id_middleware(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    // 为每个请求生成唯一ID, 记录到操作日志并通过响应头返回
+    let request_id = Uuid::new_v4().to_string();
+    if let Err(e) = res.add_header(REQUEST_ID_HEADER, &request_id, true) {
+        eprintln!("[Request Id] Failed to set response header: {}", e);
+    }
+    depot.insert("request_id", request_id);
+    ctrl.call_next(req, depot, res).await;
+}
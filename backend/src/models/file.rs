@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::DbTime;
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FileRecord {
+    pub id: i64,
+    pub storage_name: String,
+    pub original_name: String,
+    pub size: i64,
+    pub mime: String,
+    pub sha256: String,
+    pub purpose: String,
+    pub visibility: String,
+    pub blob_key: Option<String>,
+    pub uploader_id: Option<i64>,
+    pub uploader_name: Option<String>,
+    pub reference_count: i64,
+    pub url: String,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<FileVariant>,
+}
+
+impl FileRecord {
+    // 文件内容在存储中的位置, 去重之前上传的文件直接按 storage_name 保存
+    pub fn storage_key(&self) -> &str {
+        self.blob_key.as_deref().unwrap_or(&self.storage_name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FileVariant {
+    pub variant: String,
+    pub storage_name: String,
+    pub blob_key: Option<String>,
+    pub size: i64,
+    pub width: i32,
+    pub height: i32,
+    pub url: String,
+}
+
+#[derive(Debug)]
+pub struct CreateFile {
+    pub storage_name: String,
+    pub original_name: String,
+    pub size: i64,
+    pub mime: String,
+    pub sha256: String,
+    pub purpose: String,
+    pub visibility: String,
+    pub blob_key: String,
+    pub uploader_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFileVisibility {
+    pub visibility: String,
+}
+
+#[derive(Debug)]
+pub struct CreateFileVariant {
+    pub variant: String,
+    pub storage_name: String,
+    pub blob_key: String,
+    pub size: i64,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckUpload {
+    pub sha256: String,
+    pub size: i64,
+    pub file_name: String,
+    pub purpose: Option<String>,
+    pub visibility: Option<String>,
+}
+
+// 按上传者统计的存储用量, reserved 为进行中的分片上传预留的空间,
+// quota 为按用户、角色、默认配置依次确定的有效配额, 为空表示不限制
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StorageUsage {
+    pub user_id: i64,
+    pub username: String,
+    pub nickname: String,
+    pub used: i64,
+    pub file_count: i64,
+    pub reserved: i64,
+    pub user_quota: Option<i64>,
+    pub role_quota: Option<i64>,
+    pub role_unlimited: bool,
+    #[sqlx(skip)]
+    pub quota: Option<i64>,
+    #[sqlx(skip)]
+    pub quota_source: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateStorageQuota {
+    pub quota: Option<i64>,
+}
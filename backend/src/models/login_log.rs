@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::DbTime;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LoginLog {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    pub username: String,
+    pub success: bool,
+    pub failure_reason: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+}
+
+#[derive(Debug)]
+pub struct CreateLoginLog {
+    pub user_id: Option<i64>,
+    pub username: String,
+    pub success: bool,
+    pub failure_reason: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
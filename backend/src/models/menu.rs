@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+
+use crate::repositories::DbTime;
 use sqlx::FromRow;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -12,18 +14,8 @@ pub struct Menu {
     pub icon: Option<String>,
     pub sort: i32,
     pub is_hidden: bool,
+    #[sqlx(try_from = "DbTime")]
     pub created_at: String,
+    #[sqlx(try_from = "DbTime")]
     pub updated_at: String,
 }
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateMenu {
-    pub parent_id: Option<i64>,
-    pub name: String,
-    pub path: String,
-    pub component: String,
-    pub title: String,
-    pub icon: Option<String>,
-    pub sort: i32,
-    pub is_hidden: bool,
-}
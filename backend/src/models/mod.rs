@@ -1,10 +1,16 @@
+pub mod file;
+pub mod login_log;
 pub mod menu;
 pub mod operation_log;
 pub mod permission;
 pub mod role;
+pub mod upload_session;
 pub mod user;
+pub use file::*;
+pub use login_log::*;
 pub use menu::*;
 pub use operation_log::*;
 pub use permission::*;
 pub use role::*;
+pub use upload_session::*;
 pub use user::*;
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+use crate::repositories::DbTime;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct OperationLog {
     pub id: i64,
     pub user_id: i64,
@@ -8,13 +10,72 @@ pub struct OperationLog {
     pub module: String,
     pub operation: String,
     pub method: String,
+    pub path: Option<String>,
     pub params: Option<String>,
     pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
     pub status: i32,
     pub error: Option<String>,
+    pub response: Option<String>,
+    pub duration_ms: Option<i64>,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OperationLogCheckpoint {
+    pub id: i64,
+    pub last_log_id: i64,
+    pub last_hash: String,
+    pub purged_count: i64,
+    pub cutoff: String,
+    pub reason: String,
+    pub created_by: Option<String>,
+    pub archive: Option<String>,
+    #[sqlx(try_from = "DbTime")]
     pub created_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OperationLogRedaction {
+    pub id: i64,
+    pub reason: String,
+    pub redacted_count: i64,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ArchivedOperationLog {
+    pub id: i64,
+    pub archive: String,
+    pub original_id: i64,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub module: Option<String>,
+    pub operation: Option<String>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub params: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub status: Option<i32>,
+    pub error: Option<String>,
+    pub response: Option<String>,
+    pub duration_ms: Option<i64>,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: Option<String>,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
+    pub changes: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateOperationLog {
     pub user_id: i64,
@@ -22,8 +83,70 @@ pub struct CreateOperationLog {
     pub module: String,
     pub operation: String,
     pub method: String,
+    pub path: String,
     pub params: Option<String>,
     pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
     pub status: i32,
     pub error: Option<String>,
+    pub response: Option<String>,
+    pub duration_ms: i64,
+    #[serde(default)]
+    pub changes: Vec<CreateOperationLogChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OperationLogChange {
+    pub id: i64,
+    pub log_id: i64,
+    pub entity: String,
+    pub entity_id: i64,
+    pub action: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub diff: String,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateOperationLogChange {
+    pub entity: String,
+    pub entity_id: i64,
+    pub action: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OperationLogRule {
+    pub id: i64,
+    pub method: String,
+    pub path: String,
+    pub action: String,
+    pub priority: i32,
+    pub enabled: bool,
+    pub description: Option<String>,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+    #[sqlx(try_from = "DbTime")]
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveOperationLogRule {
+    pub method: String,
+    pub path: String,
+    pub action: String,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub description: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
 }
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+
+use crate::repositories::DbTime;
 
 #[derive(Debug, sqlx::FromRow, serde::Serialize, serde::Deserialize, Clone)]
 pub struct Permission {
@@ -12,7 +13,9 @@ pub struct Permission {
     pub description: Option<String>,
     pub parent_id: Option<i32>,
     pub sort: i32,
+    #[sqlx(try_from = "DbTime")]
     pub created_at: String,
+    #[sqlx(try_from = "DbTime")]
     pub updated_at: String,
 }
 
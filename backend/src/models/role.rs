@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+
+use crate::repositories::DbTime;
 use sqlx::FromRow;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -10,7 +12,12 @@ pub struct Role {
     pub status: i32,
     pub color_start: Option<String>,
     pub color_end: Option<String>,
+    // 拥有该角色的用户的存储配额(字节), 多个角色时取最大值
+    #[sqlx(default)]
+    pub storage_quota: Option<i64>,
+    #[sqlx(try_from = "DbTime")]
     pub created_at: String,
+    #[sqlx(try_from = "DbTime")]
     pub updated_at: String,
 }
 
@@ -23,19 +30,3 @@ pub struct CreateRole {
     pub color_start: Option<String>,
     pub color_end: Option<String>,
 }
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateRole {
-    pub name: Option<String>,
-    pub code: Option<String>,
-    pub description: Option<String>,
-    pub status: Option<i32>,
-    pub color_start: Option<String>,
-    pub color_end: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RolePermission {
-    pub role_id: i64,
-    pub permission_ids: Vec<i64>,
-}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::DbTime;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct UploadSession {
+    pub id: String,
+    pub user_id: i64,
+    pub purpose: String,
+    pub visibility: String,
+    pub file_name: String,
+    pub total_size: i64,
+    pub chunk_size: i64,
+    pub total_chunks: i64,
+    pub sha256: Option<String>,
+    pub status: String,
+    pub file_id: Option<i64>,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+    #[sqlx(try_from = "DbTime")]
+    pub updated_at: String,
+    #[sqlx(try_from = "DbTime")]
+    pub expires_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUploadSession {
+    pub file_name: String,
+    pub size: i64,
+    pub purpose: Option<String>,
+    pub visibility: Option<String>,
+    pub chunk_size: Option<i64>,
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CompleteUploadSession {
+    pub sha256: Option<String>,
+}
@@ -1,16 +1,28 @@
 use serde::{Deserialize, Serialize};
 
+use crate::repositories::{DbText, DbTime};
+
 #[derive(Debug, sqlx::FromRow, Serialize)]
 pub struct User {
     pub id: i64,
     pub username: String,
     pub password: String,
     pub nickname: String,
+    #[sqlx(try_from = "DbText")]
     pub email: String,
     pub avatar: Option<String>,
     pub status: i32,
+    // 单独设置的存储配额(字节), 优先于角色的配额
+    #[sqlx(default)]
+    pub storage_quota: Option<i64>,
+    #[sqlx(try_from = "DbTime")]
     pub created_at: String,
+    #[sqlx(try_from = "DbTime")]
     pub updated_at: String,
+    // 仅在用户列表中查询, 其他查询为空
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_thumbnail: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,9 +42,3 @@ pub struct UpdateUser {
     pub avatar: Option<String>,
     pub status: i32,
 }
-
-#[derive(Debug, Deserialize)]
-pub struct UpdatePasswordRequest {
-    pub old_password: String,
-    pub new_password: String,
-}
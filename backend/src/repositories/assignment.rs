@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+use std::collections::BTreeSet;
+
+use super::dialect::{placeholders, Dialect};
+use crate::services::assignment::{
+    plan, Assignment, AssignmentDiff, AssignmentError, AssignmentMode,
+};
+
+#[async_trait]
+pub trait AssignmentRepository: Send + Sync {
+    // 先校验主体和所有引用的 id 都存在, 再在同一个事务中只增删有变化的行,
+    // 中途失败时整体回滚, 不会出现用户角色被清空的情况
+    async fn apply(
+        &self,
+        assignment: &Assignment,
+        owner_id: i64,
+        ids: &[i64],
+        mode: AssignmentMode,
+    ) -> Result<AssignmentDiff, AssignmentError>;
+}
+
+macro_rules! assignment_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl AssignmentRepository for $name {
+            // 事务开始时锁定主体: SQLite 以 BEGIN IMMEDIATE 开始, 其他数据库锁定主体的行,
+            // 并发的分配请求依次执行, 不会在读取现有关联后写入时遇到锁冲突或重复插入
+            async fn apply(
+                &self,
+                assignment: &Assignment,
+                owner_id: i64,
+                ids: &[i64],
+                mode: AssignmentMode,
+            ) -> Result<AssignmentDiff, AssignmentError> {
+                let requested: BTreeSet<i64> = ids.iter().copied().collect();
+
+                let mut tx = <$db>::begin_write(&self.pool).await?;
+
+                let sql = format!(
+                    "SELECT id FROM {} WHERE id = ?{}",
+                    assignment.owner_table,
+                    <$db>::FOR_UPDATE
+                );
+                let owner = sqlx::query_scalar::<_, i64>(&<$db>::sql(&sql))
+                    .bind(owner_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if owner.is_none() {
+                    return Err(AssignmentError::OwnerNotFound);
+                }
+
+                // 移除时不校验, 允许移除指向已不存在记录的关联
+                if !matches!(mode, AssignmentMode::Remove) && !requested.is_empty() {
+                    let sql = format!(
+                        "SELECT {} FROM {} WHERE id IN ({})",
+                        <$db>::integer("id"),
+                        assignment.target_table,
+                        placeholders(requested.len())
+                    );
+                    let sql = <$db>::sql(&sql);
+                    let mut query = sqlx::query_scalar::<_, i64>(&sql);
+                    for id in &requested {
+                        query = query.bind(id);
+                    }
+                    let existing: BTreeSet<i64> =
+                        query.fetch_all(&mut *tx).await?.into_iter().collect();
+                    let missing: Vec<i64> = requested.difference(&existing).copied().collect();
+                    if !missing.is_empty() {
+                        return Err(AssignmentError::MissingTargets(missing));
+                    }
+                }
+
+                let sql = format!(
+                    "SELECT {} FROM {} WHERE {} = ?",
+                    <$db>::integer(assignment.target_column),
+                    assignment.table,
+                    assignment.owner_column
+                );
+                let current: BTreeSet<i64> = sqlx::query_scalar::<_, i64>(&<$db>::sql(&sql))
+                    .bind(owner_id)
+                    .fetch_all(&mut *tx)
+                    .await?
+                    .into_iter()
+                    .collect();
+
+                let diff = plan(current, requested, mode);
+
+                let sql = format!(
+                    "DELETE FROM {} WHERE {} = ? AND {} = ?",
+                    assignment.table, assignment.owner_column, assignment.target_column
+                );
+                let sql = <$db>::sql(&sql);
+                for id in &diff.removed {
+                    sqlx::query(&sql)
+                        .bind(owner_id)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                let sql = format!(
+                    "INSERT INTO {} ({}, {}) VALUES (?, ?)",
+                    assignment.table, assignment.owner_column, assignment.target_column
+                );
+                let sql = <$db>::sql(&sql);
+                for id in &diff.added {
+                    sqlx::query(&sql)
+                        .bind(owner_id)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+
+                tx.commit().await?;
+                Ok(diff)
+            }
+        }
+    };
+}
+
+assignment_repository!(SqliteAssignmentRepository, Sqlite);
+assignment_repository!(PostgresAssignmentRepository, Postgres);
+assignment_repository!(MySqlAssignmentRepository, MySql);
@@ -0,0 +1,255 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::{DbTime, Dialect};
+use crate::config::database::DatabaseKind;
+use crate::services::dashboard::{
+    ActiveUser, FailedLogin, LoginBucket, ModuleErrorRate, NewUsersWeek, RequestBucket,
+    RoleDistribution, Summary,
+};
+
+// 仪表盘的统计查询, start 为统计范围的起点
+#[async_trait]
+pub trait DashboardRepository: Send + Sync {
+    async fn summary(&self, start: &str) -> Result<Summary, sqlx::Error>;
+
+    // hourly 为 true 时按小时分组, 否则按天分组
+    async fn requests(&self, start: &str, hourly: bool) -> Result<Vec<RequestBucket>, sqlx::Error>;
+
+    // 错误率由调用方计算
+    async fn module_errors(&self, start: &str) -> Result<Vec<ModuleErrorRate>, sqlx::Error>;
+
+    async fn top_users(&self, start: &str, limit: i64) -> Result<Vec<ActiveUser>, sqlx::Error>;
+
+    // 登录总数和失败数
+    async fn logins(&self, start: &str) -> Result<(i64, i64), sqlx::Error>;
+
+    async fn failed_logins(
+        &self,
+        start: &str,
+        hourly: bool,
+    ) -> Result<Vec<LoginBucket>, sqlx::Error>;
+
+    async fn top_failed_usernames(
+        &self,
+        start: &str,
+        limit: i64,
+    ) -> Result<Vec<FailedLogin>, sqlx::Error>;
+
+    // 按自然周(周一开始)统计新用户
+    async fn new_users(&self, start: &str) -> Result<Vec<NewUsersWeek>, sqlx::Error>;
+
+    // 角色分布不受统计范围影响
+    async fn roles(&self) -> Result<Vec<RoleDistribution>, sqlx::Error>;
+}
+
+// 时间分组, 格式与 StatsWindow::bucket_format 一致
+fn bucket<D: Dialect>(hourly: bool) -> String {
+    let format = match (D::KIND, hourly) {
+        (DatabaseKind::Postgres, true) => "'YYYY-MM-DD HH24:00'",
+        (DatabaseKind::Postgres, false) => "'YYYY-MM-DD'",
+        (_, true) => "'%Y-%m-%d %H:00'",
+        (_, false) => "'%Y-%m-%d'",
+    };
+    match D::KIND {
+        DatabaseKind::Sqlite => format!("strftime({}, created_at)", format),
+        DatabaseKind::Postgres => format!("to_char(created_at, {})", format),
+        DatabaseKind::MySql => format!("DATE_FORMAT(created_at, {})", format),
+    }
+}
+
+// created_at 所在周的周一
+fn week<D: Dialect>() -> &'static str {
+    match D::KIND {
+        DatabaseKind::Sqlite => "date(created_at, 'weekday 0', '-6 days')",
+        DatabaseKind::Postgres => "to_char(date_trunc('week', created_at), 'YYYY-MM-DD')",
+        DatabaseKind::MySql => {
+            "DATE_FORMAT(DATE_SUB(DATE(created_at), INTERVAL WEEKDAY(created_at) DAY), '%Y-%m-%d')"
+        }
+    }
+}
+
+// 满足条件的行数
+fn count_if<D: Dialect>(condition: &str) -> String {
+    format!(
+        "COALESCE({}, 0)",
+        D::integer(&format!("SUM(CASE WHEN {} THEN 1 ELSE 0 END)", condition))
+    )
+}
+
+macro_rules! dashboard_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl DashboardRepository for $name {
+            async fn summary(&self, start: &str) -> Result<Summary, sqlx::Error> {
+                let sql = format!(
+                    r#"
+                    SELECT
+                        COUNT(*) AS requests,
+                        {} AS errors,
+                        COUNT(DISTINCT user_id) AS active_users,
+                        (SELECT COUNT(*) FROM users) AS total_users
+                    FROM operation_logs
+                    WHERE created_at >= ?
+                    "#,
+                    count_if::<$db>("status >= 400")
+                );
+                sqlx::query_as::<_, Summary>(&<$db>::sql(&sql))
+                    .bind(DbTime::from(start))
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn requests(
+                &self,
+                start: &str,
+                hourly: bool,
+            ) -> Result<Vec<RequestBucket>, sqlx::Error> {
+                let sql = format!(
+                    r#"
+                    SELECT {} AS bucket, COUNT(*) AS total, {} AS errors
+                    FROM operation_logs
+                    WHERE created_at >= ?
+                    GROUP BY bucket
+                    "#,
+                    bucket::<$db>(hourly),
+                    count_if::<$db>("status >= 400")
+                );
+                sqlx::query_as::<_, RequestBucket>(&<$db>::sql(&sql))
+                    .bind(DbTime::from(start))
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn module_errors(&self, start: &str) -> Result<Vec<ModuleErrorRate>, sqlx::Error> {
+                let sql = format!(
+                    r#"
+                    SELECT module, COUNT(*) AS total, {} AS errors
+                    FROM operation_logs
+                    WHERE created_at >= ?
+                    GROUP BY module
+                    "#,
+                    count_if::<$db>("status >= 400")
+                );
+                sqlx::query_as::<_, ModuleErrorRate>(&<$db>::sql(&sql))
+                    .bind(DbTime::from(start))
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn top_users(&self, start: &str, limit: i64) -> Result<Vec<ActiveUser>, sqlx::Error> {
+                sqlx::query_as::<_, ActiveUser>(&<$db>::sql(
+                    r#"
+                    SELECT user_id, MAX(username) AS username, COUNT(*) AS requests, MAX(created_at) AS last_active_at
+                    FROM operation_logs
+                    WHERE created_at >= ?
+                    GROUP BY user_id
+                    ORDER BY requests DESC, last_active_at DESC
+                    LIMIT ?
+                    "#,
+                ))
+                .bind(DbTime::from(start))
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn logins(&self, start: &str) -> Result<(i64, i64), sqlx::Error> {
+                let sql = format!(
+                    "SELECT COUNT(*), {} FROM login_logs WHERE created_at >= ?",
+                    count_if::<$db>("success = FALSE")
+                );
+                sqlx::query_as::<_, (i64, i64)>(&<$db>::sql(&sql))
+                    .bind(DbTime::from(start))
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn failed_logins(
+                &self,
+                start: &str,
+                hourly: bool,
+            ) -> Result<Vec<LoginBucket>, sqlx::Error> {
+                let sql = format!(
+                    r#"
+                    SELECT {} AS bucket, COUNT(*) AS failed
+                    FROM login_logs
+                    WHERE created_at >= ? AND success = FALSE
+                    GROUP BY bucket
+                    "#,
+                    bucket::<$db>(hourly)
+                );
+                sqlx::query_as::<_, LoginBucket>(&<$db>::sql(&sql))
+                    .bind(DbTime::from(start))
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn top_failed_usernames(
+                &self,
+                start: &str,
+                limit: i64,
+            ) -> Result<Vec<FailedLogin>, sqlx::Error> {
+                sqlx::query_as::<_, FailedLogin>(&<$db>::sql(
+                    r#"
+                    SELECT username, COUNT(*) AS failures
+                    FROM login_logs
+                    WHERE created_at >= ? AND success = FALSE
+                    GROUP BY username
+                    ORDER BY failures DESC
+                    LIMIT ?
+                    "#,
+                ))
+                .bind(DbTime::from(start))
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn new_users(&self, start: &str) -> Result<Vec<NewUsersWeek>, sqlx::Error> {
+                let sql = format!(
+                    r#"
+                    SELECT {} AS week, COUNT(*) AS count
+                    FROM users
+                    WHERE created_at >= ?
+                    GROUP BY week
+                    ORDER BY week
+                    "#,
+                    week::<$db>()
+                );
+                sqlx::query_as::<_, NewUsersWeek>(&<$db>::sql(&sql))
+                    .bind(DbTime::from(start))
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn roles(&self) -> Result<Vec<RoleDistribution>, sqlx::Error> {
+                sqlx::query_as::<_, RoleDistribution>(
+                    r#"
+                    SELECT r.id AS role_id, r.name, COUNT(ur.user_id) AS users
+                    FROM roles r
+                    LEFT JOIN user_roles ur ON ur.role_id = r.id
+                    GROUP BY r.id, r.name
+                    ORDER BY users DESC, r.id
+                    "#,
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+        }
+    };
+}
+
+dashboard_repository!(SqliteDashboardRepository, Sqlite);
+dashboard_repository!(PostgresDashboardRepository, Postgres);
+dashboard_repository!(MySqlDashboardRepository, MySql);
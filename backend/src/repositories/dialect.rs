@@ -0,0 +1,554 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use futures_util::future::BoxFuture;
+use sqlx::error::BoxDynError;
+use sqlx::mysql::{MySql, MySqlTypeInfo, MySqlValueRef};
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef, Postgres};
+use sqlx::query::Query;
+use sqlx::sqlite::{Sqlite, SqliteArgumentValue, SqliteTypeInfo, SqliteValueRef};
+use sqlx::{Database, Decode, Encode, Pool, Transaction, Type, TypeInfo, ValueRef};
+use std::borrow::Cow;
+
+use crate::config::database::DatabaseKind;
+
+// 与 SQLite CURRENT_TIMESTAMP 的格式保持一致
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// 各数据库的 SQL 差异. 仓储中的 SQL 统一使用 ? 作为占位符, 需要加引号的标识符使用双引号,
+// 执行前由 sql() 转换为对应数据库的写法
+pub trait Dialect: Database {
+    const KIND: DatabaseKind;
+    // 不区分大小写的模糊匹配, 与 SQLite 的 LIKE 一致
+    const LIKE: &'static str;
+    // 锁定查询到的行直到事务结束, SQLite 由 BEGIN IMMEDIATE 锁定整个数据库
+    const FOR_UPDATE: &'static str;
+    // CAST 的整数类型, 用于 SUM 等返回小数类型的聚合结果
+    const INTEGER: &'static str;
+    // 忽略唯一约束冲突的插入, 分别写在 INTO 之前和语句末尾
+    const INSERT_IGNORE: &'static str;
+    const ON_CONFLICT_IGNORE: &'static str;
+
+    fn sql(sql: &str) -> Cow<'_, str>;
+
+    // 按唯一键插入或更新指定的字段, 写在 INSERT ... VALUES (...) 之后
+    fn upsert(keys: &[&str], columns: &[&str]) -> String;
+
+    fn concat(parts: &[&str]) -> String;
+
+    // 以逗号连接分组中的文本
+    fn group_concat(expr: &str) -> String;
+
+    fn integer(expr: &str) -> String {
+        format!("CAST({} AS {})", expr, Self::INTEGER)
+    }
+
+    // 执行插入并返回自增ID, 需要在同一个连接上读取
+    fn insert<'c, 'q: 'c>(
+        conn: &'c mut Self::Connection,
+        query: Query<'q, Self, Self::Arguments<'q>>,
+    ) -> BoxFuture<'c, Result<i64, sqlx::Error>>;
+
+    // 先读后写的事务, SQLite 需要在开始时获取写锁, 其他数据库在事务中使用 FOR_UPDATE 锁定行
+    fn begin_write(
+        pool: &Pool<Self>,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>>;
+}
+
+impl Dialect for Sqlite {
+    const KIND: DatabaseKind = DatabaseKind::Sqlite;
+    const LIKE: &'static str = "LIKE";
+    const FOR_UPDATE: &'static str = "";
+    const INTEGER: &'static str = "INTEGER";
+    const INSERT_IGNORE: &'static str = "INSERT OR IGNORE";
+    const ON_CONFLICT_IGNORE: &'static str = "";
+
+    fn sql(sql: &str) -> Cow<'_, str> {
+        Cow::Borrowed(sql)
+    }
+
+    fn upsert(keys: &[&str], columns: &[&str]) -> String {
+        on_conflict(keys, columns)
+    }
+
+    fn concat(parts: &[&str]) -> String {
+        parts.join(" || ")
+    }
+
+    fn group_concat(expr: &str) -> String {
+        format!("GROUP_CONCAT({}, ',')", expr)
+    }
+
+    fn insert<'c, 'q: 'c>(
+        conn: &'c mut Self::Connection,
+        query: Query<'q, Self, Self::Arguments<'q>>,
+    ) -> BoxFuture<'c, Result<i64, sqlx::Error>> {
+        Box::pin(async move { Ok(query.execute(conn).await?.last_insert_rowid()) })
+    }
+
+    fn begin_write(
+        pool: &Pool<Self>,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>> {
+        Box::pin(pool.begin_with("BEGIN IMMEDIATE"))
+    }
+}
+
+impl Dialect for Postgres {
+    const KIND: DatabaseKind = DatabaseKind::Postgres;
+    const LIKE: &'static str = "ILIKE";
+    const FOR_UPDATE: &'static str = " FOR UPDATE";
+    const INTEGER: &'static str = "BIGINT";
+    const INSERT_IGNORE: &'static str = "INSERT";
+    const ON_CONFLICT_IGNORE: &'static str = " ON CONFLICT DO NOTHING";
+
+    // 占位符改为 $1, $2, ...
+    fn sql(sql: &str) -> Cow<'_, str> {
+        if !sql.contains('?') {
+            return Cow::Borrowed(sql);
+        }
+        let mut result = String::with_capacity(sql.len() + 16);
+        let mut quote = None;
+        let mut index = 0;
+        for c in sql.chars() {
+            match (quote, c) {
+                (None, '?') => {
+                    index += 1;
+                    result.push('$');
+                    result.push_str(&index.to_string());
+                    continue;
+                }
+                (None, '\'' | '"') => quote = Some(c),
+                (Some(q), c) if q == c => quote = None,
+                _ => {}
+            }
+            result.push(c);
+        }
+        Cow::Owned(result)
+    }
+
+    fn upsert(keys: &[&str], columns: &[&str]) -> String {
+        on_conflict(keys, columns)
+    }
+
+    fn concat(parts: &[&str]) -> String {
+        parts.join(" || ")
+    }
+
+    fn group_concat(expr: &str) -> String {
+        format!("STRING_AGG({}, ',')", expr)
+    }
+
+    fn insert<'c, 'q: 'c>(
+        conn: &'c mut Self::Connection,
+        query: Query<'q, Self, Self::Arguments<'q>>,
+    ) -> BoxFuture<'c, Result<i64, sqlx::Error>> {
+        Box::pin(async move {
+            query.execute(&mut *conn).await?;
+            sqlx::query_scalar::<_, i64>("SELECT lastval()")
+                .fetch_one(&mut *conn)
+                .await
+        })
+    }
+
+    fn begin_write(
+        pool: &Pool<Self>,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>> {
+        Box::pin(pool.begin())
+    }
+}
+
+impl Dialect for MySql {
+    const KIND: DatabaseKind = DatabaseKind::MySql;
+    // 默认的排序规则不区分大小写
+    const LIKE: &'static str = "LIKE";
+    const FOR_UPDATE: &'static str = " FOR UPDATE";
+    const INTEGER: &'static str = "SIGNED";
+    const INSERT_IGNORE: &'static str = "INSERT IGNORE";
+    const ON_CONFLICT_IGNORE: &'static str = "";
+
+    // 双引号标识符改为反引号
+    fn sql(sql: &str) -> Cow<'_, str> {
+        if !sql.contains('"') {
+            return Cow::Borrowed(sql);
+        }
+        let mut result = String::with_capacity(sql.len());
+        let mut in_string = false;
+        for c in sql.chars() {
+            match c {
+                '\'' => {
+                    in_string = !in_string;
+                    result.push(c);
+                }
+                '"' if !in_string => result.push('`'),
+                c => result.push(c),
+            }
+        }
+        Cow::Owned(result)
+    }
+
+    fn upsert(_keys: &[&str], columns: &[&str]) -> String {
+        let updates = columns
+            .iter()
+            .map(|column| format!("{0} = VALUES({0})", column))
+            .collect::<Vec<_>>();
+        format!(" ON DUPLICATE KEY UPDATE {}", updates.join(", "))
+    }
+
+    fn concat(parts: &[&str]) -> String {
+        format!("CONCAT({})", parts.join(", "))
+    }
+
+    fn group_concat(expr: &str) -> String {
+        format!("GROUP_CONCAT({} SEPARATOR ',')", expr)
+    }
+
+    fn insert<'c, 'q: 'c>(
+        conn: &'c mut Self::Connection,
+        query: Query<'q, Self, Self::Arguments<'q>>,
+    ) -> BoxFuture<'c, Result<i64, sqlx::Error>> {
+        Box::pin(async move { Ok(query.execute(conn).await?.last_insert_id() as i64) })
+    }
+
+    fn begin_write(
+        pool: &Pool<Self>,
+    ) -> BoxFuture<'_, Result<Transaction<'static, Self>, sqlx::Error>> {
+        Box::pin(pool.begin())
+    }
+}
+
+fn on_conflict(keys: &[&str], columns: &[&str]) -> String {
+    let updates = columns
+        .iter()
+        .map(|column| format!("{0} = excluded.{0}", column))
+        .collect::<Vec<_>>();
+    format!(
+        " ON CONFLICT ({}) DO UPDATE SET {}",
+        keys.join(", "),
+        updates.join(", ")
+    )
+}
+
+// 可以为空的文本字段, 模型中为空字符串. SQLite 读取 NULL 时本来就得到空字符串, PostgreSQL 和 MySQL 需要转换
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DbText(pub Option<String>);
+
+impl From<DbText> for String {
+    fn from(value: DbText) -> Self {
+        value.0.unwrap_or_default()
+    }
+}
+
+impl<DB: Database> Type<DB> for DbText
+where
+    String: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for DbText
+where
+    Option<String>: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(DbText(<Option<String> as Decode<DB>>::decode(value)?))
+    }
+}
+
+// 数据库中的时间, 模型中统一使用与 SQLite CURRENT_TIMESTAMP 相同格式的文本.
+// SQLite 按原样读写文本, PostgreSQL 和 MySQL 读写 TIMESTAMP/DATETIME 字段
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DbTime(pub Option<String>);
+
+impl DbTime {
+    fn parse(&self) -> Result<Option<NaiveDateTime>, BoxDynError> {
+        let Some(value) = &self.0 else {
+            return Ok(None);
+        };
+        let value = value.trim().replace('T', " ");
+        for format in [TIMESTAMP_FORMAT, "%Y-%m-%d %H:%M:%S%.f"] {
+            if let Ok(time) = NaiveDateTime::parse_from_str(&value, format) {
+                return Ok(Some(time));
+            }
+        }
+        match NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+            Ok(date) => Ok(date.and_hms_opt(0, 0, 0)),
+            Err(_) => Err(format!("无效的时间: {}", value).into()),
+        }
+    }
+
+    fn format(time: NaiveDateTime) -> Self {
+        DbTime(Some(time.format(TIMESTAMP_FORMAT).to_string()))
+    }
+}
+
+impl From<String> for DbTime {
+    fn from(value: String) -> Self {
+        DbTime(Some(value))
+    }
+}
+
+impl From<&str> for DbTime {
+    fn from(value: &str) -> Self {
+        DbTime(Some(value.to_string()))
+    }
+}
+
+impl From<&String> for DbTime {
+    fn from(value: &String) -> Self {
+        DbTime(Some(value.clone()))
+    }
+}
+
+impl From<DbTime> for String {
+    fn from(value: DbTime) -> Self {
+        value.0.unwrap_or_default()
+    }
+}
+
+impl From<DbTime> for Option<String> {
+    fn from(value: DbTime) -> Self {
+        value.0
+    }
+}
+
+impl Type<Sqlite> for DbTime {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <String as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for DbTime {
+    fn encode_by_ref(
+        &self,
+        args: &mut Vec<SqliteArgumentValue<'q>>,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        <Option<String> as Encode<Sqlite>>::encode_by_ref(&self.0, args)
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for DbTime {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(DbTime(<Option<String> as Decode<Sqlite>>::decode(value)?))
+    }
+}
+
+impl Type<Postgres> for DbTime {
+    fn type_info() -> PgTypeInfo {
+        <NaiveDateTime as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        matches!(ty.name(), "TIMESTAMP" | "TIMESTAMPTZ" | "DATE")
+            || <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for DbTime {
+    fn encode_by_ref(
+        &self,
+        buf: &mut PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        match self.parse()? {
+            Some(time) => <NaiveDateTime as Encode<Postgres>>::encode_by_ref(&time, buf),
+            None => Ok(sqlx::encode::IsNull::Yes),
+        }
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for DbTime {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        if value.is_null() {
+            return Ok(DbTime(None));
+        }
+        let name = value.type_info().name().to_string();
+        Ok(match name.as_str() {
+            "TIMESTAMP" => DbTime::format(<NaiveDateTime as Decode<Postgres>>::decode(value)?),
+            "TIMESTAMPTZ" => {
+                DbTime::format(<DateTime<Utc> as Decode<Postgres>>::decode(value)?.naive_utc())
+            }
+            "DATE" => DbTime(Some(
+                <NaiveDate as Decode<Postgres>>::decode(value)?.to_string(),
+            )),
+            _ => DbTime(Some(<String as Decode<Postgres>>::decode(value)?)),
+        })
+    }
+}
+
+impl Type<MySql> for DbTime {
+    fn type_info() -> MySqlTypeInfo {
+        <NaiveDateTime as Type<MySql>>::type_info()
+    }
+
+    fn compatible(ty: &MySqlTypeInfo) -> bool {
+        matches!(ty.name(), "DATETIME" | "TIMESTAMP" | "DATE")
+            || <String as Type<MySql>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, MySql> for DbTime {
+    fn encode_by_ref(&self, buf: &mut Vec<u8>) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        match self.parse()? {
+            Some(time) => <NaiveDateTime as Encode<MySql>>::encode_by_ref(&time, buf),
+            None => Ok(sqlx::encode::IsNull::Yes),
+        }
+    }
+}
+
+impl<'r> Decode<'r, MySql> for DbTime {
+    fn decode(value: MySqlValueRef<'r>) -> Result<Self, BoxDynError> {
+        if value.is_null() {
+            return Ok(DbTime(None));
+        }
+        let name = value.type_info().name().to_string();
+        Ok(match name.as_str() {
+            "DATETIME" | "TIMESTAMP" => {
+                DbTime::format(<NaiveDateTime as Decode<MySql>>::decode(value)?)
+            }
+            "DATE" => DbTime(Some(
+                <NaiveDate as Decode<MySql>>::decode(value)?.to_string(),
+            )),
+            _ => DbTime(Some(<String as Decode<MySql>>::decode(value)?)),
+        })
+    }
+}
+
+// 动态拼接的查询条件的参数, 按实际类型绑定
+#[derive(Debug, Clone)]
+pub enum SqlValue {
+    Int(i64),
+    Text(String),
+    Time(DbTime),
+}
+
+impl From<i64> for SqlValue {
+    fn from(value: i64) -> Self {
+        SqlValue::Int(value)
+    }
+}
+
+impl From<i32> for SqlValue {
+    fn from(value: i32) -> Self {
+        SqlValue::Int(value as i64)
+    }
+}
+
+impl From<String> for SqlValue {
+    fn from(value: String) -> Self {
+        SqlValue::Text(value)
+    }
+}
+
+impl From<DbTime> for SqlValue {
+    fn from(value: DbTime) -> Self {
+        SqlValue::Time(value)
+    }
+}
+
+impl Type<Sqlite> for SqlValue {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for SqlValue {
+    fn encode_by_ref(
+        &self,
+        args: &mut Vec<SqliteArgumentValue<'q>>,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        match self {
+            SqlValue::Int(value) => <i64 as Encode<Sqlite>>::encode_by_ref(value, args),
+            SqlValue::Text(value) => <String as Encode<Sqlite>>::encode_by_ref(value, args),
+            SqlValue::Time(value) => <DbTime as Encode<Sqlite>>::encode_by_ref(value, args),
+        }
+    }
+}
+
+impl Type<Postgres> for SqlValue {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+}
+
+impl Encode<'_, Postgres> for SqlValue {
+    fn encode_by_ref(
+        &self,
+        buf: &mut PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        match self {
+            SqlValue::Int(value) => <i64 as Encode<Postgres>>::encode_by_ref(value, buf),
+            SqlValue::Text(value) => <String as Encode<Postgres>>::encode_by_ref(value, buf),
+            SqlValue::Time(value) => <DbTime as Encode<Postgres>>::encode_by_ref(value, buf),
+        }
+    }
+
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(match self {
+            SqlValue::Int(_) => <i64 as Type<Postgres>>::type_info(),
+            SqlValue::Text(_) => <String as Type<Postgres>>::type_info(),
+            SqlValue::Time(_) => <DbTime as Type<Postgres>>::type_info(),
+        })
+    }
+}
+
+impl Type<MySql> for SqlValue {
+    fn type_info() -> MySqlTypeInfo {
+        <String as Type<MySql>>::type_info()
+    }
+}
+
+impl Encode<'_, MySql> for SqlValue {
+    fn encode_by_ref(&self, buf: &mut Vec<u8>) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        match self {
+            SqlValue::Int(value) => <i64 as Encode<MySql>>::encode_by_ref(value, buf),
+            SqlValue::Text(value) => <String as Encode<MySql>>::encode_by_ref(value, buf),
+            SqlValue::Time(value) => <DbTime as Encode<MySql>>::encode_by_ref(value, buf),
+        }
+    }
+
+    fn produces(&self) -> Option<MySqlTypeInfo> {
+        Some(match self {
+            SqlValue::Int(_) => <i64 as Type<MySql>>::type_info(),
+            SqlValue::Text(_) => <String as Type<MySql>>::type_info(),
+            SqlValue::Time(_) => <DbTime as Type<MySql>>::type_info(),
+        })
+    }
+}
+
+// 查询条件, 各项之间为 AND
+#[derive(Debug, Default)]
+pub struct Conditions {
+    conditions: Vec<String>,
+    pub params: Vec<SqlValue>,
+}
+
+impl Conditions {
+    pub fn push(
+        &mut self,
+        condition: impl Into<String>,
+        params: impl IntoIterator<Item = SqlValue>,
+    ) {
+        self.conditions.push(condition.into());
+        self.params.extend(params);
+    }
+
+    // WHERE 子句(含前导空格), 没有条件时为空
+    pub fn where_clause(&self) -> String {
+        if self.conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.conditions.join(" AND "))
+        }
+    }
+}
+
+pub fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
@@ -0,0 +1,618 @@
+use async_trait::async_trait;
+use sqlx::{Database, Executor, FromRow, IntoArguments, MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::{placeholders, Conditions, DbTime, Dialect, SqlValue};
+use crate::models::{CreateFile, CreateFileVariant, FileRecord, FileVariant, StorageUsage};
+use crate::services::files::{FileError, REFERENCES, URL_PREFIX};
+use crate::services::quota::{check_usage, resolve_quota, select_usage_sql};
+use crate::services::upload::UploadError;
+
+// 文件列表的筛选条件, 文件名按包含匹配, 其他字段精确匹配
+#[derive(Debug, Default)]
+pub struct FileQuery {
+    pub name: String,
+    pub purpose: String,
+    pub uploader_id: Option<i64>,
+    pub sha256: String,
+    pub visibility: String,
+}
+
+#[async_trait]
+pub trait FileRepository: Send + Sync {
+    async fn count(&self, query: &FileQuery) -> Result<i64, sqlx::Error>;
+
+    async fn list(
+        &self,
+        query: &FileQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<FileRecord>, sqlx::Error>;
+
+    // 附带缩略图等派生版本
+    async fn find(&self, id: i64) -> Result<Option<FileRecord>, sqlx::Error>;
+
+    async fn variants(&self, file_id: i64) -> Result<Vec<FileVariant>, sqlx::Error>;
+
+    // 按用途查找登记的文件, 返回上传者; 文件不存在时返回 None
+    async fn uploader_of(
+        &self,
+        storage_name: &str,
+        purpose: &str,
+    ) -> Result<Option<Option<i64>>, sqlx::Error>;
+
+    // 按访问地址查找原文件或派生版本, 返回所属文件的可见性和内容在存储中的位置
+    async fn find_key(&self, key: &str) -> Result<Option<(String, String)>, sqlx::Error>;
+
+    // 未登记的文件按存储路径统计引用次数
+    async fn reference_count_for_key(&self, key: &str) -> Result<i64, sqlx::Error>;
+
+    // 更新内容的使用时间, 内容不存在时返回 false
+    async fn touch_blob(&self, key: &str) -> Result<bool, sqlx::Error>;
+
+    async fn insert_blob(
+        &self,
+        key: &str,
+        sha256: &str,
+        size: i64,
+        mime: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    // 查找当前用户可以直接复用的内容并登记为新文件, 更新使用时间、检查配额和插入文件记录在同一事务中完成,
+    // 清理任务不会在两者之间删除内容; 只能复用自己上传过的或公开文件的内容.
+    // record 按已保存内容的类型生成文件记录
+    async fn reuse_blob(
+        &self,
+        key: &str,
+        size: i64,
+        user_id: i64,
+        record: Box<dyn FnOnce(String) -> CreateFile + Send>,
+    ) -> Result<Option<FileRecord>, FileError>;
+
+    // 登记文件和缩略图, 配额检查和插入在同一个写事务中完成, 同一用户的并发上传不会同时通过检查
+    async fn register(
+        &self,
+        file: &CreateFile,
+        variants: &[CreateFileVariant],
+    ) -> Result<FileRecord, FileError>;
+
+    async fn set_visibility(&self, id: i64, visibility: &str) -> Result<(), sqlx::Error>;
+
+    // 删除登记记录和派生版本, 存储中的内容由调用方处理
+    async fn delete(&self, id: i64) -> Result<(), sqlx::Error>;
+
+    // 指定用途中创建时间早于 cutoff 且没有被引用的文件
+    async fn orphans(
+        &self,
+        purposes: &[&str],
+        cutoff: &str,
+    ) -> Result<Vec<FileRecord>, sqlx::Error>;
+
+    // 删除没有任何文件引用且在 cutoff 之后没有使用过的内容, 返回删除的位置和大小
+    async fn delete_unused_blobs(&self, cutoff: &str) -> Result<Vec<(String, i64)>, sqlx::Error>;
+
+    async fn usage(&self, user_id: i64) -> Result<Option<StorageUsage>, sqlx::Error>;
+
+    async fn check_quota(&self, user_id: i64, size: u64) -> Result<(), UploadError>;
+
+    // 按用户名或昵称筛选的存储用量, 按用量从大到小排列
+    async fn count_usage(&self, name: &str) -> Result<i64, sqlx::Error>;
+
+    async fn list_usage(
+        &self,
+        name: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<StorageUsage>, sqlx::Error>;
+}
+
+fn reference_count_sql<D: Dialect>() -> String {
+    REFERENCES
+        .iter()
+        .map(|(table, column)| {
+            format!(
+                "(SELECT COUNT(*) FROM {} WHERE {} = {})",
+                table,
+                column,
+                D::concat(&[&format!("'{}'", URL_PREFIX), "f.storage_name"])
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+// 文件列表、详情共用的查询, 附带上传者名称、引用次数和访问地址
+fn select_files_sql<D: Dialect>() -> String {
+    format!(
+        r#"
+        SELECT f.id, f.storage_name, f.original_name, f.size, f.mime, f.sha256, f.purpose,
+            f.visibility, f.blob_key, f.uploader_id, u.username AS uploader_name, {} AS reference_count,
+            {} AS url, f.created_at
+        FROM files f
+        LEFT JOIN users u ON u.id = f.uploader_id
+        "#,
+        D::integer(&reference_count_sql::<D>()),
+        D::concat(&[&format!("'{}'", URL_PREFIX), "f.storage_name"])
+    )
+}
+
+fn conditions<D: Dialect>(query: &FileQuery) -> Conditions {
+    let mut conditions = Conditions::default();
+    if !query.name.is_empty() {
+        conditions.push(
+            format!("f.original_name {} ?", D::LIKE),
+            [SqlValue::Text(format!("%{}%", query.name))],
+        );
+    }
+    if !query.purpose.is_empty() {
+        conditions.push("f.purpose = ?", [SqlValue::Text(query.purpose.clone())]);
+    }
+    if let Some(uploader_id) = query.uploader_id {
+        conditions.push("f.uploader_id = ?", [SqlValue::Int(uploader_id)]);
+    }
+    if !query.sha256.is_empty() {
+        conditions.push(
+            "f.sha256 = ?",
+            [SqlValue::Text(query.sha256.to_lowercase())],
+        );
+    }
+    if !query.visibility.is_empty() {
+        conditions.push(
+            "f.visibility = ?",
+            [SqlValue::Text(query.visibility.clone())],
+        );
+    }
+    conditions
+}
+
+fn usage_conditions<D: Dialect>(name: &str) -> Conditions {
+    let mut conditions = Conditions::default();
+    if !name.is_empty() {
+        let pattern = format!("%{}%", name);
+        conditions.push(
+            format!("(u.username {0} ? OR u.nickname {0} ?)", D::LIKE),
+            [SqlValue::Text(pattern.clone()), SqlValue::Text(pattern)],
+        );
+    }
+    conditions
+}
+
+// 事务内外共用的配额查询
+pub(super) async fn find_usage<'c, D, E>(
+    executor: E,
+    user_id: i64,
+) -> Result<Option<StorageUsage>, sqlx::Error>
+where
+    D: Dialect,
+    E: Executor<'c, Database = D>,
+    StorageUsage: for<'r> FromRow<'r, <D as Database>::Row>,
+    i64: for<'q> sqlx::Encode<'q, D> + sqlx::Type<D>,
+    for<'q> <D as Database>::Arguments<'q>: IntoArguments<'q, D>,
+{
+    let sql = format!("{} WHERE u.id = ?", select_usage_sql::<D>());
+    let sql = D::sql(&sql);
+    let usage = sqlx::query_as::<D, StorageUsage>(&sql)
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await?;
+    Ok(usage.map(|mut usage| {
+        resolve_quota(&mut usage);
+        usage
+    }))
+}
+
+// 在写事务中锁定用户, 同一用户的并发上传依次检查配额; SQLite 的写事务已锁定整个数据库
+pub(super) async fn lock_user<'c, D, E>(executor: E, user_id: i64) -> Result<(), sqlx::Error>
+where
+    D: Dialect,
+    E: Executor<'c, Database = D>,
+    i64: for<'q> sqlx::Encode<'q, D> + sqlx::Type<D>,
+    for<'q> <D as Database>::Arguments<'q>: IntoArguments<'q, D>,
+{
+    if !D::FOR_UPDATE.is_empty() {
+        let sql = format!("SELECT id FROM users WHERE id = ?{}", D::FOR_UPDATE);
+        sqlx::query(&D::sql(&sql))
+            .bind(user_id)
+            .execute(executor)
+            .await?;
+    }
+    Ok(())
+}
+
+macro_rules! file_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+
+            async fn insert_file(
+                conn: &mut <$db as Database>::Connection,
+                file: &CreateFile,
+            ) -> Result<i64, sqlx::Error> {
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO files
+                        (storage_name, original_name, size, mime, sha256, purpose, visibility, blob_key,
+                         uploader_id)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(&file.storage_name)
+                    .bind(&file.original_name)
+                    .bind(file.size)
+                    .bind(&file.mime)
+                    .bind(&file.sha256)
+                    .bind(&file.purpose)
+                    .bind(&file.visibility)
+                    .bind(&file.blob_key)
+                    .bind(file.uploader_id);
+                <$db>::insert(conn, query).await
+            }
+        }
+
+        #[async_trait]
+        impl FileRepository for $name {
+            async fn count(&self, query: &FileQuery) -> Result<i64, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!("SELECT COUNT(*) FROM files f{}", conditions.where_clause());
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list(
+                &self,
+                query: &FileQuery,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<FileRecord>, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!(
+                    "{}{} ORDER BY f.id DESC LIMIT ? OFFSET ?",
+                    select_files_sql::<$db>(),
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, FileRecord>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn find(&self, id: i64) -> Result<Option<FileRecord>, sqlx::Error> {
+                let sql = format!("{} WHERE f.id = ?", select_files_sql::<$db>());
+                let file = sqlx::query_as::<_, FileRecord>(&<$db>::sql(&sql))
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                match file {
+                    Some(mut file) => {
+                        file.variants = self.variants(file.id).await?;
+                        Ok(Some(file))
+                    }
+                    None => Ok(None),
+                }
+            }
+
+            async fn variants(&self, file_id: i64) -> Result<Vec<FileVariant>, sqlx::Error> {
+                let sql = format!(
+                    r#"
+                    SELECT variant, storage_name, blob_key, size, width, height, {} AS url
+                    FROM file_variants
+                    WHERE file_id = ?
+                    ORDER BY width, id
+                    "#,
+                    <$db>::concat(&[&format!("'{}'", URL_PREFIX), "storage_name"])
+                );
+                sqlx::query_as::<_, FileVariant>(&<$db>::sql(&sql))
+                    .bind(file_id)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn uploader_of(
+                &self,
+                storage_name: &str,
+                purpose: &str,
+            ) -> Result<Option<Option<i64>>, sqlx::Error> {
+                sqlx::query_scalar::<_, Option<i64>>(&<$db>::sql(
+                    "SELECT uploader_id FROM files WHERE storage_name = ? AND purpose = ?",
+                ))
+                .bind(storage_name)
+                .bind(purpose)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn find_key(&self, key: &str) -> Result<Option<(String, String)>, sqlx::Error> {
+                sqlx::query_as::<_, (String, String)>(&<$db>::sql(
+                    r#"
+                    SELECT visibility, COALESCE(blob_key, storage_name) FROM files WHERE storage_name = ?
+                    UNION ALL
+                    SELECT f.visibility, COALESCE(v.blob_key, v.storage_name)
+                    FROM file_variants v
+                    JOIN files f ON f.id = v.file_id
+                    WHERE v.storage_name = ?
+                    LIMIT 1
+                    "#,
+                ))
+                .bind(key)
+                .bind(key)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn reference_count_for_key(&self, key: &str) -> Result<i64, sqlx::Error> {
+                let sql = format!(
+                    "SELECT {}",
+                    <$db>::integer(
+                        &REFERENCES
+                            .iter()
+                            .map(|(table, column)| {
+                                format!("(SELECT COUNT(*) FROM {} WHERE {} = ?)", table, column)
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" + ")
+                    )
+                );
+                let sql = <$db>::sql(&sql);
+                let url = format!("{}{}", URL_PREFIX, key);
+                let mut query = sqlx::query_scalar::<_, i64>(&sql);
+                for _ in REFERENCES {
+                    query = query.bind(&url);
+                }
+                query.fetch_one(&self.pool).await
+            }
+
+            async fn touch_blob(&self, key: &str) -> Result<bool, sqlx::Error> {
+                // MySQL 在时间没有变化时不计入影响的行数, 按是否存在判断
+                sqlx::query(&<$db>::sql(
+                    r#"UPDATE file_blobs SET last_used_at = CURRENT_TIMESTAMP WHERE "key" = ?"#,
+                ))
+                .bind(key)
+                .execute(&self.pool)
+                .await?;
+                let exists = sqlx::query_scalar::<_, i64>(&<$db>::sql(
+                    r#"SELECT COUNT(*) FROM file_blobs WHERE "key" = ?"#,
+                ))
+                .bind(key)
+                .fetch_one(&self.pool)
+                .await?;
+                Ok(exists > 0)
+            }
+
+            async fn insert_blob(
+                &self,
+                key: &str,
+                sha256: &str,
+                size: i64,
+                mime: &str,
+            ) -> Result<(), sqlx::Error> {
+                let sql = format!(
+                    r#"{} INTO file_blobs ("key", sha256, size, mime) VALUES (?, ?, ?, ?){}"#,
+                    <$db>::INSERT_IGNORE,
+                    <$db>::ON_CONFLICT_IGNORE
+                );
+                sqlx::query(&<$db>::sql(&sql))
+                    .bind(key)
+                    .bind(sha256)
+                    .bind(size)
+                    .bind(mime)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn reuse_blob(
+                &self,
+                key: &str,
+                size: i64,
+                user_id: i64,
+                record: Box<dyn FnOnce(String) -> CreateFile + Send>,
+            ) -> Result<Option<FileRecord>, FileError> {
+                let mut tx = <$db>::begin_write(&self.pool).await?;
+                lock_user(&mut *tx, user_id).await?;
+                let sql = format!(
+                    r#"
+                    SELECT mime FROM file_blobs
+                    WHERE "key" = ? AND size = ?
+                        AND EXISTS (
+                            SELECT 1 FROM files f
+                            WHERE f.blob_key = file_blobs."key"
+                                AND (f.uploader_id = ? OR f.visibility = 'public')
+                        ){}
+                    "#,
+                    <$db>::FOR_UPDATE
+                );
+                let mime = sqlx::query_scalar::<_, String>(&<$db>::sql(&sql))
+                    .bind(key)
+                    .bind(size)
+                    .bind(user_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                let Some(mime) = mime else {
+                    return Ok(None);
+                };
+                sqlx::query(&<$db>::sql(
+                    r#"UPDATE file_blobs SET last_used_at = CURRENT_TIMESTAMP WHERE "key" = ?"#,
+                ))
+                .bind(key)
+                .execute(&mut *tx)
+                .await?;
+
+                check_usage(find_usage(&mut *tx, user_id).await?, size as u64)?;
+                let id = Self::insert_file(&mut tx, &record(mime)).await?;
+                tx.commit().await?;
+                Ok(self.find(id).await?)
+            }
+
+            async fn register(
+                &self,
+                file: &CreateFile,
+                variants: &[CreateFileVariant],
+            ) -> Result<FileRecord, FileError> {
+                let size = file.size + variants.iter().map(|variant| variant.size).sum::<i64>();
+                let mut tx = <$db>::begin_write(&self.pool).await?;
+                lock_user(&mut *tx, file.uploader_id).await?;
+                check_usage(find_usage(&mut *tx, file.uploader_id).await?, size as u64)?;
+                let id = Self::insert_file(&mut tx, file).await?;
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO file_variants (file_id, variant, storage_name, blob_key, size, width, height)
+                    VALUES (?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                for variant in variants {
+                    sqlx::query(&sql)
+                        .bind(id)
+                        .bind(&variant.variant)
+                        .bind(&variant.storage_name)
+                        .bind(&variant.blob_key)
+                        .bind(variant.size)
+                        .bind(variant.width as i32)
+                        .bind(variant.height as i32)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                tx.commit().await?;
+                Ok(self.find(id).await?.ok_or(sqlx::Error::RowNotFound)?)
+            }
+
+            async fn set_visibility(&self, id: i64, visibility: &str) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql("UPDATE files SET visibility = ? WHERE id = ?"))
+                    .bind(visibility)
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn delete(&self, id: i64) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query(&<$db>::sql("DELETE FROM file_variants WHERE file_id = ?"))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query(&<$db>::sql("DELETE FROM files WHERE id = ?"))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await
+            }
+
+            async fn orphans(
+                &self,
+                purposes: &[&str],
+                cutoff: &str,
+            ) -> Result<Vec<FileRecord>, sqlx::Error> {
+                let sql = format!(
+                    "{} WHERE f.purpose IN ({}) AND f.created_at < ? AND {} = 0",
+                    select_files_sql::<$db>(),
+                    placeholders(purposes.len()),
+                    reference_count_sql::<$db>()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut query = sqlx::query_as::<_, FileRecord>(&sql);
+                for purpose in purposes {
+                    query = query.bind(*purpose);
+                }
+                query.bind(DbTime::from(cutoff)).fetch_all(&self.pool).await
+            }
+
+            async fn delete_unused_blobs(
+                &self,
+                cutoff: &str,
+            ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+                let unused = r#"
+                    last_used_at < ?
+                    AND NOT EXISTS (SELECT 1 FROM files WHERE blob_key = file_blobs."key")
+                    AND NOT EXISTS (SELECT 1 FROM file_variants WHERE blob_key = file_blobs."key")
+                "#;
+                let sql = format!(r#"SELECT "key", size FROM file_blobs WHERE {}"#, unused);
+                let candidates = sqlx::query_as::<_, (String, i64)>(&<$db>::sql(&sql))
+                    .bind(DbTime::from(cutoff))
+                    .fetch_all(&self.pool)
+                    .await?;
+
+                // 查询之后可能有新的上传复用了内容, 删除时再检查一次, 只返回实际删除的内容
+                let sql = format!(r#"DELETE FROM file_blobs WHERE "key" = ? AND {}"#, unused);
+                let sql = <$db>::sql(&sql);
+                let mut removed = Vec::new();
+                for (key, size) in candidates {
+                    let deleted = sqlx::query(&sql)
+                        .bind(&key)
+                        .bind(DbTime::from(cutoff))
+                        .execute(&self.pool)
+                        .await?
+                        .rows_affected();
+                    if deleted > 0 {
+                        removed.push((key, size));
+                    }
+                }
+                Ok(removed)
+            }
+
+            async fn usage(&self, user_id: i64) -> Result<Option<StorageUsage>, sqlx::Error> {
+                find_usage(&self.pool, user_id).await
+            }
+
+            async fn check_quota(&self, user_id: i64, size: u64) -> Result<(), UploadError> {
+                check_usage(find_usage(&self.pool, user_id).await?, size)
+            }
+
+            async fn count_usage(&self, name: &str) -> Result<i64, sqlx::Error> {
+                let conditions = usage_conditions::<$db>(name);
+                let sql = format!("SELECT COUNT(*) FROM users u{}", conditions.where_clause());
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list_usage(
+                &self,
+                name: &str,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<StorageUsage>, sqlx::Error> {
+                let conditions = usage_conditions::<$db>(name);
+                let sql = format!(
+                    "{}{} ORDER BY used DESC, u.id LIMIT ? OFFSET ?",
+                    select_usage_sql::<$db>(),
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, StorageUsage>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                let mut items = list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await?;
+                items.iter_mut().for_each(resolve_quota);
+                Ok(items)
+            }
+        }
+    };
+}
+
+file_repository!(SqliteFileRepository, Sqlite);
+file_repository!(PostgresFileRepository, Postgres);
+file_repository!(MySqlFileRepository, MySql);
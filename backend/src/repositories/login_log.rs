@@ -0,0 +1,150 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::{Conditions, DbTime, Dialect, SqlValue};
+use crate::models::{CreateLoginLog, LoginLog};
+
+// 登录日志的筛选条件, 用户名按包含匹配, 时间为已解析的起止时间
+#[derive(Debug, Default)]
+pub struct LoginLogQuery {
+    pub username: String,
+    pub user_id: Option<i64>,
+    pub success: Option<bool>,
+    pub ip: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+}
+
+#[async_trait]
+pub trait LoginLogRepository: Send + Sync {
+    async fn record(&self, log: &CreateLoginLog) -> Result<LoginLog, sqlx::Error>;
+
+    async fn count(&self, query: &LoginLogQuery) -> Result<i64, sqlx::Error>;
+
+    // 按时间倒序
+    async fn list(
+        &self,
+        query: &LoginLogQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<LoginLog>, sqlx::Error>;
+
+    // 删除 before 之前的登录日志, 返回删除的条数
+    async fn purge(&self, before: &str) -> Result<u64, sqlx::Error>;
+}
+
+fn conditions<D: Dialect>(query: &LoginLogQuery) -> Conditions {
+    let mut conditions = Conditions::default();
+    if !query.username.is_empty() {
+        conditions.push(
+            format!("username {} ?", D::LIKE),
+            [SqlValue::Text(format!("%{}%", query.username))],
+        );
+    }
+    if let Some(user_id) = query.user_id {
+        conditions.push("user_id = ?", [SqlValue::Int(user_id)]);
+    }
+    match query.success {
+        Some(true) => conditions.push("success = TRUE", []),
+        Some(false) => conditions.push("success = FALSE", []),
+        None => {}
+    }
+    if let Some(ip) = &query.ip {
+        conditions.push("ip = ?", [SqlValue::Text(ip.clone())]);
+    }
+    if let Some(start_time) = &query.start_time {
+        conditions.push("created_at >= ?", [SqlValue::Time(start_time.into())]);
+    }
+    if let Some(end_time) = &query.end_time {
+        conditions.push("created_at <= ?", [SqlValue::Time(end_time.into())]);
+    }
+    conditions
+}
+
+macro_rules! login_log_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl LoginLogRepository for $name {
+            async fn record(&self, log: &CreateLoginLog) -> Result<LoginLog, sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO login_logs
+                        (user_id, username, success, failure_reason, ip, user_agent, request_id)
+                    VALUES (?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(log.user_id)
+                    .bind(&log.username)
+                    .bind(log.success)
+                    .bind(&log.failure_reason)
+                    .bind(&log.ip)
+                    .bind(&log.user_agent)
+                    .bind(&log.request_id);
+                let id = <$db>::insert(&mut conn, query).await?;
+
+                sqlx::query_as::<_, LoginLog>(&<$db>::sql("SELECT * FROM login_logs WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&mut *conn)
+                    .await
+            }
+
+            async fn count(&self, query: &LoginLogQuery) -> Result<i64, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!("SELECT COUNT(*) FROM login_logs{}", conditions.where_clause());
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list(
+                &self,
+                query: &LoginLogQuery,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<LoginLog>, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!(
+                    "SELECT * FROM login_logs{} ORDER BY created_at DESC, id DESC LIMIT ? OFFSET ?",
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, LoginLog>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn purge(&self, before: &str) -> Result<u64, sqlx::Error> {
+                let result = sqlx::query(&<$db>::sql("DELETE FROM login_logs WHERE created_at < ?"))
+                    .bind(DbTime::from(before))
+                    .execute(&self.pool)
+                    .await?;
+                Ok(result.rows_affected())
+            }
+        }
+    };
+}
+
+login_log_repository!(SqliteLoginLogRepository, Sqlite);
+login_log_repository!(PostgresLoginLogRepository, Postgres);
+login_log_repository!(MySqlLoginLogRepository, MySql);
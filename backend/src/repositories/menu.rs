@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::Dialect;
+use crate::models::Menu;
+
+#[async_trait]
+pub trait MenuRepository: Send + Sync {
+    // 不包含隐藏的菜单, 按排序号排列
+    async fn list_visible(&self) -> Result<Vec<Menu>, sqlx::Error>;
+
+    async fn find(&self, id: i64) -> Result<Option<Menu>, sqlx::Error>;
+
+    async fn create(&self, menu: &Menu) -> Result<Menu, sqlx::Error>;
+
+    // 只更新标题、路径、图标、上级和排序号
+    async fn update(&self, id: i64, menu: &Menu) -> Result<Menu, sqlx::Error>;
+
+    async fn delete(&self, id: i64) -> Result<(), sqlx::Error>;
+}
+
+macro_rules! menu_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl MenuRepository for $name {
+            async fn list_visible(&self) -> Result<Vec<Menu>, sqlx::Error> {
+                sqlx::query_as::<_, Menu>("SELECT * FROM menus WHERE is_hidden = FALSE ORDER BY sort")
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn find(&self, id: i64) -> Result<Option<Menu>, sqlx::Error> {
+                sqlx::query_as::<_, Menu>(&<$db>::sql("SELECT * FROM menus WHERE id = ?"))
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn create(&self, menu: &Menu) -> Result<Menu, sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(menu.parent_id)
+                    .bind(&menu.name)
+                    .bind(&menu.path)
+                    .bind(&menu.component)
+                    .bind(&menu.title)
+                    .bind(&menu.icon)
+                    .bind(menu.sort)
+                    .bind(menu.is_hidden);
+                let id = <$db>::insert(&mut conn, query).await?;
+
+                sqlx::query_as::<_, Menu>(&<$db>::sql("SELECT * FROM menus WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&mut *conn)
+                    .await
+            }
+
+            async fn update(&self, id: i64, menu: &Menu) -> Result<Menu, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    "UPDATE menus SET title = ?, path = ?, icon = ?, parent_id = ?, sort = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+                ))
+                .bind(&menu.title)
+                .bind(&menu.path)
+                .bind(&menu.icon)
+                .bind(menu.parent_id)
+                .bind(menu.sort)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+                sqlx::query_as::<_, Menu>(&<$db>::sql("SELECT * FROM menus WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn delete(&self, id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql("DELETE FROM menus WHERE id = ?"))
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+        }
+    };
+}
+
+menu_repository!(SqliteMenuRepository, Sqlite);
+menu_repository!(PostgresMenuRepository, Postgres);
+menu_repository!(MySqlMenuRepository, MySql);
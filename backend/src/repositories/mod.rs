@@ -0,0 +1,139 @@
+pub mod assignment;
+pub mod dashboard;
+pub mod dialect;
+pub mod file;
+pub mod login_log;
+pub mod menu;
+pub mod operation_log;
+pub mod operation_log_rule;
+pub mod permission;
+pub mod role;
+pub mod schema;
+pub mod upload_session;
+pub mod user;
+
+use futures_util::stream::BoxStream;
+use sqlx::{MySqlPool, PgPool, SqlitePool};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+pub use assignment::AssignmentRepository;
+pub use dashboard::DashboardRepository;
+pub use dialect::{DbText, DbTime, Dialect};
+pub use file::FileRepository;
+pub use login_log::LoginLogRepository;
+pub use menu::MenuRepository;
+pub use operation_log::OperationLogRepository;
+pub use operation_log_rule::OperationLogRuleRepository;
+pub use permission::{PermissionQuery, PermissionRepository};
+pub use role::RoleRepository;
+pub use schema::SchemaRepository;
+pub use upload_session::UploadSessionRepository;
+pub use user::UserRepository;
+
+// 控制器和服务通过仓储读写数据, 不直接依赖具体的数据库和 SQL 方言.
+// 启动时按 DATABASE_URL 选择 SQLite、PostgreSQL 或 MySQL 的实现
+#[derive(Clone)]
+pub struct Repositories {
+    pub menus: Arc<dyn MenuRepository>,
+    pub permissions: Arc<dyn PermissionRepository>,
+    pub roles: Arc<dyn RoleRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub assignments: Arc<dyn AssignmentRepository>,
+    pub files: Arc<dyn FileRepository>,
+    pub upload_sessions: Arc<dyn UploadSessionRepository>,
+    pub login_logs: Arc<dyn LoginLogRepository>,
+    pub operation_logs: Arc<dyn OperationLogRepository>,
+    pub operation_log_rules: Arc<dyn OperationLogRuleRepository>,
+    pub dashboard: Arc<dyn DashboardRepository>,
+    pub schema: Arc<dyn SchemaRepository>,
+}
+
+impl Repositories {
+    pub fn sqlite(pool: SqlitePool) -> Self {
+        Self {
+            menus: Arc::new(menu::SqliteMenuRepository::new(pool.clone())),
+            permissions: Arc::new(permission::SqlitePermissionRepository::new(pool.clone())),
+            roles: Arc::new(role::SqliteRoleRepository::new(pool.clone())),
+            users: Arc::new(user::SqliteUserRepository::new(pool.clone())),
+            assignments: Arc::new(assignment::SqliteAssignmentRepository::new(pool.clone())),
+            files: Arc::new(file::SqliteFileRepository::new(pool.clone())),
+            upload_sessions: Arc::new(upload_session::SqliteUploadSessionRepository::new(
+                pool.clone(),
+            )),
+            login_logs: Arc::new(login_log::SqliteLoginLogRepository::new(pool.clone())),
+            operation_logs: Arc::new(operation_log::SqliteOperationLogRepository::new(
+                pool.clone(),
+            )),
+            operation_log_rules: Arc::new(
+                operation_log_rule::SqliteOperationLogRuleRepository::new(pool.clone()),
+            ),
+            dashboard: Arc::new(dashboard::SqliteDashboardRepository::new(pool.clone())),
+            schema: Arc::new(schema::SqliteSchemaRepository::new(pool)),
+        }
+    }
+
+    pub fn postgres(pool: PgPool) -> Self {
+        Self {
+            menus: Arc::new(menu::PostgresMenuRepository::new(pool.clone())),
+            permissions: Arc::new(permission::PostgresPermissionRepository::new(pool.clone())),
+            roles: Arc::new(role::PostgresRoleRepository::new(pool.clone())),
+            users: Arc::new(user::PostgresUserRepository::new(pool.clone())),
+            assignments: Arc::new(assignment::PostgresAssignmentRepository::new(pool.clone())),
+            files: Arc::new(file::PostgresFileRepository::new(pool.clone())),
+            upload_sessions: Arc::new(upload_session::PostgresUploadSessionRepository::new(
+                pool.clone(),
+            )),
+            login_logs: Arc::new(login_log::PostgresLoginLogRepository::new(pool.clone())),
+            operation_logs: Arc::new(operation_log::PostgresOperationLogRepository::new(
+                pool.clone(),
+            )),
+            operation_log_rules: Arc::new(
+                operation_log_rule::PostgresOperationLogRuleRepository::new(pool.clone()),
+            ),
+            dashboard: Arc::new(dashboard::PostgresDashboardRepository::new(pool.clone())),
+            schema: Arc::new(schema::PostgresSchemaRepository::new(pool)),
+        }
+    }
+
+    pub fn mysql(pool: MySqlPool) -> Self {
+        Self {
+            menus: Arc::new(menu::MySqlMenuRepository::new(pool.clone())),
+            permissions: Arc::new(permission::MySqlPermissionRepository::new(pool.clone())),
+            roles: Arc::new(role::MySqlRoleRepository::new(pool.clone())),
+            users: Arc::new(user::MySqlUserRepository::new(pool.clone())),
+            assignments: Arc::new(assignment::MySqlAssignmentRepository::new(pool.clone())),
+            files: Arc::new(file::MySqlFileRepository::new(pool.clone())),
+            upload_sessions: Arc::new(upload_session::MySqlUploadSessionRepository::new(
+                pool.clone(),
+            )),
+            login_logs: Arc::new(login_log::MySqlLoginLogRepository::new(pool.clone())),
+            operation_logs: Arc::new(operation_log::MySqlOperationLogRepository::new(
+                pool.clone(),
+            )),
+            operation_log_rules: Arc::new(
+                operation_log_rule::MySqlOperationLogRuleRepository::new(pool.clone()),
+            ),
+            dashboard: Arc::new(dashboard::MySqlDashboardRepository::new(pool.clone())),
+            schema: Arc::new(schema::MySqlSchemaRepository::new(pool)),
+        }
+    }
+}
+
+// 在后台任务中逐行读取查询结果, 通过有界通道交给调用方, 导出大量数据时不必一次读入内存
+pub(crate) fn stream_rows<T, F, Fut>(produce: F) -> BoxStream<'static, Result<T, sqlx::Error>>
+where
+    T: Send + 'static,
+    F: FnOnce(mpsc::Sender<Result<T, sqlx::Error>>) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(produce(tx));
+    Box::pin(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|row| (row, rx))
+    }))
+}
+
+#[cfg(test)]
+mod tests;
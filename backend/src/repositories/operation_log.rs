@@ -0,0 +1,867 @@
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use sqlx::{Database, MySql, Pool, Postgres, Sqlite};
+use std::collections::HashMap;
+
+use super::dialect::{placeholders, Conditions, DbTime, Dialect, SqlValue};
+use super::stream_rows;
+use crate::config::database::DatabaseKind;
+use crate::models::{
+    ArchivedOperationLog, CreateOperationLog, OperationLog, OperationLogChange,
+    OperationLogCheckpoint, OperationLogRedaction,
+};
+use crate::services::operation_log::{RedactedRow, GENESIS_HASH};
+use crate::services::operation_log_archive::ArchiveEntry;
+use crate::services::operation_log_filter::OperationLogFilter;
+use crate::utils::ip::ip_key;
+
+// trigram 分词至少需要三个字符, 更短的关键词退回到 LIKE 查询
+const FTS_MIN_CHARS: usize = 3;
+
+// 待写入的日志, 哈希在写入事务中锁定链头后按链的顺序计算
+pub struct NewOperationLog<'a> {
+    pub log: &'a CreateOperationLog,
+    pub created_at: &'a str,
+}
+
+// 一次清洗事件, rows 按日志ID排序
+pub struct NewRedaction<'a> {
+    pub reason: &'a str,
+    pub created_at: &'a str,
+    pub rows: &'a [RedactedRow],
+}
+
+// 按前一个哈希计算日志或清洗事件的哈希
+pub type LogHasher = for<'a> fn(&NewOperationLog<'a>, &str) -> String;
+pub type RedactionHasher = for<'a> fn(&NewRedaction<'a>, &str) -> String;
+
+// 清理到 last_log_id 为止的日志时记录的检查点
+pub struct NewCheckpoint<'a> {
+    pub last_log_id: i64,
+    pub last_hash: &'a str,
+    pub cutoff: &'a str,
+    pub reason: &'a str,
+    pub created_by: Option<&'a str>,
+    pub archive: &'a str,
+}
+
+// 导入的归档日志的筛选条件, 用户名按包含匹配
+#[derive(Debug, Default)]
+pub struct ArchivedLogQuery {
+    pub archive: String,
+    pub username: String,
+}
+
+#[async_trait]
+pub trait OperationLogRepository: Send + Sync {
+    async fn count(&self, filter: &OperationLogFilter) -> Result<i64, sqlx::Error>;
+
+    async fn list(
+        &self,
+        filter: &OperationLogFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<OperationLog>, sqlx::Error>;
+
+    // 边查询边返回, 不在内存中缓存整张表
+    fn export(
+        &self,
+        filter: &OperationLogFilter,
+    ) -> BoxStream<'static, Result<OperationLog, sqlx::Error>>;
+
+    async fn find(&self, id: i64) -> Result<Option<OperationLog>, sqlx::Error>;
+
+    async fn changes(&self, log_id: i64) -> Result<Vec<OperationLogChange>, sqlx::Error>;
+
+    // 在同一事务中写入日志及其数据变更, 锁定链头后从链尾开始依次计算哈希, 返回日志ID
+    async fn insert(
+        &self,
+        logs: &[NewOperationLog<'_>],
+        hasher: LogHasher,
+    ) -> Result<Vec<i64>, sqlx::Error>;
+
+    async fn between(&self, first_id: i64, last_id: i64) -> Result<Vec<OperationLog>, sqlx::Error>;
+
+    // 按ID顺序分批读取 after_id 之后的日志
+    async fn after(&self, after_id: i64, limit: i64) -> Result<Vec<OperationLog>, sqlx::Error>;
+
+    // 只返回有参数的日志, 用于重新脱敏
+    async fn with_params_after(
+        &self,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<OperationLog>, sqlx::Error>;
+
+    // before_id 之前(为空时为全部)最后一条日志的哈希, 没有日志时返回 None
+    async fn last_hash(
+        &self,
+        before_id: Option<i64>,
+    ) -> Result<Option<Option<String>>, sqlx::Error>;
+
+    // 第一条已封存(sealed 为 true)或未封存的日志ID
+    async fn first_id(&self, sealed: bool) -> Result<Option<i64>, sqlx::Error>;
+
+    // 重新串联哈希链, 每项为 (日志ID, 上一条的哈希, 哈希), 在同一事务中更新
+    async fn set_hashes(&self, hashes: &[(i64, String, String)]) -> Result<(), sqlx::Error>;
+
+    async fn missing_ip_keys(&self) -> Result<Vec<(i64, String)>, sqlx::Error>;
+
+    async fn set_ip_keys(&self, keys: &[(i64, String)]) -> Result<(), sqlx::Error>;
+
+    // created_at 早于 before 的最大日志ID
+    async fn max_id_before(&self, before: &str) -> Result<Option<i64>, sqlx::Error>;
+
+    // 按ID倒序跳过 offset 条后的日志ID, 用于按条数保留
+    async fn id_at_offset(&self, offset: i64) -> Result<Option<i64>, sqlx::Error>;
+
+    // ID不大于 up_to_id 的日志及其数据变更, 最多 limit 条
+    async fn archive_batch(
+        &self,
+        up_to_id: i64,
+        limit: i64,
+    ) -> Result<Vec<ArchiveEntry>, sqlx::Error>;
+
+    // 删除到检查点为止的日志并记录检查点, 返回删除的条数和检查点ID
+    async fn purge(&self, checkpoint: &NewCheckpoint<'_>) -> Result<(i64, i64), sqlx::Error>;
+
+    // 按ID倒序
+    async fn checkpoints(&self) -> Result<Vec<OperationLogCheckpoint>, sqlx::Error>;
+
+    // 最近一次清理留下的检查点哈希
+    async fn last_checkpoint_hash(&self) -> Result<Option<String>, sqlx::Error>;
+
+    // 按ID倒序
+    async fn redactions(&self) -> Result<Vec<OperationLogRedaction>, sqlx::Error>;
+
+    async fn redacted_rows(&self, redaction_id: i64) -> Result<Vec<RedactedRow>, sqlx::Error>;
+
+    // 写入清洗后的日志参数, 有已封存的日志被清洗时同时记录清洗事件, 返回事件ID.
+    // 事件串联在上一个清洗事件之后, 在写入事务中锁定链头后计算哈希
+    async fn redact(
+        &self,
+        params: &[(i64, Option<String>)],
+        redaction: Option<&NewRedaction<'_>>,
+        hasher: RedactionHasher,
+    ) -> Result<Option<i64>, sqlx::Error>;
+
+    // 重新导入同一归档时替换上次导入的数据
+    async fn import_archive(
+        &self,
+        name: &str,
+        entries: &[ArchiveEntry],
+    ) -> Result<u64, sqlx::Error>;
+
+    // 每个归档已导入的条数
+    async fn archived_counts(&self) -> Result<Vec<(String, i64)>, sqlx::Error>;
+
+    async fn count_archived(&self, query: &ArchivedLogQuery) -> Result<i64, sqlx::Error>;
+
+    async fn list_archived(
+        &self,
+        query: &ArchivedLogQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ArchivedOperationLog>, sqlx::Error>;
+
+    // archive 为空时移除全部导入的归档日志
+    async fn delete_archived(&self, archive: &str) -> Result<u64, sqlx::Error>;
+}
+
+fn conditions<D: Dialect>(filter: &OperationLogFilter) -> Conditions {
+    let mut conditions = Conditions::default();
+    let like = |value: &str| SqlValue::Text(format!("%{}%", value));
+
+    if !filter.username.is_empty() {
+        conditions.push(format!("username {} ?", D::LIKE), [like(&filter.username)]);
+    }
+    if !filter.module.is_empty() {
+        conditions.push(format!("module {} ?", D::LIKE), [like(&filter.module)]);
+    }
+    if !filter.operation.is_empty() {
+        conditions.push(
+            format!("operation {} ?", D::LIKE),
+            [like(&filter.operation)],
+        );
+    }
+    if let Some(status) = filter.status {
+        conditions.push("status = ?", [SqlValue::from(status)]);
+    }
+    if !filter.status_classes.is_empty() {
+        let classes = vec!["status BETWEEN ? AND ?"; filter.status_classes.len()];
+        let params = filter.status_classes.iter().flat_map(|class| {
+            [
+                SqlValue::from(class * 100),
+                SqlValue::from(class * 100 + 99),
+            ]
+        });
+        conditions.push(format!("({})", classes.join(" OR ")), params);
+    }
+    if !filter.methods.is_empty() {
+        conditions.push(
+            format!("method IN ({})", placeholders(filter.methods.len())),
+            filter.methods.iter().cloned().map(SqlValue::Text),
+        );
+    }
+    if let Some(user_id) = filter.user_id {
+        conditions.push("user_id = ?", [SqlValue::Int(user_id)]);
+    }
+    if let Some(ip) = &filter.ip {
+        conditions.push(
+            "ip_key BETWEEN ? AND ?",
+            [
+                SqlValue::Text(ip.start.clone()),
+                SqlValue::Text(ip.end.clone()),
+            ],
+        );
+    }
+    if let Some(start_time) = &filter.start_time {
+        conditions.push("created_at >= ?", [SqlValue::Time(start_time.into())]);
+    }
+    if let Some(end_time) = &filter.end_time {
+        conditions.push("created_at <= ?", [SqlValue::Time(end_time.into())]);
+    }
+    if !filter.keyword.is_empty() {
+        if D::KIND == DatabaseKind::Sqlite && filter.keyword.chars().count() >= FTS_MIN_CHARS {
+            // 作为短语整体匹配, 避免关键词中的 FTS 语法字符被解析
+            conditions.push(
+                "id IN (SELECT rowid FROM operation_logs_fts WHERE operation_logs_fts MATCH ?)",
+                [SqlValue::Text(format!(
+                    "\"{}\"",
+                    filter.keyword.replace('"', "\"\"")
+                ))],
+            );
+        } else {
+            // PostgreSQL 由 pg_trgm 索引加速
+            conditions.push(
+                format!("(params {0} ? OR error {0} ?)", D::LIKE),
+                [like(&filter.keyword), like(&filter.keyword)],
+            );
+        }
+    }
+    conditions
+}
+
+fn archived_conditions<D: Dialect>(query: &ArchivedLogQuery) -> Conditions {
+    let mut conditions = Conditions::default();
+    if !query.archive.is_empty() {
+        conditions.push("archive = ?", [SqlValue::Text(query.archive.clone())]);
+    }
+    if !query.username.is_empty() {
+        conditions.push(
+            format!("username {} ?", D::LIKE),
+            [SqlValue::Text(format!("%{}%", query.username))],
+        );
+    }
+    conditions
+}
+
+macro_rules! operation_log_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+
+            // 在写事务中锁定链头, 各实例依次读取和延长哈希链; SQLite 的写事务已锁定整个数据库
+            async fn lock_chain(conn: &mut <$db as Database>::Connection) -> Result<(), sqlx::Error> {
+                if !<$db>::FOR_UPDATE.is_empty() {
+                    let sql = format!("SELECT id FROM operation_log_chain_head{}", <$db>::FOR_UPDATE);
+                    sqlx::query(&sql).execute(&mut *conn).await?;
+                }
+                Ok(())
+            }
+
+            // 链尾日志的哈希, 没有日志或最后一条未封存时为最近一次清理的检查点哈希或起点哈希.
+            // 使用锁定读取, MySQL 的可重复读事务中也能读到其他事务刚提交的日志
+            async fn chain_tip(conn: &mut <$db as Database>::Connection) -> Result<String, sqlx::Error> {
+                let sql = format!(
+                    "SELECT hash FROM operation_logs ORDER BY id DESC LIMIT 1{}",
+                    <$db>::FOR_UPDATE
+                );
+                let hash = sqlx::query_scalar::<_, Option<String>>(&sql)
+                    .fetch_optional(&mut *conn)
+                    .await?
+                    .flatten();
+                if let Some(hash) = hash {
+                    return Ok(hash);
+                }
+                let sql = format!(
+                    "SELECT last_hash FROM operation_log_checkpoints ORDER BY id DESC LIMIT 1{}",
+                    <$db>::FOR_UPDATE
+                );
+                let anchor = sqlx::query_scalar::<_, String>(&sql)
+                    .fetch_optional(&mut *conn)
+                    .await?;
+                Ok(anchor.unwrap_or_else(|| GENESIS_HASH.to_string()))
+            }
+        }
+
+        #[async_trait]
+        impl OperationLogRepository for $name {
+            async fn count(&self, filter: &OperationLogFilter) -> Result<i64, sqlx::Error> {
+                let conditions = conditions::<$db>(filter);
+                let sql = format!("SELECT COUNT(*) FROM operation_logs{}", conditions.where_clause());
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list(
+                &self,
+                filter: &OperationLogFilter,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<OperationLog>, sqlx::Error> {
+                let conditions = conditions::<$db>(filter);
+                let sql = format!(
+                    "SELECT * FROM operation_logs{}{} LIMIT ? OFFSET ?",
+                    conditions.where_clause(),
+                    filter.order_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, OperationLog>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            fn export(
+                &self,
+                filter: &OperationLogFilter,
+            ) -> BoxStream<'static, Result<OperationLog, sqlx::Error>> {
+                let pool = self.pool.clone();
+                let conditions = conditions::<$db>(filter);
+                let sql = format!(
+                    "SELECT * FROM operation_logs{}{}",
+                    conditions.where_clause(),
+                    filter.order_clause()
+                );
+                stream_rows(move |tx| async move {
+                    let sql = <$db>::sql(&sql);
+                    let mut export_query = sqlx::query_as::<_, OperationLog>(&sql);
+                    for param in conditions.params {
+                        export_query = export_query.bind(param);
+                    }
+                    let mut rows = export_query.fetch(&pool);
+                    while let Some(row) = rows.next().await {
+                        if tx.send(row).await.is_err() {
+                            break;
+                        }
+                    }
+                })
+            }
+
+            async fn find(&self, id: i64) -> Result<Option<OperationLog>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLog>(&<$db>::sql(
+                    "SELECT * FROM operation_logs WHERE id = ?",
+                ))
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn changes(&self, log_id: i64) -> Result<Vec<OperationLogChange>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLogChange>(&<$db>::sql(
+                    "SELECT * FROM operation_log_changes WHERE log_id = ? ORDER BY id",
+                ))
+                .bind(log_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn insert(
+                &self,
+                logs: &[NewOperationLog<'_>],
+                hasher: LogHasher,
+            ) -> Result<Vec<i64>, sqlx::Error> {
+                let mut log_ids = Vec::with_capacity(logs.len());
+                let mut tx = <$db>::begin_write(&self.pool).await?;
+                Self::lock_chain(&mut *tx).await?;
+                let mut prev_hash = Self::chain_tip(&mut *tx).await?;
+                let log_sql = <$db>::sql(
+                    r#"
+                    INSERT INTO operation_logs (
+                        user_id, username, module, operation, method, path,
+                        params, ip, user_agent, request_id, status, error,
+                        response, duration_ms, created_at, prev_hash, hash, ip_key
+                    )
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let change_sql = <$db>::sql(
+                    r#"
+                    INSERT INTO operation_log_changes (log_id, entity, entity_id, action, "before", "after", diff)
+                    VALUES (?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+
+                for new_log in logs {
+                    let NewOperationLog { log, created_at } = new_log;
+                    let hash = hasher(new_log, &prev_hash);
+                    let query = sqlx::query(&log_sql)
+                        .bind(log.user_id)
+                        .bind(&log.username)
+                        .bind(&log.module)
+                        .bind(&log.operation)
+                        .bind(&log.method)
+                        .bind(&log.path)
+                        .bind(&log.params)
+                        .bind(&log.ip)
+                        .bind(&log.user_agent)
+                        .bind(&log.request_id)
+                        .bind(log.status)
+                        .bind(&log.error)
+                        .bind(&log.response)
+                        .bind(log.duration_ms)
+                        .bind(DbTime::from(*created_at))
+                        .bind(&prev_hash)
+                        .bind(&hash)
+                        .bind(log.ip.as_deref().and_then(ip_key));
+                    let log_id = <$db>::insert(&mut *tx, query).await?;
+
+                    for change in &log.changes {
+                        sqlx::query(&change_sql)
+                            .bind(log_id)
+                            .bind(&change.entity)
+                            .bind(change.entity_id)
+                            .bind(&change.action)
+                            .bind(&change.before)
+                            .bind(&change.after)
+                            .bind(&change.diff)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    log_ids.push(log_id);
+                    prev_hash = hash;
+                }
+
+                tx.commit().await?;
+                Ok(log_ids)
+            }
+
+            async fn between(
+                &self,
+                first_id: i64,
+                last_id: i64,
+            ) -> Result<Vec<OperationLog>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLog>(&<$db>::sql(
+                    "SELECT * FROM operation_logs WHERE id BETWEEN ? AND ? ORDER BY id",
+                ))
+                .bind(first_id)
+                .bind(last_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn after(
+                &self,
+                after_id: i64,
+                limit: i64,
+            ) -> Result<Vec<OperationLog>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLog>(&<$db>::sql(
+                    "SELECT * FROM operation_logs WHERE id > ? ORDER BY id LIMIT ?",
+                ))
+                .bind(after_id)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn with_params_after(
+                &self,
+                after_id: i64,
+                limit: i64,
+            ) -> Result<Vec<OperationLog>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLog>(&<$db>::sql(
+                    r#"
+                    SELECT * FROM operation_logs
+                    WHERE id > ? AND params IS NOT NULL
+                    ORDER BY id
+                    LIMIT ?
+                    "#,
+                ))
+                .bind(after_id)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn last_hash(
+                &self,
+                before_id: Option<i64>,
+            ) -> Result<Option<Option<String>>, sqlx::Error> {
+                let mut conditions = Conditions::default();
+                if let Some(before_id) = before_id {
+                    conditions.push("id < ?", [SqlValue::Int(before_id)]);
+                }
+                let sql = format!(
+                    "SELECT hash FROM operation_logs{} ORDER BY id DESC LIMIT 1",
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut hash_query = sqlx::query_scalar::<_, Option<String>>(&sql);
+                for param in conditions.params {
+                    hash_query = hash_query.bind(param);
+                }
+                hash_query.fetch_optional(&self.pool).await
+            }
+
+            async fn first_id(&self, sealed: bool) -> Result<Option<i64>, sqlx::Error> {
+                let sql = if sealed {
+                    "SELECT MIN(id) FROM operation_logs WHERE hash IS NOT NULL"
+                } else {
+                    "SELECT MIN(id) FROM operation_logs WHERE hash IS NULL"
+                };
+                sqlx::query_scalar::<_, Option<i64>>(sql)
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn set_hashes(&self, hashes: &[(i64, String, String)]) -> Result<(), sqlx::Error> {
+                let sql = <$db>::sql("UPDATE operation_logs SET prev_hash = ?, hash = ? WHERE id = ?");
+                let mut tx = self.pool.begin().await?;
+                for (id, prev_hash, hash) in hashes {
+                    sqlx::query(&sql)
+                        .bind(prev_hash)
+                        .bind(hash)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                tx.commit().await
+            }
+
+            async fn missing_ip_keys(&self) -> Result<Vec<(i64, String)>, sqlx::Error> {
+                sqlx::query_as::<_, (i64, String)>(
+                    "SELECT id, ip FROM operation_logs WHERE ip_key IS NULL AND ip IS NOT NULL",
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn set_ip_keys(&self, keys: &[(i64, String)]) -> Result<(), sqlx::Error> {
+                let sql = <$db>::sql("UPDATE operation_logs SET ip_key = ? WHERE id = ?");
+                let mut tx = self.pool.begin().await?;
+                for (id, key) in keys {
+                    sqlx::query(&sql)
+                        .bind(key)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                tx.commit().await
+            }
+
+            async fn max_id_before(&self, before: &str) -> Result<Option<i64>, sqlx::Error> {
+                sqlx::query_scalar::<_, Option<i64>>(&<$db>::sql(
+                    "SELECT MAX(id) FROM operation_logs WHERE created_at < ?",
+                ))
+                .bind(DbTime::from(before))
+                .fetch_one(&self.pool)
+                .await
+            }
+
+            async fn id_at_offset(&self, offset: i64) -> Result<Option<i64>, sqlx::Error> {
+                sqlx::query_scalar::<_, i64>(&<$db>::sql(
+                    "SELECT id FROM operation_logs ORDER BY id DESC LIMIT 1 OFFSET ?",
+                ))
+                .bind(offset)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn archive_batch(
+                &self,
+                up_to_id: i64,
+                limit: i64,
+            ) -> Result<Vec<ArchiveEntry>, sqlx::Error> {
+                let logs = sqlx::query_as::<_, OperationLog>(&<$db>::sql(
+                    "SELECT * FROM operation_logs WHERE id <= ? ORDER BY id LIMIT ?",
+                ))
+                .bind(up_to_id)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+                let Some(last_id) = logs.last().map(|log| log.id) else {
+                    return Ok(Vec::new());
+                };
+
+                let changes = sqlx::query_as::<_, OperationLogChange>(&<$db>::sql(
+                    "SELECT * FROM operation_log_changes WHERE log_id <= ? ORDER BY id",
+                ))
+                .bind(last_id)
+                .fetch_all(&self.pool)
+                .await?;
+                let mut changes_by_log: HashMap<i64, Vec<OperationLogChange>> = HashMap::new();
+                for change in changes {
+                    changes_by_log.entry(change.log_id).or_default().push(change);
+                }
+                Ok(logs
+                    .into_iter()
+                    .map(|log| {
+                        let changes = changes_by_log.remove(&log.id).unwrap_or_default();
+                        ArchiveEntry { log, changes }
+                    })
+                    .collect())
+            }
+
+            async fn purge(&self, checkpoint: &NewCheckpoint<'_>) -> Result<(i64, i64), sqlx::Error> {
+                let mut tx = <$db>::begin_write(&self.pool).await?;
+                Self::lock_chain(&mut *tx).await?;
+                sqlx::query(&<$db>::sql("DELETE FROM operation_log_changes WHERE log_id <= ?"))
+                    .bind(checkpoint.last_log_id)
+                    .execute(&mut *tx)
+                    .await?;
+                let purged = sqlx::query(&<$db>::sql("DELETE FROM operation_logs WHERE id <= ?"))
+                    .bind(checkpoint.last_log_id)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected() as i64;
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO operation_log_checkpoints (last_log_id, last_hash, purged_count, cutoff, reason, created_by, archive)
+                    VALUES (?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(checkpoint.last_log_id)
+                    .bind(checkpoint.last_hash)
+                    .bind(purged)
+                    .bind(checkpoint.cutoff)
+                    .bind(checkpoint.reason)
+                    .bind(checkpoint.created_by)
+                    .bind(checkpoint.archive);
+                let checkpoint_id = <$db>::insert(&mut *tx, query).await?;
+                tx.commit().await?;
+                Ok((purged, checkpoint_id))
+            }
+
+            async fn checkpoints(&self) -> Result<Vec<OperationLogCheckpoint>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLogCheckpoint>(
+                    "SELECT * FROM operation_log_checkpoints ORDER BY id DESC",
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn last_checkpoint_hash(&self) -> Result<Option<String>, sqlx::Error> {
+                sqlx::query_scalar::<_, String>(
+                    "SELECT last_hash FROM operation_log_checkpoints ORDER BY id DESC LIMIT 1",
+                )
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn redactions(&self) -> Result<Vec<OperationLogRedaction>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLogRedaction>(
+                    "SELECT * FROM operation_log_redactions ORDER BY id DESC",
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn redacted_rows(&self, redaction_id: i64) -> Result<Vec<RedactedRow>, sqlx::Error> {
+                sqlx::query_as::<_, RedactedRow>(&<$db>::sql(
+                    r#"
+                    SELECT log_id, original_hash, redacted_hash FROM operation_log_redacted_rows
+                    WHERE redaction_id = ? ORDER BY log_id
+                    "#,
+                ))
+                .bind(redaction_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn redact(
+                &self,
+                params: &[(i64, Option<String>)],
+                redaction: Option<&NewRedaction<'_>>,
+                hasher: RedactionHasher,
+            ) -> Result<Option<i64>, sqlx::Error> {
+                let mut tx = <$db>::begin_write(&self.pool).await?;
+                let sql = <$db>::sql("UPDATE operation_logs SET params = ? WHERE id = ?");
+                for (id, params) in params {
+                    sqlx::query(&sql)
+                        .bind(params)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+
+                let Some(redaction) = redaction else {
+                    tx.commit().await?;
+                    return Ok(None);
+                };
+                Self::lock_chain(&mut *tx).await?;
+                let sql = format!(
+                    "SELECT hash FROM operation_log_redactions ORDER BY id DESC LIMIT 1{}",
+                    <$db>::FOR_UPDATE
+                );
+                let prev_hash = sqlx::query_scalar::<_, String>(&sql)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .unwrap_or_else(|| GENESIS_HASH.to_string());
+                let hash = hasher(redaction, &prev_hash);
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO operation_log_redactions (reason, redacted_count, created_at, prev_hash, hash)
+                    VALUES (?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(redaction.reason)
+                    .bind(redaction.rows.len() as i64)
+                    .bind(DbTime::from(redaction.created_at))
+                    .bind(&prev_hash)
+                    .bind(&hash);
+                let redaction_id = <$db>::insert(&mut *tx, query).await?;
+
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO operation_log_redacted_rows (redaction_id, log_id, original_hash, redacted_hash)
+                    VALUES (?, ?, ?, ?)
+                    "#,
+                );
+                for row in redaction.rows {
+                    sqlx::query(&sql)
+                        .bind(redaction_id)
+                        .bind(row.log_id)
+                        .bind(&row.original_hash)
+                        .bind(&row.redacted_hash)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                tx.commit().await?;
+                Ok(Some(redaction_id))
+            }
+
+            async fn import_archive(
+                &self,
+                name: &str,
+                entries: &[ArchiveEntry],
+            ) -> Result<u64, sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query(&<$db>::sql("DELETE FROM archived_operation_logs WHERE archive = ?"))
+                    .bind(name)
+                    .execute(&mut *tx)
+                    .await?;
+
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO archived_operation_logs (
+                        archive, original_id, user_id, username, module, operation, method, path,
+                        params, ip, user_agent, request_id, status, error, response, duration_ms,
+                        created_at, prev_hash, hash, changes
+                    )
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                for entry in entries {
+                    let log = &entry.log;
+                    sqlx::query(&sql)
+                        .bind(name)
+                        .bind(log.id)
+                        .bind(log.user_id)
+                        .bind(&log.username)
+                        .bind(&log.module)
+                        .bind(&log.operation)
+                        .bind(&log.method)
+                        .bind(&log.path)
+                        .bind(&log.params)
+                        .bind(&log.ip)
+                        .bind(&log.user_agent)
+                        .bind(&log.request_id)
+                        .bind(log.status)
+                        .bind(&log.error)
+                        .bind(&log.response)
+                        .bind(log.duration_ms)
+                        .bind(DbTime::from(&log.created_at))
+                        .bind(&log.prev_hash)
+                        .bind(&log.hash)
+                        .bind(serde_json::to_string(&entry.changes).unwrap_or_default())
+                        .execute(&mut *tx)
+                        .await?;
+                }
+
+                tx.commit().await?;
+                Ok(entries.len() as u64)
+            }
+
+            async fn archived_counts(&self) -> Result<Vec<(String, i64)>, sqlx::Error> {
+                sqlx::query_as::<_, (String, i64)>(
+                    "SELECT archive, COUNT(*) FROM archived_operation_logs GROUP BY archive",
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn count_archived(&self, query: &ArchivedLogQuery) -> Result<i64, sqlx::Error> {
+                let conditions = archived_conditions::<$db>(query);
+                let sql = format!(
+                    "SELECT COUNT(*) FROM archived_operation_logs{}",
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list_archived(
+                &self,
+                query: &ArchivedLogQuery,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<ArchivedOperationLog>, sqlx::Error> {
+                let conditions = archived_conditions::<$db>(query);
+                let sql = format!(
+                    "SELECT * FROM archived_operation_logs{} ORDER BY original_id DESC LIMIT ? OFFSET ?",
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, ArchivedOperationLog>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn delete_archived(&self, archive: &str) -> Result<u64, sqlx::Error> {
+                let result = if archive.is_empty() {
+                    sqlx::query("DELETE FROM archived_operation_logs")
+                        .execute(&self.pool)
+                        .await?
+                } else {
+                    sqlx::query(&<$db>::sql("DELETE FROM archived_operation_logs WHERE archive = ?"))
+                        .bind(archive)
+                        .execute(&self.pool)
+                        .await?
+                };
+                Ok(result.rows_affected())
+            }
+        }
+    };
+}
+
+operation_log_repository!(SqliteOperationLogRepository, Sqlite);
+operation_log_repository!(PostgresOperationLogRepository, Postgres);
+operation_log_repository!(MySqlOperationLogRepository, MySql);
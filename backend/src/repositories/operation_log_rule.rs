@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::Dialect;
+use crate::models::{OperationLogRule, SaveOperationLogRule};
+
+#[async_trait]
+pub trait OperationLogRuleRepository: Send + Sync {
+    // 按优先级从高到低
+    async fn list(&self) -> Result<Vec<OperationLogRule>, sqlx::Error>;
+
+    async fn enabled(&self) -> Result<Vec<OperationLogRule>, sqlx::Error>;
+
+    async fn find(&self, id: i64) -> Result<Option<OperationLogRule>, sqlx::Error>;
+
+    async fn create(&self, rule: &SaveOperationLogRule) -> Result<OperationLogRule, sqlx::Error>;
+
+    async fn update(
+        &self,
+        id: i64,
+        rule: &SaveOperationLogRule,
+    ) -> Result<OperationLogRule, sqlx::Error>;
+
+    async fn delete(&self, id: i64) -> Result<(), sqlx::Error>;
+}
+
+macro_rules! operation_log_rule_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl OperationLogRuleRepository for $name {
+            async fn list(&self) -> Result<Vec<OperationLogRule>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLogRule>(
+                    "SELECT * FROM operation_log_rules ORDER BY priority DESC, id",
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn enabled(&self) -> Result<Vec<OperationLogRule>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLogRule>(
+                    "SELECT * FROM operation_log_rules WHERE enabled = TRUE ORDER BY priority DESC, id",
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn find(&self, id: i64) -> Result<Option<OperationLogRule>, sqlx::Error> {
+                sqlx::query_as::<_, OperationLogRule>(&<$db>::sql(
+                    "SELECT * FROM operation_log_rules WHERE id = ?",
+                ))
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn create(
+                &self,
+                rule: &SaveOperationLogRule,
+            ) -> Result<OperationLogRule, sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO operation_log_rules (method, path, action, priority, enabled, description)
+                    VALUES (?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(&rule.method)
+                    .bind(&rule.path)
+                    .bind(&rule.action)
+                    .bind(rule.priority)
+                    .bind(rule.enabled)
+                    .bind(&rule.description);
+                let id = <$db>::insert(&mut conn, query).await?;
+
+                sqlx::query_as::<_, OperationLogRule>(&<$db>::sql(
+                    "SELECT * FROM operation_log_rules WHERE id = ?",
+                ))
+                .bind(id)
+                .fetch_one(&mut *conn)
+                .await
+            }
+
+            async fn update(
+                &self,
+                id: i64,
+                rule: &SaveOperationLogRule,
+            ) -> Result<OperationLogRule, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    r#"
+                    UPDATE operation_log_rules
+                    SET method = ?, path = ?, action = ?, priority = ?, enabled = ?, description = ?,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ?
+                    "#,
+                ))
+                .bind(&rule.method)
+                .bind(&rule.path)
+                .bind(&rule.action)
+                .bind(rule.priority)
+                .bind(rule.enabled)
+                .bind(&rule.description)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+                self.find(id).await?.ok_or(sqlx::Error::RowNotFound)
+            }
+
+            async fn delete(&self, id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql("DELETE FROM operation_log_rules WHERE id = ?"))
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+        }
+    };
+}
+
+operation_log_rule_repository!(SqliteOperationLogRuleRepository, Sqlite);
+operation_log_rule_repository!(PostgresOperationLogRuleRepository, Postgres);
+operation_log_rule_repository!(MySqlOperationLogRuleRepository, MySql);
@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::{Conditions, Dialect, SqlValue};
+use crate::models::{CreatePermission, Permission, UpdatePermission};
+
+// 权限列表的筛选条件, 为空时不筛选; 名称和编码按包含匹配
+#[derive(Debug, Default)]
+pub struct PermissionQuery {
+    pub name: String,
+    pub code: String,
+}
+
+// 接口权限及其上级权限的名称, 用于操作日志的模块名和操作名
+#[derive(Debug, sqlx::FromRow)]
+pub struct ApiPermission {
+    pub action: String,
+    pub resource: String,
+    pub name: String,
+    pub code: String,
+    pub parent_name: Option<String>,
+}
+
+#[async_trait]
+pub trait PermissionRepository: Send + Sync {
+    async fn count(&self, query: &PermissionQuery) -> Result<i64, sqlx::Error>;
+
+    async fn list(
+        &self,
+        query: &PermissionQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Permission>, sqlx::Error>;
+
+    // 全部权限, 按排序号排列
+    async fn list_all(&self) -> Result<Vec<Permission>, sqlx::Error>;
+
+    async fn find(&self, id: i64) -> Result<Option<Permission>, sqlx::Error>;
+
+    async fn create(&self, permission: &CreatePermission) -> Result<Permission, sqlx::Error>;
+
+    async fn update(
+        &self,
+        id: i64,
+        permission: &UpdatePermission,
+    ) -> Result<Permission, sqlx::Error>;
+
+    // 直接下级权限, 删除前检查, 上级外键没有级联删除
+    async fn children(&self, id: i64) -> Result<Vec<Permission>, sqlx::Error>;
+
+    async fn delete(&self, id: i64) -> Result<(), sqlx::Error>;
+
+    async fn for_role(&self, role_id: i64) -> Result<Vec<Permission>, sqlx::Error>;
+
+    // 用户是否通过已启用的角色拥有指定的权限编码
+    async fn has_permission(&self, user_id: i64, code: &str) -> Result<bool, sqlx::Error>;
+
+    async fn api_permissions(&self) -> Result<Vec<ApiPermission>, sqlx::Error>;
+}
+
+fn conditions<D: Dialect>(query: &PermissionQuery) -> Conditions {
+    let mut conditions = Conditions::default();
+    if !query.name.is_empty() {
+        conditions.push(
+            format!("name {} ?", D::LIKE),
+            [SqlValue::Text(format!("%{}%", query.name))],
+        );
+    }
+    if !query.code.is_empty() {
+        conditions.push(
+            format!("code {} ?", D::LIKE),
+            [SqlValue::Text(format!("%{}%", query.code))],
+        );
+    }
+    conditions
+}
+
+macro_rules! permission_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl PermissionRepository for $name {
+            async fn count(&self, query: &PermissionQuery) -> Result<i64, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!("SELECT COUNT(*) FROM permissions{}", conditions.where_clause());
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list(
+                &self,
+                query: &PermissionQuery,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<Permission>, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!(
+                    "SELECT * FROM permissions{} ORDER BY id LIMIT ? OFFSET ?",
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, Permission>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn list_all(&self) -> Result<Vec<Permission>, sqlx::Error> {
+                sqlx::query_as::<_, Permission>("SELECT * FROM permissions ORDER BY sort, id")
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn find(&self, id: i64) -> Result<Option<Permission>, sqlx::Error> {
+                sqlx::query_as::<_, Permission>(&<$db>::sql("SELECT * FROM permissions WHERE id = ?"))
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn create(&self, permission: &CreatePermission) -> Result<Permission, sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(&permission.name)
+                    .bind(&permission.code)
+                    .bind(&permission.type_name)
+                    .bind(&permission.resource)
+                    .bind(&permission.action)
+                    .bind(permission.parent_id)
+                    .bind(permission.sort)
+                    .bind(&permission.description)
+                    .bind(&permission.color_start)
+                    .bind(&permission.color_end);
+                let id = <$db>::insert(&mut conn, query).await?;
+
+                sqlx::query_as::<_, Permission>(&<$db>::sql("SELECT * FROM permissions WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&mut *conn)
+                    .await
+            }
+
+            async fn update(
+                &self,
+                id: i64,
+                permission: &UpdatePermission,
+            ) -> Result<Permission, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    r#"
+                    UPDATE permissions
+                    SET name = ?, code = ?, type_name = ?, resource = ?, action = ?,
+                        parent_id = ?, sort = ?, description = ?, color_start = ?, color_end = ?,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ?
+                    "#,
+                ))
+                .bind(&permission.name)
+                .bind(&permission.code)
+                .bind(&permission.type_name)
+                .bind(&permission.resource)
+                .bind(&permission.action)
+                .bind(permission.parent_id)
+                .bind(permission.sort)
+                .bind(&permission.description)
+                .bind(&permission.color_start)
+                .bind(&permission.color_end)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+                sqlx::query_as::<_, Permission>(&<$db>::sql("SELECT * FROM permissions WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn children(&self, id: i64) -> Result<Vec<Permission>, sqlx::Error> {
+                sqlx::query_as::<_, Permission>(&<$db>::sql(
+                    "SELECT * FROM permissions WHERE parent_id = ? ORDER BY sort, id",
+                ))
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn delete(&self, id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql("DELETE FROM permissions WHERE id = ?"))
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn for_role(&self, role_id: i64) -> Result<Vec<Permission>, sqlx::Error> {
+                sqlx::query_as::<_, Permission>(&<$db>::sql(
+                    r#"
+                    SELECT p.* FROM permissions p
+                    INNER JOIN role_permissions rp ON rp.permission_id = p.id
+                    WHERE rp.role_id = ?
+                    "#,
+                ))
+                .bind(role_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn has_permission(&self, user_id: i64, code: &str) -> Result<bool, sqlx::Error> {
+                let count = sqlx::query_scalar::<_, i64>(&<$db>::sql(
+                    r#"
+                    SELECT COUNT(*)
+                    FROM user_roles ur
+                    JOIN roles r ON r.id = ur.role_id
+                    JOIN role_permissions rp ON rp.role_id = ur.role_id
+                    JOIN permissions p ON p.id = rp.permission_id
+                    WHERE ur.user_id = ? AND r.status = 1 AND p.code = ?
+                    "#,
+                ))
+                .bind(user_id)
+                .bind(code)
+                .fetch_one(&self.pool)
+                .await?;
+                Ok(count > 0)
+            }
+
+            async fn api_permissions(&self) -> Result<Vec<ApiPermission>, sqlx::Error> {
+                sqlx::query_as::<_, ApiPermission>(
+                    r#"
+                    SELECT p.action, p.resource, p.name, p.code, parent.name AS parent_name
+                    FROM permissions p
+                    LEFT JOIN permissions parent ON parent.id = p.parent_id
+                    WHERE p.type_name = 'API' AND p.resource IS NOT NULL AND p.action IS NOT NULL
+                    "#,
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+        }
+    };
+}
+
+permission_repository!(SqlitePermissionRepository, Sqlite);
+permission_repository!(PostgresPermissionRepository, Postgres);
+permission_repository!(MySqlPermissionRepository, MySql);
@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::{Conditions, Dialect, SqlValue};
+use crate::models::{CreateRole, Role};
+
+// 角色列表的筛选条件, 为空时不筛选; 名称和编码按包含匹配
+#[derive(Debug, Default)]
+pub struct RoleQuery {
+    pub name: String,
+    pub code: String,
+}
+
+#[async_trait]
+pub trait RoleRepository: Send + Sync {
+    async fn count(&self, query: &RoleQuery) -> Result<i64, sqlx::Error>;
+
+    async fn list(
+        &self,
+        query: &RoleQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Role>, sqlx::Error>;
+
+    async fn find(&self, id: i64) -> Result<Option<Role>, sqlx::Error>;
+
+    async fn create(&self, role: &CreateRole) -> Result<Role, sqlx::Error>;
+
+    async fn update(&self, id: i64, role: &CreateRole) -> Result<Role, sqlx::Error>;
+
+    async fn delete(&self, id: i64) -> Result<(), sqlx::Error>;
+
+    // 角色已分配的权限ID
+    async fn permission_ids(&self, id: i64) -> Result<Vec<i64>, sqlx::Error>;
+
+    // 用户拥有的角色
+    async fn for_user(&self, user_id: i64) -> Result<Vec<Role>, sqlx::Error>;
+
+    // 角色不存在时返回 None
+    async fn set_storage_quota(
+        &self,
+        id: i64,
+        quota: Option<i64>,
+    ) -> Result<Option<Role>, sqlx::Error>;
+}
+
+fn conditions<D: Dialect>(query: &RoleQuery) -> Conditions {
+    let mut conditions = Conditions::default();
+    if !query.name.is_empty() {
+        conditions.push(
+            format!("name {} ?", D::LIKE),
+            [SqlValue::Text(format!("%{}%", query.name))],
+        );
+    }
+    if !query.code.is_empty() {
+        conditions.push(
+            format!("code {} ?", D::LIKE),
+            [SqlValue::Text(format!("%{}%", query.code))],
+        );
+    }
+    conditions
+}
+
+macro_rules! role_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl RoleRepository for $name {
+            async fn count(&self, query: &RoleQuery) -> Result<i64, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!("SELECT COUNT(*) FROM roles{}", conditions.where_clause());
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list(
+                &self,
+                query: &RoleQuery,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<Role>, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!(
+                    "SELECT * FROM roles{} ORDER BY id LIMIT ? OFFSET ?",
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, Role>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn find(&self, id: i64) -> Result<Option<Role>, sqlx::Error> {
+                sqlx::query_as::<_, Role>(&<$db>::sql("SELECT * FROM roles WHERE id = ?"))
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn create(&self, role: &CreateRole) -> Result<Role, sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                let sql = <$db>::sql("INSERT INTO roles (name, code, description) VALUES (?, ?, ?)");
+                let query = sqlx::query(&sql)
+                    .bind(&role.name)
+                    .bind(&role.code)
+                    .bind(&role.description);
+                let id = <$db>::insert(&mut conn, query).await?;
+
+                sqlx::query_as::<_, Role>(&<$db>::sql("SELECT * FROM roles WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&mut *conn)
+                    .await
+            }
+
+            async fn update(&self, id: i64, role: &CreateRole) -> Result<Role, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    r#"
+                    UPDATE roles
+                    SET name = ?, code = ?, description = ?,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ?
+                    "#,
+                ))
+                .bind(&role.name)
+                .bind(&role.code)
+                .bind(&role.description)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+                sqlx::query_as::<_, Role>(&<$db>::sql("SELECT * FROM roles WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn delete(&self, id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql("DELETE FROM roles WHERE id = ?"))
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn permission_ids(&self, id: i64) -> Result<Vec<i64>, sqlx::Error> {
+                let sql = format!(
+                    "SELECT {} FROM role_permissions WHERE role_id = ?",
+                    <$db>::integer("permission_id")
+                );
+                sqlx::query_scalar::<_, i64>(&<$db>::sql(&sql))
+                    .bind(id)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn for_user(&self, user_id: i64) -> Result<Vec<Role>, sqlx::Error> {
+                sqlx::query_as::<_, Role>(&<$db>::sql(
+                    r#"
+                    SELECT r.* FROM roles r
+                    INNER JOIN user_roles ur ON ur.role_id = r.id
+                    WHERE ur.user_id = ?
+                    "#,
+                ))
+                .bind(user_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn set_storage_quota(
+                &self,
+                id: i64,
+                quota: Option<i64>,
+            ) -> Result<Option<Role>, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    "UPDATE roles SET storage_quota = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                ))
+                .bind(quota)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+                self.find(id).await
+            }
+        }
+    };
+}
+
+role_repository!(SqliteRoleRepository, Sqlite);
+role_repository!(PostgresRoleRepository, Postgres);
+role_repository!(MySqlRoleRepository, MySql);
@@ -0,0 +1,659 @@
+use async_trait::async_trait;
+use sqlx::{Database, Executor, MySql, Pool, Postgres, Sqlite};
+use std::time::Instant;
+
+use super::dialect::Dialect;
+use crate::config::database::DatabaseKind;
+use crate::config::migrations::{AppliedMigration, Migration};
+use crate::config::seed::{SeedError, SeedFile, SeedMenu, SeedPermission, SeedRole, SeedUser};
+use crate::utils::password::hash_password;
+
+// 违反外键约束的数据, 例如没有启用外键约束时删除用户后残留的用户角色关联
+#[derive(Debug, sqlx::FromRow)]
+pub struct OrphanedRows {
+    pub table_name: String,
+    pub parent: String,
+    pub count: i64,
+}
+
+// 表结构迁移、初始数据和数据库检查.
+// PostgreSQL 和 MySQL 始终检查外键约束, 不会产生孤立数据, 完整性检查也只适用于 SQLite 的数据库文件
+#[async_trait]
+pub trait SchemaRepository: Send + Sync {
+    fn kind(&self) -> DatabaseKind;
+
+    // 没有迁移记录表时先创建
+    async fn applied_migrations(&self) -> Result<Vec<AppliedMigration>, sqlx::Error>;
+
+    // 引入迁移之前创建的数据库: 已经有表但没有迁移记录
+    async fn is_legacy(&self) -> Result<bool, sqlx::Error>;
+
+    // 在一个事务中执行迁移并记录, 返回耗时(毫秒). MySQL 的 DDL 会隐式提交, 失败时可能只执行了一部分.
+    // upgrade_legacy 为 true 时先补齐旧版本缺少的字段
+    async fn apply_migration(
+        &self,
+        migration: &Migration,
+        upgrade_legacy: bool,
+    ) -> Result<i64, sqlx::Error>;
+
+    // 已写入的初始数据的最大版本号, 没有时为 0
+    async fn seed_version(&self) -> Result<i64, sqlx::Error>;
+
+    // 在一个事务中写入全部初始数据, 任何一项出错都不会写入
+    async fn apply_seed(&self, seed: &SeedFile, checksum: &str) -> Result<(), SeedError>;
+
+    // 数据库文件的完整性检查结果, 不支持时返回 None
+    async fn integrity_check(&self) -> Result<Option<Vec<String>>, sqlx::Error>;
+
+    async fn orphaned_rows(&self) -> Result<Vec<OrphanedRows>, sqlx::Error>;
+
+    // 一组孤立数据的行号, 最多 limit 个
+    async fn orphaned_rowids(
+        &self,
+        orphan: &OrphanedRows,
+        limit: i64,
+    ) -> Result<Vec<i64>, sqlx::Error>;
+
+    // 清理违反外键约束的数据, 最多重复 rounds 次, 在一个事务中执行
+    async fn fix_orphaned_rows(&self, rounds: usize) -> Result<(), sqlx::Error>;
+}
+
+fn migrations_table<D: Dialect>() -> &'static str {
+    match D::KIND {
+        DatabaseKind::Sqlite => {
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                execution_ms INTEGER NOT NULL
+            )
+            "#
+        }
+        DatabaseKind::Postgres => {
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                execution_ms BIGINT NOT NULL
+            )
+            "#
+        }
+        DatabaseKind::MySql => {
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version BIGINT NOT NULL PRIMARY KEY,
+                name VARCHAR(255) NOT NULL,
+                checksum CHAR(64) NOT NULL,
+                applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                execution_ms BIGINT NOT NULL
+            ) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4
+            "#
+        }
+    }
+}
+
+// 旧版本启动时通过 CREATE TABLE IF NOT EXISTS 建表并逐个补充字段, 只有 SQLite 有这样的数据库
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("users", "storage_quota", "INTEGER"),
+    ("roles", "storage_quota", "INTEGER"),
+    ("operation_logs", "path", "TEXT"),
+    ("operation_logs", "user_agent", "TEXT"),
+    ("operation_logs", "request_id", "TEXT"),
+    ("operation_logs", "response", "TEXT"),
+    ("operation_logs", "duration_ms", "INTEGER"),
+    ("operation_logs", "prev_hash", "TEXT"),
+    ("operation_logs", "hash", "TEXT"),
+    ("operation_logs", "ip_num", "INTEGER"),
+    ("operation_log_checkpoints", "archive", "TEXT"),
+    ("files", "visibility", "TEXT NOT NULL DEFAULT 'private'"),
+    ("files", "blob_key", "TEXT"),
+    ("file_variants", "blob_key", "TEXT"),
+    (
+        "upload_sessions",
+        "visibility",
+        "TEXT NOT NULL DEFAULT 'private'",
+    ),
+];
+
+macro_rules! schema_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+
+            async fn table_exists(
+                conn: &mut <$db as Database>::Connection,
+                table: &str,
+            ) -> Result<bool, sqlx::Error> {
+                let count = sqlx::query_scalar::<_, i64>(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?",
+                )
+                .bind(table)
+                .fetch_one(&mut *conn)
+                .await?;
+                Ok(count > 0)
+            }
+
+            // 补齐旧版本缺少的字段, 之后第一个迁移中的建表和索引语句才能在旧数据库上执行.
+            // 表不存在时跳过, 由迁移创建完整的表
+            async fn upgrade_legacy_schema(
+                conn: &mut <$db as Database>::Connection,
+            ) -> Result<(), sqlx::Error> {
+                for (table, column, definition) in LEGACY_COLUMNS {
+                    let column_exists = sqlx::query_scalar::<_, i64>(
+                        "SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?",
+                    )
+                    .bind(table)
+                    .bind(column)
+                    .fetch_one(&mut *conn)
+                    .await?;
+
+                    if Self::table_exists(conn, table).await? && column_exists == 0 {
+                        sqlx::query(&format!(
+                            "ALTER TABLE {} ADD COLUMN {} {}",
+                            table, column, definition
+                        ))
+                        .execute(&mut *conn)
+                        .await?;
+                        println!("Added column {}.{}", table, column);
+                    }
+                }
+
+                // 头像始终公开
+                if Self::table_exists(conn, "files").await? {
+                    sqlx::query("UPDATE files SET visibility = 'public' WHERE purpose = 'avatar'")
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                Ok(())
+            }
+
+            async fn find_id(
+                conn: &mut <$db as Database>::Connection,
+                table: &str,
+                column: &str,
+                key: &str,
+            ) -> Result<Option<i64>, sqlx::Error> {
+                let sql = format!(
+                    "SELECT {} FROM {} WHERE {} = ? ORDER BY id LIMIT 1",
+                    <$db>::integer("id"),
+                    table,
+                    column
+                );
+                sqlx::query_scalar::<_, i64>(&<$db>::sql(&sql))
+                    .bind(key)
+                    .fetch_optional(&mut *conn)
+                    .await
+            }
+
+            // 上级必须在文件中排在前面或已存在于数据库中
+            async fn parent_id(
+                conn: &mut <$db as Database>::Connection,
+                table: &str,
+                column: &str,
+                parent: Option<&str>,
+                kind: &str,
+            ) -> Result<Option<i64>, SeedError> {
+                let Some(parent) = parent else {
+                    return Ok(None);
+                };
+                match Self::find_id(conn, table, column, parent).await? {
+                    Some(id) => Ok(Some(id)),
+                    None => Err(SeedError::Invalid(format!(
+                        "上级{}不存在: {}",
+                        kind, parent
+                    ))),
+                }
+            }
+
+            // 权限和角色按编码更新, 但名称也是唯一的; 名称已被其他编码使用时直接报告, 而不是在写入时出现唯一约束错误
+            async fn check_name_conflict(
+                conn: &mut <$db as Database>::Connection,
+                table: &str,
+                kind: &str,
+                name: &str,
+                code: &str,
+            ) -> Result<(), SeedError> {
+                let sql = format!("SELECT code FROM {} WHERE name = ? AND code != ?", table);
+                let other = sqlx::query_scalar::<_, String>(&<$db>::sql(&sql))
+                    .bind(name)
+                    .bind(code)
+                    .fetch_optional(&mut *conn)
+                    .await?;
+                match other {
+                    Some(other) => Err(SeedError::Invalid(format!(
+                        "{}名称 {} 已被 {} 使用, 不能用于 {}",
+                        kind, name, other, code
+                    ))),
+                    None => Ok(()),
+                }
+            }
+
+            // 菜单没有编码, 按路由名称匹配
+            async fn upsert_menu(
+                conn: &mut <$db as Database>::Connection,
+                menu: &SeedMenu,
+            ) -> Result<(), SeedError> {
+                let parent_id =
+                    Self::parent_id(conn, "menus", "name", menu.parent.as_deref(), "菜单").await?;
+
+                match Self::find_id(conn, "menus", "name", &menu.name).await? {
+                    Some(id) => {
+                        sqlx::query(&<$db>::sql(
+                            r#"
+                            UPDATE menus
+                            SET parent_id = ?, path = ?, component = ?, title = ?, icon = ?, sort = ?, is_hidden = ?,
+                                updated_at = CURRENT_TIMESTAMP
+                            WHERE id = ?
+                            "#,
+                        ))
+                        .bind(parent_id)
+                        .bind(&menu.path)
+                        .bind(&menu.component)
+                        .bind(&menu.title)
+                        .bind(&menu.icon)
+                        .bind(menu.sort)
+                        .bind(menu.is_hidden)
+                        .bind(id)
+                        .execute(&mut *conn)
+                        .await?;
+                    }
+                    None => {
+                        sqlx::query(&<$db>::sql(
+                            r#"
+                            INSERT INTO menus (parent_id, name, path, component, title, icon, sort, is_hidden)
+                            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                            "#,
+                        ))
+                        .bind(parent_id)
+                        .bind(&menu.name)
+                        .bind(&menu.path)
+                        .bind(&menu.component)
+                        .bind(&menu.title)
+                        .bind(&menu.icon)
+                        .bind(menu.sort)
+                        .bind(menu.is_hidden)
+                        .execute(&mut *conn)
+                        .await?;
+                    }
+                }
+                Ok(())
+            }
+
+            async fn upsert_permission(
+                conn: &mut <$db as Database>::Connection,
+                permission: &SeedPermission,
+            ) -> Result<(), SeedError> {
+                let parent_id = Self::parent_id(
+                    conn,
+                    "permissions",
+                    "code",
+                    permission.parent.as_deref(),
+                    "权限",
+                )
+                .await?;
+                Self::check_name_conflict(
+                    conn,
+                    "permissions",
+                    "权限",
+                    &permission.name,
+                    &permission.code,
+                )
+                .await?;
+
+                let sql = format!(
+                    r#"
+                    INSERT INTO permissions (name, code, type_name, resource, action, parent_id, sort, description, color_start, color_end)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?){}
+                    "#,
+                    <$db>::upsert(
+                        &["code"],
+                        &[
+                            "name",
+                            "type_name",
+                            "resource",
+                            "action",
+                            "parent_id",
+                            "sort",
+                            "description",
+                            "color_start",
+                            "color_end",
+                            "updated_at",
+                        ],
+                    )
+                );
+                sqlx::query(&<$db>::sql(&sql))
+                    .bind(&permission.name)
+                    .bind(&permission.code)
+                    .bind(&permission.type_name)
+                    .bind(&permission.resource)
+                    .bind(&permission.action)
+                    .bind(parent_id.map(|id| id as i32))
+                    .bind(permission.sort as i32)
+                    .bind(&permission.description)
+                    .bind(&permission.color_start)
+                    .bind(&permission.color_end)
+                    .execute(&mut *conn)
+                    .await?;
+                Ok(())
+            }
+
+            // 角色状态只在创建时写入, 不会重新启用管理员停用的角色
+            async fn upsert_role(
+                conn: &mut <$db as Database>::Connection,
+                role: &SeedRole,
+            ) -> Result<(), SeedError> {
+                Self::check_name_conflict(conn, "roles", "角色", &role.name, &role.code).await?;
+                let sql = format!(
+                    r#"
+                    INSERT INTO roles (name, code, description, status)
+                    VALUES (?, ?, ?, ?){}
+                    "#,
+                    <$db>::upsert(&["code"], &["name", "description", "updated_at"])
+                );
+                sqlx::query(&<$db>::sql(&sql))
+                    .bind(&role.name)
+                    .bind(&role.code)
+                    .bind(&role.description)
+                    .bind(role.status)
+                    .execute(&mut *conn)
+                    .await?;
+                let role_id = Self::find_id(conn, "roles", "code", &role.code)
+                    .await?
+                    .ok_or(sqlx::Error::RowNotFound)?;
+
+                for code in &role.permissions {
+                    if code == "*" {
+                        let sql = format!(
+                            r#"
+                            {} INTO role_permissions (role_id, permission_id)
+                            SELECT ?, id FROM permissions{}
+                            "#,
+                            <$db>::INSERT_IGNORE,
+                            <$db>::ON_CONFLICT_IGNORE
+                        );
+                        sqlx::query(&<$db>::sql(&sql))
+                            .bind(role_id)
+                            .execute(&mut *conn)
+                            .await?;
+                        continue;
+                    }
+                    let permission_id = Self::find_id(conn, "permissions", "code", code)
+                        .await?
+                        .ok_or_else(|| {
+                            SeedError::Invalid(format!(
+                                "角色 {} 的权限不存在: {}",
+                                role.code, code
+                            ))
+                        })?;
+                    let sql = format!(
+                        "{} INTO role_permissions (role_id, permission_id) VALUES (?, ?){}",
+                        <$db>::INSERT_IGNORE,
+                        <$db>::ON_CONFLICT_IGNORE
+                    );
+                    sqlx::query(&<$db>::sql(&sql))
+                        .bind(role_id)
+                        .bind(permission_id as i32)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                Ok(())
+            }
+
+            // 用户只在不存在时创建
+            async fn create_user(
+                conn: &mut <$db as Database>::Connection,
+                user: &SeedUser,
+            ) -> Result<(), SeedError> {
+                if Self::find_id(conn, "users", "username", &user.username)
+                    .await?
+                    .is_some()
+                {
+                    return Ok(());
+                }
+                let sql = <$db>::sql(
+                    "INSERT INTO users (username, password, nickname, status) VALUES (?, ?, ?, 1)",
+                );
+                let query = sqlx::query(&sql)
+                    .bind(&user.username)
+                    .bind(hash_password(&user.password))
+                    .bind(&user.nickname);
+                let user_id = <$db>::insert(&mut *conn, query).await?;
+
+                for code in &user.roles {
+                    let role_id = Self::find_id(conn, "roles", "code", code)
+                        .await?
+                        .ok_or_else(|| {
+                            SeedError::Invalid(format!(
+                                "用户 {} 的角色不存在: {}",
+                                user.username, code
+                            ))
+                        })?;
+                    let sql = format!(
+                        "{} INTO user_roles (user_id, role_id) VALUES (?, ?){}",
+                        <$db>::INSERT_IGNORE,
+                        <$db>::ON_CONFLICT_IGNORE
+                    );
+                    sqlx::query(&<$db>::sql(&sql))
+                        .bind(user_id)
+                        .bind(role_id)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                println!(
+                    "Created user {} with password: {}",
+                    user.username, user.password
+                );
+                Ok(())
+            }
+        }
+
+        #[async_trait]
+        impl SchemaRepository for $name {
+            fn kind(&self) -> DatabaseKind {
+                <$db>::KIND
+            }
+
+            async fn applied_migrations(&self) -> Result<Vec<AppliedMigration>, sqlx::Error> {
+                sqlx::query(migrations_table::<$db>())
+                    .execute(&self.pool)
+                    .await?;
+                sqlx::query_as::<_, AppliedMigration>(
+                    "SELECT version, name, checksum, applied_at, execution_ms FROM schema_migrations ORDER BY version",
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn is_legacy(&self) -> Result<bool, sqlx::Error> {
+                if <$db>::KIND != DatabaseKind::Sqlite || !self.applied_migrations().await?.is_empty() {
+                    return Ok(false);
+                }
+                let mut conn = self.pool.acquire().await?;
+                Self::table_exists(&mut conn, "users").await
+            }
+
+            async fn apply_migration(
+                &self,
+                migration: &Migration,
+                upgrade_legacy: bool,
+            ) -> Result<i64, sqlx::Error> {
+                let started = Instant::now();
+                let mut tx = self.pool.begin().await?;
+                if upgrade_legacy {
+                    Self::upgrade_legacy_schema(&mut tx).await?;
+                }
+                // 迁移文件包含多条语句, 不使用预处理语句执行
+                (&mut *tx).execute(migration.sql).await?;
+                let execution_ms = started.elapsed().as_millis() as i64;
+                sqlx::query(&<$db>::sql(
+                    "INSERT INTO schema_migrations (version, name, checksum, execution_ms) VALUES (?, ?, ?, ?)",
+                ))
+                .bind(migration.version)
+                .bind(migration.name)
+                .bind(migration.checksum())
+                .bind(execution_ms)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                Ok(execution_ms)
+            }
+
+            async fn seed_version(&self) -> Result<i64, sqlx::Error> {
+                let version = sqlx::query_scalar::<_, Option<i64>>(
+                    "SELECT MAX(version) FROM seed_history",
+                )
+                .fetch_one(&self.pool)
+                .await?;
+                Ok(version.unwrap_or(0))
+            }
+
+            async fn apply_seed(&self, seed: &SeedFile, checksum: &str) -> Result<(), SeedError> {
+                let mut tx = self.pool.begin().await?;
+                for menu in &seed.menus {
+                    Self::upsert_menu(&mut tx, menu).await?;
+                }
+                for permission in &seed.permissions {
+                    Self::upsert_permission(&mut tx, permission).await?;
+                }
+                for role in &seed.roles {
+                    Self::upsert_role(&mut tx, role).await?;
+                }
+                for user in &seed.users {
+                    Self::create_user(&mut tx, user).await?;
+                }
+                let sql = format!(
+                    "INSERT INTO seed_history (version, checksum) VALUES (?, ?){}",
+                    <$db>::upsert(&["version"], &["checksum", "applied_at"])
+                );
+                sqlx::query(&<$db>::sql(&sql))
+                    .bind(seed.version)
+                    .bind(checksum)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                Ok(())
+            }
+
+            async fn integrity_check(&self) -> Result<Option<Vec<String>>, sqlx::Error> {
+                if <$db>::KIND != DatabaseKind::Sqlite {
+                    return Ok(None);
+                }
+                sqlx::query_scalar::<_, String>("PRAGMA integrity_check")
+                    .fetch_all(&self.pool)
+                    .await
+                    .map(Some)
+            }
+
+            async fn orphaned_rows(&self) -> Result<Vec<OrphanedRows>, sqlx::Error> {
+                if <$db>::KIND != DatabaseKind::Sqlite {
+                    return Ok(Vec::new());
+                }
+                sqlx::query_as::<_, OrphanedRows>(
+                    r#"
+                    SELECT "table" AS table_name, parent, COUNT(*) AS count
+                    FROM pragma_foreign_key_check
+                    GROUP BY "table", parent
+                    ORDER BY "table", parent
+                    "#,
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn orphaned_rowids(
+                &self,
+                orphan: &OrphanedRows,
+                limit: i64,
+            ) -> Result<Vec<i64>, sqlx::Error> {
+                sqlx::query_scalar::<_, i64>(
+                    r#"SELECT rowid FROM pragma_foreign_key_check WHERE "table" = ? AND parent = ? LIMIT ?"#,
+                )
+                .bind(&orphan.table_name)
+                .bind(&orphan.parent)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            // 外键为 ON DELETE SET NULL 或可为空且没有级联删除时置空, 例如上级权限已删除的权限改为顶级权限;
+            // 其余(级联删除的关联数据)直接删除
+            async fn fix_orphaned_rows(&self, rounds: usize) -> Result<(), sqlx::Error> {
+                if <$db>::KIND != DatabaseKind::Sqlite {
+                    return Ok(());
+                }
+                let mut tx = self.pool.begin().await?;
+                for _ in 0..rounds {
+                    let groups = sqlx::query_as::<_, (String, i64)>(
+                        r#"SELECT DISTINCT "table", fkid FROM pragma_foreign_key_check ORDER BY "table", fkid"#,
+                    )
+                    .fetch_all(&mut *tx)
+                    .await?;
+                    if groups.is_empty() {
+                        break;
+                    }
+
+                    for (table, fkid) in groups {
+                        let columns = sqlx::query_as::<_, (String, String, bool)>(
+                            r#"
+                            SELECT fk."from", fk.on_delete, ti."notnull"
+                            FROM pragma_foreign_key_list(?) fk
+                            JOIN pragma_table_info(?) ti ON ti.name = fk."from"
+                            WHERE fk.id = ?
+                            "#,
+                        )
+                        .bind(&table)
+                        .bind(&table)
+                        .bind(fkid)
+                        .fetch_all(&mut *tx)
+                        .await?;
+
+                        let nullable = columns
+                            .iter()
+                            .all(|(_, on_delete, not_null)| on_delete != "CASCADE" && !not_null);
+                        let rows = format!(
+                            r#"rowid IN (SELECT rowid FROM pragma_foreign_key_check('{}') WHERE fkid = {})"#,
+                            table.replace('\'', "''"),
+                            fkid
+                        );
+                        let sql = if nullable && !columns.is_empty() {
+                            let assignments = columns
+                                .iter()
+                                .map(|(column, _, _)| format!(r#""{}" = NULL"#, column))
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            format!(r#"UPDATE "{}" SET {} WHERE {}"#, table, assignments, rows)
+                        } else {
+                            format!(r#"DELETE FROM "{}" WHERE {}"#, table, rows)
+                        };
+                        let affected = sqlx::query(&sql).execute(&mut *tx).await?.rows_affected();
+                        println!(
+                            "[Database] {} {} orphaned rows in {}",
+                            if nullable {
+                                "Cleared references of"
+                            } else {
+                                "Deleted"
+                            },
+                            affected,
+                            table
+                        );
+                    }
+                }
+                tx.commit().await
+            }
+        }
+    };
+}
+
+schema_repository!(SqliteSchemaRepository, Sqlite);
+schema_repository!(PostgresSchemaRepository, Postgres);
+schema_repository!(MySqlSchemaRepository, MySql);
@@ -0,0 +1,1106 @@
+// 在每种数据库上执行同一组仓储操作, 检查各方言的 SQL 和迁移文件.
+// SQLite 使用临时文件; PostgreSQL 和 MySQL 通过 TEST_POSTGRES_URL 和 TEST_MYSQL_URL 指定
+// (见 docker-compose.db.yml), 默认忽略, 用 cargo test -- --include-ignored 运行,
+// 没有设置对应地址时失败.
+// 测试会清空指定的数据库
+use chrono::{Duration, Utc};
+use futures_util::StreamExt;
+use sqlx::mysql::MySqlPoolOptions;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Executor, MySqlPool, PgPool};
+use std::str::FromStr;
+
+use super::file::FileQuery;
+use super::login_log::LoginLogQuery;
+use super::operation_log::{ArchivedLogQuery, NewCheckpoint};
+use super::role::RoleQuery;
+use super::upload_session::NewUploadSession;
+use super::user::{UpdateProfile, UserQuery};
+use super::{PermissionQuery, Repositories};
+use crate::config::database::init_database;
+use crate::config::{migrations, seed};
+use crate::models::{
+    CreateFile, CreateFileVariant, CreateLoginLog, CreateOperationLog, CreateOperationLogChange,
+    CreatePermission, CreateRole, CreateUser, Menu, SaveOperationLogRule, UpdatePermission,
+    UpdateUser,
+};
+use crate::services::assignment::{AssignmentMode, ROLE_PERMISSIONS, USER_ROLES};
+use crate::services::files::URL_PREFIX;
+use crate::services::operation_log::{
+    insert_operation_logs, record_redaction, verify_chain, PendingLog,
+};
+use crate::services::operation_log_filter::{IpFilter, OperationLogFilter};
+use crate::services::quota::UNLIMITED_QUOTA;
+use crate::services::upload::UploadError;
+use crate::utils::ip::ip_key;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn timestamp(offset: Duration) -> String {
+    (Utc::now() + offset).format(TIMESTAMP_FORMAT).to_string()
+}
+
+#[tokio::test]
+async fn sqlite() {
+    let path = std::env::temp_dir().join(format!("backend-test-{}.db", uuid::Uuid::new_v4()));
+    let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+        .unwrap()
+        .create_if_missing(true)
+        .foreign_keys(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(4)
+        .connect_with(options.clone())
+        .await
+        .unwrap();
+    // 另一个连接池模拟同时写入的其他实例
+    let other = SqlitePoolOptions::new()
+        .max_connections(4)
+        .connect_with(options)
+        .await
+        .unwrap();
+    exercise(Repositories::sqlite(pool), Repositories::sqlite(other)).await;
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_POSTGRES_URL"]
+async fn postgres() {
+    let url = std::env::var("TEST_POSTGRES_URL").expect("TEST_POSTGRES_URL is not set");
+    let pool = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&url)
+        .await
+        .unwrap();
+    reset_postgres(&pool).await;
+    let other = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&url)
+        .await
+        .unwrap();
+    exercise(Repositories::postgres(pool), Repositories::postgres(other)).await;
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_MYSQL_URL"]
+async fn mysql() {
+    let url = std::env::var("TEST_MYSQL_URL").expect("TEST_MYSQL_URL is not set");
+    let pool = MySqlPoolOptions::new()
+        .max_connections(4)
+        .connect(&url)
+        .await
+        .unwrap();
+    reset_mysql(&pool).await;
+    let other = MySqlPoolOptions::new()
+        .max_connections(4)
+        .connect(&url)
+        .await
+        .unwrap();
+    exercise(Repositories::mysql(pool), Repositories::mysql(other)).await;
+}
+
+async fn reset_postgres(pool: &PgPool) {
+    pool.execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public;")
+        .await
+        .unwrap();
+}
+
+async fn reset_mysql(pool: &MySqlPool) {
+    let mut conn = pool.acquire().await.unwrap();
+    let tables = sqlx::query_scalar::<_, String>(
+        "SELECT CAST(table_name AS CHAR) FROM information_schema.tables WHERE table_schema = DATABASE()",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .unwrap();
+    conn.execute("SET FOREIGN_KEY_CHECKS = 0").await.unwrap();
+    for table in tables {
+        conn.execute(format!("DROP TABLE `{}`", table).as_str())
+            .await
+            .unwrap();
+    }
+    conn.execute("SET FOREIGN_KEY_CHECKS = 1").await.unwrap();
+}
+
+// other 与 repos 连接同一个数据库, 使用各自的连接池
+async fn exercise(repos: Repositories, other: Repositories) {
+    schema(&repos).await;
+    let (user_id, role_id) = accounts(&repos).await;
+    menus(&repos).await;
+    files(&repos, user_id, role_id).await;
+    upload_sessions(&repos, user_id).await;
+    login_logs(&repos, user_id).await;
+    operation_log_rules(&repos).await;
+    operation_logs(&repos, user_id).await;
+    dashboard(&repos).await;
+    concurrent_operation_logs(&repos, &other, user_id).await;
+    repos.users.delete(user_id).await.unwrap();
+    assert!(repos.users.find(user_id).await.unwrap().is_none());
+}
+
+async fn schema(repos: &Repositories) {
+    init_database(repos).await.unwrap();
+    let kind = repos.schema.kind();
+    assert!(migrations::pending(repos).await.unwrap().is_empty());
+    assert_eq!(
+        repos.schema.applied_migrations().await.unwrap().len(),
+        migrations::migrations(kind).len()
+    );
+    assert!(!repos.schema.is_legacy().await.unwrap());
+
+    // 重复写入初始数据时按编码更新, 不会产生重复数据
+    let permissions = repos.permissions.list_all().await.unwrap().len();
+    let (seed_file, checksum) = seed::load().unwrap();
+    seed::apply(repos, &seed_file, &checksum).await.unwrap();
+    assert_eq!(
+        repos.permissions.list_all().await.unwrap().len(),
+        permissions
+    );
+    assert_eq!(
+        repos.schema.seed_version().await.unwrap(),
+        seed_file.version
+    );
+
+    let admin = repos
+        .users
+        .find_by_username("admin")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(repos
+        .permissions
+        .has_permission(admin.id, "system:log:view")
+        .await
+        .unwrap());
+
+    assert!(repos.schema.orphaned_rows().await.unwrap().is_empty());
+    repos.schema.fix_orphaned_rows(1).await.unwrap();
+    if let Some(integrity) = repos.schema.integrity_check().await.unwrap() {
+        assert_eq!(integrity, vec!["ok".to_string()]);
+    }
+}
+
+async fn accounts(repos: &Repositories) -> (i64, i64) {
+    let user = repos
+        .users
+        .create(
+            &CreateUser {
+                username: "tester".to_string(),
+                password: String::new(),
+                nickname: "测试用户".to_string(),
+                email: Some("tester@example.com".to_string()),
+                avatar: None,
+                status: 1,
+            },
+            "hashed",
+        )
+        .await
+        .unwrap();
+    assert_eq!(user.username, "tester");
+    assert_eq!(user.password, "hashed");
+
+    let query = UserQuery {
+        nickname: "测试".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(repos.users.count(&query).await.unwrap(), 1);
+    assert_eq!(
+        repos.users.list(&query, 10, 0).await.unwrap()[0].id,
+        user.id
+    );
+
+    let updated = repos
+        .users
+        .update(
+            user.id,
+            &UpdateUser {
+                nickname: "测试".to_string(),
+                email: Some("changed@example.com".to_string()),
+                avatar: None,
+                status: 1,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.email, "changed@example.com");
+    let updated = repos
+        .users
+        .update_profile(
+            user.id,
+            &UpdateProfile {
+                nickname: "测试员".to_string(),
+                email: "tester@example.com".to_string(),
+                avatar: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.nickname, "测试员");
+    repos
+        .users
+        .update_password(user.id, "rehashed")
+        .await
+        .unwrap();
+    let updated = repos
+        .users
+        .set_storage_quota(user.id, Some(1000))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.password, "rehashed");
+    assert_eq!(updated.storage_quota, Some(1000));
+
+    let role = repos
+        .roles
+        .create(&CreateRole {
+            name: "测试角色".to_string(),
+            code: "tester".to_string(),
+            description: None,
+            status: Some(1),
+            color_start: None,
+            color_end: None,
+        })
+        .await
+        .unwrap();
+    let query = RoleQuery {
+        code: "test".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(repos.roles.count(&query).await.unwrap(), 1);
+    assert_eq!(
+        repos.roles.list(&query, 10, 0).await.unwrap()[0].id,
+        role.id
+    );
+    let updated = repos
+        .roles
+        .update(
+            role.id,
+            &CreateRole {
+                name: "测试角色".to_string(),
+                code: "tester".to_string(),
+                description: Some("用于测试".to_string()),
+                status: Some(1),
+                color_start: None,
+                color_end: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.description.as_deref(), Some("用于测试"));
+
+    let parent = repos
+        .permissions
+        .create(&permission("测试权限", "test", None))
+        .await
+        .unwrap();
+    let child = repos
+        .permissions
+        .create(&permission("测试查看", "test:view", Some(parent.id)))
+        .await
+        .unwrap();
+    let query = PermissionQuery {
+        code: "test".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(repos.permissions.count(&query).await.unwrap(), 2);
+    assert_eq!(
+        repos.permissions.list(&query, 10, 0).await.unwrap().len(),
+        2
+    );
+    assert_eq!(
+        repos.permissions.children(parent.id as i64).await.unwrap()[0].id,
+        child.id
+    );
+    let updated = repos
+        .permissions
+        .update(
+            child.id as i64,
+            &UpdatePermission {
+                name: "测试查看".to_string(),
+                code: "test:view".to_string(),
+                type_name: "API".to_string(),
+                resource: Some("/test".to_string()),
+                action: Some("GET".to_string()),
+                parent_id: Some(parent.id),
+                sort: 2,
+                description: None,
+                color_start: None,
+                color_end: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.sort, 2);
+    assert!(repos
+        .permissions
+        .api_permissions()
+        .await
+        .unwrap()
+        .iter()
+        .any(|p| p.code == "test:view"));
+
+    let diff = repos
+        .assignments
+        .apply(
+            &ROLE_PERMISSIONS,
+            role.id,
+            &[parent.id as i64, child.id as i64],
+            AssignmentMode::Replace,
+        )
+        .await
+        .unwrap();
+    assert_eq!(diff.added.len(), 2);
+    let diff = repos
+        .assignments
+        .apply(
+            &ROLE_PERMISSIONS,
+            role.id,
+            &[parent.id as i64],
+            AssignmentMode::Remove,
+        )
+        .await
+        .unwrap();
+    assert_eq!(diff.removed, vec![parent.id as i64]);
+    assert_eq!(
+        repos.roles.permission_ids(role.id).await.unwrap(),
+        vec![child.id as i64]
+    );
+    assert_eq!(repos.permissions.for_role(role.id).await.unwrap().len(), 1);
+
+    repos
+        .assignments
+        .apply(&USER_ROLES, user.id, &[role.id], AssignmentMode::Add)
+        .await
+        .unwrap();
+    assert_eq!(repos.roles.for_user(user.id).await.unwrap()[0].id, role.id);
+    assert!(repos
+        .permissions
+        .has_permission(user.id, "test:view")
+        .await
+        .unwrap());
+    assert!(!repos
+        .permissions
+        .has_permission(user.id, "test")
+        .await
+        .unwrap());
+
+    let exported: Vec<_> = repos
+        .users
+        .export(&UserQuery {
+            username: "tester".to_string(),
+            ..Default::default()
+        })
+        .collect()
+        .await;
+    assert_eq!(exported.len(), 1);
+    assert_eq!(
+        exported[0].as_ref().unwrap().roles.as_deref(),
+        Some("测试角色")
+    );
+
+    // 删除仍有下级的权限前需要先删除下级
+    repos.permissions.delete(child.id as i64).await.unwrap();
+    repos.permissions.delete(parent.id as i64).await.unwrap();
+    assert!(repos
+        .permissions
+        .find(parent.id as i64)
+        .await
+        .unwrap()
+        .is_none());
+
+    (user.id, role.id)
+}
+
+fn permission(name: &str, code: &str, parent_id: Option<i32>) -> CreatePermission {
+    CreatePermission {
+        name: name.to_string(),
+        code: code.to_string(),
+        type_name: "API".to_string(),
+        resource: Some("/test".to_string()),
+        action: Some("GET".to_string()),
+        parent_id,
+        sort: 1,
+        description: None,
+        color_start: None,
+        color_end: None,
+    }
+}
+
+async fn menus(repos: &Repositories) {
+    let mut menu = Menu {
+        id: 0,
+        parent_id: None,
+        name: "Test".to_string(),
+        path: "/test".to_string(),
+        component: "views/test/index".to_string(),
+        title: "测试".to_string(),
+        icon: None,
+        sort: 99,
+        is_hidden: false,
+        created_at: String::new(),
+        updated_at: String::new(),
+    };
+    let created = repos.menus.create(&menu).await.unwrap();
+    assert!(repos
+        .menus
+        .list_visible()
+        .await
+        .unwrap()
+        .iter()
+        .any(|m| m.id == created.id));
+
+    // 显示状态不通过更新修改
+    menu.title = "测试菜单".to_string();
+    menu.is_hidden = true;
+    let updated = repos.menus.update(created.id, &menu).await.unwrap();
+    assert_eq!(updated.title, "测试菜单");
+    assert!(!updated.is_hidden);
+
+    repos.menus.delete(created.id).await.unwrap();
+    assert!(repos.menus.find(created.id).await.unwrap().is_none());
+}
+
+fn new_file(user_id: i64, key: &str, size: i64) -> CreateFile {
+    CreateFile {
+        storage_name: format!("{}.png", uuid::Uuid::new_v4()),
+        original_name: "test.png".to_string(),
+        size,
+        mime: "image/png".to_string(),
+        sha256: key.to_string(),
+        purpose: "attachment".to_string(),
+        visibility: "private".to_string(),
+        blob_key: key.to_string(),
+        uploader_id: user_id,
+    }
+}
+
+async fn files(repos: &Repositories, user_id: i64, role_id: i64) {
+    repos
+        .files
+        .insert_blob("blob-a", "blob-a", 400, "image/png")
+        .await
+        .unwrap();
+    repos
+        .files
+        .insert_blob("blob-a-thumb", "blob-a-thumb", 10, "image/png")
+        .await
+        .unwrap();
+    assert!(repos.files.touch_blob("blob-a").await.unwrap());
+    assert!(!repos.files.touch_blob("missing").await.unwrap());
+
+    let file = repos
+        .files
+        .register(
+            &new_file(user_id, "blob-a", 400),
+            &[CreateFileVariant {
+                variant: "thumbnail".to_string(),
+                storage_name: "thumb.png".to_string(),
+                blob_key: "blob-a-thumb".to_string(),
+                size: 10,
+                width: 16,
+                height: 16,
+            }],
+        )
+        .await
+        .unwrap();
+    assert_eq!(file.size, 400);
+    assert_eq!(repos.files.variants(file.id).await.unwrap().len(), 1);
+    assert_eq!(
+        repos
+            .files
+            .uploader_of(&file.storage_name, "attachment")
+            .await
+            .unwrap(),
+        Some(Some(user_id))
+    );
+    assert_eq!(
+        repos.files.find_key(&file.storage_name).await.unwrap(),
+        Some(("private".to_string(), "blob-a".to_string()))
+    );
+
+    // 相同内容再次上传时复用已存储的文件
+    let reused = repos
+        .files
+        .reuse_blob(
+            "blob-a",
+            400,
+            user_id,
+            Box::new(move |mime| CreateFile {
+                mime,
+                ..new_file(user_id, "blob-a", 400)
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reused.blob_key.as_deref(), Some("blob-a"));
+    assert_ne!(reused.storage_name, file.storage_name);
+
+    // 被用户头像引用的文件
+    repos
+        .users
+        .update_profile(
+            user_id,
+            &UpdateProfile {
+                nickname: "测试员".to_string(),
+                email: String::new(),
+                avatar: Some(format!("{}{}", URL_PREFIX, file.storage_name)),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        repos
+            .files
+            .reference_count_for_key(&file.storage_name)
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(repos
+        .files
+        .reuse_blob(
+            "missing",
+            1,
+            user_id,
+            Box::new(move |_| new_file(user_id, "missing", 1))
+        )
+        .await
+        .unwrap()
+        .is_none());
+
+    let query = FileQuery {
+        uploader_id: Some(user_id),
+        ..Default::default()
+    };
+    assert_eq!(repos.files.count(&query).await.unwrap(), 2);
+    assert_eq!(repos.files.list(&query, 10, 0).await.unwrap().len(), 2);
+
+    let usage = repos.files.usage(user_id).await.unwrap().unwrap();
+    // 用量包含缩略图
+    assert_eq!(usage.used, 810);
+    assert_eq!(usage.file_count, 2);
+    assert_eq!(usage.user_quota, Some(1000));
+    assert_eq!(repos.files.count_usage("tester").await.unwrap(), 1);
+    assert_eq!(
+        repos.files.list_usage("tester", 10, 0).await.unwrap()[0].used,
+        810
+    );
+    assert!(matches!(
+        repos.files.check_quota(user_id, 300).await,
+        Err(UploadError::QuotaExceeded { .. })
+    ));
+    repos.files.check_quota(user_id, 190).await.unwrap();
+
+    // 用户配额为空时使用角色配额
+    repos.users.set_storage_quota(user_id, None).await.unwrap();
+    repos
+        .roles
+        .set_storage_quota(role_id, Some(5000))
+        .await
+        .unwrap()
+        .unwrap();
+    let usage = repos.files.usage(user_id).await.unwrap().unwrap();
+    assert_eq!(usage.role_quota, Some(5000));
+    repos.files.check_quota(user_id, 4000).await.unwrap();
+
+    // 角色配额为空表示未设置, 不再视为不限制
+    repos
+        .roles
+        .set_storage_quota(role_id, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let usage = repos.files.usage(user_id).await.unwrap().unwrap();
+    assert_eq!(usage.role_quota, None);
+    assert!(!usage.role_unlimited);
+    assert_ne!(usage.quota_source.as_deref(), Some("role"));
+
+    // 角色配额为 -1 时不限制, 即使其他角色设置了配额
+    repos
+        .roles
+        .set_storage_quota(role_id, Some(UNLIMITED_QUOTA))
+        .await
+        .unwrap()
+        .unwrap();
+    let usage = repos.files.usage(user_id).await.unwrap().unwrap();
+    assert_eq!(usage.role_quota, None);
+    assert!(usage.role_unlimited);
+    assert_eq!(usage.quota, None);
+    assert_eq!(usage.quota_source.as_deref(), Some("role"));
+
+    // 用户配额为 -1 时不限制, 优先于角色配额
+    repos
+        .roles
+        .set_storage_quota(role_id, Some(5000))
+        .await
+        .unwrap()
+        .unwrap();
+    repos
+        .users
+        .set_storage_quota(user_id, Some(UNLIMITED_QUOTA))
+        .await
+        .unwrap();
+    let usage = repos.files.usage(user_id).await.unwrap().unwrap();
+    assert_eq!(usage.quota, None);
+    assert_eq!(usage.quota_source.as_deref(), Some("user"));
+    repos.files.check_quota(user_id, 1 << 40).await.unwrap();
+    repos.users.set_storage_quota(user_id, None).await.unwrap();
+
+    repos
+        .files
+        .set_visibility(reused.id, "public")
+        .await
+        .unwrap();
+    assert_eq!(
+        repos
+            .files
+            .find(reused.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .visibility,
+        "public"
+    );
+
+    // 附件没有被引用时视为孤立文件
+    let orphans = repos
+        .files
+        .orphans(&["attachment"], &timestamp(Duration::minutes(1)))
+        .await
+        .unwrap();
+    assert_eq!(orphans.len(), 1);
+    assert_eq!(orphans[0].id, reused.id);
+
+    repos.files.delete(reused.id).await.unwrap();
+    repos.files.delete(file.id).await.unwrap();
+    assert!(repos.files.find(file.id).await.unwrap().is_none());
+    let removed = repos
+        .files
+        .delete_unused_blobs(&timestamp(Duration::minutes(1)))
+        .await
+        .unwrap();
+    assert_eq!(removed.len(), 2);
+    assert!(repos
+        .files
+        .find_key(&file.storage_name)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn upload_sessions(repos: &Repositories, user_id: i64) {
+    let session = repos
+        .upload_sessions
+        .create(&NewUploadSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id,
+            purpose: "attachment".to_string(),
+            visibility: "private".to_string(),
+            file_name: "large.bin".to_string(),
+            total_size: 2000,
+            chunk_size: 1000,
+            total_chunks: 2,
+            sha256: None,
+            expires_at: timestamp(Duration::hours(1)),
+        })
+        .await
+        .unwrap();
+    assert_eq!(session.status, "uploading");
+
+    // 未完成的会话占用配额
+    let usage = repos.files.usage(user_id).await.unwrap().unwrap();
+    assert_eq!(usage.reserved, 2000);
+    assert!(matches!(
+        repos.files.check_quota(user_id, 3500).await,
+        Err(UploadError::QuotaExceeded { .. })
+    ));
+
+    let now = timestamp(Duration::zero());
+    assert!(repos
+        .upload_sessions
+        .find(&session.id, user_id, &now)
+        .await
+        .unwrap()
+        .is_some());
+    assert!(repos
+        .upload_sessions
+        .find(&session.id, user_id + 1, &now)
+        .await
+        .unwrap()
+        .is_none());
+
+    let expires_at = timestamp(Duration::hours(2));
+    for index in [1, 0, 1] {
+        repos
+            .upload_sessions
+            .save_chunk(&session.id, index, 1000, "chunk", &expires_at)
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        repos.upload_sessions.chunks(&session.id).await.unwrap(),
+        vec![(0, 1000), (1, 1000)]
+    );
+
+    assert!(repos
+        .upload_sessions
+        .transition(&session.id, "uploading", "completing")
+        .await
+        .unwrap());
+    assert!(!repos
+        .upload_sessions
+        .transition(&session.id, "uploading", "completing")
+        .await
+        .unwrap());
+    repos
+        .upload_sessions
+        .set_status(&session.id, "uploading")
+        .await
+        .unwrap();
+
+    let later = timestamp(Duration::hours(3));
+    let expired = repos
+        .upload_sessions
+        .expired(&later, "completing", &later)
+        .await
+        .unwrap();
+    assert_eq!(expired, vec![session.id.clone()]);
+    assert!(repos
+        .upload_sessions
+        .expired(&now, "completing", &now)
+        .await
+        .unwrap()
+        .is_empty());
+
+    repos.upload_sessions.delete(&session.id).await.unwrap();
+    assert!(repos
+        .upload_sessions
+        .chunks(&session.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+async fn login_logs(repos: &Repositories, user_id: i64) {
+    for success in [true, false, false] {
+        repos
+            .login_logs
+            .record(&CreateLoginLog {
+                user_id: Some(user_id),
+                username: "tester".to_string(),
+                success,
+                failure_reason: (!success).then(|| "密码错误".to_string()),
+                ip: Some("10.0.0.1".to_string()),
+                user_agent: None,
+                request_id: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    let query = LoginLogQuery {
+        user_id: Some(user_id),
+        success: Some(false),
+        start_time: Some(timestamp(-Duration::minutes(1))),
+        ..Default::default()
+    };
+    assert_eq!(repos.login_logs.count(&query).await.unwrap(), 2);
+    let logs = repos.login_logs.list(&query, 10, 0).await.unwrap();
+    assert!(logs.iter().all(|log| !log.success));
+
+    assert_eq!(
+        repos
+            .login_logs
+            .purge(&timestamp(-Duration::minutes(1)))
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+async fn operation_log_rules(repos: &Repositories) {
+    let mut rule = SaveOperationLogRule {
+        method: "POST".to_string(),
+        path: "/test".to_string(),
+        action: "exclude".to_string(),
+        priority: 1,
+        enabled: false,
+        description: None,
+    };
+    let created = repos.operation_log_rules.create(&rule).await.unwrap();
+    assert!(!repos
+        .operation_log_rules
+        .enabled()
+        .await
+        .unwrap()
+        .iter()
+        .any(|r| r.id == created.id));
+
+    rule.enabled = true;
+    let updated = repos
+        .operation_log_rules
+        .update(created.id, &rule)
+        .await
+        .unwrap();
+    assert!(updated.enabled);
+    assert!(repos
+        .operation_log_rules
+        .enabled()
+        .await
+        .unwrap()
+        .iter()
+        .any(|r| r.id == created.id));
+
+    repos.operation_log_rules.delete(created.id).await.unwrap();
+    assert!(repos
+        .operation_log_rules
+        .find(created.id)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+// 两个实例同时批量写入日志, 哈希链不分叉
+async fn concurrent_operation_logs(repos: &Repositories, other: &Repositories, user_id: i64) {
+    const WRITERS: usize = 4;
+    const BATCHES: usize = 5;
+    const BATCH_SIZE: usize = 3;
+
+    let before = verify_chain(repos).await.unwrap();
+    assert!(before.valid, "{:?}", before);
+
+    let writers = (0..WRITERS).map(|writer| {
+        let repos = if writer % 2 == 0 { repos } else { other };
+        async move {
+            for _ in 0..BATCHES {
+                let logs: Vec<_> = (0..BATCH_SIZE)
+                    .map(|_| operation_log(user_id, "PUT", 200, r#"{"nickname":"b"}"#))
+                    .collect();
+                insert_operation_logs(repos, &logs).await.unwrap();
+            }
+        }
+    });
+    futures_util::future::join_all(writers).await;
+
+    let report = verify_chain(repos).await.unwrap();
+    assert!(report.valid, "{:?}", report);
+    assert_eq!(
+        report.checked,
+        before.checked + (WRITERS * BATCHES * BATCH_SIZE) as i64
+    );
+}
+
+fn operation_log(user_id: i64, method: &str, status: i32, params: &str) -> PendingLog {
+    PendingLog::new(CreateOperationLog {
+        user_id,
+        username: "tester".to_string(),
+        module: "用户管理".to_string(),
+        operation: "更新用户".to_string(),
+        method: method.to_string(),
+        path: "/users/1".to_string(),
+        params: Some(params.to_string()),
+        ip: Some("10.0.0.1".to_string()),
+        user_agent: None,
+        request_id: None,
+        status,
+        error: (status >= 400).then(|| "参数错误".to_string()),
+        response: None,
+        duration_ms: 5,
+        changes: vec![CreateOperationLogChange {
+            entity: "user".to_string(),
+            entity_id: 1,
+            action: "update".to_string(),
+            before: Some(r#"{"nickname":"a"}"#.to_string()),
+            after: Some(r#"{"nickname":"b"}"#.to_string()),
+            diff: r#"{"nickname":["a","b"]}"#.to_string(),
+        }],
+    })
+}
+
+async fn operation_logs(repos: &Repositories, user_id: i64) {
+    let logs = vec![
+        operation_log(user_id, "PUT", 200, r#"{"password":"secret"}"#),
+        operation_log(user_id, "PUT", 400, r#"{"nickname":"b"}"#),
+        operation_log(user_id, "DELETE", 200, r#"{"reason":"cleanup"}"#),
+    ];
+    let ids = insert_operation_logs(repos, &logs).await.unwrap();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(
+        repos
+            .operation_logs
+            .between(ids[0], ids[2])
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    let filter = OperationLogFilter {
+        sort_by: "id".to_string(),
+        methods: vec!["PUT".to_string()],
+        status_classes: vec![4],
+        ..Default::default()
+    };
+    assert_eq!(repos.operation_logs.count(&filter).await.unwrap(), 1);
+    let key = ip_key("10.0.0.1").unwrap();
+    let filter = OperationLogFilter {
+        sort_by: "created_at".to_string(),
+        sort_desc: true,
+        keyword: "cleanup".to_string(),
+        ip: Some(IpFilter {
+            start: key.clone(),
+            end: key,
+        }),
+        ..Default::default()
+    };
+    let found = repos.operation_logs.list(&filter, 10, 0).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, ids[2]);
+    let exported: Vec<_> = repos
+        .operation_logs
+        .export(&OperationLogFilter {
+            sort_by: "id".to_string(),
+            ..Default::default()
+        })
+        .collect()
+        .await;
+    assert_eq!(exported.len(), 3);
+
+    let changes = repos.operation_logs.changes(ids[0]).await.unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].after.as_deref(), Some(r#"{"nickname":"b"}"#));
+    assert!(repos
+        .operation_logs
+        .missing_ip_keys()
+        .await
+        .unwrap()
+        .is_empty());
+
+    let report = verify_chain(repos).await.unwrap();
+    assert!(report.valid, "{:?}", report);
+    assert_eq!(report.checked, 3);
+
+    // 清洗参数后哈希链按清洗记录仍然校验通过
+    let mut redacted = repos.operation_logs.find(ids[0]).await.unwrap().unwrap();
+    redacted.params = Some(r#"{"password":"***"}"#.to_string());
+    let redaction_id = record_redaction(repos, "测试", &[redacted])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        repos
+            .operation_logs
+            .redacted_rows(redaction_id)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    let report = verify_chain(repos).await.unwrap();
+    assert!(report.valid, "{:?}", report);
+    assert_eq!(report.redacted, 1);
+
+    // 归档并删除前两条日志, 检查点作为剩余日志的链起点
+    let entries = repos
+        .operation_logs
+        .archive_batch(ids[1], 100)
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+    let last = &entries[1].log;
+    let cutoff = timestamp(Duration::zero());
+    let (purged, _) = repos
+        .operation_logs
+        .purge(&NewCheckpoint {
+            last_log_id: last.id,
+            last_hash: last.hash.as_deref().unwrap(),
+            cutoff: &cutoff,
+            reason: "测试",
+            created_by: Some("tester"),
+            archive: "operation_logs_test.jsonl.gz",
+        })
+        .await
+        .unwrap();
+    assert_eq!(purged, 2);
+    assert_eq!(repos.operation_logs.checkpoints().await.unwrap().len(), 1);
+    let report = verify_chain(repos).await.unwrap();
+    assert!(report.valid, "{:?}", report);
+    assert_eq!(report.checked, 1);
+
+    // 导入归档供排查
+    let archive = "operation_logs_test.jsonl.gz";
+    assert_eq!(
+        repos
+            .operation_logs
+            .import_archive(archive, &entries)
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        repos.operation_logs.archived_counts().await.unwrap(),
+        vec![(archive.to_string(), 2)]
+    );
+    let query = ArchivedLogQuery {
+        archive: archive.to_string(),
+        username: "test".to_string(),
+    };
+    assert_eq!(
+        repos.operation_logs.count_archived(&query).await.unwrap(),
+        2
+    );
+    let archived = repos
+        .operation_logs
+        .list_archived(&query, 10, 0)
+        .await
+        .unwrap();
+    assert!(archived.iter().all(|log| log
+        .changes
+        .as_deref()
+        .is_some_and(|c| c.contains("nickname"))));
+    assert_eq!(repos.operation_logs.delete_archived("").await.unwrap(), 2);
+}
+
+async fn dashboard(repos: &Repositories) {
+    let start = timestamp(-Duration::days(1));
+    let summary = repos.dashboard.summary(&start).await.unwrap();
+    assert_eq!(summary.requests, 1);
+    assert_eq!(summary.errors, 0);
+    assert!(!repos
+        .dashboard
+        .requests(&start, true)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        repos.dashboard.module_errors(&start).await.unwrap()[0].total,
+        1
+    );
+    assert_eq!(
+        repos.dashboard.top_users(&start, 5).await.unwrap()[0].requests,
+        1
+    );
+    assert_eq!(repos.dashboard.logins(&start).await.unwrap(), (3, 2));
+    assert_eq!(
+        repos.dashboard.failed_logins(&start, false).await.unwrap()[0].failed,
+        2
+    );
+    assert_eq!(
+        repos
+            .dashboard
+            .top_failed_usernames(&start, 5)
+            .await
+            .unwrap()[0]
+            .failures,
+        2
+    );
+    assert!(!repos.dashboard.new_users(&start).await.unwrap().is_empty());
+    assert!(repos
+        .dashboard
+        .roles()
+        .await
+        .unwrap()
+        .iter()
+        .any(|r| r.name == "测试角色" && r.users == 1));
+}
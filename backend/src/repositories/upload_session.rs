@@ -0,0 +1,267 @@
+use async_trait::async_trait;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::{DbTime, Dialect};
+use super::file::{find_usage, lock_user};
+use crate::models::UploadSession;
+use crate::services::quota::check_usage;
+use crate::services::upload::UploadError;
+
+// 新建会话的字段, 状态和时间使用默认值
+#[derive(Debug)]
+pub struct NewUploadSession {
+    pub id: String,
+    pub user_id: i64,
+    pub purpose: String,
+    pub visibility: String,
+    pub file_name: String,
+    pub total_size: i64,
+    pub chunk_size: i64,
+    pub total_chunks: i64,
+    pub sha256: Option<String>,
+    pub expires_at: String,
+}
+
+#[async_trait]
+pub trait UploadSessionRepository: Send + Sync {
+    // 会话按声明的大小预留配额, 检查和创建在同一事务中, 并发创建的会话不会同时通过检查
+    async fn create(&self, session: &NewUploadSession) -> Result<UploadSession, UploadError>;
+
+    // 只返回属于该用户且在 now 之后过期的会话
+    async fn find(
+        &self,
+        id: &str,
+        user_id: i64,
+        now: &str,
+    ) -> Result<Option<UploadSession>, sqlx::Error>;
+
+    // 已收到的分片序号和大小, 按序号排列
+    async fn chunks(&self, id: &str) -> Result<Vec<(i64, i64)>, sqlx::Error>;
+
+    // 记录分片并顺延会话的过期时间, 重复的分片覆盖之前的记录
+    async fn save_chunk(
+        &self,
+        id: &str,
+        index: i64,
+        size: i64,
+        sha256: &str,
+        expires_at: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    // 状态为 from 时改为 to, 返回是否修改成功
+    async fn transition(&self, id: &str, from: &str, to: &str) -> Result<bool, sqlx::Error>;
+
+    async fn set_status(&self, id: &str, status: &str) -> Result<(), sqlx::Error>;
+
+    // 记录登记的文件并删除分片记录
+    async fn complete(&self, id: &str, status: &str, file_id: i64) -> Result<(), sqlx::Error>;
+
+    async fn delete(&self, id: &str) -> Result<(), sqlx::Error>;
+
+    // 在 now 之前过期的会话; 状态为 busy 的会话只在 stalled 之后没有更新时返回
+    async fn expired(
+        &self,
+        now: &str,
+        busy: &str,
+        stalled: &str,
+    ) -> Result<Vec<String>, sqlx::Error>;
+}
+
+macro_rules! upload_session_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl UploadSessionRepository for $name {
+            async fn create(&self, session: &NewUploadSession) -> Result<UploadSession, UploadError> {
+                let mut tx = <$db>::begin_write(&self.pool).await?;
+                lock_user(&mut *tx, session.user_id).await?;
+                check_usage(
+                    find_usage(&mut *tx, session.user_id).await?,
+                    session.total_size as u64,
+                )?;
+                sqlx::query(&<$db>::sql(
+                    r#"
+                    INSERT INTO upload_sessions
+                        (id, user_id, purpose, visibility, file_name, total_size, chunk_size, total_chunks,
+                         sha256, expires_at)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                ))
+                .bind(&session.id)
+                .bind(session.user_id)
+                .bind(&session.purpose)
+                .bind(&session.visibility)
+                .bind(&session.file_name)
+                .bind(session.total_size)
+                .bind(session.chunk_size)
+                .bind(session.total_chunks)
+                .bind(&session.sha256)
+                .bind(DbTime::from(&session.expires_at))
+                .execute(&mut *tx)
+                .await?;
+                let created = sqlx::query_as::<_, UploadSession>(&<$db>::sql(
+                    "SELECT * FROM upload_sessions WHERE id = ?",
+                ))
+                .bind(&session.id)
+                .fetch_one(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                Ok(created)
+            }
+
+            async fn find(
+                &self,
+                id: &str,
+                user_id: i64,
+                now: &str,
+            ) -> Result<Option<UploadSession>, sqlx::Error> {
+                sqlx::query_as::<_, UploadSession>(&<$db>::sql(
+                    "SELECT * FROM upload_sessions WHERE id = ? AND user_id = ? AND expires_at > ?",
+                ))
+                .bind(id)
+                .bind(user_id)
+                .bind(DbTime::from(now))
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn chunks(&self, id: &str) -> Result<Vec<(i64, i64)>, sqlx::Error> {
+                sqlx::query_as::<_, (i64, i64)>(&<$db>::sql(
+                    r#"
+                    SELECT chunk_index, size FROM upload_session_chunks
+                    WHERE session_id = ?
+                    ORDER BY chunk_index
+                    "#,
+                ))
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn save_chunk(
+                &self,
+                id: &str,
+                index: i64,
+                size: i64,
+                sha256: &str,
+                expires_at: &str,
+            ) -> Result<(), sqlx::Error> {
+                let sql = format!(
+                    r#"
+                    INSERT INTO upload_session_chunks (session_id, chunk_index, size, sha256)
+                    VALUES (?, ?, ?, ?){}
+                    "#,
+                    <$db>::upsert(&["session_id", "chunk_index"], &["size", "sha256"])
+                );
+                sqlx::query(&<$db>::sql(&sql))
+                    .bind(id)
+                    .bind(index)
+                    .bind(size)
+                    .bind(sha256)
+                    .execute(&self.pool)
+                    .await?;
+
+                sqlx::query(&<$db>::sql(
+                    "UPDATE upload_sessions SET updated_at = CURRENT_TIMESTAMP, expires_at = ? WHERE id = ?",
+                ))
+                .bind(DbTime::from(expires_at))
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn transition(&self, id: &str, from: &str, to: &str) -> Result<bool, sqlx::Error> {
+                let updated = sqlx::query(&<$db>::sql(
+                    r#"
+                    UPDATE upload_sessions SET status = ?, updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ? AND status = ?
+                    "#,
+                ))
+                .bind(to)
+                .bind(id)
+                .bind(from)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+                Ok(updated > 0)
+            }
+
+            async fn set_status(&self, id: &str, status: &str) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    "UPDATE upload_sessions SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                ))
+                .bind(status)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn complete(&self, id: &str, status: &str, file_id: i64) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query(&<$db>::sql(
+                    r#"
+                    UPDATE upload_sessions SET status = ?, file_id = ?, updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ?
+                    "#,
+                ))
+                .bind(status)
+                .bind(file_id)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query(&<$db>::sql("DELETE FROM upload_session_chunks WHERE session_id = ?"))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await
+            }
+
+            async fn delete(&self, id: &str) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query(&<$db>::sql("DELETE FROM upload_session_chunks WHERE session_id = ?"))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query(&<$db>::sql("DELETE FROM upload_sessions WHERE id = ?"))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await
+            }
+
+            async fn expired(
+                &self,
+                now: &str,
+                busy: &str,
+                stalled: &str,
+            ) -> Result<Vec<String>, sqlx::Error> {
+                sqlx::query_scalar::<_, String>(&<$db>::sql(
+                    r#"
+                    SELECT id FROM upload_sessions
+                    WHERE expires_at <= ? AND (status != ? OR updated_at <= ?)
+                    "#,
+                ))
+                .bind(DbTime::from(now))
+                .bind(busy)
+                .bind(DbTime::from(stalled))
+                .fetch_all(&self.pool)
+                .await
+            }
+        }
+    };
+}
+
+upload_session_repository!(SqliteUploadSessionRepository, Sqlite);
+upload_session_repository!(PostgresUploadSessionRepository, Postgres);
+upload_session_repository!(MySqlUploadSessionRepository, MySql);
@@ -0,0 +1,313 @@
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use sqlx::{MySql, Pool, Postgres, Sqlite};
+
+use super::dialect::{Conditions, DbText, DbTime, Dialect, SqlValue};
+use super::stream_rows;
+use crate::models::{CreateUser, UpdateUser, User};
+use crate::services::files::URL_PREFIX;
+
+// 用户列表和导出共用的筛选条件, 文本字段按包含匹配
+#[derive(Debug, Default, Clone)]
+pub struct UserQuery {
+    pub username: String,
+    pub nickname: String,
+    pub email: String,
+    pub status: Option<i32>,
+}
+
+// 导出的一行, 角色名称以逗号连接
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserExportRow {
+    pub id: i64,
+    pub username: String,
+    pub nickname: String,
+    #[sqlx(try_from = "DbText")]
+    pub email: String,
+    pub roles: Option<String>,
+    pub status: i32,
+    #[sqlx(try_from = "DbTime")]
+    pub created_at: String,
+    #[sqlx(try_from = "DbTime")]
+    pub updated_at: String,
+}
+
+// 个人资料中可以修改的字段
+#[derive(Debug)]
+pub struct UpdateProfile {
+    pub nickname: String,
+    pub email: String,
+    pub avatar: Option<String>,
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find(&self, id: i64) -> Result<Option<User>, sqlx::Error>;
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error>;
+
+    async fn count(&self, query: &UserQuery) -> Result<i64, sqlx::Error>;
+
+    // 附带头像的最小缩略图地址
+    async fn list(
+        &self,
+        query: &UserQuery,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, sqlx::Error>;
+
+    // 按 ID 顺序逐行读取, 不在内存中缓存整张表
+    fn export(&self, query: &UserQuery) -> BoxStream<'static, Result<UserExportRow, sqlx::Error>>;
+
+    // password 为加密后的密码
+    async fn create(&self, user: &CreateUser, password: &str) -> Result<User, sqlx::Error>;
+
+    async fn update(&self, id: i64, user: &UpdateUser) -> Result<User, sqlx::Error>;
+
+    async fn update_profile(&self, id: i64, profile: &UpdateProfile) -> Result<User, sqlx::Error>;
+
+    async fn update_password(&self, id: i64, password: &str) -> Result<(), sqlx::Error>;
+
+    async fn delete(&self, id: i64) -> Result<(), sqlx::Error>;
+
+    // 用户不存在时返回 None
+    async fn set_storage_quota(
+        &self,
+        id: i64,
+        quota: Option<i64>,
+    ) -> Result<Option<User>, sqlx::Error>;
+}
+
+fn conditions<D: Dialect>(query: &UserQuery) -> Conditions {
+    let mut conditions = Conditions::default();
+    for (column, value) in [
+        ("username", &query.username),
+        ("nickname", &query.nickname),
+        ("email", &query.email),
+    ] {
+        if !value.is_empty() {
+            conditions.push(
+                format!("{} {} ?", column, D::LIKE),
+                [SqlValue::Text(format!("%{}%", value))],
+            );
+        }
+    }
+    if let Some(status) = query.status {
+        conditions.push("status = ?", [SqlValue::from(status)]);
+    }
+    conditions
+}
+
+// 用户列表中使用的最小缩略图地址, 头像不是登记过的文件时为空
+fn avatar_thumbnail_sql<D: Dialect>() -> String {
+    format!(
+        r#"(
+            SELECT {url}
+            FROM file_variants v
+            JOIN files f ON f.id = v.file_id
+            WHERE f.storage_name = substr(users.avatar, {start}) AND users.avatar LIKE '{prefix}%'
+            ORDER BY v.width
+            LIMIT 1
+        )"#,
+        url = D::concat(&[&format!("'{}'", URL_PREFIX), "v.storage_name"]),
+        prefix = URL_PREFIX,
+        start = URL_PREFIX.len() + 1
+    )
+}
+
+macro_rules! user_repository {
+    ($name:ident, $db:ty) => {
+        pub struct $name {
+            pool: Pool<$db>,
+        }
+
+        impl $name {
+            pub fn new(pool: Pool<$db>) -> Self {
+                Self { pool }
+            }
+        }
+
+        #[async_trait]
+        impl UserRepository for $name {
+            async fn find(&self, id: i64) -> Result<Option<User>, sqlx::Error> {
+                sqlx::query_as::<_, User>(&<$db>::sql("SELECT * FROM users WHERE id = ?"))
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+                sqlx::query_as::<_, User>(&<$db>::sql("SELECT * FROM users WHERE username = ?"))
+                    .bind(username)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn count(&self, query: &UserQuery) -> Result<i64, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!("SELECT COUNT(*) FROM users{}", conditions.where_clause());
+                let sql = <$db>::sql(&sql);
+                let mut count_query = sqlx::query_scalar::<_, i64>(&sql);
+                for param in conditions.params {
+                    count_query = count_query.bind(param);
+                }
+                count_query.fetch_one(&self.pool).await
+            }
+
+            async fn list(
+                &self,
+                query: &UserQuery,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<User>, sqlx::Error> {
+                let conditions = conditions::<$db>(query);
+                let sql = format!(
+                    "SELECT users.*, {} AS avatar_thumbnail FROM users{} ORDER BY id LIMIT ? OFFSET ?",
+                    avatar_thumbnail_sql::<$db>(),
+                    conditions.where_clause()
+                );
+                let sql = <$db>::sql(&sql);
+                let mut list_query = sqlx::query_as::<_, User>(&sql);
+                for param in conditions.params {
+                    list_query = list_query.bind(param);
+                }
+                list_query
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            fn export(
+                &self,
+                query: &UserQuery,
+            ) -> BoxStream<'static, Result<UserExportRow, sqlx::Error>> {
+                let pool = self.pool.clone();
+                let conditions = conditions::<$db>(query);
+                let sql = format!(
+                    r#"
+                    SELECT id, username, nickname, email, status, created_at, updated_at,
+                        (SELECT {} FROM user_roles ur
+                         JOIN roles r ON r.id = ur.role_id WHERE ur.user_id = users.id) AS roles
+                    FROM users{}
+                    ORDER BY id
+                    "#,
+                    <$db>::group_concat("r.name"),
+                    conditions.where_clause()
+                );
+                stream_rows(move |tx| async move {
+                    let sql = <$db>::sql(&sql);
+                    let mut export_query = sqlx::query_as::<_, UserExportRow>(&sql);
+                    for param in conditions.params {
+                        export_query = export_query.bind(param);
+                    }
+                    let mut rows = export_query.fetch(&pool);
+                    while let Some(row) = rows.next().await {
+                        if tx.send(row).await.is_err() {
+                            break;
+                        }
+                    }
+                })
+            }
+
+            async fn create(&self, user: &CreateUser, password: &str) -> Result<User, sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                let sql = <$db>::sql(
+                    r#"
+                    INSERT INTO users (username, password, nickname, email, avatar, status)
+                    VALUES (?, ?, ?, ?, ?, ?)
+                    "#,
+                );
+                let query = sqlx::query(&sql)
+                    .bind(&user.username)
+                    .bind(password)
+                    .bind(&user.nickname)
+                    .bind(&user.email)
+                    .bind(&user.avatar)
+                    .bind(user.status);
+                let id = <$db>::insert(&mut conn, query).await?;
+
+                sqlx::query_as::<_, User>(&<$db>::sql("SELECT * FROM users WHERE id = ?"))
+                    .bind(id)
+                    .fetch_one(&mut *conn)
+                    .await
+            }
+
+            async fn update(&self, id: i64, user: &UpdateUser) -> Result<User, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    r#"
+                    UPDATE users
+                    SET nickname = ?, email = ?, avatar = ?, status = ?,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ?
+                    "#,
+                ))
+                .bind(&user.nickname)
+                .bind(&user.email)
+                .bind(&user.avatar)
+                .bind(user.status)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+                self.find(id).await?.ok_or(sqlx::Error::RowNotFound)
+            }
+
+            async fn update_profile(
+                &self,
+                id: i64,
+                profile: &UpdateProfile,
+            ) -> Result<User, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    r#"
+                    UPDATE users SET nickname = ?, email = ?, avatar = ?, updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ?
+                    "#,
+                ))
+                .bind(&profile.nickname)
+                .bind(&profile.email)
+                .bind(&profile.avatar)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+                self.find(id).await?.ok_or(sqlx::Error::RowNotFound)
+            }
+
+            async fn update_password(&self, id: i64, password: &str) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql("UPDATE users SET password = ? WHERE id = ?"))
+                    .bind(password)
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn delete(&self, id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query(&<$db>::sql("DELETE FROM users WHERE id = ?"))
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn set_storage_quota(
+                &self,
+                id: i64,
+                quota: Option<i64>,
+            ) -> Result<Option<User>, sqlx::Error> {
+                sqlx::query(&<$db>::sql(
+                    "UPDATE users SET storage_quota = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                ))
+                .bind(quota)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+                self.find(id).await
+            }
+        }
+    };
+}
+
+user_repository!(SqliteUserRepository, Sqlite);
+user_repository!(PostgresUserRepository, Postgres);
+user_repository!(MySqlUserRepository, MySql);
@@ -0,0 +1,104 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+// 用户角色和角色权限都是"一个主体关联一组对象"的关联表, 分配逻辑共用
+pub struct Assignment {
+    pub table: &'static str,
+    pub owner_table: &'static str,
+    pub owner_column: &'static str,
+    pub target_table: &'static str,
+    pub target_column: &'static str,
+}
+
+pub const USER_ROLES: Assignment = Assignment {
+    table: "user_roles",
+    owner_table: "users",
+    owner_column: "user_id",
+    target_table: "roles",
+    target_column: "role_id",
+};
+
+pub const ROLE_PERMISSIONS: Assignment = Assignment {
+    table: "role_permissions",
+    owner_table: "roles",
+    owner_column: "role_id",
+    target_table: "permissions",
+    target_column: "permission_id",
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum AssignmentMode {
+    // 整体替换为给定的集合
+    Replace,
+    // 在现有基础上增加
+    Add,
+    // 从现有集合中移除
+    Remove,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssignmentDiff {
+    pub added: Vec<i64>,
+    pub removed: Vec<i64>,
+    #[serde(skip)]
+    pub before: Vec<i64>,
+    #[serde(skip)]
+    pub after: Vec<i64>,
+}
+
+impl AssignmentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum AssignmentError {
+    OwnerNotFound,
+    // 请求中引用了不存在的 id
+    MissingTargets(Vec<i64>),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for AssignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssignmentError::OwnerNotFound => write!(f, "记录不存在"),
+            AssignmentError::MissingTargets(ids) => write!(
+                f,
+                "以下 id 不存在: {}",
+                ids.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            AssignmentError::Database(e) => write!(f, "数据库错误: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AssignmentError {
+    fn from(e: sqlx::Error) -> Self {
+        AssignmentError::Database(e)
+    }
+}
+
+// 按分配方式计算目标集合和需要增删的行
+pub fn plan(
+    current: BTreeSet<i64>,
+    requested: BTreeSet<i64>,
+    mode: AssignmentMode,
+) -> AssignmentDiff {
+    let target: BTreeSet<i64> = match mode {
+        AssignmentMode::Replace => requested,
+        AssignmentMode::Add => current.union(&requested).copied().collect(),
+        AssignmentMode::Remove => current.difference(&requested).copied().collect(),
+    };
+    AssignmentDiff {
+        added: target.difference(&current).copied().collect(),
+        removed: current.difference(&target).copied().collect(),
+        before: current.into_iter().collect(),
+        after: target.into_iter().collect(),
+    }
+}
@@ -0,0 +1,246 @@
+use salvo::prelude::*;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+
+use crate::models::{LoginLog, OperationLog};
+
+// 每个订阅者最多积压的事件数, 消费过慢时跳过旧事件
+const EVENT_CAPACITY: usize = 1024;
+
+// 没有事件时定期发送注释行, 防止代理断开连接并及时发现客户端已断开
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// 客户端断线后重连的等待毫秒数
+const RETRY_MS: u64 = 3000;
+
+const LOGIN_MODULE: &str = "登录";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum AuditEvent {
+    OperationLog(OperationLog),
+    Login(LoginLog),
+}
+
+impl AuditEvent {
+    fn event_type(&self) -> &'static str {
+        match self {
+            AuditEvent::OperationLog(_) => "operation_log",
+            AuditEvent::Login(_) => "login",
+        }
+    }
+
+    fn id(&self) -> i64 {
+        match self {
+            AuditEvent::OperationLog(log) => log.id,
+            AuditEvent::Login(log) => log.id,
+        }
+    }
+
+    fn user_id(&self) -> Option<i64> {
+        match self {
+            AuditEvent::OperationLog(log) => Some(log.user_id),
+            AuditEvent::Login(log) => log.user_id,
+        }
+    }
+
+    fn username(&self) -> &str {
+        match self {
+            AuditEvent::OperationLog(log) => &log.username,
+            AuditEvent::Login(log) => &log.username,
+        }
+    }
+
+    fn module(&self) -> &str {
+        match self {
+            AuditEvent::OperationLog(log) => &log.module,
+            AuditEvent::Login(_) => LOGIN_MODULE,
+        }
+    }
+
+    // 登录事件按成功 200、失败 401 参与状态码筛选
+    fn status(&self) -> i32 {
+        match self {
+            AuditEvent::OperationLog(log) => log.status,
+            AuditEvent::Login(log) if log.success => 200,
+            AuditEvent::Login(_) => 401,
+        }
+    }
+
+    fn to_frame(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_default();
+        format!(
+            "event: {}\nid: {}:{}\ndata: {}\n\n",
+            self.event_type(),
+            self.event_type(),
+            self.id(),
+            data
+        )
+    }
+}
+
+// 实时审计事件的广播通道, 由日志写入服务在日志落库后发布
+#[derive(Clone)]
+pub struct AuditEvents {
+    sender: broadcast::Sender<AuditEvent>,
+    closing: Arc<watch::Sender<bool>>,
+}
+
+impl AuditEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        let (closing, _) = watch::channel(false);
+        Self {
+            sender,
+            closing: Arc::new(closing),
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn publish(&self, event: AuditEvent) {
+        // 没有订阅者时发送失败, 直接忽略
+        let _ = self.sender.send(event);
+    }
+
+    // 服务退出时结束所有事件流, 否则长连接会阻塞优雅停机
+    pub fn close(&self) {
+        self.closing.send_replace(true);
+    }
+}
+
+impl Default for AuditEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AuditEventFilter {
+    pub types: Vec<&'static str>,
+    pub module: String,
+    pub user_id: Option<i64>,
+    pub username: String,
+    pub status: Option<i32>,
+    pub status_classes: Vec<i32>,
+}
+
+impl AuditEventFilter {
+    pub fn from_request(req: &mut Request) -> Result<Self, String> {
+        let text = |req: &mut Request, key: &str| {
+            req.query::<String>(key)
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let mut filter = AuditEventFilter {
+            module: text(req, "module"),
+            user_id: req.query::<i64>("user_id"),
+            username: text(req, "username"),
+            status: req.query::<i32>("status"),
+            ..Default::default()
+        };
+
+        for event_type in split(&text(req, "type")) {
+            match event_type {
+                "operation_log" => filter.types.push("operation_log"),
+                "login" => filter.types.push("login"),
+                other => return Err(format!("无效的事件类型: {}", other)),
+            }
+        }
+
+        for class in split(&text(req, "status_class")) {
+            match class.to_lowercase().as_str() {
+                "2xx" => filter.status_classes.push(2),
+                "3xx" => filter.status_classes.push(3),
+                "4xx" => filter.status_classes.push(4),
+                "5xx" => filter.status_classes.push(5),
+                _ => return Err(format!("无效的状态分类: {}", class)),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    fn matches(&self, event: &AuditEvent) -> bool {
+        if !self.types.is_empty() && !self.types.contains(&event.event_type()) {
+            return false;
+        }
+        if !self.module.is_empty() && !event.module().contains(&self.module) {
+            return false;
+        }
+        if self.user_id.is_some() && event.user_id() != self.user_id {
+            return false;
+        }
+        if !self.username.is_empty() && !event.username().contains(&self.username) {
+            return false;
+        }
+        if self.status.is_some_and(|status| status != event.status()) {
+            return false;
+        }
+        if !self.status_classes.is_empty() && !self.status_classes.contains(&(event.status() / 100))
+        {
+            return false;
+        }
+        true
+    }
+}
+
+fn split(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(|s| s.trim()).filter(|s| !s.is_empty())
+}
+
+// 以 Server-Sent Events 格式持续推送匹配的事件, 直到客户端断开或服务退出
+pub fn stream_audit_events(res: &mut Response, events: &AuditEvents, filter: AuditEventFilter) {
+    let _ = res.add_header("Content-Type", "text/event-stream", true);
+    let _ = res.add_header("Cache-Control", "no-cache", true);
+    // 禁止 nginx 等反向代理缓冲事件
+    let _ = res.add_header("X-Accel-Buffering", "no", true);
+
+    let mut sender = res.channel();
+    let mut receiver = events.sender.subscribe();
+    let mut closing = events.closing.subscribe();
+
+    tokio::spawn(async move {
+        if *closing.borrow_and_update() {
+            return;
+        }
+        if sender
+            .send_data(format!("retry: {}\n\n", RETRY_MS))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+        keep_alive.tick().await;
+        loop {
+            let frame = tokio::select! {
+                received = receiver.recv() => match received {
+                    Ok(event) if filter.matches(&event) => event.to_frame(),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        format!("event: lagged\ndata: {{\"skipped\":{}}}\n\n", skipped)
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = keep_alive.tick() => ": keep-alive\n\n".to_string(),
+                _ = closing.changed() => break,
+            };
+            if sender.send_data(frame).await.is_err() {
+                break;
+            }
+        }
+        println!("[Audit Stream] Subscriber disconnected");
+    });
+}
@@ -0,0 +1,295 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use salvo::http::StatusCode;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::config::upload::upload_config;
+use crate::models::{CreateFile, CreateFileVariant, FileRecord};
+use crate::repositories::Repositories;
+use crate::services::file_access::Visibility;
+use crate::services::files::{put_blob, BlobSource, FileError, URL_PREFIX};
+use crate::services::storage::StorageError;
+use crate::services::upload::{validate_upload, UploadError, UploadPurpose};
+
+// 解码前限制图片尺寸和内存, 防止小文件解压出超大图片
+const MAX_SOURCE_DIMENSION: u32 = 8192;
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+
+const JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug)]
+pub enum AvatarError {
+    Upload(UploadError),
+    Image(String),
+    InvalidCrop(String),
+    File(FileError),
+}
+
+impl AvatarError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AvatarError::Upload(e) => e.status_code(),
+            AvatarError::Image(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AvatarError::InvalidCrop(_) => StatusCode::BAD_REQUEST,
+            AvatarError::File(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Display for AvatarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AvatarError::Upload(e) => write!(f, "{}", e),
+            AvatarError::Image(e) => write!(f, "无法处理图片: {}", e),
+            AvatarError::InvalidCrop(e) => write!(f, "{}", e),
+            AvatarError::File(e) => write!(f, "保存头像失败: {}", e),
+        }
+    }
+}
+
+impl From<UploadError> for AvatarError {
+    fn from(e: UploadError) -> Self {
+        AvatarError::Upload(e)
+    }
+}
+
+impl From<image::ImageError> for AvatarError {
+    fn from(e: image::ImageError) -> Self {
+        AvatarError::Image(e.to_string())
+    }
+}
+
+impl From<FileError> for AvatarError {
+    fn from(e: FileError) -> Self {
+        match e {
+            FileError::Upload(e) => AvatarError::Upload(e),
+            e => AvatarError::File(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AvatarError {
+    fn from(e: sqlx::Error) -> Self {
+        AvatarError::File(FileError::Database(e))
+    }
+}
+
+impl From<StorageError> for AvatarError {
+    fn from(e: StorageError) -> Self {
+        AvatarError::File(FileError::Storage(e))
+    }
+}
+
+// 裁剪区域, 坐标以按 EXIF 方向旋转后的图片为准
+#[derive(Debug, Clone, Copy)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct EncodedImage {
+    variant: String,
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+struct ProcessedAvatar {
+    extension: &'static str,
+    mime: &'static str,
+    original: EncodedImage,
+    thumbnails: Vec<EncodedImage>,
+}
+
+// 头像只能使用上传过的头像文件, 指定上传者时还必须是该用户上传的; 空字符串表示清除头像
+pub async fn check_avatar_url(
+    repos: &Repositories,
+    url: &str,
+    uploader_id: Option<i64>,
+) -> Result<(), String> {
+    if url.is_empty() {
+        return Ok(());
+    }
+    let storage_name = url.strip_prefix(URL_PREFIX).unwrap_or_default();
+    let owner = repos
+        .files
+        .uploader_of(storage_name, UploadPurpose::Avatar.name())
+        .await
+        .map_err(|e| format!("检查头像失败: {}", e))?;
+
+    match (owner, uploader_id) {
+        (None, _) => Err("头像必须通过头像上传接口上传".into()),
+        (Some(owner), Some(uploader_id)) if owner != Some(uploader_id) => {
+            Err("只能使用自己上传的头像".into())
+        }
+        _ => Ok(()),
+    }
+}
+
+// 校验并处理头像, 原图和各尺寸缩略图重新编码后保存, 不保留 EXIF 等元数据
+pub async fn store_avatar(
+    repos: &Repositories,
+    source: &Path,
+    original_name: &str,
+    size: u64,
+    crop: Option<CropRect>,
+    uploader_id: i64,
+) -> Result<FileRecord, AvatarError> {
+    let purpose = UploadPurpose::Avatar;
+    let detected = validate_upload(purpose, original_name, source, size, purpose.max_size())?;
+
+    let path: PathBuf = source.to_path_buf();
+    let processed =
+        tokio::task::spawn_blocking(move || process_avatar(&path, &detected.extension, crop))
+            .await
+            .map_err(|e| AvatarError::Image(e.to_string()))??;
+
+    // 原图和缩略图都按内容保存, 登记失败时已写入的内容没有引用, 由清理任务删除
+    let extension = processed.extension;
+    let mime = processed.mime;
+    let stem = Uuid::new_v4();
+    let mut variants = Vec::new();
+    for thumbnail in processed.thumbnails {
+        let size = thumbnail.data.len() as i64;
+        let sha256 = format!("{:x}", Sha256::digest(&thumbnail.data));
+        let blob_key = put_blob(
+            repos,
+            BlobSource::Bytes(thumbnail.data),
+            &sha256,
+            extension,
+            mime,
+            size,
+        )
+        .await?;
+        variants.push(CreateFileVariant {
+            storage_name: format!("{}_{}.{}", stem, thumbnail.variant, extension),
+            variant: thumbnail.variant,
+            blob_key,
+            size,
+            width: thumbnail.width,
+            height: thumbnail.height,
+        });
+    }
+
+    let size = processed.original.data.len() as i64;
+    let sha256 = format!("{:x}", Sha256::digest(&processed.original.data));
+    let blob_key = put_blob(
+        repos,
+        BlobSource::Bytes(processed.original.data),
+        &sha256,
+        extension,
+        mime,
+        size,
+    )
+    .await?;
+
+    let record = CreateFile {
+        storage_name: format!("{}.{}", stem, extension),
+        original_name: original_name.to_string(),
+        size,
+        mime: mime.to_string(),
+        sha256,
+        purpose: purpose.name().to_string(),
+        visibility: Visibility::Public.name().to_string(),
+        blob_key,
+        uploader_id,
+    };
+    Ok(repos.files.register(&record, &variants).await?)
+}
+
+fn process_avatar(
+    path: &Path,
+    extension: &str,
+    crop: Option<CropRect>,
+) -> Result<ProcessedAvatar, AvatarError> {
+    let mut reader = ImageReader::open(path)
+        .map_err(|e| AvatarError::Image(e.to_string()))?
+        .with_guessed_format()
+        .map_err(|e| AvatarError::Image(e.to_string()))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+    reader.limits(limits);
+
+    // 先按 EXIF 方向旋转, 重新编码后方向信息和其他元数据都不再保留
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    if let Some(crop) = crop {
+        let fits = crop.width > 0
+            && crop.height > 0
+            && crop
+                .x
+                .checked_add(crop.width)
+                .is_some_and(|r| r <= image.width())
+            && crop
+                .y
+                .checked_add(crop.height)
+                .is_some_and(|b| b <= image.height());
+        if !fits {
+            return Err(AvatarError::InvalidCrop(format!(
+                "裁剪区域超出图片范围 {}x{}",
+                image.width(),
+                image.height()
+            )));
+        }
+        image = image.crop_imm(crop.x, crop.y, crop.width, crop.height);
+    }
+
+    let max_dimension = upload_config().avatar_max_dimension;
+    if image.width() > max_dimension || image.height() > max_dimension {
+        image = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+    }
+
+    // JPEG 仍保存为 JPEG, 其他格式统一保存为 PNG 以保留透明度
+    let (extension, mime) = match extension {
+        "jpg" => ("jpg", "image/jpeg"),
+        _ => ("png", "image/png"),
+    };
+    let original = encode(&image, "original", extension)?;
+    let thumbnails = upload_config()
+        .avatar_thumbnail_sizes
+        .iter()
+        .map(|&size| {
+            let thumbnail = image.resize_to_fill(size, size, FilterType::Lanczos3);
+            encode(&thumbnail, &size.to_string(), extension)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ProcessedAvatar {
+        extension,
+        mime,
+        original,
+        thumbnails,
+    })
+}
+
+fn encode(
+    image: &DynamicImage,
+    variant: &str,
+    extension: &str,
+) -> Result<EncodedImage, AvatarError> {
+    let mut data = Vec::new();
+    if extension == "jpg" {
+        let encoder = JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY);
+        image.to_rgb8().write_with_encoder(encoder)?;
+    } else {
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+    }
+    Ok(EncodedImage {
+        variant: variant.to_string(),
+        data,
+        width: image.width(),
+        height: image.height(),
+    })
+}
@@ -0,0 +1,132 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::config::operation_log::path_matches;
+use crate::models::OperationLogRule;
+use crate::repositories::permission::ApiPermission;
+use crate::repositories::Repositories;
+
+// 缓存有效期, 其他实例直接修改数据库时最迟在此时间后生效
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+// 权限表中 API 类型权限的资源路径带有 /api 前缀, 实际路由没有
+const API_PREFIX: &str = "/api";
+
+#[derive(Debug)]
+struct PermissionLabel {
+    method: String,
+    path: String,
+    module: String,
+    operation: String,
+}
+
+#[derive(Debug)]
+pub struct CaptureRules {
+    rules: Vec<OperationLogRule>,
+    labels: Vec<PermissionLabel>,
+    loaded_at: Instant,
+}
+
+impl CaptureRules {
+    // 按优先级从高到低取第一条匹配的规则, 没有规则匹配时只记录非 GET 请求
+    pub fn should_capture(&self, method: &str, path: &str) -> bool {
+        match self
+            .rules
+            .iter()
+            .find(|rule| rule_matches(rule, method, path))
+        {
+            Some(rule) => rule.action == "include",
+            None => method != "GET",
+        }
+    }
+
+    // 从匹配的权限记录得到模块名和操作名, 通配符越少越优先
+    pub fn labels(&self, method: &str, path: &str) -> Option<(String, String)> {
+        self.labels
+            .iter()
+            .filter(|label| label.method.eq_ignore_ascii_case(method))
+            .filter(|label| path_matches(&label.path, path))
+            .min_by_key(|label| label.path.matches('*').count())
+            .map(|label| (label.module.clone(), label.operation.clone()))
+    }
+}
+
+fn rule_matches(rule: &OperationLogRule, method: &str, path: &str) -> bool {
+    (rule.method == "*" || rule.method.eq_ignore_ascii_case(method))
+        && path_matches(&rule.path, path)
+}
+
+static CACHE: RwLock<Option<Arc<CaptureRules>>> = RwLock::new(None);
+
+// 获取缓存的规则, 缓存不存在或已过期时从数据库重新加载
+pub async fn capture_rules(repos: &Repositories) -> Arc<CaptureRules> {
+    if let Some(cached) = CACHE.read().unwrap().as_ref() {
+        if cached.loaded_at.elapsed() < CACHE_TTL {
+            return cached.clone();
+        }
+    }
+
+    match load(repos).await {
+        Ok(rules) => {
+            let rules = Arc::new(rules);
+            *CACHE.write().unwrap() = Some(rules.clone());
+            rules
+        }
+        Err(e) => {
+            eprintln!("[Capture Rules] Failed to load rules: {}", e);
+            // 加载失败时继续使用旧规则
+            match CACHE.read().unwrap().as_ref() {
+                Some(cached) => cached.clone(),
+                None => Arc::new(CaptureRules {
+                    rules: Vec::new(),
+                    labels: Vec::new(),
+                    loaded_at: Instant::now(),
+                }),
+            }
+        }
+    }
+}
+
+// 规则或权限修改后调用, 下次请求时重新加载
+pub fn invalidate_capture_rules() {
+    *CACHE.write().unwrap() = None;
+}
+
+async fn load(repos: &Repositories) -> Result<CaptureRules, sqlx::Error> {
+    let rules = repos.operation_log_rules.enabled().await?;
+
+    // 模块名取上级权限的名称, 操作名取权限本身的名称
+    let rows = repos.permissions.api_permissions().await?;
+
+    let labels = rows
+        .into_iter()
+        .map(|permission: ApiPermission| {
+            let resource = permission.resource;
+            let code = permission.code;
+            let path = resource
+                .strip_prefix(API_PREFIX)
+                .filter(|path| path.starts_with('/'))
+                .unwrap_or(&resource)
+                .to_string();
+            // 没有上级权限时用权限编码的上一级作为模块名
+            let module = permission
+                .parent_name
+                .unwrap_or_else(|| match code.rsplit_once(':') {
+                    Some((prefix, _)) => prefix.to_string(),
+                    None => code.clone(),
+                });
+            PermissionLabel {
+                method: permission.action,
+                path,
+                module,
+                operation: permission.name,
+            }
+        })
+        .collect();
+
+    Ok(CaptureRules {
+        rules,
+        labels,
+        loaded_at: Instant::now(),
+    })
+}
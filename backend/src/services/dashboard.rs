@@ -0,0 +1,273 @@
+use chrono::{DateTime, Duration as ChronoDuration, DurationRound, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::repositories::{DbTime, Repositories};
+
+// 统计结果缓存时间, 仪表盘数据不要求实时
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+// 活跃用户和登录失败用户名的返回数量
+const TOP_LIMIT: i64 = 10;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatsWindow {
+    Day,
+    Week,
+    Month,
+    Quarter,
+}
+
+impl StatsWindow {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "24h" => Ok(StatsWindow::Day),
+            "" | "7d" => Ok(StatsWindow::Week),
+            "30d" => Ok(StatsWindow::Month),
+            "90d" => Ok(StatsWindow::Quarter),
+            other => Err(format!("不支持的统计范围: {}", other)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            StatsWindow::Day => "24h",
+            StatsWindow::Week => "7d",
+            StatsWindow::Month => "30d",
+            StatsWindow::Quarter => "90d",
+        }
+    }
+
+    fn length(&self) -> ChronoDuration {
+        match self {
+            StatsWindow::Day => ChronoDuration::hours(24),
+            StatsWindow::Week => ChronoDuration::days(7),
+            StatsWindow::Month => ChronoDuration::days(30),
+            StatsWindow::Quarter => ChronoDuration::days(90),
+        }
+    }
+
+    // 24 小时按小时统计, 其余按天统计
+    fn step(&self) -> ChronoDuration {
+        match self {
+            StatsWindow::Day => ChronoDuration::hours(1),
+            _ => ChronoDuration::days(1),
+        }
+    }
+
+    fn hourly(&self) -> bool {
+        *self == StatsWindow::Day
+    }
+
+    fn bucket_format(&self) -> &'static str {
+        match self {
+            StatsWindow::Day => "%Y-%m-%d %H:00",
+            _ => "%Y-%m-%d",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DashboardStats {
+    pub window: &'static str,
+    pub start_time: String,
+    pub generated_at: String,
+    pub summary: Summary,
+    pub requests: Vec<RequestBucket>,
+    pub module_errors: Vec<ModuleErrorRate>,
+    pub top_users: Vec<ActiveUser>,
+    pub logins: LoginStats,
+    pub new_users: Vec<NewUsersWeek>,
+    pub roles: Vec<RoleDistribution>,
+    #[serde(skip)]
+    computed_at: Option<Instant>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Summary {
+    pub requests: i64,
+    pub errors: i64,
+    pub active_users: i64,
+    pub total_users: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct RequestBucket {
+    pub bucket: String,
+    pub total: i64,
+    pub errors: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ModuleErrorRate {
+    pub module: String,
+    pub total: i64,
+    pub errors: i64,
+    // 由 errors / total 计算
+    #[sqlx(default)]
+    pub error_rate: f64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ActiveUser {
+    pub user_id: i64,
+    pub username: String,
+    pub requests: i64,
+    #[sqlx(try_from = "DbTime")]
+    pub last_active_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoginStats {
+    pub total: i64,
+    pub failed: i64,
+    pub failed_by_time: Vec<LoginBucket>,
+    pub top_failed_usernames: Vec<FailedLogin>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct LoginBucket {
+    pub bucket: String,
+    pub failed: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct FailedLogin {
+    pub username: String,
+    pub failures: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct NewUsersWeek {
+    pub week: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct RoleDistribution {
+    pub role_id: i64,
+    pub name: String,
+    pub users: i64,
+}
+
+// 按统计范围缓存, 互斥锁同时避免缓存过期时的重复计算
+static CACHE: LazyLock<Mutex<HashMap<StatsWindow, Arc<DashboardStats>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub async fn dashboard_stats(
+    repos: &Repositories,
+    window: StatsWindow,
+) -> Result<Arc<DashboardStats>, sqlx::Error> {
+    let mut cache = CACHE.lock().await;
+    if let Some(stats) = cache.get(&window) {
+        if stats
+            .computed_at
+            .is_some_and(|computed_at| computed_at.elapsed() < CACHE_TTL)
+        {
+            return Ok(stats.clone());
+        }
+    }
+
+    let stats = Arc::new(compute(repos, window).await?);
+    cache.insert(window, stats.clone());
+    Ok(stats)
+}
+
+async fn compute(repos: &Repositories, window: StatsWindow) -> Result<DashboardStats, sqlx::Error> {
+    let now = Utc::now();
+    // 起点对齐到整点或整天, 使第一个分组是完整的
+    let start = (now - window.length())
+        .duration_trunc(window.step())
+        .unwrap_or(now - window.length());
+    let start_time = start.format(TIMESTAMP_FORMAT).to_string();
+    let stats = &repos.dashboard;
+
+    let summary = stats.summary(&start_time).await?;
+    let requests = stats.requests(&start_time, window.hourly()).await?;
+
+    let mut module_errors = stats.module_errors(&start_time).await?;
+    for module in &mut module_errors {
+        let rate = module.errors as f64 / module.total.max(1) as f64;
+        module.error_rate = (rate * 10000.0).round() / 10000.0;
+    }
+    module_errors.sort_by(|a, b| {
+        b.error_rate
+            .total_cmp(&a.error_rate)
+            .then(b.total.cmp(&a.total))
+    });
+
+    let top_users = stats.top_users(&start_time, TOP_LIMIT).await?;
+    let (login_total, login_failed) = stats.logins(&start_time).await?;
+    let failed_by_time = stats.failed_logins(&start_time, window.hourly()).await?;
+    let top_failed_usernames = stats.top_failed_usernames(&start_time, TOP_LIMIT).await?;
+    let new_users = stats.new_users(&start_time).await?;
+    let roles = stats.roles().await?;
+
+    Ok(DashboardStats {
+        window: window.name(),
+        start_time,
+        generated_at: now.format(TIMESTAMP_FORMAT).to_string(),
+        summary,
+        requests: fill_buckets(window, start, now, requests, |bucket| RequestBucket {
+            bucket,
+            total: 0,
+            errors: 0,
+        }),
+        module_errors,
+        top_users,
+        logins: LoginStats {
+            total: login_total,
+            failed: login_failed,
+            failed_by_time: fill_buckets(window, start, now, failed_by_time, |bucket| {
+                LoginBucket { bucket, failed: 0 }
+            }),
+            top_failed_usernames,
+        },
+        new_users,
+        roles,
+        computed_at: Some(Instant::now()),
+    })
+}
+
+trait Bucketed {
+    fn bucket(&self) -> &str;
+}
+
+impl Bucketed for RequestBucket {
+    fn bucket(&self) -> &str {
+        &self.bucket
+    }
+}
+
+impl Bucketed for LoginBucket {
+    fn bucket(&self) -> &str {
+        &self.bucket
+    }
+}
+
+// 补齐没有数据的时间段, 便于前端直接绘制连续的折线
+fn fill_buckets<T: Bucketed>(
+    window: StatsWindow,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    rows: Vec<T>,
+    empty: impl Fn(String) -> T,
+) -> Vec<T> {
+    let mut rows: HashMap<String, T> = rows
+        .into_iter()
+        .map(|row| (row.bucket().to_string(), row))
+        .collect();
+    let mut buckets = Vec::new();
+    let mut time = start;
+    while time <= end {
+        let bucket = time.format(window.bucket_format()).to_string();
+        buckets.push(rows.remove(&bucket).unwrap_or_else(|| empty(bucket)));
+        time += window.step();
+    }
+    buckets
+}
@@ -0,0 +1,380 @@
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use salvo::http::body::BodySender;
+use salvo::prelude::*;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::models::CreateOperationLog;
+use crate::services::log_writer::LogWriter;
+
+// 导出时每次从数据库读取后排队等待写出的行数
+pub const EXPORT_QUEUE_SIZE: usize = 256;
+
+// Excel 单个工作表的最大行数(含表头), 超出后自动新建工作表
+const XLSX_MAX_ROWS: u32 = 1_048_576;
+
+// 写出到响应体的缓冲大小
+const BODY_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "" | "csv" => Ok(ExportFormat::Csv),
+            "xlsx" => Ok(ExportFormat::Xlsx),
+            other => Err(format!("不支持的导出格式: {}", other)),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+impl Lang {
+    // 优先使用 lang 参数, 其次是 Accept-Language 请求头, 默认中文
+    pub fn from_request(req: &mut Request) -> Self {
+        let lang = req
+            .query::<String>("lang")
+            .or_else(|| req.header::<String>("Accept-Language"))
+            .unwrap_or_default()
+            .to_lowercase();
+        if lang.starts_with("en") {
+            Lang::En
+        } else {
+            Lang::Zh
+        }
+    }
+}
+
+pub struct ExportColumn {
+    pub zh: &'static str,
+    pub en: &'static str,
+    pub width: f64,
+}
+
+impl ExportColumn {
+    pub const fn new(zh: &'static str, en: &'static str, width: f64) -> Self {
+        Self { zh, en, width }
+    }
+
+    fn header(&self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::Zh => self.zh,
+            Lang::En => self.en,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    Empty,
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<Option<String>> for Cell {
+    fn from(value: Option<String>) -> Self {
+        value.map(Cell::Text).unwrap_or(Cell::Empty)
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+impl From<i32> for Cell {
+    fn from(value: i32) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+impl From<Option<i64>> for Cell {
+    fn from(value: Option<i64>) -> Self {
+        value.map(Cell::from).unwrap_or(Cell::Empty)
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Csv(csv::Error),
+    Xlsx(XlsxError),
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "{}", e),
+            ExportError::Csv(e) => write!(f, "{}", e),
+            ExportError::Xlsx(e) => write!(f, "{}", e),
+            ExportError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<csv::Error> for ExportError {
+    fn from(e: csv::Error) -> Self {
+        ExportError::Csv(e)
+    }
+}
+
+impl From<XlsxError> for ExportError {
+    fn from(e: XlsxError) -> Self {
+        ExportError::Xlsx(e)
+    }
+}
+
+impl From<sqlx::Error> for ExportError {
+    fn from(e: sqlx::Error) -> Self {
+        ExportError::Database(e)
+    }
+}
+
+// 在阻塞线程中把编码后的数据写入响应体
+struct BodyWriter {
+    sender: BodySender,
+    handle: Handle,
+}
+
+impl Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.handle
+            .block_on(self.sender.send_data(buf.to_vec()))
+            .map(|_| buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// 设置下载响应头, 在后台线程中边接收行边编码, 返回写出的行数.
+// CSV 逐行写出; XLSX 只能在所有行写完后生成, 行数据和生成的文件都保存在临时文件中
+pub fn stream_export(
+    res: &mut Response,
+    format: ExportFormat,
+    file_name: &str,
+    columns: &'static [ExportColumn],
+    lang: Lang,
+    rows: mpsc::Receiver<Vec<Cell>>,
+) -> JoinHandle<Result<u64, ExportError>> {
+    let _ = res.add_header("Content-Type", format.content_type(), true);
+    let _ = res.add_header(
+        "Content-Disposition",
+        format!(
+            "attachment; filename=\"{}.{}\"",
+            file_name,
+            format.extension()
+        ),
+        true,
+    );
+
+    let writer = BufWriter::with_capacity(
+        BODY_BUFFER_SIZE,
+        BodyWriter {
+            sender: res.channel(),
+            handle: Handle::current(),
+        },
+    );
+    let headers: Vec<&'static str> = columns.iter().map(|c| c.header(lang)).collect();
+    let widths: Vec<f64> = columns.iter().map(|c| c.width).collect();
+
+    tokio::task::spawn_blocking(move || match format {
+        ExportFormat::Csv => write_csv(writer, &headers, rows),
+        ExportFormat::Xlsx => write_xlsx(writer, &headers, &widths, rows),
+    })
+}
+
+fn write_csv<W: Write>(
+    mut writer: W,
+    headers: &[&str],
+    mut rows: mpsc::Receiver<Vec<Cell>>,
+) -> Result<u64, ExportError> {
+    // 写入 BOM, 否则 Excel 打开中文会乱码
+    writer.write_all(b"\xEF\xBB\xBF")?;
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(headers)?;
+
+    let mut count = 0;
+    while let Some(row) = rows.blocking_recv() {
+        csv.write_record(row.iter().map(|cell| match cell {
+            Cell::Text(text) => escape_formula(text),
+            Cell::Number(number) => number.to_string(),
+            Cell::Empty => String::new(),
+        }))?;
+        count += 1;
+    }
+    csv.flush()?;
+    Ok(count)
+}
+
+// 防止以公式字符开头的内容在表格软件中被当作公式执行
+fn escape_formula(text: &str) -> String {
+    if text.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn write_xlsx<W: Write + Send>(
+    mut writer: W,
+    headers: &[&str],
+    widths: &[f64],
+    mut rows: mpsc::Receiver<Vec<Cell>>,
+) -> Result<u64, ExportError> {
+    let mut workbook = Workbook::new();
+    let header_format = Format::new().set_bold();
+    let mut worksheet = new_sheet(&mut workbook, headers, widths, &header_format)?;
+    let mut row_num: u32 = 1;
+    let mut count = 0;
+
+    while let Some(row) = rows.blocking_recv() {
+        if row_num >= XLSX_MAX_ROWS {
+            workbook.push_worksheet(worksheet);
+            worksheet = new_sheet(&mut workbook, headers, widths, &header_format)?;
+            row_num = 1;
+        }
+        for (col, cell) in row.into_iter().enumerate() {
+            let col = col as u16;
+            match cell {
+                Cell::Text(text) => {
+                    worksheet.write_string(row_num, col, text)?;
+                }
+                Cell::Number(number) => {
+                    worksheet.write_number(row_num, col, number)?;
+                }
+                Cell::Empty => {}
+            }
+        }
+        row_num += 1;
+        count += 1;
+    }
+
+    workbook.push_worksheet(worksheet);
+
+    // 写入内存会在生成压缩包时保留整个文件, 先保存到临时文件再分块写出
+    let path = std::env::temp_dir().join(format!("export-{}.xlsx", uuid::Uuid::new_v4()));
+    let result = workbook
+        .save(&path)
+        .map_err(ExportError::from)
+        .and_then(|_| {
+            let mut file = File::open(&path)?;
+            io::copy(&mut file, &mut writer)?;
+            writer.flush()?;
+            Ok(count)
+        });
+    let _ = fs::remove_file(&path);
+    result
+}
+
+// 常量内存模式的工作表, 行数据直接写入临时文件而不是保留在内存中
+fn new_sheet(
+    workbook: &mut Workbook,
+    headers: &[&str],
+    widths: &[f64],
+    header_format: &Format,
+) -> Result<Worksheet, XlsxError> {
+    let mut worksheet = workbook.new_worksheet_with_constant_memory();
+    for (col, (header, width)) in headers.iter().zip(widths).enumerate() {
+        worksheet.set_column_width(col as u16, *width)?;
+        worksheet.write_string_with_format(0, col as u16, *header, header_format)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    Ok(worksheet)
+}
+
+// 导出完成后记录审计日志, 包含导出格式、行数和失败原因
+pub async fn record_export(
+    writer: LogWriter,
+    log: Option<CreateOperationLog>,
+    started_at: Option<Instant>,
+    format: ExportFormat,
+    producer: JoinHandle<Result<(), sqlx::Error>>,
+    export: JoinHandle<Result<u64, ExportError>>,
+) {
+    let produced = producer.await.map_err(io::Error::other);
+    let exported = export.await.map_err(io::Error::other);
+    let result = match (produced, exported) {
+        (Ok(Err(e)), _) => Err(ExportError::Database(e)),
+        (Err(e), _) | (_, Err(e)) => Err(ExportError::Io(e)),
+        (_, Ok(result)) => result,
+    };
+
+    let Some(mut log) = log else {
+        return;
+    };
+    log.duration_ms = started_at
+        .map(|t| t.elapsed().as_millis() as i64)
+        .unwrap_or_default();
+    match result {
+        Ok(rows) => {
+            println!("[Export] Exported {} rows as {}", rows, format.extension());
+            log.status = StatusCode::OK.as_u16() as i32;
+            log.response = Some(json!({ "format": format.extension(), "rows": rows }).to_string());
+        }
+        Err(e) => {
+            eprintln!("[Export] Export failed: {}", e);
+            log.status = StatusCode::INTERNAL_SERVER_ERROR.as_u16() as i32;
+            log.error = Some(
+                json!({
+                    "status": log.status,
+                    "message": format!("导出失败: {}", e)
+                })
+                .to_string(),
+            );
+        }
+    }
+
+    writer.submit(log).await;
+}
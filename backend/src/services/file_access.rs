@@ -0,0 +1,297 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::config::upload::upload_config;
+use crate::models::FileRecord;
+use crate::repositories::Repositories;
+use crate::services::files::URL_PREFIX;
+
+pub const MANAGE_PERMISSION: &str = "system:file:manage";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Public,
+    Private,
+}
+
+impl Visibility {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "public" => Some(Visibility::Public),
+            "private" => Some(Visibility::Private),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+// 通过 /uploads/ 访问时的授权结果
+pub enum KeyAccess {
+    // 允许访问, 附带文件内容在存储中的位置
+    Allowed(String),
+    // 私有文件缺少签名, 按文件不存在处理, 不暴露文件是否存在
+    Hidden,
+    // 签名错误或已过期
+    Denied,
+}
+
+fn mac(secret: &[u8]) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length")
+}
+
+// 签名内容为存储路径和过期时间(Unix 秒), 地址中的其他参数不参与签名
+fn signature(secret: &[u8], key: &str, expires: i64) -> String {
+    let mut mac = mac(secret);
+    mac.update(format!("{}\n{}", key, expires).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// 有效期超过配置的上限时按上限计算
+pub fn expires_at(expires_in: u64) -> i64 {
+    let expires_in = expires_in.clamp(1, upload_config().signed_url_max_expires_secs);
+    chrono::Utc::now().timestamp() + expires_in as i64
+}
+
+pub fn sign_key(key: &str, expires_at: i64) -> String {
+    format!(
+        "{}{}?expires={}&signature={}",
+        URL_PREFIX,
+        key,
+        expires_at,
+        signature(&upload_config().signing_secret, key, expires_at)
+    )
+}
+
+fn verify_signature(secret: &[u8], key: &str, expires: i64, signature: &str, now: i64) -> bool {
+    if expires < now {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = mac(secret);
+    mac.update(format!("{}\n{}", key, expires).as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+// 原文件和派生版本(缩略图)都按所属文件的可见性判断;
+// 文件登记之前上传的文件只有仍被引用(例如正在使用的头像)时才公开.
+// 按内容保存的位置不能直接访问, 只能通过各次上传的地址访问
+pub async fn check_key_access(
+    repos: &Repositories,
+    key: &str,
+    expires: Option<i64>,
+    signature: Option<&str>,
+) -> Result<KeyAccess, sqlx::Error> {
+    let registered = repos.files.find_key(key).await?;
+
+    let (public, storage_key) = match registered {
+        Some((visibility, storage_key)) => (
+            Visibility::parse(&visibility) == Some(Visibility::Public),
+            storage_key,
+        ),
+        None => (
+            repos.files.reference_count_for_key(key).await? > 0,
+            key.to_string(),
+        ),
+    };
+    if public {
+        return Ok(KeyAccess::Allowed(storage_key));
+    }
+
+    Ok(match (expires, signature) {
+        (Some(expires), Some(signature))
+            if verify_signature(
+                &upload_config().signing_secret,
+                key,
+                expires,
+                signature,
+                chrono::Utc::now().timestamp(),
+            ) =>
+        {
+            KeyAccess::Allowed(storage_key)
+        }
+        (None, None) => KeyAccess::Hidden,
+        _ => KeyAccess::Denied,
+    })
+}
+
+// 公开文件所有登录用户都可以访问, 私有文件只有上传者和文件管理员可以访问
+pub async fn can_read_file(
+    repos: &Repositories,
+    user_id: i64,
+    file: &FileRecord,
+) -> Result<bool, sqlx::Error> {
+    if file.visibility == Visibility::Public.name() {
+        return Ok(true);
+    }
+    can_modify_file(repos, user_id, file).await
+}
+
+pub async fn can_modify_file(
+    repos: &Repositories,
+    user_id: i64,
+    file: &FileRecord,
+) -> Result<bool, sqlx::Error> {
+    if file.uploader_id == Some(user_id) {
+        return Ok(true);
+    }
+    repos
+        .permissions
+        .has_permission(user_id, MANAGE_PERMISSION)
+        .await
+}
+
+// 私有文件的访问地址替换为签名地址, 供前端直接嵌入图片或下载
+pub fn sign_file_urls(file: &mut FileRecord, expires_in: u64) -> Option<i64> {
+    if file.visibility == Visibility::Public.name() {
+        return None;
+    }
+    let expires_at = expires_at(expires_in);
+    file.url = sign_key(&file.storage_name, expires_at);
+    for variant in &mut file.variants {
+        variant.url = sign_key(&variant.storage_name, expires_at);
+    }
+    Some(expires_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test-signing-secret";
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn valid_signature() {
+        let expires = NOW + 60;
+        let sig = signature(SECRET, "a/private.pdf", expires);
+        assert!(verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires,
+            &sig,
+            NOW
+        ));
+        // 过期时间当秒仍然有效
+        assert!(verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires,
+            &sig,
+            expires
+        ));
+    }
+
+    #[test]
+    fn expired_signature() {
+        let expires = NOW - 1;
+        let sig = signature(SECRET, "a/private.pdf", expires);
+        assert!(!verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires,
+            &sig,
+            NOW
+        ));
+    }
+
+    #[test]
+    fn tampered_path() {
+        let expires = NOW + 60;
+        let sig = signature(SECRET, "a/private.pdf", expires);
+        assert!(!verify_signature(
+            SECRET,
+            "a/private.pdf.thumb",
+            expires,
+            &sig,
+            NOW
+        ));
+        assert!(!verify_signature(
+            SECRET,
+            "b/private.pdf",
+            expires,
+            &sig,
+            NOW
+        ));
+    }
+
+    #[test]
+    fn tampered_expiry() {
+        let expires = NOW + 60;
+        let sig = signature(SECRET, "a/private.pdf", expires);
+        assert!(!verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires + 3600,
+            &sig,
+            NOW
+        ));
+    }
+
+    #[test]
+    fn tampered_signature() {
+        let expires = NOW + 60;
+        let sig = signature(SECRET, "a/private.pdf", expires);
+
+        let mut flipped = sig.clone().into_bytes();
+        flipped[0] = if flipped[0] == b'0' { b'1' } else { b'0' };
+        let flipped = String::from_utf8(flipped).unwrap();
+        assert!(!verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires,
+            &flipped,
+            NOW
+        ));
+
+        assert!(!verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires,
+            &sig[..32],
+            NOW
+        ));
+        assert!(!verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires,
+            "not-hex",
+            NOW
+        ));
+        assert!(!verify_signature(SECRET, "a/private.pdf", expires, "", NOW));
+    }
+
+    #[test]
+    fn signature_for_another_users_file() {
+        // 为其他用户的私有文件签发的地址不能用来访问自己无权访问的文件
+        let expires = NOW + 60;
+        let other = signature(SECRET, "uploads/bob-avatar.png", expires);
+        assert!(!verify_signature(
+            SECRET,
+            "uploads/alice-contract.pdf",
+            expires,
+            &other,
+            NOW
+        ));
+    }
+
+    #[test]
+    fn signature_from_another_secret() {
+        let expires = NOW + 60;
+        let sig = signature(b"another-deployment", "a/private.pdf", expires);
+        assert!(!verify_signature(
+            SECRET,
+            "a/private.pdf",
+            expires,
+            &sig,
+            NOW
+        ));
+    }
+}
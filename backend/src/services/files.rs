@@ -0,0 +1,246 @@
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::upload::upload_config;
+use crate::models::{CreateFile, FileRecord};
+use crate::repositories::Repositories;
+use crate::services::file_access::Visibility;
+use crate::services::storage::{storage, StorageError};
+use crate::services::upload::{DetectedType, UploadError, UploadPurpose};
+use crate::services::upload_session::purge_expired_sessions;
+
+pub const URL_PREFIX: &str = "/uploads/";
+
+// 保存上传文件地址的字段, 用于统计文件是否仍被引用
+pub const REFERENCES: &[(&str, &str)] = &[("users", "avatar")];
+
+// 由引用关系决定生命周期的用途, 附件没有引用方, 只能手动删除
+const SWEPT_PURPOSES: &[&str] = &["avatar"];
+
+// 没有被引用的内容最少保留的时间(小时)
+const BLOB_MIN_GRACE_HOURS: i64 = 1;
+
+#[derive(Debug)]
+pub enum FileError {
+    Database(sqlx::Error),
+    Io(io::Error),
+    Storage(StorageError),
+    Upload(UploadError),
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Database(e) => write!(f, "{}", e),
+            FileError::Io(e) => write!(f, "{}", e),
+            FileError::Storage(e) => write!(f, "{}", e),
+            FileError::Upload(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for FileError {
+    fn from(e: sqlx::Error) -> Self {
+        FileError::Database(e)
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(e: io::Error) -> Self {
+        FileError::Io(e)
+    }
+}
+
+impl From<UploadError> for FileError {
+    fn from(e: UploadError) -> Self {
+        FileError::Upload(e)
+    }
+}
+
+impl From<StorageError> for FileError {
+    fn from(e: StorageError) -> Self {
+        FileError::Storage(e)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SweepResult {
+    pub removed: u64,
+    pub bytes: i64,
+    pub blobs: u64,
+    pub blob_bytes: i64,
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// 将校验通过的临时文件按内容写入存储并登记, 已有相同内容时只登记不重复保存
+#[allow(clippy::too_many_arguments)]
+pub async fn store_upload(
+    repos: &Repositories,
+    source: &Path,
+    original_name: &str,
+    size: u64,
+    sha256: String,
+    detected: DetectedType,
+    purpose: UploadPurpose,
+    visibility: Visibility,
+    uploader_id: i64,
+) -> Result<FileRecord, FileError> {
+    let blob_key = put_blob(
+        repos,
+        BlobSource::Path(source),
+        &sha256,
+        &detected.extension,
+        &detected.mime,
+        size as i64,
+    )
+    .await?;
+
+    // 每次上传使用独立的名称作为访问地址, 可见性和上传者等信息按上传分别记录;
+    // 使用识别出的扩展名, 不信任客户端文件名
+    let record = CreateFile {
+        storage_name: format!("{}.{}", Uuid::new_v4(), detected.extension),
+        original_name: original_name.to_string(),
+        size: size as i64,
+        mime: detected.mime,
+        sha256,
+        purpose: purpose.name().to_string(),
+        visibility: visibility.name().to_string(),
+        blob_key,
+        uploader_id,
+    };
+    repos.files.register(&record, &[]).await
+}
+
+pub enum BlobSource<'a> {
+    Path(&'a Path),
+    Bytes(Vec<u8>),
+}
+
+pub fn blob_key(sha256: &str, extension: &str) -> String {
+    format!("blobs/{}/{}.{}", &sha256[..2], sha256, extension)
+}
+
+// 相同内容只保存一份. 先更新 last_used_at 再决定是否跳过写入, 保证正在使用的内容不会被清理任务删除
+pub async fn put_blob(
+    repos: &Repositories,
+    source: BlobSource<'_>,
+    sha256: &str,
+    extension: &str,
+    mime: &str,
+    size: i64,
+) -> Result<String, FileError> {
+    let key = blob_key(sha256, extension);
+    if repos.files.touch_blob(&key).await? {
+        return Ok(key);
+    }
+
+    match source {
+        BlobSource::Path(path) => storage().put(&key, path, mime).await?,
+        BlobSource::Bytes(data) => storage().put_bytes(&key, data, mime).await?,
+    }
+    repos.files.insert_blob(&key, sha256, size, mime).await?;
+    Ok(key)
+}
+
+// 先删除登记记录, 按内容保存的文件由清理任务在没有引用后删除; 去重之前上传的文件直接删除
+pub async fn delete_file(repos: &Repositories, file: &FileRecord) -> Result<(), FileError> {
+    let variants = repos.files.variants(file.id).await?;
+    repos.files.delete(file.id).await?;
+    for variant in variants.iter().filter(|variant| variant.blob_key.is_none()) {
+        storage().delete(&variant.storage_name).await?;
+    }
+    if file.blob_key.is_none() {
+        storage().delete(&file.storage_name).await?;
+    }
+    Ok(())
+}
+
+// 删除没有任何文件引用且超过宽限期未被使用的内容. 写入内容和登记文件之间还要处理图片等,
+// 所以内容至少在最后一次使用后保留 BLOB_MIN_GRACE_HOURS, 即使孤立文件的宽限期更短
+async fn sweep_unused_blobs(
+    repos: &Repositories,
+    grace_hours: i64,
+) -> Result<SweepResult, FileError> {
+    let cutoff = (chrono::Utc::now()
+        - chrono::Duration::hours(grace_hours.max(BLOB_MIN_GRACE_HOURS)))
+    .format("%Y-%m-%d %H:%M:%S")
+    .to_string();
+    let blobs = repos.files.delete_unused_blobs(&cutoff).await?;
+
+    let mut result = SweepResult::default();
+    for (key, size) in blobs {
+        storage().delete(&key).await?;
+        result.removed += 1;
+        result.bytes += size;
+    }
+    Ok(result)
+}
+
+// 删除上传超过宽限期且没有被引用的文件, 例如更换头像后的旧头像
+pub async fn sweep_orphan_files(repos: &Repositories) -> Result<SweepResult, FileError> {
+    let grace_hours = upload_config().orphan_grace_hours as i64;
+    let cutoff = (chrono::Utc::now() - chrono::Duration::hours(grace_hours))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let orphans = repos.files.orphans(SWEPT_PURPOSES, &cutoff).await?;
+
+    let mut result = SweepResult::default();
+    for file in orphans {
+        delete_file(repos, &file).await?;
+        result.removed += 1;
+        result.bytes += file.size;
+    }
+    let blobs = sweep_unused_blobs(repos, grace_hours).await?;
+    result.blobs = blobs.removed;
+    result.blob_bytes = blobs.bytes;
+    Ok(result)
+}
+
+// 启动孤立文件和过期上传会话的清理定时任务
+pub fn spawn_file_sweep_task(repos: Repositories) {
+    let interval_secs = upload_config().sweep_interval_secs;
+    if interval_secs == 0 {
+        println!("[File Sweep] Orphan file sweep is disabled");
+        return;
+    }
+
+    let period = Duration::from_secs(interval_secs.max(60));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            match purge_expired_sessions(&repos).await {
+                Ok(removed) if removed > 0 => {
+                    println!("[File Sweep] Removed {} expired upload sessions", removed)
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[File Sweep] Failed to remove upload sessions: {}", e),
+            }
+            match sweep_orphan_files(&repos).await {
+                Ok(result) if result.removed > 0 || result.blobs > 0 => println!(
+                    "[File Sweep] Removed {} orphan files ({} bytes) and {} unused blobs ({} bytes)",
+                    result.removed, result.bytes, result.blobs, result.blob_bytes
+                ),
+                Ok(_) => {}
+                Err(e) => eprintln!("[File Sweep] Failed to remove orphan files: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,290 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::config::operation_log::operation_log_config;
+use crate::models::CreateOperationLog;
+use crate::repositories::Repositories;
+use crate::services::audit_stream::{AuditEvent, AuditEvents};
+use crate::services::operation_log::{insert_operation_logs, PendingLog};
+
+// 保留最近写入失败的日志条数
+const RECENT_FAILURES: usize = 20;
+
+// 写入失败的单条日志, 用于在写入状态中排查
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedLog {
+    pub request_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub created_at: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    enqueued: AtomicU64,
+    written: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+    batches: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    recent_failures: Mutex<VecDeque<FailedLog>>,
+}
+
+impl Counters {
+    fn record_failure(&self, pending: &PendingLog, error: &sqlx::Error) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        *self.last_error.lock().unwrap() = Some(error.to_string());
+
+        let mut failures = self.recent_failures.lock().unwrap();
+        if failures.len() >= RECENT_FAILURES {
+            failures.pop_front();
+        }
+        failures.push_back(FailedLog {
+            request_id: pending.log.request_id.clone(),
+            method: pending.log.method.clone(),
+            path: pending.log.path.clone(),
+            created_at: pending.created_at.clone(),
+            error: error.to_string(),
+        });
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogWriterStats {
+    pub capacity: usize,
+    pub queued: usize,
+    pub enqueued: u64,
+    pub written: u64,
+    pub dropped: u64,
+    pub failed: u64,
+    pub batches: u64,
+    pub last_error: Option<String>,
+    pub recent_failures: Vec<FailedLog>,
+    pub subscribers: usize,
+}
+
+// 操作日志写入服务, 请求处理只负责入队, 由后台任务批量写入数据库
+#[derive(Clone)]
+pub struct LogWriter {
+    sender: mpsc::Sender<PendingLog>,
+    counters: Arc<Counters>,
+    enqueue_timeout: Duration,
+    events: AuditEvents,
+}
+
+pub struct LogWriterHandle {
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl LogWriter {
+    pub fn spawn(repos: Repositories) -> (Self, LogWriterHandle) {
+        let config = operation_log_config();
+        let (sender, receiver) = mpsc::channel(config.queue_size);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let counters = Arc::new(Counters::default());
+        let events = AuditEvents::new();
+
+        let task = tokio::spawn(run(
+            repos,
+            receiver,
+            shutdown_rx,
+            counters.clone(),
+            events.clone(),
+            config.batch_size,
+            Duration::from_millis(config.flush_interval_ms),
+        ));
+
+        let writer = LogWriter {
+            sender,
+            counters,
+            enqueue_timeout: Duration::from_millis(config.enqueue_timeout_ms),
+            events,
+        };
+        let handle = LogWriterHandle {
+            shutdown: shutdown_tx,
+            task,
+        };
+        (writer, handle)
+    }
+
+    // 队列满时最多等待 enqueue_timeout, 超时则丢弃并计数, 避免拖垮请求处理
+    pub async fn submit(&self, log: CreateOperationLog) {
+        let pending = PendingLog::new(log);
+        match self
+            .sender
+            .send_timeout(pending, self.enqueue_timeout)
+            .await
+        {
+            Ok(()) => {
+                self.counters.enqueued.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                eprintln!("[Log Writer] Dropped operation log: {}", e);
+            }
+        }
+    }
+
+    // 实时审计事件, 操作日志落库后由写入服务发布
+    pub fn events(&self) -> &AuditEvents {
+        &self.events
+    }
+
+    pub fn stats(&self) -> LogWriterStats {
+        let counters = &self.counters;
+        let capacity = self.sender.max_capacity();
+        LogWriterStats {
+            capacity,
+            queued: capacity - self.sender.capacity(),
+            enqueued: counters.enqueued.load(Ordering::Relaxed),
+            written: counters.written.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            failed: counters.failed.load(Ordering::Relaxed),
+            batches: counters.batches.load(Ordering::Relaxed),
+            last_error: counters.last_error.lock().unwrap().clone(),
+            recent_failures: counters
+                .recent_failures
+                .lock()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect(),
+            subscribers: self.events.subscriber_count(),
+        }
+    }
+}
+
+impl LogWriterHandle {
+    // 停止接收新日志, 写完队列中剩余的日志后返回
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        if let Err(e) = self.task.await {
+            eprintln!("[Log Writer] Writer task failed: {}", e);
+        }
+    }
+}
+
+async fn run(
+    repos: Repositories,
+    mut receiver: mpsc::Receiver<PendingLog>,
+    mut shutdown: oneshot::Receiver<()>,
+    counters: Arc<Counters>,
+    events: AuditEvents,
+    batch_size: usize,
+    flush_interval: Duration,
+) {
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut deadline = Instant::now() + flush_interval;
+
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Some(log) => {
+                    if batch.is_empty() {
+                        deadline = Instant::now() + flush_interval;
+                    }
+                    batch.push(log);
+                    if batch.len() >= batch_size {
+                        flush(&repos, &mut batch, &counters, &events).await;
+                    }
+                }
+                None => break,
+            },
+            _ = tokio::time::sleep_until(deadline), if !batch.is_empty() => {
+                flush(&repos, &mut batch, &counters, &events).await;
+            }
+            _ = &mut shutdown => {
+                println!("[Log Writer] Shutting down, draining queued logs");
+                receiver.close();
+                while let Some(log) = receiver.recv().await {
+                    batch.push(log);
+                    if batch.len() >= batch_size {
+                        flush(&repos, &mut batch, &counters, &events).await;
+                    }
+                }
+                break;
+            }
+        }
+    }
+
+    flush(&repos, &mut batch, &counters, &events).await;
+    println!("[Log Writer] Stopped");
+}
+
+async fn flush(
+    repos: &Repositories,
+    batch: &mut Vec<PendingLog>,
+    counters: &Counters,
+    events: &AuditEvents,
+) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let count = batch.len() as u64;
+    match insert_operation_logs(repos, batch).await {
+        Ok(ids) => {
+            counters.written.fetch_add(count, Ordering::Relaxed);
+            counters.batches.fetch_add(1, Ordering::Relaxed);
+            publish(repos, &ids, events).await;
+        }
+        // 整批在同一事务中写入, 失败时逐条重试, 只丢弃确实无法写入的日志并逐条记录原因
+        Err(e) => {
+            eprintln!(
+                "[Log Writer] Failed to write batch of {} operation logs, retrying one by one: {}",
+                count, e
+            );
+            let mut ids = Vec::new();
+            for pending in batch.iter() {
+                match insert_operation_logs(repos, std::slice::from_ref(pending)).await {
+                    Ok(written) => {
+                        counters.written.fetch_add(1, Ordering::Relaxed);
+                        ids.extend(written);
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "[Log Writer] Failed to write operation log {} {} (request {}): {}",
+                            pending.log.method,
+                            pending.log.path,
+                            pending.log.request_id.as_deref().unwrap_or("-"),
+                            e
+                        );
+                        counters.record_failure(pending, &e);
+                    }
+                }
+            }
+            if !ids.is_empty() {
+                counters.batches.fetch_add(1, Ordering::Relaxed);
+                publish(repos, &ids, events).await;
+            }
+        }
+    }
+    batch.clear();
+}
+
+// 有订阅者时读取刚写入的日志(含哈希和创建时间)并广播
+async fn publish(repos: &Repositories, ids: &[i64], events: &AuditEvents) {
+    let (Some(first), Some(last)) = (ids.first(), ids.last()) else {
+        return;
+    };
+    if !events.has_subscribers() {
+        return;
+    }
+
+    match repos.operation_logs.between(*first, *last).await {
+        Ok(logs) => {
+            for log in logs {
+                events.publish(AuditEvent::OperationLog(log));
+            }
+        }
+        Err(e) => eprintln!("[Log Writer] Failed to load logs for audit stream: {}", e),
+    }
+}
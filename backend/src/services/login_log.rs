@@ -0,0 +1,31 @@
+use crate::config::operation_log::operation_log_config;
+use crate::models::{CreateLoginLog, LoginLog};
+use crate::repositories::Repositories;
+use crate::services::retention::days_ago;
+
+// 登录失败原因, 只记录在日志中, 不返回给客户端
+pub const REASON_USER_NOT_FOUND: &str = "user_not_found";
+pub const REASON_WRONG_PASSWORD: &str = "wrong_password";
+
+// 登录请求量不大, 直接写入而不经过操作日志的写入队列
+pub async fn record_login(repos: &Repositories, log: CreateLoginLog) -> Option<LoginLog> {
+    match repos.login_logs.record(&log).await {
+        Ok(login_log) => Some(login_log),
+        Err(e) => {
+            eprintln!(
+                "[Login Log] Failed to record login of {}: {}",
+                log.username, e
+            );
+            None
+        }
+    }
+}
+
+// 删除超过保留天数的登录日志, 返回删除的条数
+pub async fn purge_login_logs(repos: &Repositories) -> Result<u64, sqlx::Error> {
+    let days = operation_log_config().login_log_retention_days;
+    if days == 0 {
+        return Ok(0);
+    }
+    repos.login_logs.purge(&days_ago(days)).await
+}
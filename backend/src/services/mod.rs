@@ -0,0 +1,18 @@
+pub mod assignment;
+pub mod audit_stream;
+pub mod avatar;
+pub mod capture_rules;
+pub mod dashboard;
+pub mod export;
+pub mod file_access;
+pub mod files;
+pub mod log_writer;
+pub mod login_log;
+pub mod operation_log;
+pub mod operation_log_archive;
+pub mod operation_log_filter;
+pub mod quota;
+pub mod retention;
+pub mod storage;
+pub mod upload;
+pub mod upload_session;
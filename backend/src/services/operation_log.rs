@@ -0,0 +1,588 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::config::operation_log::operation_log_config;
+use crate::models::{CreateOperationLog, OperationLog, OperationLogChange};
+use crate::repositories::operation_log::{NewCheckpoint, NewOperationLog, NewRedaction};
+use crate::repositories::Repositories;
+use crate::services::operation_log_archive::{remove_archive, write_archive};
+use crate::utils::ip::ip_key;
+
+// 链的起点哈希
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// 日志按顺序串联. 写入、清洗和清理在数据库事务中锁定链头, 多个实例之间也依次执行;
+// 进程内的锁让归档文件的写入和封存旧数据与本进程的其他清理依次进行
+static CHAIN_LOCK: Mutex<()> = Mutex::const_new(());
+
+const VERIFY_BATCH_SIZE: i64 = 1000;
+
+// 每个归档文件最多包含的日志条数
+const ARCHIVE_BATCH_SIZE: i64 = 10000;
+
+// 参与哈希计算的日志内容, 按字段声明顺序序列化
+#[derive(Serialize)]
+struct HashedLog<'a> {
+    user_id: i64,
+    username: &'a str,
+    module: &'a str,
+    operation: &'a str,
+    method: &'a str,
+    path: Option<&'a str>,
+    params: Option<&'a str>,
+    ip: Option<&'a str>,
+    user_agent: Option<&'a str>,
+    request_id: Option<&'a str>,
+    status: i32,
+    error: Option<&'a str>,
+    response: Option<&'a str>,
+    duration_ms: Option<i64>,
+    created_at: &'a str,
+    changes: Vec<HashedChange<'a>>,
+}
+
+#[derive(Serialize)]
+struct HashedChange<'a> {
+    entity: &'a str,
+    entity_id: i64,
+    action: &'a str,
+    diff: &'a str,
+}
+
+impl<'a> HashedLog<'a> {
+    fn from_new(log: &'a CreateOperationLog, created_at: &'a str) -> Self {
+        Self {
+            user_id: log.user_id,
+            username: &log.username,
+            module: &log.module,
+            operation: &log.operation,
+            method: &log.method,
+            path: Some(&log.path),
+            params: log.params.as_deref(),
+            ip: log.ip.as_deref(),
+            user_agent: log.user_agent.as_deref(),
+            request_id: log.request_id.as_deref(),
+            status: log.status,
+            error: log.error.as_deref(),
+            response: log.response.as_deref(),
+            duration_ms: Some(log.duration_ms),
+            created_at,
+            changes: log
+                .changes
+                .iter()
+                .map(|c| HashedChange {
+                    entity: &c.entity,
+                    entity_id: c.entity_id,
+                    action: &c.action,
+                    diff: &c.diff,
+                })
+                .collect(),
+        }
+    }
+
+    fn from_stored(log: &'a OperationLog, changes: &'a [OperationLogChange]) -> Self {
+        Self {
+            user_id: log.user_id,
+            username: &log.username,
+            module: &log.module,
+            operation: &log.operation,
+            method: &log.method,
+            path: log.path.as_deref(),
+            params: log.params.as_deref(),
+            ip: log.ip.as_deref(),
+            user_agent: log.user_agent.as_deref(),
+            request_id: log.request_id.as_deref(),
+            status: log.status,
+            error: log.error.as_deref(),
+            response: log.response.as_deref(),
+            duration_ms: log.duration_ms,
+            created_at: &log.created_at,
+            changes: changes
+                .iter()
+                .map(|c| HashedChange {
+                    entity: &c.entity,
+                    entity_id: c.entity_id,
+                    action: &c.action,
+                    diff: &c.diff,
+                })
+                .collect(),
+        }
+    }
+
+    fn hash(&self, prev_hash: &str) -> String {
+        let content = serde_json::to_string(self).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash.as_bytes());
+        hasher.update(content.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenLink {
+    pub id: i64,
+    pub reason: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainReport {
+    pub valid: bool,
+    pub checked: i64,
+    // 内容经过清洗, 按清洗记录校验通过的日志条数
+    pub redacted: i64,
+    pub anchor: String,
+    pub first_broken: Option<BrokenLink>,
+    // 第一条哈希不一致的清洗记录, 其后的清洗记录都不再采信
+    pub broken_redaction: Option<BrokenLink>,
+}
+
+// 清洗事件中的一条日志
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct RedactedRow {
+    pub log_id: i64,
+    pub original_hash: String,
+    pub redacted_hash: String,
+}
+
+#[derive(Serialize)]
+struct HashedRedaction<'a> {
+    reason: &'a str,
+    created_at: &'a str,
+    rows: &'a [RedactedRow],
+}
+
+impl HashedRedaction<'_> {
+    fn hash(&self, prev_hash: &str) -> String {
+        let content = serde_json::to_string(self).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash.as_bytes());
+        hasher.update(content.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+#[derive(Debug)]
+pub enum PurgeError {
+    Database(sqlx::Error),
+    Archive(std::io::Error),
+    Unsealed(i64),
+}
+
+impl std::fmt::Display for PurgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PurgeError::Database(e) => write!(f, "{}", e),
+            PurgeError::Archive(e) => write!(f, "写入归档文件失败: {}", e),
+            PurgeError::Unsealed(id) => write!(f, "日志 {} 未封存, 无法作为检查点", id),
+        }
+    }
+}
+
+impl From<sqlx::Error> for PurgeError {
+    fn from(e: sqlx::Error) -> Self {
+        PurgeError::Database(e)
+    }
+}
+
+impl From<std::io::Error> for PurgeError {
+    fn from(e: std::io::Error) -> Self {
+        PurgeError::Archive(e)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurgeResult {
+    pub purged: i64,
+    pub checkpoint_id: Option<i64>,
+    pub archives: Vec<String>,
+}
+
+fn now_timestamp() -> String {
+    // 与 SQLite CURRENT_TIMESTAMP 的格式保持一致
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// 最近一次清理留下的检查点哈希, 没有则为起点哈希
+async fn chain_anchor(repos: &Repositories) -> Result<String, sqlx::Error> {
+    let anchor = repos.operation_logs.last_checkpoint_hash().await?;
+    Ok(anchor.unwrap_or_else(|| GENESIS_HASH.to_string()))
+}
+
+// before_id 之前最后一条日志的哈希, 没有日志时为链的起点
+async fn last_hash(repos: &Repositories, before_id: Option<i64>) -> Result<String, sqlx::Error> {
+    match repos.operation_logs.last_hash(before_id).await? {
+        Some(Some(hash)) => Ok(hash),
+        _ => chain_anchor(repos).await,
+    }
+}
+
+// 等待写入的日志, 记录时间取提交时刻而不是批量写入的时刻
+#[derive(Debug)]
+pub struct PendingLog {
+    pub log: CreateOperationLog,
+    pub created_at: String,
+}
+
+impl PendingLog {
+    pub fn new(log: CreateOperationLog) -> Self {
+        Self {
+            log,
+            created_at: now_timestamp(),
+        }
+    }
+}
+
+fn hash_new_log(new_log: &NewOperationLog<'_>, prev_hash: &str) -> String {
+    HashedLog::from_new(new_log.log, new_log.created_at).hash(prev_hash)
+}
+
+fn hash_redaction(redaction: &NewRedaction<'_>, prev_hash: &str) -> String {
+    HashedRedaction {
+        reason: redaction.reason,
+        created_at: redaction.created_at,
+        rows: redaction.rows,
+    }
+    .hash(prev_hash)
+}
+
+// 在同一事务中批量写入日志及其关联的数据变更, 并依次串联到哈希链上
+pub async fn insert_operation_logs(
+    repos: &Repositories,
+    logs: &[PendingLog],
+) -> Result<Vec<i64>, sqlx::Error> {
+    let new_logs: Vec<_> = logs
+        .iter()
+        .map(|PendingLog { log, created_at }| NewOperationLog { log, created_at })
+        .collect();
+    repos.operation_logs.insert(&new_logs, hash_new_log).await
+}
+
+// 从指定ID开始重新计算哈希链, 仅用于启动时封存启用哈希链之前的旧数据
+pub async fn reseal_from(repos: &Repositories, from_id: i64) -> Result<i64, sqlx::Error> {
+    let _guard = CHAIN_LOCK.lock().await;
+
+    let mut prev_hash = last_hash(repos, Some(from_id)).await?;
+    let mut last_id = from_id - 1;
+    let mut sealed = 0;
+    loop {
+        let logs = repos
+            .operation_logs
+            .after(last_id, VERIFY_BATCH_SIZE)
+            .await?;
+        if logs.is_empty() {
+            break;
+        }
+
+        let mut hashes = Vec::with_capacity(logs.len());
+        for log in &logs {
+            let changes = repos.operation_logs.changes(log.id).await?;
+            let hash = HashedLog::from_stored(log, &changes).hash(&prev_hash);
+            hashes.push((
+                log.id,
+                std::mem::replace(&mut prev_hash, hash.clone()),
+                hash,
+            ));
+            last_id = log.id;
+            sealed += 1;
+        }
+        repos.operation_logs.set_hashes(&hashes).await?;
+    }
+    Ok(sealed)
+}
+
+// 写入清洗后的日志参数, 不改变日志原有的哈希链.
+// 已封存的日志按新内容重新计算哈希, 与原哈希一起记录为一条串联哈希的清洗事件, 返回事件ID
+pub async fn record_redaction(
+    repos: &Repositories,
+    reason: &str,
+    logs: &[OperationLog],
+) -> Result<Option<i64>, sqlx::Error> {
+    let mut rows = Vec::new();
+    for log in logs {
+        if let (Some(prev_hash), Some(hash)) = (&log.prev_hash, &log.hash) {
+            let changes = repos.operation_logs.changes(log.id).await?;
+            rows.push(RedactedRow {
+                log_id: log.id,
+                original_hash: hash.clone(),
+                redacted_hash: HashedLog::from_stored(log, &changes).hash(prev_hash),
+            });
+        }
+    }
+    rows.sort_by_key(|row| row.log_id);
+
+    let params: Vec<_> = logs
+        .iter()
+        .map(|log| (log.id, log.params.clone()))
+        .collect();
+    if rows.is_empty() {
+        return repos
+            .operation_logs
+            .redact(&params, None, hash_redaction)
+            .await;
+    }
+
+    let created_at = now_timestamp();
+    let redaction = NewRedaction {
+        reason,
+        created_at: &created_at,
+        rows: &rows,
+    };
+    repos
+        .operation_logs
+        .redact(&params, Some(&redaction), hash_redaction)
+        .await
+}
+
+// 按顺序校验清洗事件的哈希, 返回可以采信的清洗记录(按日志ID索引)和第一条不一致的事件
+async fn trusted_redactions(
+    repos: &Repositories,
+) -> Result<(HashMap<i64, Vec<RedactedRow>>, Option<BrokenLink>), sqlx::Error> {
+    let mut redactions = repos.operation_logs.redactions().await?;
+    redactions.reverse();
+
+    let mut trusted: HashMap<i64, Vec<RedactedRow>> = HashMap::new();
+    let mut expected_prev = GENESIS_HASH.to_string();
+    for redaction in redactions {
+        let rows = repos.operation_logs.redacted_rows(redaction.id).await?;
+        let computed = HashedRedaction {
+            reason: &redaction.reason,
+            created_at: &redaction.created_at,
+            rows: &rows,
+        }
+        .hash(&redaction.prev_hash);
+
+        let broken = if redaction.prev_hash != expected_prev {
+            Some((
+                "与上一条清洗记录的哈希不一致",
+                expected_prev.clone(),
+                redaction.prev_hash.clone(),
+            ))
+        } else if computed != redaction.hash {
+            Some(("清洗记录内容与哈希不一致", computed, redaction.hash.clone()))
+        } else {
+            None
+        };
+        if let Some((reason, expected, actual)) = broken {
+            let link = BrokenLink {
+                id: redaction.id,
+                reason: reason.to_string(),
+                expected: Some(expected),
+                actual: Some(actual),
+            };
+            return Ok((trusted, Some(link)));
+        }
+
+        for row in rows {
+            trusted.entry(row.log_id).or_default().push(row);
+        }
+        expected_prev = redaction.hash;
+    }
+    Ok((trusted, None))
+}
+
+// 启用哈希链之前写入的日志没有哈希, 启动时统一封存
+// 只处理早于第一条已封存日志的记录, 之后出现的未封存记录在校验时报告
+pub async fn seal_legacy_logs(repos: &Repositories) -> Result<(), sqlx::Error> {
+    let first_sealed = repos.operation_logs.first_id(true).await?;
+    let first_legacy = repos.operation_logs.first_id(false).await?;
+
+    if let (None, Some(from_id)) = (first_sealed, first_legacy) {
+        let sealed = reseal_from(repos, from_id).await?;
+        println!(
+            "Sealed {} legacy operation log rows into the hash chain",
+            sealed
+        );
+    }
+    Ok(())
+}
+
+// 补充早期日志缺失的地址排序键, 不参与哈希计算
+pub async fn backfill_ip_keys(repos: &Repositories) -> Result<(), sqlx::Error> {
+    let keys: Vec<_> = repos
+        .operation_logs
+        .missing_ip_keys()
+        .await?
+        .into_iter()
+        .filter_map(|(id, ip)| ip_key(&ip).map(|key| (id, key)))
+        .collect();
+    repos.operation_logs.set_ip_keys(&keys).await?;
+
+    if !keys.is_empty() {
+        println!("Filled ip_key for {} operation log rows", keys.len());
+    }
+    Ok(())
+}
+
+// 沿哈希链逐条校验, 报告第一处断裂
+pub async fn verify_chain(repos: &Repositories) -> Result<ChainReport, sqlx::Error> {
+    let anchor = chain_anchor(repos).await?;
+    let (redactions, broken_redaction) = trusted_redactions(repos).await?;
+    let mut expected_prev = anchor.clone();
+    let mut last_id = 0;
+    let mut checked = 0;
+    let mut redacted = 0;
+
+    loop {
+        let logs = repos
+            .operation_logs
+            .after(last_id, VERIFY_BATCH_SIZE)
+            .await?;
+        if logs.is_empty() {
+            break;
+        }
+
+        for log in &logs {
+            checked += 1;
+            last_id = log.id;
+
+            let broken = |reason: &str, expected: Option<String>, actual: Option<String>| {
+                Ok(ChainReport {
+                    valid: false,
+                    checked,
+                    redacted,
+                    anchor: anchor.clone(),
+                    first_broken: Some(BrokenLink {
+                        id: log.id,
+                        reason: reason.to_string(),
+                        expected,
+                        actual,
+                    }),
+                    broken_redaction: broken_redaction.clone(),
+                })
+            };
+
+            let hash = match log.hash {
+                Some(ref hash) => hash,
+                None => return broken("日志未封存", None, None),
+            };
+
+            if log.prev_hash.as_deref() != Some(expected_prev.as_str()) {
+                return broken(
+                    "与上一条日志的哈希不一致, 日志可能被删除或插入",
+                    Some(expected_prev),
+                    log.prev_hash.clone(),
+                );
+            }
+
+            let changes = repos.operation_logs.changes(log.id).await?;
+            let computed = HashedLog::from_stored(log, &changes).hash(&expected_prev);
+            // 清洗过的日志保留原哈希, 新内容的哈希需要与清洗记录一致
+            let redaction_matches = || {
+                redactions.get(&log.id).is_some_and(|rows| {
+                    rows.iter()
+                        .any(|row| &row.original_hash == hash && row.redacted_hash == computed)
+                })
+            };
+            if &computed != hash && redaction_matches() {
+                redacted += 1;
+            } else if &computed != hash {
+                return broken(
+                    "日志内容与哈希不一致, 日志可能被修改",
+                    Some(computed),
+                    Some(hash.clone()),
+                );
+            }
+
+            expected_prev = hash.clone();
+        }
+    }
+
+    Ok(ChainReport {
+        valid: broken_redaction.is_none(),
+        checked,
+        redacted,
+        anchor,
+        first_broken: None,
+        broken_redaction,
+    })
+}
+
+// 按保留策略计算需要清理到的最大日志ID, 不会超过最短保留期限
+pub async fn expired_log_id(
+    repos: &Repositories,
+    cutoff: Option<&str>,
+    max_rows: Option<i64>,
+    floor_cutoff: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let mut up_to_id = None;
+
+    if let Some(cutoff) = cutoff {
+        up_to_id = repos.operation_logs.max_id_before(cutoff).await?;
+    }
+
+    if let Some(max_rows) = max_rows {
+        let overflow = repos.operation_logs.id_at_offset(max_rows).await?;
+        up_to_id = up_to_id.max(overflow);
+    }
+
+    // 最短保留期限内的日志不能清理
+    let floor = repos.operation_logs.max_id_before(floor_cutoff).await?;
+
+    Ok(match (up_to_id, floor) {
+        (Some(id), Some(floor)) => Some(id.min(floor)),
+        _ => None,
+    })
+}
+
+// 将ID不大于 up_to_id 的日志分批归档后删除, 每批记录一个检查点作为剩余日志的链起点
+pub async fn purge_operation_logs(
+    repos: &Repositories,
+    up_to_id: i64,
+    cutoff: &str,
+    reason: &str,
+    created_by: Option<&str>,
+) -> Result<PurgeResult, PurgeError> {
+    let archive_dir = &operation_log_config().archive_dir;
+    let mut result = PurgeResult {
+        purged: 0,
+        checkpoint_id: None,
+        archives: Vec::new(),
+    };
+
+    loop {
+        let _guard = CHAIN_LOCK.lock().await;
+
+        let entries = repos
+            .operation_logs
+            .archive_batch(up_to_id, ARCHIVE_BATCH_SIZE)
+            .await?;
+        let last = match entries.last() {
+            Some(last) => &last.log,
+            None => break,
+        };
+
+        // 未封存的日志不能作为检查点
+        let (last_id, last_hash) = match last.hash {
+            Some(ref hash) => (last.id, hash.clone()),
+            None => return Err(PurgeError::Unsealed(last.id)),
+        };
+
+        // 先落盘归档文件, 删除失败时移除归档避免重复
+        let archive = write_archive(archive_dir, &entries).await?;
+        let checkpoint = NewCheckpoint {
+            last_log_id: last_id,
+            last_hash: &last_hash,
+            cutoff,
+            reason,
+            created_by,
+            archive: &archive,
+        };
+        let (purged, checkpoint_id) = match repos.operation_logs.purge(&checkpoint).await {
+            Ok(purged) => purged,
+            Err(e) => {
+                remove_archive(archive_dir, &archive).await;
+                return Err(e.into());
+            }
+        };
+
+        result.purged += purged;
+        result.checkpoint_id = Some(checkpoint_id);
+        result.archives.push(archive);
+    }
+
+    Ok(result)
+}
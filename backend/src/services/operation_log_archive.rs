@@ -0,0 +1,175 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::models::{OperationLog, OperationLogChange};
+use crate::repositories::Repositories;
+
+const ARCHIVE_PREFIX: &str = "operation_logs_";
+const ARCHIVE_SUFFIX: &str = ".jsonl.gz";
+
+// 归档文件每行一条日志及其数据变更
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub log: OperationLog,
+    pub changes: Vec<OperationLogChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchiveFile {
+    pub name: String,
+    pub size: u64,
+    pub modified: String,
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    InvalidName,
+    Io(io::Error),
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::InvalidName => write!(f, "无效的归档文件名"),
+            ArchiveError::Io(e) => write!(f, "{}", e),
+            ArchiveError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(e: io::Error) -> Self {
+        ArchiveError::Io(e)
+    }
+}
+
+impl From<sqlx::Error> for ArchiveError {
+    fn from(e: sqlx::Error) -> Self {
+        ArchiveError::Database(e)
+    }
+}
+
+// 只接受本模块生成的文件名, 防止路径穿越
+pub fn archive_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let valid = name.starts_with(ARCHIVE_PREFIX)
+        && name.ends_with(ARCHIVE_SUFFIX)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    valid.then(|| dir.join(name))
+}
+
+// 写入压缩的 JSON Lines 归档文件, 返回文件名
+pub async fn write_archive(dir: &Path, entries: &[ArchiveEntry]) -> io::Result<String> {
+    let first_id = entries.first().map(|e| e.log.id).unwrap_or_default();
+    let last_id = entries.last().map(|e| e.log.id).unwrap_or_default();
+    let name = format!(
+        "{}{}_{}_{}{}",
+        ARCHIVE_PREFIX,
+        first_id,
+        last_id,
+        chrono::Utc::now().format("%Y%m%d%H%M%S"),
+        ARCHIVE_SUFFIX
+    );
+
+    let lines = entries
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    let dir = dir.to_path_buf();
+    let path = dir.join(&name);
+
+    tokio::task::spawn_blocking(move || -> io::Result<()> {
+        fs::create_dir_all(&dir)?;
+        // 先写临时文件, 完整落盘后再改名
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path)?;
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+        for line in &lines {
+            writeln!(encoder, "{}", line)?;
+        }
+        let file = encoder.finish()?.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)
+    })
+    .await
+    .map_err(io::Error::other)??;
+
+    Ok(name)
+}
+
+pub async fn remove_archive(dir: &Path, name: &str) {
+    if let Some(path) = archive_path(dir, name) {
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            eprintln!("[Archive] Failed to remove archive {}: {}", name, e);
+        }
+    }
+}
+
+pub async fn list_archives(dir: &Path) -> io::Result<Vec<ArchiveFile>> {
+    let mut archives = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(archives),
+        Err(e) => return Err(e),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if archive_path(dir, &name).is_none() {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        let modified = metadata
+            .modified()
+            .map(|t| {
+                chrono::DateTime::<chrono::Utc>::from(t)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        archives.push(ArchiveFile {
+            name,
+            size: metadata.len(),
+            modified,
+        });
+    }
+
+    archives.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(archives)
+}
+
+pub async fn read_archive(dir: &Path, name: &str) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+    let path = archive_path(dir, name).ok_or(ArchiveError::InvalidName)?;
+    let entries = tokio::task::spawn_blocking(move || -> io::Result<Vec<ArchiveEntry>> {
+        let reader = BufReader::new(GzDecoder::new(File::open(path)?));
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push(serde_json::from_str(&line)?);
+        }
+        Ok(entries)
+    })
+    .await
+    .map_err(io::Error::other)??;
+    Ok(entries)
+}
+
+// 将归档重新导入到独立的表中供排查, 不影响线上日志的哈希链
+pub async fn import_archive(
+    repos: &Repositories,
+    dir: &Path,
+    name: &str,
+) -> Result<u64, ArchiveError> {
+    let entries = read_archive(dir, name).await?;
+    Ok(repos.operation_logs.import_archive(name, &entries).await?)
+}
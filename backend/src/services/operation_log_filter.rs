@@ -0,0 +1,151 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use salvo::prelude::*;
+
+use crate::utils::ip::{ip_key, parse_cidr};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// 允许客户端排序的字段
+const SORT_COLUMNS: &[&str] = &[
+    "id",
+    "created_at",
+    "duration_ms",
+    "status",
+    "username",
+    "module",
+    "operation",
+    "method",
+    "ip_key",
+];
+
+const METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+// 操作日志查询条件, 列表和导出共用, 由仓储转换为对应数据库的 SQL
+#[derive(Debug, Default)]
+pub struct OperationLogFilter {
+    pub username: String,
+    pub module: String,
+    pub operation: String,
+    pub status: Option<i32>,
+    pub status_classes: Vec<i32>,
+    pub methods: Vec<String>,
+    pub user_id: Option<i64>,
+    pub ip: Option<IpFilter>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub keyword: String,
+    pub sort_by: String,
+    pub sort_desc: bool,
+}
+
+// 地址排序键的范围, 单个地址时起止相同
+#[derive(Debug)]
+pub struct IpFilter {
+    pub start: String,
+    pub end: String,
+}
+
+impl OperationLogFilter {
+    pub fn from_request(req: &mut Request) -> Result<Self, String> {
+        let text = |req: &mut Request, key: &str| {
+            req.query::<String>(key)
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let mut filter = OperationLogFilter {
+            username: text(req, "username"),
+            module: text(req, "module"),
+            operation: text(req, "operation"),
+            status: req.query::<i32>("status"),
+            user_id: req.query::<i64>("user_id"),
+            keyword: text(req, "keyword"),
+            ..Default::default()
+        };
+
+        for class in split(&text(req, "status_class")) {
+            match class.to_lowercase().as_str() {
+                "2xx" => filter.status_classes.push(2),
+                "3xx" => filter.status_classes.push(3),
+                "4xx" => filter.status_classes.push(4),
+                "5xx" => filter.status_classes.push(5),
+                _ => return Err(format!("无效的状态分类: {}", class)),
+            }
+        }
+
+        for method in split(&text(req, "method")) {
+            let method = method.to_uppercase();
+            if !METHODS.contains(&method.as_str()) {
+                return Err(format!("无效的请求方法: {}", method));
+            }
+            filter.methods.push(method);
+        }
+
+        let ip = text(req, "ip");
+        if !ip.is_empty() {
+            let (start, end) = if ip.contains('/') {
+                parse_cidr(&ip).ok_or_else(|| format!("无效的IP网段: {}", ip))?
+            } else {
+                let key = ip_key(&ip).ok_or_else(|| format!("无效的IP地址: {}", ip))?;
+                (key.clone(), key)
+            };
+            filter.ip = Some(IpFilter { start, end });
+        }
+
+        let start_time = text(req, "start_time");
+        if !start_time.is_empty() {
+            filter.start_time = Some(parse_time(&start_time, false)?);
+        }
+        let end_time = text(req, "end_time");
+        if !end_time.is_empty() {
+            filter.end_time = Some(parse_time(&end_time, true)?);
+        }
+
+        let sort_by = text(req, "sort_by");
+        filter.sort_by = match sort_by.as_str() {
+            "" => "created_at".to_string(),
+            "ip" | "ip_num" => "ip_key".to_string(),
+            column if SORT_COLUMNS.contains(&column) => column.to_string(),
+            column => return Err(format!("不支持的排序字段: {}", column)),
+        };
+        filter.sort_desc = match text(req, "sort_order").to_lowercase().as_str() {
+            "" | "desc" | "descending" => true,
+            "asc" | "ascending" => false,
+            order => return Err(format!("无效的排序方式: {}", order)),
+        };
+
+        Ok(filter)
+    }
+
+    pub fn order_clause(&self) -> String {
+        let order = if self.sort_desc { "DESC" } else { "ASC" };
+        if self.sort_by == "id" {
+            format!(" ORDER BY id {}", order)
+        } else {
+            format!(" ORDER BY {} {}, id {}", self.sort_by, order, order)
+        }
+    }
+}
+
+fn split(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(|s| s.trim()).filter(|s| !s.is_empty())
+}
+
+// 支持 "2024-01-01" 和 "2024-01-01 08:00:00", 只有日期的结束时间包含当天
+pub fn parse_time(value: &str, end_of_day: bool) -> Result<String, String> {
+    let value = value.replace('T', " ");
+    if let Ok(time) = NaiveDateTime::parse_from_str(&value, TIMESTAMP_FORMAT) {
+        return Ok(time.format(TIMESTAMP_FORMAT).to_string());
+    }
+    let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .map_err(|_| format!("无效的时间: {}", value))?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time
+        .unwrap_or_default()
+        .format(TIMESTAMP_FORMAT)
+        .to_string())
+}
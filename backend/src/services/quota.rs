@@ -0,0 +1,84 @@
+use crate::config::upload::upload_config;
+use crate::models::StorageUsage;
+use crate::repositories::Dialect;
+use crate::services::upload::UploadError;
+
+// 用户或角色的 storage_quota 为该值时不限制配额, 为空表示未设置
+pub const UNLIMITED_QUOTA: i64 = -1;
+
+// 用户的存储用量按上传记录的大小计算(包括缩略图), 相同内容去重保存时仍分别计入各自的上传者;
+// 未过期的分片上传会话按声明的大小预留配额, 开始合并后改为按登记的文件计算.
+// 角色配额取已启用角色中设置了配额的最大值, 任一已启用角色不限制配额时不限制
+pub fn select_usage_sql<D: Dialect>() -> String {
+    format!(
+        r#"
+    SELECT u.id AS user_id, u.username, u.nickname,
+        COALESCE(s.used, 0) AS used, COALESCE(s.file_count, 0) AS file_count,
+        (
+            SELECT {reserved}
+            FROM upload_sessions us
+            WHERE us.user_id = u.id AND us.status = 'uploading'
+                AND us.expires_at > CURRENT_TIMESTAMP
+        ) AS reserved,
+        u.storage_quota AS user_quota,
+        (
+            SELECT MAX(r.storage_quota)
+            FROM user_roles ur
+            JOIN roles r ON r.id = ur.role_id
+            WHERE ur.user_id = u.id AND r.status = 1 AND r.storage_quota >= 0
+        ) AS role_quota,
+        EXISTS (
+            SELECT 1
+            FROM user_roles ur
+            JOIN roles r ON r.id = ur.role_id
+            WHERE ur.user_id = u.id AND r.status = 1 AND r.storage_quota = {unlimited}
+        ) AS role_unlimited
+    FROM users u
+    LEFT JOIN (
+        SELECT f.uploader_id,
+            {used} AS used,
+            COUNT(*) AS file_count
+        FROM files f
+        GROUP BY f.uploader_id
+    ) s ON s.uploader_id = u.id
+    "#,
+        unlimited = UNLIMITED_QUOTA,
+        reserved = D::integer("COALESCE(SUM(us.total_size), 0)"),
+        used = D::integer(
+            "SUM(f.size + COALESCE((SELECT SUM(v.size) FROM file_variants v WHERE v.file_id = f.id), 0))"
+        ),
+    )
+}
+
+// 单独设置的配额优先, 其次取已启用角色中最大的配额(有角色不限制时不限制),
+// 都没有设置时使用默认配额
+pub fn resolve_quota(usage: &mut StorageUsage) {
+    let default_quota = upload_config().default_quota;
+    let (quota, source) = match (usage.user_quota, usage.role_quota) {
+        (Some(UNLIMITED_QUOTA), _) => (None, "user"),
+        (Some(quota), _) => (Some(quota), "user"),
+        (None, _) if usage.role_unlimited => (None, "role"),
+        (None, Some(quota)) => (Some(quota), "role"),
+        (None, None) if default_quota > 0 => (Some(default_quota as i64), "default"),
+        (None, None) => (None, "unlimited"),
+    };
+    usage.quota = quota;
+    usage.quota_source = Some(source.to_string());
+}
+
+// 加上预留的空间和本次上传后超过配额时拒绝. 接收文件前的检查只用于提前拒绝,
+// 登记文件或创建会话时在同一个写事务中再检查一次, 避免并发上传同时通过检查
+pub fn check_usage(usage: Option<StorageUsage>, size: u64) -> Result<(), UploadError> {
+    let Some(usage) = usage else {
+        return Ok(());
+    };
+    let used = usage.used + usage.reserved;
+    match usage.quota {
+        Some(quota) if used + size as i64 > quota => Err(UploadError::QuotaExceeded {
+            used: used as u64,
+            quota: quota as u64,
+            size,
+        }),
+        _ => Ok(()),
+    }
+}
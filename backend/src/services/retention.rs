@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use crate::config::operation_log::operation_log_config;
+use crate::repositories::Repositories;
+use crate::services::login_log::purge_login_logs;
+use crate::services::operation_log::{
+    expired_log_id, purge_operation_logs, PurgeError, PurgeResult,
+};
+
+pub fn days_ago(days: usize) -> String {
+    (chrono::Utc::now() - chrono::Duration::days(days as i64))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+// 按配置的保留策略归档并清理过期的操作日志
+pub async fn run_operation_log_retention(repos: &Repositories) -> Result<PurgeResult, PurgeError> {
+    let config = operation_log_config();
+    let floor = days_ago(config.min_retention_days);
+    let cutoff = (config.retention_days > 0).then(|| days_ago(config.retention_days));
+    let max_rows = (config.retention_max_rows > 0).then_some(config.retention_max_rows as i64);
+
+    match expired_log_id(repos, cutoff.as_deref(), max_rows, &floor).await? {
+        Some(up_to_id) => {
+            purge_operation_logs(
+                repos,
+                up_to_id,
+                cutoff.as_deref().unwrap_or(&floor),
+                "retention",
+                None,
+            )
+            .await
+        }
+        None => Ok(PurgeResult {
+            purged: 0,
+            checkpoint_id: None,
+            archives: Vec::new(),
+        }),
+    }
+}
+
+// 启动后台定时任务
+pub fn spawn_retention_task(repos: Repositories) {
+    let config = operation_log_config();
+    let operation_logs_enabled = config.retention_days > 0 || config.retention_max_rows > 0;
+    if !operation_logs_enabled {
+        println!("[Retention] Operation log retention is disabled");
+    }
+    if config.login_log_retention_days == 0 {
+        println!("[Retention] Login log retention is disabled");
+        if !operation_logs_enabled {
+            return;
+        }
+    }
+
+    let period = Duration::from_secs(config.retention_interval_secs.max(60));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if operation_logs_enabled {
+                match run_operation_log_retention(&repos).await {
+                    Ok(result) if result.purged > 0 => println!(
+                        "[Retention] Archived and purged {} operation logs into {:?}",
+                        result.purged, result.archives
+                    ),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("[Retention] Failed to purge operation logs: {}", e),
+                }
+            }
+            match purge_login_logs(&repos).await {
+                Ok(purged) if purged > 0 => {
+                    println!("[Retention] Purged {} login logs", purged)
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[Retention] Failed to purge login logs: {}", e),
+            }
+        }
+    });
+}
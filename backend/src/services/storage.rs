@@ -0,0 +1,301 @@
+use async_trait::async_trait;
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region, RequestChecksumCalculation};
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::storage::{storage_config, S3Config, StorageBackend, StorageConfig};
+
+#[derive(Debug)]
+pub enum StorageError {
+    NotFound,
+    Io(io::Error),
+    Backend(String),
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::NotFound => write!(f, "文件不存在"),
+            StorageError::Io(e) => write!(f, "{}", e),
+            StorageError::Backend(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => StorageError::NotFound,
+            _ => StorageError::Io(e),
+        }
+    }
+}
+
+// 下载时强制使用的响应类型, 重定向到预签名地址时由对象存储按这些值返回
+pub struct DownloadOptions<'a> {
+    pub content_type: &'a str,
+    pub disposition: String,
+}
+
+pub enum Download {
+    // 重定向到对象存储的预签名地址
+    Redirect(String),
+    // 本地磁盘上的文件, 由服务端直接发送
+    File(PathBuf),
+    // 从对象存储读取的文件内容, 由服务端边读边转发
+    Stream {
+        body: ByteStream,
+        length: Option<i64>,
+    },
+}
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn put(&self, key: &str, source: &Path, content_type: &str) -> Result<(), StorageError>;
+
+    async fn put_bytes(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), StorageError>;
+
+    // 文件已不存在时视为删除成功
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    async fn download(
+        &self,
+        key: &str,
+        options: &DownloadOptions<'_>,
+    ) -> Result<Download, StorageError>;
+}
+
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn put(&self, key: &str, source: &Path, _content_type: &str) -> Result<(), StorageError> {
+        let path = self.dir.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(source, path).await?;
+        Ok(())
+    }
+
+    async fn put_bytes(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        _content_type: &str,
+    ) -> Result<(), StorageError> {
+        let path = self.dir.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, data).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_file(self.dir.join(key)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(StorageError::Io(e)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn download(
+        &self,
+        key: &str,
+        _options: &DownloadOptions<'_>,
+    ) -> Result<Download, StorageError> {
+        let path = self.dir.join(key);
+        if !tokio::fs::metadata(&path).await?.is_file() {
+            return Err(StorageError::NotFound);
+        }
+        Ok(Download::File(path))
+    }
+}
+
+// S3 兼容的对象存储, 本地开发可以使用 MinIO
+pub struct S3Storage {
+    client: Client,
+    bucket: String,
+    prefix: String,
+    presign_expires: Duration,
+    redirect: bool,
+}
+
+impl S3Storage {
+    pub async fn new(config: &S3Config, presign_expires: Duration, redirect: bool) -> Self {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(config.region.clone()));
+        // 未配置密钥时使用 AWS 默认的凭证链(环境变量、配置文件、实例角色等)
+        if let (Some(access_key), Some(secret_key)) = (&config.access_key, &config.secret_key) {
+            loader = loader.credentials_provider(Credentials::new(
+                access_key, secret_key, None, None, "storage",
+            ));
+        }
+        let shared = loader.load().await;
+
+        let mut builder =
+            aws_sdk_s3::config::Builder::from(&shared).force_path_style(config.force_path_style);
+        if let Some(endpoint) = &config.endpoint {
+            // 部分兼容服务不支持 aws-chunked 格式的校验和上传, 只在接口要求时计算
+            builder = builder
+                .endpoint_url(endpoint)
+                .request_checksum_calculation(RequestChecksumCalculation::WhenRequired);
+        }
+
+        Self {
+            client: Client::from_conf(builder.build()),
+            bucket: config.bucket.clone(),
+            prefix: config.prefix.clone(),
+            presign_expires,
+            redirect,
+        }
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        body: ByteStream,
+        content_type: &str,
+    ) -> Result<(), StorageError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .content_type(content_type)
+            .body(body)
+            .send()
+            .await
+            .map_err(backend_error)?;
+        Ok(())
+    }
+}
+
+fn backend_error(e: impl std::error::Error) -> StorageError {
+    StorageError::Backend(DisplayErrorContext(e).to_string())
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn put(&self, key: &str, source: &Path, content_type: &str) -> Result<(), StorageError> {
+        let body = ByteStream::from_path(source).await.map_err(backend_error)?;
+        self.put_object(key, body, content_type).await
+    }
+
+    async fn put_bytes(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), StorageError> {
+        self.put_object(key, ByteStream::from(data), content_type)
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .send()
+            .await
+            .map_err(backend_error)?;
+        Ok(())
+    }
+
+    async fn download(
+        &self,
+        key: &str,
+        options: &DownloadOptions<'_>,
+    ) -> Result<Download, StorageError> {
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .response_content_type(options.content_type)
+            .response_content_disposition(&options.disposition);
+
+        if self.redirect {
+            let presigning = PresigningConfig::expires_in(self.presign_expires)
+                .map_err(|e| StorageError::Backend(e.to_string()))?;
+            let presigned = request.presigned(presigning).await.map_err(backend_error)?;
+            return Ok(Download::Redirect(presigned.uri().to_string()));
+        }
+
+        // 转发模式下按块转发响应体, 不把整个文件读入内存
+        let output = match request.send().await {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Err(StorageError::NotFound)
+            }
+            Err(e) => return Err(backend_error(e)),
+        };
+        Ok(Download::Stream {
+            length: output.content_length,
+            body: output.body,
+        })
+    }
+}
+
+static STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+// 按配置创建存储后端, 需要在处理请求和执行命令之前调用
+pub async fn init_storage() -> Result<(), String> {
+    let config: &StorageConfig = storage_config()?;
+    let storage: Box<dyn Storage> = match config.backend {
+        StorageBackend::Local => Box::new(
+            LocalStorage::new(&config.local_dir)
+                .map_err(|e| format!("无法创建上传目录 {}: {}", config.local_dir, e))?,
+        ),
+        StorageBackend::S3 => Box::new(
+            S3Storage::new(
+                &config.s3,
+                Duration::from_secs(config.presign_expires_secs),
+                config.redirect,
+            )
+            .await,
+        ),
+    };
+    println!("[Storage] Using {} storage", storage.name());
+    let _ = STORAGE.set(storage);
+    Ok(())
+}
+
+pub fn storage() -> &'static dyn Storage {
+    STORAGE.get().expect("storage is not initialized").as_ref()
+}
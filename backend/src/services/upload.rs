@@ -0,0 +1,297 @@
+use salvo::http::StatusCode;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::config::upload::upload_config;
+use crate::services::file_access::Visibility;
+
+// 读取文件头用于识别类型, docx 等 Office 文件需要查看压缩包内的前几个条目
+const SNIFF_BYTES: u64 = 64 * 1024;
+
+// 没有文件头特征的纯文本类型, 只在内容是 UTF-8 文本时允许
+const TEXT_TYPES: &[(&str, &str)] = &[("txt", "text/plain"), ("csv", "text/csv")];
+
+// Office 文档本身是 zip 压缩包, 文件头识别不一定能区分, 识别为 zip 时按扩展名处理
+const OOXML_TYPES: &[(&str, &str)] = &[
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+];
+
+// 下载时可以在浏览器中直接显示的类型, 其余一律作为附件下载
+const INLINE_TYPES: &[(&str, &str)] = &[
+    ("jpg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadPurpose {
+    Avatar,
+    Attachment,
+}
+
+impl UploadPurpose {
+    pub fn parse(value: &str) -> Result<Self, UploadError> {
+        match value {
+            "avatar" => Ok(UploadPurpose::Avatar),
+            "" | "attachment" => Ok(UploadPurpose::Attachment),
+            other => Err(UploadError::InvalidPurpose(other.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UploadPurpose::Avatar => "avatar",
+            UploadPurpose::Attachment => "attachment",
+        }
+    }
+
+    pub fn max_size(&self) -> u64 {
+        let config = upload_config();
+        match self {
+            UploadPurpose::Avatar => config.avatar_max_size.min(config.max_size),
+            UploadPurpose::Attachment => config.max_size,
+        }
+    }
+
+    // 分片上传允许的最大文件大小, 头像仍按普通上传的限制
+    pub fn max_chunked_size(&self) -> u64 {
+        let config = upload_config();
+        match self {
+            UploadPurpose::Avatar => self.max_size(),
+            UploadPurpose::Attachment => config.chunked_max_size.max(config.max_size),
+        }
+    }
+
+    // 只按扩展名预先检查, 用于分片上传开始前尽早拒绝, 内容仍在合并后校验
+    pub fn check_extension(&self, file_name: &str) -> Result<(), UploadError> {
+        let claimed = claimed_extension(file_name);
+        if self.allowed_types().contains(&claimed) {
+            Ok(())
+        } else {
+            Err(UploadError::UnsupportedType(claimed))
+        }
+    }
+
+    // 头像会显示在用户列表等处, 始终公开; 附件默认私有, 上传时可以指定为公开
+    pub fn visibility(&self, requested: Option<&str>) -> Result<Visibility, UploadError> {
+        let requested = match requested.map(str::trim).filter(|v| !v.is_empty()) {
+            Some(value) => Some(
+                Visibility::parse(value)
+                    .ok_or_else(|| UploadError::InvalidVisibility(value.to_string()))?,
+            ),
+            None => None,
+        };
+        match (self, requested) {
+            (UploadPurpose::Avatar, Some(Visibility::Private)) => Err(
+                UploadError::InvalidVisibility("private, 头像只能公开".into()),
+            ),
+            (UploadPurpose::Avatar, _) => Ok(Visibility::Public),
+            (UploadPurpose::Attachment, requested) => Ok(requested.unwrap_or(Visibility::Private)),
+        }
+    }
+
+    fn allowed_types(&self) -> &'static [String] {
+        let config = upload_config();
+        match self {
+            UploadPurpose::Avatar => &config.avatar_types,
+            UploadPurpose::Attachment => &config.attachment_types,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UploadError {
+    InvalidPurpose(String),
+    InvalidVisibility(String),
+    TooLarge(u64),
+    UnsupportedType(String),
+    ContentMismatch(String, String),
+    QuotaExceeded { used: u64, quota: u64, size: u64 },
+    Io(io::Error),
+    Database(sqlx::Error),
+}
+
+impl UploadError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            UploadError::InvalidPurpose(_) | UploadError::InvalidVisibility(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            UploadError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadError::UnsupportedType(_) | UploadError::ContentMismatch(_, _) => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+            UploadError::QuotaExceeded { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadError::Io(_) | UploadError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadError::InvalidPurpose(purpose) => write!(f, "无效的上传用途: {}", purpose),
+            UploadError::InvalidVisibility(value) => write!(f, "无效的可见性: {}", value),
+            UploadError::TooLarge(limit) => write!(f, "文件大小不能超过 {}", format_size(*limit)),
+            UploadError::UnsupportedType(ext) if ext.is_empty() => write!(f, "无法识别的文件类型"),
+            UploadError::UnsupportedType(ext) => write!(f, "不支持的文件类型: {}", ext),
+            UploadError::ContentMismatch(claimed, detected) => write!(
+                f,
+                "文件内容与扩展名不符: 扩展名为 {}, 实际内容为 {}",
+                claimed, detected
+            ),
+            UploadError::QuotaExceeded { used, quota, size } => write!(
+                f,
+                "存储空间不足: 已使用 {}, 配额 {}, 本次上传 {}",
+                format_size(*used),
+                format_size(*quota),
+                format_size(*size)
+            ),
+            UploadError::Io(e) => write!(f, "读取上传文件失败: {}", e),
+            UploadError::Database(e) => write!(f, "检查存储配额失败: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for UploadError {
+    fn from(e: sqlx::Error) -> Self {
+        UploadError::Database(e)
+    }
+}
+
+impl From<io::Error> for UploadError {
+    fn from(e: io::Error) -> Self {
+        UploadError::Io(e)
+    }
+}
+
+#[derive(Debug)]
+pub struct DetectedType {
+    pub extension: String,
+    pub mime: String,
+}
+
+// 校验大小和类型, 以文件内容识别出的类型为准, 与扩展名不符时拒绝
+pub fn validate_upload(
+    purpose: UploadPurpose,
+    file_name: &str,
+    path: &Path,
+    size: u64,
+    max_size: u64,
+) -> Result<DetectedType, UploadError> {
+    if size > max_size {
+        return Err(UploadError::TooLarge(max_size));
+    }
+
+    let claimed = claimed_extension(file_name);
+    let allowed = purpose.allowed_types();
+
+    let mut head = Vec::new();
+    File::open(path)?.take(SNIFF_BYTES).read_to_end(&mut head)?;
+
+    match infer::get(&head) {
+        Some(kind) if kind.extension() == "zip" && allowed.contains(&claimed) => {
+            let mime = OOXML_TYPES
+                .iter()
+                .find(|(ext, _)| *ext == claimed)
+                .map(|(_, mime)| *mime)
+                .unwrap_or(kind.mime_type());
+            if claimed != "zip" && mime == kind.mime_type() {
+                return Err(UploadError::ContentMismatch(claimed, "zip".into()));
+            }
+            Ok(DetectedType {
+                extension: claimed,
+                mime: mime.to_string(),
+            })
+        }
+        Some(kind) => {
+            let detected = normalize_extension(kind.extension());
+            if !allowed.contains(&detected) {
+                return Err(UploadError::UnsupportedType(detected));
+            }
+            if claimed != detected {
+                return Err(UploadError::ContentMismatch(claimed, detected));
+            }
+            Ok(DetectedType {
+                extension: detected,
+                mime: kind.mime_type().to_string(),
+            })
+        }
+        None => {
+            let text_type = TEXT_TYPES.iter().find(|(ext, _)| *ext == claimed);
+            match text_type {
+                Some((ext, mime)) if allowed.contains(&claimed) => {
+                    if !looks_like_text(&head) {
+                        return Err(UploadError::ContentMismatch(claimed, "二进制数据".into()));
+                    }
+                    Ok(DetectedType {
+                        extension: ext.to_string(),
+                        mime: mime.to_string(),
+                    })
+                }
+                _ => Err(UploadError::UnsupportedType(claimed)),
+            }
+        }
+    }
+}
+
+// 下载时使用的 Content-Type, 以及是否允许在浏览器中直接显示
+pub fn download_content_type(file_name: &str) -> (&'static str, bool) {
+    let ext = claimed_extension(file_name);
+    match INLINE_TYPES
+        .iter()
+        .find(|(inline_ext, _)| *inline_ext == ext)
+    {
+        Some((_, mime)) => (mime, true),
+        None => ("application/octet-stream", false),
+    }
+}
+
+pub fn claimed_extension(file_name: &str) -> String {
+    Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(normalize_extension)
+        .unwrap_or_default()
+}
+
+fn normalize_extension(ext: &str) -> String {
+    match ext.to_lowercase().as_str() {
+        "jpeg" | "jpe" => "jpg".to_string(),
+        ext => ext.to_string(),
+    }
+}
+
+// 不含 NUL 且是合法 UTF-8 的内容视为文本, 允许读取截断在多字节字符中间
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1}MB", bytes as f64 / 1024.0 / 1024.0)
+    } else if bytes >= 1024 {
+        format!("{:.1}KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{}B", bytes)
+    }
+}
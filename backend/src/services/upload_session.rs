@@ -0,0 +1,508 @@
+use salvo::http::StatusCode;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::config::upload::upload_config;
+use crate::models::{CreateUploadSession, FileRecord, UploadSession};
+use crate::repositories::upload_session::NewUploadSession;
+use crate::repositories::Repositories;
+use crate::services::files::{store_upload, FileError};
+use crate::services::upload::{validate_upload, UploadError, UploadPurpose};
+
+// 单个会话最多的分片数, 避免过小的分片产生大量请求和记录
+const MAX_CHUNKS: i64 = 10_000;
+
+const STATUS_UPLOADING: &str = "uploading";
+const STATUS_COMPLETING: &str = "completing";
+const STATUS_COMPLETED: &str = "completed";
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const AVATAR_NOT_SUPPORTED: &str = "头像不支持分片上传, 请使用 /upload/avatar 上传";
+
+#[derive(Debug)]
+pub enum SessionError {
+    NotFound,
+    Invalid(String),
+    Conflict(String),
+    ChecksumMismatch { expected: String, actual: String },
+    Upload(UploadError),
+    File(FileError),
+    Database(sqlx::Error),
+    Io(io::Error),
+}
+
+impl SessionError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            SessionError::NotFound => StatusCode::NOT_FOUND,
+            SessionError::Invalid(_) => StatusCode::BAD_REQUEST,
+            SessionError::Conflict(_) => StatusCode::CONFLICT,
+            SessionError::ChecksumMismatch { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            SessionError::Upload(e) => e.status_code(),
+            SessionError::File(_) | SessionError::Database(_) | SessionError::Io(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::NotFound => write!(f, "上传会话不存在或已过期"),
+            SessionError::Invalid(message) | SessionError::Conflict(message) => {
+                write!(f, "{}", message)
+            }
+            SessionError::ChecksumMismatch { expected, actual } => {
+                write!(f, "校验和不一致: 期望 {}, 实际 {}", expected, actual)
+            }
+            SessionError::Upload(e) => write!(f, "{}", e),
+            SessionError::File(e) => write!(f, "保存文件失败: {}", e),
+            SessionError::Database(e) => write!(f, "{}", e),
+            SessionError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<UploadError> for SessionError {
+    fn from(e: UploadError) -> Self {
+        SessionError::Upload(e)
+    }
+}
+
+impl From<FileError> for SessionError {
+    fn from(e: FileError) -> Self {
+        match e {
+            FileError::Upload(e) => SessionError::Upload(e),
+            e => SessionError::File(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SessionError {
+    fn from(e: sqlx::Error) -> Self {
+        SessionError::Database(e)
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(e: io::Error) -> Self {
+        SessionError::Io(e)
+    }
+}
+
+// 会话详情, 附带已收到的分片序号, 客户端据此续传缺少的分片
+#[derive(Debug, Serialize)]
+pub struct UploadSessionStatus {
+    #[serde(flatten)]
+    pub session: UploadSession,
+    pub received_chunks: Vec<i64>,
+    pub received_size: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkReceipt {
+    pub index: i64,
+    pub size: i64,
+    pub sha256: String,
+}
+
+fn session_dir(id: &str) -> PathBuf {
+    Path::new(&upload_config().session_dir).join(id)
+}
+
+fn chunk_path(id: &str, index: i64) -> PathBuf {
+    session_dir(id).join(format!("{:05}.part", index))
+}
+
+fn expires_at() -> String {
+    let ttl = chrono::Duration::hours(upload_config().session_ttl_hours as i64);
+    (chrono::Utc::now() + ttl)
+        .format(TIMESTAMP_FORMAT)
+        .to_string()
+}
+
+fn normalize_checksum(value: &str) -> Result<String, SessionError> {
+    let value = value.trim().to_lowercase();
+    if value.len() != 64 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(SessionError::Invalid(
+            "sha256 必须是 64 位十六进制字符串".into(),
+        ));
+    }
+    Ok(value)
+}
+
+pub async fn create_session(
+    repos: &Repositories,
+    user_id: i64,
+    request: CreateUploadSession,
+) -> Result<UploadSessionStatus, SessionError> {
+    let file_name = request.file_name.trim().to_string();
+    if file_name.is_empty() {
+        return Err(SessionError::Invalid("文件名不能为空".into()));
+    }
+    let purpose = UploadPurpose::parse(request.purpose.as_deref().unwrap_or("").trim())?;
+    // 头像需要解码裁剪、去除 EXIF 并生成缩略图, 只能通过 /upload/avatar 上传
+    if purpose == UploadPurpose::Avatar {
+        return Err(SessionError::Invalid(AVATAR_NOT_SUPPORTED.into()));
+    }
+    purpose.check_extension(&file_name)?;
+    let visibility = purpose.visibility(request.visibility.as_deref())?;
+
+    if request.size <= 0 {
+        return Err(SessionError::Invalid("文件大小必须大于 0".into()));
+    }
+    if request.size as u64 > purpose.max_chunked_size() {
+        return Err(UploadError::TooLarge(purpose.max_chunked_size()).into());
+    }
+
+    let config = upload_config();
+    let chunk_size = request.chunk_size.unwrap_or(config.chunk_size as i64);
+    if chunk_size <= 0 || chunk_size as u64 > config.max_chunk_size {
+        return Err(SessionError::Invalid(format!(
+            "分片大小必须在 1 到 {} 字节之间",
+            config.max_chunk_size
+        )));
+    }
+    let total_chunks = (request.size + chunk_size - 1) / chunk_size;
+    if total_chunks > MAX_CHUNKS {
+        return Err(SessionError::Invalid(format!(
+            "分片数量不能超过 {}, 请增大分片大小",
+            MAX_CHUNKS
+        )));
+    }
+    let sha256 = request
+        .sha256
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+        .map(normalize_checksum)
+        .transpose()?;
+
+    // 会话按声明的大小预留配额
+    let id = Uuid::new_v4().to_string();
+    let session = repos
+        .upload_sessions
+        .create(&NewUploadSession {
+            id: id.clone(),
+            user_id,
+            purpose: purpose.name().to_string(),
+            visibility: visibility.name().to_string(),
+            file_name,
+            total_size: request.size,
+            chunk_size,
+            total_chunks,
+            sha256,
+            expires_at: expires_at(),
+        })
+        .await?;
+
+    if let Err(e) = std::fs::create_dir_all(session_dir(&id)) {
+        delete_session(repos, &id).await?;
+        return Err(e.into());
+    }
+
+    Ok(UploadSessionStatus {
+        session,
+        received_chunks: Vec::new(),
+        received_size: 0,
+    })
+}
+
+// 只能访问自己创建且未过期的会话
+pub async fn find_session(
+    repos: &Repositories,
+    id: &str,
+    user_id: i64,
+) -> Result<UploadSession, SessionError> {
+    let now = chrono::Utc::now().format(TIMESTAMP_FORMAT).to_string();
+    repos
+        .upload_sessions
+        .find(id, user_id, &now)
+        .await?
+        .ok_or(SessionError::NotFound)
+}
+
+pub async fn session_status(
+    repos: &Repositories,
+    session: UploadSession,
+) -> Result<UploadSessionStatus, SessionError> {
+    let chunks = repos.upload_sessions.chunks(&session.id).await?;
+
+    Ok(UploadSessionStatus {
+        session,
+        received_size: chunks.iter().map(|(_, size)| size).sum(),
+        received_chunks: chunks.into_iter().map(|(index, _)| index).collect(),
+    })
+}
+
+// 重复上传同一分片时覆盖之前的内容, 便于客户端在不确定是否成功时重试
+pub async fn save_chunk(
+    repos: &Repositories,
+    session: &UploadSession,
+    index: i64,
+    data: &[u8],
+    checksum: Option<&str>,
+) -> Result<ChunkReceipt, SessionError> {
+    if session.status != STATUS_UPLOADING {
+        return Err(SessionError::Conflict(format!(
+            "上传会话状态为 {}, 不能继续上传分片",
+            session.status
+        )));
+    }
+    if index < 0 || index >= session.total_chunks {
+        return Err(SessionError::Invalid(format!(
+            "分片序号必须在 0 到 {} 之间",
+            session.total_chunks - 1
+        )));
+    }
+
+    // 除最后一片外每片大小必须等于分片大小
+    let expected_size = if index == session.total_chunks - 1 {
+        session.total_size - index * session.chunk_size
+    } else {
+        session.chunk_size
+    };
+    if data.len() as i64 != expected_size {
+        return Err(SessionError::Invalid(format!(
+            "分片 {} 的大小应为 {} 字节, 实际为 {} 字节",
+            index,
+            expected_size,
+            data.len()
+        )));
+    }
+
+    let sha256 = format!("{:x}", Sha256::digest(data));
+    if let Some(checksum) = checksum {
+        let expected = normalize_checksum(checksum)?;
+        if expected != sha256 {
+            return Err(SessionError::ChecksumMismatch {
+                expected,
+                actual: sha256,
+            });
+        }
+    }
+
+    // 先写临时文件再重命名, 避免中断时留下不完整的分片
+    let path = chunk_path(&session.id, index);
+    let temp_path = path.with_extension("tmp");
+    std::fs::create_dir_all(session_dir(&session.id))?;
+    std::fs::write(&temp_path, data)?;
+    std::fs::rename(&temp_path, &path)?;
+
+    // 有上传活动时顺延过期时间
+    repos
+        .upload_sessions
+        .save_chunk(&session.id, index, expected_size, &sha256, &expires_at())
+        .await?;
+
+    Ok(ChunkReceipt {
+        index,
+        size: expected_size,
+        sha256,
+    })
+}
+
+// 合并所有分片并校验整体的 sha256, 通过内容校验后写入存储并登记文件
+pub async fn complete_session(
+    repos: &Repositories,
+    session: &UploadSession,
+    checksum: Option<&str>,
+) -> Result<FileRecord, SessionError> {
+    if session.status != STATUS_UPLOADING {
+        return Err(SessionError::Conflict(format!(
+            "上传会话状态为 {}, 不能完成",
+            session.status
+        )));
+    }
+    let expected = match checksum
+        .filter(|value| !value.trim().is_empty())
+        .map(normalize_checksum)
+        .transpose()?
+        .or_else(|| session.sha256.clone())
+    {
+        Some(expected) => expected,
+        None => {
+            return Err(SessionError::Invalid(
+                "完成上传时必须提供文件的 sha256".into(),
+            ))
+        }
+    };
+    if session
+        .sha256
+        .as_ref()
+        .is_some_and(|sha256| *sha256 != expected)
+    {
+        return Err(SessionError::Invalid(
+            "sha256 与创建会话时提供的不一致".into(),
+        ));
+    }
+
+    // 标记为合并中, 防止重复完成或在合并时继续上传分片
+    let claimed = repos
+        .upload_sessions
+        .transition(&session.id, STATUS_UPLOADING, STATUS_COMPLETING)
+        .await?;
+    if !claimed {
+        return Err(SessionError::Conflict("上传会话正在合并或已完成".into()));
+    }
+
+    match assemble_and_store(repos, session, &expected).await {
+        Ok(file) => {
+            repos
+                .upload_sessions
+                .complete(&session.id, STATUS_COMPLETED, file.id)
+                .await?;
+            remove_session_dir(&session.id);
+            Ok(file)
+        }
+        Err(e) => {
+            // 失败后恢复为上传中, 客户端可以重新上传分片后再次完成或放弃
+            let _ = std::fs::remove_file(session_dir(&session.id).join("assembled"));
+            repos
+                .upload_sessions
+                .set_status(&session.id, STATUS_UPLOADING)
+                .await?;
+            Err(e)
+        }
+    }
+}
+
+async fn assemble_and_store(
+    repos: &Repositories,
+    session: &UploadSession,
+    expected: &str,
+) -> Result<FileRecord, SessionError> {
+    // 引入限制之前创建的头像会话不能完成
+    if session.purpose == UploadPurpose::Avatar.name() {
+        return Err(SessionError::Invalid(AVATAR_NOT_SUPPORTED.into()));
+    }
+
+    let received: Vec<i64> = repos
+        .upload_sessions
+        .chunks(&session.id)
+        .await?
+        .into_iter()
+        .map(|(index, _)| index)
+        .collect();
+    let missing: Vec<String> = (0..session.total_chunks)
+        .filter(|index| received.binary_search(index).is_err())
+        .map(|index| index.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(SessionError::Conflict(format!(
+            "还有 {} 个分片未上传: {}",
+            missing.len(),
+            missing.join(", ")
+        )));
+    }
+
+    let assembled = session_dir(&session.id).join("assembled");
+    let id = session.id.clone();
+    let total_chunks = session.total_chunks;
+    let target = assembled.clone();
+    let actual = tokio::task::spawn_blocking(move || concat_chunks(&id, total_chunks, &target))
+        .await
+        .map_err(|e| SessionError::Io(io::Error::other(e)))??;
+    if actual != expected {
+        return Err(SessionError::ChecksumMismatch {
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+
+    let purpose = UploadPurpose::parse(&session.purpose)?;
+    let visibility = purpose.visibility(Some(&session.visibility))?;
+    let size = session.total_size as u64;
+    let detected = validate_upload(
+        purpose,
+        &session.file_name,
+        &assembled,
+        size,
+        purpose.max_chunked_size(),
+    )?;
+    // 开始合并后会话不再预留配额, 上传期间可能有其他上传占用了配额, 合并后再检查一次;
+    // 登记文件时还会在事务中做最终的检查
+    repos.files.check_quota(session.user_id, size).await?;
+    let file = store_upload(
+        repos,
+        &assembled,
+        &session.file_name,
+        size,
+        actual,
+        detected,
+        purpose,
+        visibility,
+        session.user_id,
+    )
+    .await?;
+    Ok(file)
+}
+
+// 按序号拼接分片, 同时计算整个文件的 sha256
+fn concat_chunks(id: &str, total_chunks: i64, target: &Path) -> io::Result<String> {
+    let mut output = io::BufWriter::new(std::fs::File::create(target)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    for index in 0..total_chunks {
+        let mut chunk = std::fs::File::open(chunk_path(id, index))?;
+        loop {
+            let read = chunk.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            output.write_all(&buffer[..read])?;
+        }
+    }
+    output.flush()?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub async fn abort_session(
+    repos: &Repositories,
+    session: &UploadSession,
+) -> Result<(), SessionError> {
+    if session.status == STATUS_COMPLETING {
+        return Err(SessionError::Conflict("上传会话正在合并, 不能放弃".into()));
+    }
+    delete_session(repos, &session.id).await?;
+    Ok(())
+}
+
+async fn delete_session(repos: &Repositories, id: &str) -> Result<(), sqlx::Error> {
+    repos.upload_sessions.delete(id).await?;
+    remove_session_dir(id);
+    Ok(())
+}
+
+fn remove_session_dir(id: &str) {
+    if let Err(e) = std::fs::remove_dir_all(session_dir(id)) {
+        if e.kind() != io::ErrorKind::NotFound {
+            eprintln!("[Upload Session] Failed to remove chunks of {}: {}", id, e);
+        }
+    }
+}
+
+// 清理过期的会话和暂存的分片, 已完成的会话保留到过期以便客户端查询结果
+pub async fn purge_expired_sessions(repos: &Repositories) -> Result<u64, sqlx::Error> {
+    // 合并中的会话只在长时间没有进展时清理, 例如合并过程中服务被重启
+    let now = chrono::Utc::now();
+    let stalled = now - chrono::Duration::hours(1);
+    let ids = repos
+        .upload_sessions
+        .expired(
+            &now.format(TIMESTAMP_FORMAT).to_string(),
+            STATUS_COMPLETING,
+            &stalled.format(TIMESTAMP_FORMAT).to_string(),
+        )
+        .await?;
+
+    for id in &ids {
+        delete_session(repos, id).await?;
+    }
+    Ok(ids.len() as u64)
+}
@@ -0,0 +1,82 @@
+use salvo::Depot;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::operation_log::operation_log_config;
+use crate::models::{CreateOperationLog, CreateOperationLogChange};
+use crate::utils::redact::redact_json;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+// 比较两个对象的字段, 返回发生变化的字段
+pub fn diff_fields(before: &Value, after: &Value) -> Vec<FieldChange> {
+    let empty = serde_json::Map::new();
+    let before_map = before.as_object().unwrap_or(&empty);
+    let after_map = after.as_object().unwrap_or(&empty);
+
+    let mut fields: Vec<&String> = before_map.keys().chain(after_map.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let old = before_map.get(field).cloned().unwrap_or(Value::Null);
+            let new = after_map.get(field).cloned().unwrap_or(Value::Null);
+            (old != new).then(|| FieldChange {
+                field: field.clone(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+// 将实体修改前后的快照附加到当前请求的操作日志中
+pub fn record_change<T: Serialize>(
+    depot: &mut Depot,
+    entity: &str,
+    entity_id: i64,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    let action = match (before.is_some(), after.is_some()) {
+        (true, true) => "update",
+        (true, false) => "delete",
+        (false, true) => "create",
+        (false, false) => return,
+    };
+
+    let log = match depot.get_mut::<CreateOperationLog>("operation_log") {
+        Ok(log) => log,
+        Err(_) => return,
+    };
+
+    let before = before.map(snapshot);
+    let after = after.map(snapshot);
+    let diff = diff_fields(
+        before.as_ref().unwrap_or(&Value::Null),
+        after.as_ref().unwrap_or(&Value::Null),
+    );
+
+    log.changes.push(CreateOperationLogChange {
+        entity: entity.to_string(),
+        entity_id,
+        action: action.to_string(),
+        before: before.map(|v| v.to_string()),
+        after: after.map(|v| v.to_string()),
+        diff: serde_json::to_string(&diff).unwrap_or_default(),
+    });
+}
+
+// 快照中的敏感字段(如密码哈希)同样需要脱敏
+fn snapshot<T: Serialize>(entity: &T) -> Value {
+    let mut value = serde_json::to_value(entity).unwrap_or(Value::Null);
+    redact_json(&mut value, &operation_log_config().redact_keys);
+    value
+}
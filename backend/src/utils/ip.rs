@@ -0,0 +1,160 @@
+use std::net::{IpAddr, SocketAddr};
+
+// 解析客户端地址, 兼容 "socket://1.2.3.4:5678"、"1.2.3.4:5678"、"[::1]:5678" 和纯IP
+pub fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    let value = value.strip_prefix("socket://").unwrap_or(value);
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Some(normalize(addr.ip()));
+    }
+    value.parse::<IpAddr>().ok().map(normalize)
+}
+
+// IPv4 映射的 IPv6 地址按 IPv4 处理
+fn normalize(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+// 地址的排序键: IPv4 按映射的 IPv6 地址计算, 固定32位的十六进制字符串, 字典序与地址大小一致,
+// 两种地址可以在同一列中按范围查询
+pub fn ip_key(value: &str) -> Option<String> {
+    parse_ip(value).map(|ip| format!("{:032x}", ip_number(ip)))
+}
+
+fn ip_number(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u128::from(v4.to_ipv6_mapped()),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+// 解析 IPv4 或 IPv6 网段, 返回起止地址的排序键, 例如 "10.0.0.0/8"、"2001:db8::/32"
+pub fn parse_cidr(value: &str) -> Option<(String, String)> {
+    let (addr, prefix) = value.trim().split_once('/')?;
+    let addr = normalize(addr.trim().parse::<IpAddr>().ok()?);
+    let prefix = prefix.trim().parse::<u32>().ok()?;
+    // IPv4 地址映射到 IPv6 后前96位固定
+    let prefix = match addr {
+        IpAddr::V4(_) if prefix <= 32 => prefix + 96,
+        IpAddr::V6(_) if prefix <= 128 => prefix,
+        _ => return None,
+    };
+    let mask = if prefix == 0 {
+        0
+    } else {
+        u128::MAX << (128 - prefix)
+    };
+    let start = ip_number(addr) & mask;
+    let end = start | !mask;
+    Some((format!("{:032x}", start), format!("{:032x}", end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(value: &str) -> String {
+        ip_key(value).unwrap()
+    }
+
+    fn cidr(value: &str) -> (String, String) {
+        parse_cidr(value).unwrap()
+    }
+
+    #[test]
+    fn parse_ip_formats() {
+        let v4: IpAddr = "1.2.3.4".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(parse_ip("1.2.3.4"), Some(v4));
+        assert_eq!(parse_ip(" 1.2.3.4:5678 "), Some(v4));
+        assert_eq!(parse_ip("socket://1.2.3.4:5678"), Some(v4));
+        assert_eq!(parse_ip("2001:db8::1"), Some(v6));
+        assert_eq!(parse_ip("[2001:db8::1]:443"), Some(v6));
+        assert_eq!(parse_ip("socket://[2001:db8::1]:443"), Some(v6));
+        assert_eq!(parse_ip("unknown"), None);
+    }
+
+    #[test]
+    fn ipv4_mapped_ipv6_is_ipv4() {
+        assert_eq!(parse_ip("::ffff:1.2.3.4"), parse_ip("1.2.3.4"));
+        assert_eq!(parse_ip("[::ffff:1.2.3.4]:80"), parse_ip("1.2.3.4"));
+        assert_eq!(key("::ffff:10.0.0.1"), key("10.0.0.1"));
+        assert_eq!(cidr("::ffff:10.0.0.0/8"), cidr("10.0.0.0/8"));
+    }
+
+    #[test]
+    fn ip_key_order() {
+        assert_eq!(key("0.0.0.0"), "00000000000000000000ffff00000000");
+        assert_eq!(key("1.2.3.4"), "00000000000000000000ffff01020304");
+        assert!(key("9.255.255.255") < key("10.0.0.0"));
+        assert!(key("10.0.0.2") < key("10.0.0.10"));
+        assert!(key("::1") < key("0.0.0.0"));
+        assert!(key("255.255.255.255") < key("2001:db8::"));
+        assert!(key("2001:db8::ffff") < key("2001:db8::1:0"));
+    }
+
+    #[test]
+    fn ipv4_cidr_bounds() {
+        let (start, end) = cidr("10.1.2.3/8");
+        assert_eq!(start, key("10.0.0.0"));
+        assert_eq!(end, key("10.255.255.255"));
+
+        assert_eq!(
+            cidr("192.168.1.7/32"),
+            (key("192.168.1.7"), key("192.168.1.7"))
+        );
+        assert_eq!(
+            cidr("172.16.0.0/12"),
+            (key("172.16.0.0"), key("172.31.255.255"))
+        );
+
+        // /0 只覆盖 IPv4 地址
+        assert_eq!(cidr("0.0.0.0/0"), (key("0.0.0.0"), key("255.255.255.255")));
+    }
+
+    #[test]
+    fn ipv6_cidr_bounds() {
+        assert_eq!(
+            cidr("2001:db8::/32"),
+            (
+                key("2001:db8::"),
+                key("2001:db8:ffff:ffff:ffff:ffff:ffff:ffff")
+            )
+        );
+        assert_eq!(
+            cidr("2001:db8:0:1::5/64"),
+            (
+                key("2001:db8:0:1::"),
+                key("2001:db8:0:1:ffff:ffff:ffff:ffff")
+            )
+        );
+        assert_eq!(
+            cidr("2001:db8::1/128"),
+            (key("2001:db8::1"), key("2001:db8::1"))
+        );
+        assert_eq!(
+            cidr("::/0"),
+            (
+                "00000000000000000000000000000000".to_string(),
+                "ffffffffffffffffffffffffffffffff".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn cidr_rejects_bad_input() {
+        assert_eq!(parse_cidr("10.0.0.0/33"), None);
+        assert_eq!(parse_cidr("2001:db8::/129"), None);
+        assert_eq!(parse_cidr("10.0.0.0/-1"), None);
+        assert_eq!(parse_cidr("10.0.0.0/abc"), None);
+        assert_eq!(parse_cidr("10.0.0.0/"), None);
+        assert_eq!(parse_cidr("10.0.0.0"), None);
+        assert_eq!(parse_cidr("10.0.0/8"), None);
+    }
+}
@@ -1,2 +1,5 @@
+pub mod audit;
+pub mod ip;
 pub mod jwt;
 pub mod password;
+pub mod redact;
@@ -0,0 +1,250 @@
+use serde_json::{json, Value};
+
+use crate::config::operation_log::OperationLogConfig;
+
+pub const REDACTED: &str = "******";
+
+// 字段名模式匹配, 不区分大小写, * 匹配任意字符
+pub fn key_matches(pattern: &str, key: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let key = key.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == key;
+    }
+
+    let mut rest = key.as_str();
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+// 递归脱敏 JSON 中命中模式的字段
+pub fn redact_json(value: &mut Value, patterns: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if patterns.iter().any(|p| key_matches(p, key)) {
+                    *v = json!(REDACTED);
+                } else {
+                    redact_json(v, patterns);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                redact_json(item, patterns);
+            }
+        }
+        _ => {}
+    }
+}
+
+// 对请求参数 {"query": {...}, "body": ...} 应用路由规则和字段名规则
+pub fn redact_params(method: &str, path: &str, params: &mut Value, config: &OperationLogConfig) {
+    for rule in config
+        .redact_rules
+        .iter()
+        .filter(|rule| rule.matches(method, path))
+    {
+        if rule.redacts_whole_body() {
+            if !params["body"].is_null() {
+                params["body"] = json!(REDACTED);
+            }
+        } else {
+            redact_json(&mut params["body"], &rule.fields);
+            redact_json(&mut params["query"], &rule.fields);
+        }
+    }
+
+    redact_json(params, &config.redact_keys);
+}
+
+// 超过长度限制时只保留预览内容
+pub fn truncate_params(params: String, max_bytes: usize) -> String {
+    if params.len() <= max_bytes {
+        return params;
+    }
+
+    let mut end = max_bytes;
+    while !params.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    json!({
+        "truncated": true,
+        "size": params.len(),
+        "preview": &params[..end]
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::operation_log::RedactRule;
+
+    fn patterns(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    fn config(keys: &[&str], rules: Vec<RedactRule>) -> OperationLogConfig {
+        OperationLogConfig {
+            redact_keys: patterns(keys),
+            redact_rules: rules,
+            ..OperationLogConfig::from_env()
+        }
+    }
+
+    #[test]
+    fn key_matches_globs() {
+        assert!(key_matches("*password*", "password"));
+        assert!(key_matches("*password*", "oldPassword"));
+        assert!(key_matches("*password*", "PASSWORD_CONFIRM"));
+        assert!(!key_matches("*password*", "passwd"));
+
+        assert!(key_matches("*token", "access_token"));
+        assert!(!key_matches("*token", "token_type"));
+        assert!(key_matches("api_*", "API_KEY"));
+        assert!(!key_matches("api_*", "my_api_key"));
+        assert!(key_matches("x-*-token", "X-Csrf-Token"));
+        assert!(!key_matches("x-*-token", "x-token"));
+        assert!(key_matches("*", "anything"));
+    }
+
+    #[test]
+    fn key_matches_exact() {
+        assert!(key_matches("authorization", "Authorization"));
+        assert!(!key_matches("authorization", "proxy-authorization"));
+        assert!(!key_matches("authorization", "authorization_header"));
+    }
+
+    #[test]
+    fn redact_json_nested() {
+        let mut value = json!({
+            "username": "alice",
+            "password": "secret",
+            "profile": {
+                "nickname": "A",
+                "apiToken": "abc",
+                "tags": ["token", "password"]
+            },
+            "accounts": [
+                {"name": "main", "password": "p1"},
+                [{"refresh_token": "r1", "id": 1}]
+            ],
+            "credentials": {"user": "x", "pass": "y"}
+        });
+        redact_json(
+            &mut value,
+            &patterns(&["*password*", "*token*", "credentials"]),
+        );
+
+        assert_eq!(
+            value,
+            json!({
+                "username": "alice",
+                "password": REDACTED,
+                "profile": {
+                    "nickname": "A",
+                    "apiToken": REDACTED,
+                    "tags": ["token", "password"]
+                },
+                "accounts": [
+                    {"name": "main", "password": REDACTED},
+                    [{"refresh_token": REDACTED, "id": 1}]
+                ],
+                "credentials": REDACTED
+            })
+        );
+    }
+
+    #[test]
+    fn redact_params_query_access_token() {
+        let config = config(&["*token*", "authorization"], Vec::new());
+        let mut params = json!({
+            "query": {"access_token": "eyJhbGciOi", "page": "1"},
+            "body": null
+        });
+        redact_params("GET", "/operation-logs/stream", &mut params, &config);
+
+        assert_eq!(
+            params,
+            json!({
+                "query": {"access_token": REDACTED, "page": "1"},
+                "body": null
+            })
+        );
+    }
+
+    #[test]
+    fn redact_params_route_rules() {
+        let config = config(
+            &["*secret*"],
+            vec![
+                RedactRule {
+                    method: "PATCH".to_string(),
+                    path: "/profile/password".to_string(),
+                    fields: vec!["*".to_string()],
+                },
+                RedactRule {
+                    method: "POST".to_string(),
+                    path: "/users".to_string(),
+                    fields: vec!["pin".to_string()],
+                },
+            ],
+        );
+
+        let mut params = json!({
+            "query": {},
+            "body": {"old": "a", "new": "b"}
+        });
+        redact_params("PATCH", "/profile/password", &mut params, &config);
+        assert_eq!(params["body"], json!(REDACTED));
+
+        let mut params = json!({
+            "query": {"pin": "1234"},
+            "body": {"username": "bob", "pin": "0000", "client_secret": "s"}
+        });
+        redact_params("POST", "/users", &mut params, &config);
+        assert_eq!(
+            params,
+            json!({
+                "query": {"pin": REDACTED},
+                "body": {"username": "bob", "pin": REDACTED, "client_secret": REDACTED}
+            })
+        );
+
+        // 规则只对匹配的方法和路径生效
+        let mut params = json!({"query": {}, "body": {"pin": "0000"}});
+        redact_params("PUT", "/users", &mut params, &config);
+        assert_eq!(params["body"]["pin"], json!("0000"));
+    }
+
+    #[test]
+    fn truncate_params_keeps_char_boundary() {
+        assert_eq!(truncate_params("short".to_string(), 10), "short");
+
+        let truncated: Value =
+            serde_json::from_str(&truncate_params("密码密码".to_string(), 4)).unwrap();
+        assert_eq!(truncated["truncated"], json!(true));
+        assert_eq!(truncated["size"], json!(12));
+        assert_eq!(truncated["preview"], json!("密"));
+    }
+}
@@ -0,0 +1,9 @@
+import request from '@/utils/request'
+
+export function getDashboardStats(params) {
+    return request({
+        url: '/dashboard/stats',
+        method: 'get',
+        params
+    })
+}
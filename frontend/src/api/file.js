@@ -0,0 +1,78 @@
+import request from '@/utils/request'
+
+export function getFiles(params) {
+    return request({
+        url: '/files',
+        method: 'get',
+        params
+    })
+}
+
+export function getFile(id) {
+    return request({
+        url: `/files/${id}`,
+        method: 'get'
+    })
+}
+
+export function deleteFile(id) {
+    return request({
+        url: `/files/${id}`,
+        method: 'delete'
+    })
+}
+
+export function getFileSignedUrl(id, expiresIn) {
+    return request({
+        url: `/files/${id}/signed-url`,
+        method: 'get',
+        params: { expires_in: expiresIn }
+    })
+}
+
+export function updateFileVisibility(id, visibility) {
+    return request({
+        url: `/files/${id}/visibility`,
+        method: 'patch',
+        data: { visibility }
+    })
+}
+
+export function downloadFile(id) {
+    return request({
+        url: `/files/${id}/download`,
+        method: 'get',
+        responseType: 'blob'
+    })
+}
+
+export function getStorageUsage(params) {
+    return request({
+        url: '/files/usage',
+        method: 'get',
+        params
+    })
+}
+
+export function getMyStorageUsage() {
+    return request({
+        url: '/profile/storage',
+        method: 'get'
+    })
+}
+
+export function updateUserStorageQuota(userId, quota) {
+    return request({
+        url: `/users/${userId}/storage-quota`,
+        method: 'put',
+        data: { quota }
+    })
+}
+
+export function updateRoleStorageQuota(roleId, quota) {
+    return request({
+        url: `/roles/${roleId}/storage-quota`,
+        method: 'put',
+        data: { quota }
+    })
+}
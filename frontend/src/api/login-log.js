@@ -0,0 +1,17 @@
+import request from '@/utils/request'
+
+export function getLoginLogs(params) {
+    return request({
+        url: '/login-logs',
+        method: 'get',
+        params
+    })
+}
+
+export function getMyLoginLogs(params) {
+    return request({
+        url: '/profile/logins',
+        method: 'get',
+        params
+    })
+}
@@ -8,9 +8,117 @@ export function getOperationLogs(params) {
     })
 }
 
-export function deleteOperationLogs() {
+export function deleteOperationLogs(params) {
     return request({
         url: '/operation-logs',
+        method: 'delete',
+        params
+    })
+}
+
+export function verifyOperationLogs() {
+    return request({
+        url: '/operation-logs/verify',
+        method: 'get'
+    })
+} 
+export function getOperationLog(id) {
+    return request({
+        url: `/operation-logs/${id}`,
+        method: 'get'
+    })
+}
+
+export function getOperationLogArchives() {
+    return request({
+        url: '/operation-logs/archives',
+        method: 'get'
+    })
+}
+
+export function downloadOperationLogArchive(name) {
+    return request({
+        url: `/operation-logs/archives/${name}`,
+        method: 'get',
+        responseType: 'blob'
+    })
+}
+
+export function importOperationLogArchive(name) {
+    return request({
+        url: `/operation-logs/archives/${name}/import`,
+        method: 'post'
+    })
+}
+
+export function getArchivedOperationLogs(params) {
+    return request({
+        url: '/operation-logs/archived',
+        method: 'get',
+        params
+    })
+}
+
+export function deleteArchivedOperationLogs(params) {
+    return request({
+        url: '/operation-logs/archived',
+        method: 'delete',
+        params
+    })
+}
+
+export function exportOperationLogs(params) {
+    return request({
+        url: '/operation-logs/export',
+        method: 'get',
+        params,
+        responseType: 'blob',
+        timeout: 0
+    })
+}
+
+export function getOperationLogWriterStats() {
+    return request({
+        url: '/operation-logs/writer-stats',
+        method: 'get'
+    })
+}
+
+// EventSource 无法设置请求头, token 通过 access_token 参数传递
+export function openOperationLogStream(params = {}) {
+    const query = new URLSearchParams({
+        ...params,
+        access_token: localStorage.getItem('token') || ''
+    })
+    return new EventSource(`${request.defaults.baseURL}/operation-logs/stream?${query}`)
+}
+
+export function getOperationLogRules() {
+    return request({
+        url: '/operation-log-rules',
+        method: 'get'
+    })
+}
+
+export function createOperationLogRule(data) {
+    return request({
+        url: '/operation-log-rules',
+        method: 'post',
+        data
+    })
+}
+
+export function updateOperationLogRule(id, data) {
+    return request({
+        url: `/operation-log-rules/${id}`,
+        method: 'put',
+        data
+    })
+}
+
+export function deleteOperationLogRule(id) {
+    return request({
+        url: `/operation-log-rules/${id}`,
         method: 'delete'
     })
-} 
\ No newline at end of file
+}
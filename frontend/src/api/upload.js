@@ -0,0 +1,82 @@
+import request from '@/utils/request'
+
+export function checkUpload(data) {
+    return request({
+        url: '/upload/check',
+        method: 'post',
+        data
+    })
+}
+
+export function createUploadSession(data) {
+    return request({
+        url: '/upload/sessions',
+        method: 'post',
+        data
+    })
+}
+
+export function getUploadSession(id) {
+    return request({
+        url: `/upload/sessions/${id}`,
+        method: 'get'
+    })
+}
+
+export function uploadChunk(id, index, blob) {
+    return request({
+        url: `/upload/sessions/${id}/chunks/${index}`,
+        method: 'put',
+        data: blob,
+        headers: { 'Content-Type': 'application/octet-stream' },
+        timeout: 0
+    })
+}
+
+export function completeUploadSession(id, sha256) {
+    return request({
+        url: `/upload/sessions/${id}/complete`,
+        method: 'post',
+        data: { sha256 },
+        timeout: 0
+    })
+}
+
+export function abortUploadSession(id) {
+    return request({
+        url: `/upload/sessions/${id}`,
+        method: 'delete'
+    })
+}
+
+async function sha256Hex(file) {
+    const digest = await crypto.subtle.digest('SHA-256', await file.arrayBuffer())
+    return Array.from(new Uint8Array(digest))
+        .map((b) => b.toString(16).padStart(2, '0'))
+        .join('')
+}
+
+// 分片上传大文件, 服务端已有相同内容时直接返回; 传入 sessionId 时只补传服务端缺少的分片
+export async function uploadInChunks(file, { purpose = 'attachment', visibility, sessionId, onProgress } = {}) {
+    const sha256 = await sha256Hex(file)
+    if (!sessionId) {
+        const checked = await checkUpload({ sha256, size: file.size, file_name: file.name, purpose, visibility })
+        if (checked.exists) {
+            onProgress?.(1)
+            return checked.file
+        }
+    }
+    const session = sessionId
+        ? await getUploadSession(sessionId)
+        : await createUploadSession({ file_name: file.name, size: file.size, purpose, visibility, sha256 })
+    const received = new Set(session.received_chunks)
+    for (let index = 0; index < session.total_chunks; index++) {
+        if (!received.has(index)) {
+            const start = index * session.chunk_size
+            await uploadChunk(session.id, index, file.slice(start, start + session.chunk_size))
+            received.add(index)
+        }
+        onProgress?.(received.size / session.total_chunks, session.id)
+    }
+    return completeUploadSession(session.id, sha256)
+}
@@ -0,0 +1,19 @@
+/**
+ * 保存文件流响应，文件名优先取自 Content-Disposition 响应头
+ * @param {object} response axios 响应（responseType 为 blob）
+ * @param {string} fallbackName 默认文件名
+ */
+export function saveBlobResponse(response, fallbackName) {
+  const disposition = response.headers['content-disposition'] || '';
+  const match = disposition.match(/filename="?([^";]+)"?/);
+  const fileName = match ? match[1] : fallbackName;
+
+  const url = URL.createObjectURL(response.data);
+  const link = document.createElement('a');
+  link.href = url;
+  link.download = fileName;
+  document.body.appendChild(link);
+  link.click();
+  document.body.removeChild(link);
+  URL.revokeObjectURL(url);
+}